@@ -0,0 +1,72 @@
+use reqwest::{RequestBuilder, Response};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use crate::trace;
+
+///
+/// Backend API endpoints with failover between replicas
+///
+/// # Fields
+///
+/// - urls: Vec<String> - The base URLs of the backend replicas, in order of preference
+/// - active: AtomicUsize - The index of the last URL that accepted a connection
+/// - trace: bool - Whether requests and responses are logged for debugging
+pub(crate) struct Backend {
+    urls: Vec<String>,
+    active: AtomicUsize,
+    trace: bool,
+}
+
+impl Backend {
+    pub(crate) fn new(urls: Vec<String>, trace: bool) -> Self {
+        Self {
+            urls,
+            active: AtomicUsize::new(0),
+            trace,
+        }
+    }
+
+    ///
+    /// Send a request to the backend, failing over to the next replica on connection failure
+    /// Starts with the last URL that worked and remembers whichever URL accepts the connection
+    ///
+    /// # Arguments
+    ///
+    /// - build: F - Builds the request for a given base URL
+    ///
+    /// # Returns
+    ///
+    /// Result<Response, reqwest::Error> - The response from the first reachable replica
+    ///
+    /// # Errors
+    ///
+    /// If no replica accepts the connection, the error from the last attempt is returned
+    ///
+    pub(crate) async fn send<F>(&self, build: F) -> Result<Response, reqwest::Error>
+    where
+        F: Fn(&str) -> RequestBuilder,
+    {
+        let start = self.active.load(Ordering::Relaxed);
+        let mut last_error = None;
+
+        for offset in 0..self.urls.len() {
+            let index = (start + offset) % self.urls.len();
+            match trace::send(build(&self.urls[index]), self.trace, None).await {
+                Ok(response) => {
+                    if index != start {
+                        println!("Failed over to backend: {}", self.urls[index]);
+                    }
+                    self.active.store(index, Ordering::Relaxed);
+                    return Ok(response);
+                }
+                Err(e) if e.is_connect() => {
+                    eprintln!("Backend {} unreachable: {}", self.urls[index], e);
+                    last_error = Some(e);
+                }
+                Err(e) => return Err(e),
+            }
+        }
+
+        Err(last_error.expect("backend has at least one URL"))
+    }
+}
@@ -0,0 +1,61 @@
+use encoding_rs::{Encoding, UTF_8};
+use reqwest::header::CONTENT_TYPE;
+use reqwest::Response;
+
+///
+/// Read a response body as text, aborting once it grows past a size limit
+/// The body is read chunk by chunk, so a huge response is never held in memory in full, then decoded with the charset
+/// of its `Content-Type` like `Response::text` does, UTF-8 when there is none
+///
+/// # Arguments
+///
+/// - response: Response - The response to read
+/// - max_bytes: usize - The largest body accepted
+///
+/// # Returns
+///
+/// Result<Option<String>, reqwest::Error> - The body, or None if it is larger than the limit
+///
+/// # Errors
+///
+/// If reading the body fails, an error is returned
+///
+pub(crate) async fn read_capped(
+    mut response: Response,
+    max_bytes: usize,
+) -> Result<Option<String>, reqwest::Error> {
+    if response
+        .content_length()
+        .is_some_and(|length| length > max_bytes as u64)
+    {
+        return Ok(None);
+    }
+
+    let encoding = charset(&response).unwrap_or(UTF_8);
+    let mut body = Vec::new();
+    while let Some(chunk) = response.chunk().await? {
+        if body.len() + chunk.len() > max_bytes {
+            return Ok(None);
+        }
+        body.extend_from_slice(&chunk);
+    }
+    let (text, _, _) = encoding.decode(&body);
+    Ok(Some(text.into_owned()))
+}
+
+///
+/// Get the encoding named by the charset of a response's `Content-Type`
+///
+/// # Arguments
+///
+/// - response: &Response - The response
+///
+/// # Returns
+///
+/// Option<&'static Encoding> - The encoding, or None if there is no charset or it isn't a known encoding
+///
+fn charset(response: &Response) -> Option<&'static Encoding> {
+    let content_type = response.headers().get(CONTENT_TYPE)?.to_str().ok()?;
+    let mime = content_type.parse::<mime::Mime>().ok()?;
+    Encoding::for_label(mime.get_param(mime::CHARSET)?.as_str().as_bytes())
+}
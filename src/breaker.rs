@@ -0,0 +1,109 @@
+use chrono::{DateTime, TimeDelta, Utc};
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+///
+/// Thresholds of the per-provider circuit breaker
+///
+/// # Fields
+///
+/// - failure_threshold: u32 - Consecutive failures after which a provider is skipped
+/// - cooldown: Duration - How long a provider is skipped before it is tried again
+#[derive(Clone, Copy, Debug)]
+pub struct BreakerConfig {
+    pub failure_threshold: u32,
+    pub cooldown: Duration,
+}
+
+///
+/// Circuit breaker state of a single provider
+/// Closed while `opened_at` is None, open during the cooldown after it and half-open once the cooldown has passed
+///
+/// # Fields
+///
+/// - consecutive_failures: u32 - Failures since the provider last succeeded
+/// - opened_at: Option<DateTime<Utc>> - When the breaker last opened
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub(crate) struct CircuitBreaker {
+    consecutive_failures: u32,
+    opened_at: Option<DateTime<Utc>>,
+}
+
+impl CircuitBreaker {
+    ///
+    /// Check whether the provider may be scraped, i.e. the breaker is closed or half-open
+    ///
+    /// # Arguments
+    ///
+    /// - config: &BreakerConfig - The breaker thresholds
+    /// - now: DateTime<Utc> - The current time
+    ///
+    /// # Returns
+    ///
+    /// bool - True if the provider should be scraped
+    ///
+    pub(crate) fn allows(&self, config: &BreakerConfig, now: DateTime<Utc>) -> bool {
+        match self.opened_at {
+            Some(opened_at) => now - opened_at >= cooldown(config),
+            None => true,
+        }
+    }
+
+    ///
+    /// Record the outcome of scraping the provider
+    /// A success closes the breaker, a failure while half-open reopens it for another cooldown
+    ///
+    /// # Arguments
+    ///
+    /// - config: &BreakerConfig - The breaker thresholds
+    /// - success: bool - Whether the provider yielded a price
+    /// - now: DateTime<Utc> - The current time
+    ///
+    pub(crate) fn record(&mut self, config: &BreakerConfig, success: bool, now: DateTime<Utc>) {
+        if success {
+            *self = Self::default();
+            return;
+        }
+
+        self.consecutive_failures += 1;
+        if self.opened_at.is_some() || self.consecutive_failures >= config.failure_threshold {
+            self.opened_at = Some(now);
+        }
+    }
+}
+
+fn cooldown(config: &BreakerConfig) -> TimeDelta {
+    TimeDelta::from_std(config.cooldown).unwrap_or(TimeDelta::max_value())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn breaker_opens_half_opens_and_closes() {
+        let config = BreakerConfig {
+            failure_threshold: 2,
+            cooldown: Duration::from_secs(60),
+        };
+        let start = Utc::now();
+        let mut breaker = CircuitBreaker::default();
+
+        breaker.record(&config, false, start);
+        assert!(breaker.allows(&config, start));
+        breaker.record(&config, false, start);
+        assert!(!breaker.allows(&config, start + TimeDelta::seconds(30)));
+
+        // Half-open after the cooldown, a single failure reopens it
+        let half_open = start + TimeDelta::seconds(60);
+        assert!(breaker.allows(&config, half_open));
+        breaker.record(&config, false, half_open);
+        assert!(!breaker.allows(&config, half_open + TimeDelta::seconds(30)));
+
+        let retry = half_open + TimeDelta::seconds(60);
+        breaker.record(&config, true, retry);
+        assert!(breaker.allows(&config, retry));
+        breaker.record(&config, false, retry);
+        assert!(breaker.allows(&config, retry));
+    }
+}
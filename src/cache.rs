@@ -0,0 +1,72 @@
+use reqwest::header::{HeaderMap, ETAG, IF_MODIFIED_SINCE, IF_NONE_MATCH, LAST_MODIFIED};
+use reqwest::RequestBuilder;
+use serde::{Deserialize, Serialize};
+
+///
+/// Cache validators for a provider page, remembered between runs to issue conditional requests
+///
+/// # Fields
+///
+/// - etag: Option<String> - The `ETag` header of the last response
+/// - last_modified: Option<String> - The `Last-Modified` header of the last response
+/// - price: Option<f64> - The last price scraped from the page
+#[derive(Clone, Debug, Default, PartialEq, Deserialize, Serialize)]
+pub(crate) struct CachedPage {
+    pub(crate) etag: Option<String>,
+    pub(crate) last_modified: Option<String>,
+    pub(crate) price: Option<f64>,
+}
+
+impl CachedPage {
+    ///
+    /// Build the cache validators from the headers of a provider page response
+    ///
+    /// # Arguments
+    ///
+    /// - headers: &HeaderMap - The response headers
+    ///
+    /// # Returns
+    ///
+    /// Option<CachedPage> - The validators, or None if the page doesn't support conditional requests
+    ///
+    pub(crate) fn from_headers(headers: &HeaderMap) -> Option<Self> {
+        let header_value = |name| {
+            headers
+                .get(name)
+                .and_then(|value| value.to_str().ok())
+                .map(str::to_string)
+        };
+        let etag = header_value(ETAG);
+        let last_modified = header_value(LAST_MODIFIED);
+
+        if etag.is_none() && last_modified.is_none() {
+            return None;
+        }
+        Some(Self {
+            etag,
+            last_modified,
+            price: None,
+        })
+    }
+
+    ///
+    /// Add the conditional request headers to a request
+    ///
+    /// # Arguments
+    ///
+    /// - request: RequestBuilder - The request to add the headers to
+    ///
+    /// # Returns
+    ///
+    /// RequestBuilder - The request with `If-None-Match`/`If-Modified-Since` set where known
+    ///
+    pub(crate) fn apply(&self, mut request: RequestBuilder) -> RequestBuilder {
+        if let Some(etag) = &self.etag {
+            request = request.header(IF_NONE_MATCH, etag);
+        }
+        if let Some(last_modified) = &self.last_modified {
+            request = request.header(IF_MODIFIED_SINCE, last_modified);
+        }
+        request
+    }
+}
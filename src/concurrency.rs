@@ -0,0 +1,112 @@
+use crate::report::{FailureKind, RunReport};
+use std::fmt;
+
+/// Concurrency limit of a run in the fixed mode
+pub(crate) const FIXED_CONCURRENCY: usize = 10;
+
+/// Concurrency limit adaptive mode starts at, low so a fresh scraper doesn't hammer rate-limited providers
+const START_CONCURRENCY: usize = 2;
+
+/// Upper bound of the adaptive concurrency limit
+const MAX_CONCURRENCY: usize = 64;
+
+///
+/// Concurrency limit of scraping runs, adapted between runs AIMD-style in adaptive mode
+/// The limit grows by one after a run without timeouts or rate limiting and halves after one with them
+///
+/// # Fields
+///
+/// - limit: usize - The number of providers scraped at once in the next run
+/// - adaptive: bool - Whether the limit is adjusted after every run
+#[derive(Clone, Copy, Debug)]
+pub struct Concurrency {
+    limit: usize,
+    adaptive: bool,
+}
+
+impl Concurrency {
+    ///
+    /// Create the concurrency limit, starting low in adaptive mode
+    ///
+    /// # Arguments
+    ///
+    /// - adaptive: bool - Whether the limit adapts to the outcome of every run
+    ///
+    /// # Returns
+    ///
+    /// Concurrency - The concurrency limit
+    ///
+    pub fn new(adaptive: bool) -> Self {
+        let limit = if adaptive {
+            START_CONCURRENCY
+        } else {
+            FIXED_CONCURRENCY
+        };
+        Self { limit, adaptive }
+    }
+
+    pub(crate) fn limit(&self) -> usize {
+        self.limit
+    }
+
+    ///
+    /// Adjust the limit to the outcome of a run, a no-op in fixed mode
+    /// Timeouts and rate limiting mean the providers are overloaded, anything else counts as headroom
+    ///
+    /// # Arguments
+    ///
+    /// - report: &RunReport - The report of the finished run
+    ///
+    pub(crate) fn adjust(&mut self, report: &RunReport) {
+        if !self.adaptive {
+            return;
+        }
+
+        let overloaded = [FailureKind::Timeout, FailureKind::RateLimited]
+            .iter()
+            .any(|kind| report.failures.contains_key(kind));
+        self.limit = if overloaded {
+            (self.limit / 2).max(1)
+        } else {
+            (self.limit + 1).min(MAX_CONCURRENCY)
+        };
+    }
+}
+
+impl fmt::Display for Concurrency {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.adaptive {
+            write!(f, "adaptive, from {} up to {}", self.limit, MAX_CONCURRENCY)
+        } else {
+            write!(f, "{}", self.limit)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn adaptive_limit_grows_additively_and_backs_off_multiplicatively() {
+        let mut concurrency = Concurrency::new(true);
+        let healthy = RunReport::default();
+        let mut overloaded = RunReport::default();
+        overloaded.failures.insert(FailureKind::RateLimited, 1);
+
+        for _ in 0..6 {
+            concurrency.adjust(&healthy);
+        }
+        assert_eq!(concurrency.limit(), 8);
+        concurrency.adjust(&overloaded);
+        assert_eq!(concurrency.limit(), 4);
+        for _ in 0..3 {
+            concurrency.adjust(&overloaded);
+        }
+        assert_eq!(concurrency.limit(), 1);
+
+        let mut fixed = Concurrency::new(false);
+        fixed.adjust(&overloaded);
+        assert_eq!(fixed.limit(), FIXED_CONCURRENCY);
+    }
+}
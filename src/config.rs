@@ -0,0 +1,443 @@
+use reqwest::header::{HeaderMap, HeaderValue, ACCEPT_LANGUAGE};
+use reqwest::{ClientBuilder, Url};
+use std::collections::HashMap;
+use std::fmt;
+use std::net::{IpAddr, SocketAddr};
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::breaker::BreakerConfig;
+use crate::dns::FamilyResolver;
+use crate::snapshot::SnapshotDir;
+use crate::tls::ClientTls;
+use crate::updated_at::Timezone;
+
+///
+/// Tunables for the scraper
+///
+/// # Fields
+///
+/// - providers_file: Option<PathBuf> - Local JSON/TOML file with full provider entries, used instead of the API
+/// - prices_output: Option<PathBuf> - File to append scraped prices to as JSON lines when using a providers file
+/// - output_json: Option<PathBuf> - File to write a summary of every run to, appended to as JSON lines for `.jsonl` files
+/// - metrics_file: Option<PathBuf> - File every run's stats are appended to, as CSV for `.csv` files and JSON lines otherwise
+/// - resume_file: Option<PathBuf> - File the providers done in the current run are recorded in, so a run interrupted by a crash resumes
+/// - post_concurrency: usize - Maximum number of price posts in flight at once
+/// - snapshots: Option<SnapshotDir> - Where to save pages that yield no price, off when None
+/// - auth_warmup: Duration - Delay between logging in and the first API call, for slowly propagating tokens
+/// - fail_on_empty: bool - Fail a run that has no providers to scrape, instead of only warning
+/// - fail_threshold_pct: Option<f64> - Fail the run when more than this percentage of providers fail
+/// - per_provider_intervals: bool - Skip providers scraped more recently than their own poll interval
+/// - groups: Vec<String> - Only scrape providers in these groups, all providers when empty
+/// - group_intervals: HashMap<String, Duration> - Minimum time between scrapes of the providers in a group, for providers without their own poll interval
+/// - price_retries: u32 - How many times a failed price post is retried before the price is dropped
+/// - run_retries: u32 - How many times a failed run post is retried
+/// - retry_base_delay: Duration - The delay before the first retry, doubled for every following retry
+/// - strict_decimals: bool - Reject prices whose decimals don't match the provider's expected decimals, instead of only warning
+/// - strict_parse: bool - Fail a provider as unparseable when any matched element isn't a valid price, instead of skipping the element
+/// - post_scraped_at: bool - Send the time each page was fetched with its price, instead of letting the API timestamp it on receipt
+/// - updated_at_timezone: Timezone - Timezone the update times on provider pages are read in, `Europe/Copenhagen` by default
+/// - verify: bool - Fetch every page a second time and only post prices that hold, at the cost of an extra request per provider
+/// - verify_tolerance: f64 - Largest difference between the two fetches that still counts as the same price
+/// - max_retry_after: Duration - Longest `Retry-After` of a rate limited provider page that is waited out to retry it once in the run
+/// - since_last_success: Option<Duration> - Skip a run when the API has a run that ended less than this long ago, e.g. from another instance
+/// - warmup_runs: u32 - Runs after startup that scrape without posting, to warm caches and connections
+/// - bulk_post: bool - Post the prices of a run in batches to the bulk prices path instead of one request per provider
+/// - bulk_batch_size: usize - Maximum number of prices in a single bulk post
+/// - webdriver_url: String - WebDriver endpoint of the headless browser used for providers that need rendering
+/// - render_timeout: Duration - How long to wait for a rendered page to show the provider's selector
+/// - heartbeat_url: Option<String> - URL pinged with a GET after every successful run, for external uptime monitoring
+/// - heartbeat_fail_url: Option<String> - URL pinged with a GET after every failed run
+/// - reconcile: bool - Compare every scraped price with the API's latest price and report the ones that differ
+/// - reconcile_tolerance: f64 - Largest difference to the API's latest price that isn't reported
+/// - alert_webhook: Option<String> - URL a JSON alert is posted to when a provider's price moves more than the alert threshold
+/// - alert_change_pct: f64 - Percentage a price has to move from the last scraped price to trigger an alert
+/// - breaker: Option<BreakerConfig> - Skip providers for a cooldown after repeated failures, off when None
+/// - status_addr: Option<SocketAddr> - Address the status server listens on, serving the last runs at `/runs`, off when None
+/// - recent_runs: usize - How many run reports the status server keeps
+/// - statsd_addr: Option<String> - StatsD or DogStatsD endpoint to send run metrics to, requires the `statsd` feature
+/// - publish_url: Option<String> - Redis or NATS broker every scraped price is published to, requires the `pubsub` feature
+/// - otlp_endpoint: Option<String> - OTLP/HTTP collector the trace of every run is exported to, requires the `otel` feature
+/// - publish_subject: String - Channel or subject prices are published on
+/// - publish_only: bool - Only publish prices to the broker, without posting them to the API
+/// - on_price: Option<String> - Shell command run for every scraped price, with the price as arguments and JSON on stdin
+/// - on_price_timeout: Duration - How long the price command may run before it is killed
+/// - preserve_provider_order: bool - Scrape providers in the order the API lists them, instead of sorted by ID
+/// - shuffle_providers: bool - Scrape providers in a random order every run, so no provider is always scraped last
+/// - shuffle_seed: Option<u64> - Seed of the shuffle, for a reproducible order, random when None
+/// - sample_rate: f64 - Fraction of the scraped prices posted to the API, the rest are scraped but not posted
+/// - sample_seed: Option<u64> - Seed of the sampling, for reproducible sampling decisions, random when None
+/// - min_post_interval: Option<Duration> - Minimum time between posts for the same provider, later prices are throttled
+/// - max_response_bytes: usize - Largest provider page read, larger pages fail the provider
+/// - max_providers: Option<usize> - Scrape at most this many providers per run, all of them when None
+/// - rotate_providers: bool - Move the window of capped providers along every run, instead of always scraping the first ones
+/// - adaptive_concurrency: bool - Adapt the scraping concurrency to timeouts and rate limiting, instead of a fixed limit of 10
+/// - tls: Option<ClientTls> - Client certificate for mutual TLS to the API, only sent to the API and never to provider pages
+/// - signing_secret: Option<String> - Shared secret price and run posts are signed with, unsigned when None
+/// - send_referer: bool - Send every provider page request with a `Referer` of the page's own origin
+/// - preflight: bool - Send a HEAD request before every provider page GET and skip the GET if the page is gone or isn't the expected type
+/// - trace_requests: bool - Log the method, URL, headers, status and start of the body of every API and provider request, with secrets redacted
+/// - paths: ApiPaths - Routes of the API endpoints, for backends with another layout
+/// - http: HttpConfig - Tuning of the HTTP client used for the API and provider pages
+#[derive(Clone, Debug)]
+pub struct ScraperConfig {
+    pub providers_file: Option<PathBuf>,
+    pub prices_output: Option<PathBuf>,
+    pub output_json: Option<PathBuf>,
+    pub metrics_file: Option<PathBuf>,
+    pub resume_file: Option<PathBuf>,
+    pub post_concurrency: usize,
+    pub snapshots: Option<SnapshotDir>,
+    pub auth_warmup: Duration,
+    pub fail_on_empty: bool,
+    pub fail_threshold_pct: Option<f64>,
+    pub per_provider_intervals: bool,
+    pub groups: Vec<String>,
+    pub group_intervals: HashMap<String, Duration>,
+    pub price_retries: u32,
+    pub run_retries: u32,
+    pub retry_base_delay: Duration,
+    pub strict_decimals: bool,
+    pub strict_parse: bool,
+    pub post_scraped_at: bool,
+    pub updated_at_timezone: Timezone,
+    pub verify: bool,
+    pub verify_tolerance: f64,
+    pub max_retry_after: Duration,
+    pub since_last_success: Option<Duration>,
+    pub warmup_runs: u32,
+    pub bulk_post: bool,
+    pub bulk_batch_size: usize,
+    pub webdriver_url: String,
+    pub render_timeout: Duration,
+    pub heartbeat_url: Option<String>,
+    pub heartbeat_fail_url: Option<String>,
+    pub reconcile: bool,
+    pub reconcile_tolerance: f64,
+    pub alert_webhook: Option<String>,
+    pub alert_change_pct: f64,
+    pub breaker: Option<BreakerConfig>,
+    pub status_addr: Option<SocketAddr>,
+    pub recent_runs: usize,
+    pub statsd_addr: Option<String>,
+    pub publish_url: Option<String>,
+    pub otlp_endpoint: Option<String>,
+    pub publish_subject: String,
+    pub publish_only: bool,
+    pub on_price: Option<String>,
+    pub on_price_timeout: Duration,
+    pub preserve_provider_order: bool,
+    pub shuffle_providers: bool,
+    pub shuffle_seed: Option<u64>,
+    pub sample_rate: f64,
+    pub sample_seed: Option<u64>,
+    pub min_post_interval: Option<Duration>,
+    pub max_response_bytes: usize,
+    pub max_providers: Option<usize>,
+    pub rotate_providers: bool,
+    pub adaptive_concurrency: bool,
+    pub tls: Option<ClientTls>,
+    pub signing_secret: Option<String>,
+    pub send_referer: bool,
+    pub preflight: bool,
+    pub trace_requests: bool,
+    pub paths: ApiPaths,
+    pub http: HttpConfig,
+}
+
+impl Default for ScraperConfig {
+    fn default() -> Self {
+        Self {
+            providers_file: None,
+            prices_output: None,
+            output_json: None,
+            metrics_file: None,
+            resume_file: None,
+            post_concurrency: 10,
+            snapshots: None,
+            auth_warmup: Duration::ZERO,
+            fail_on_empty: false,
+            fail_threshold_pct: None,
+            per_provider_intervals: false,
+            groups: vec![],
+            group_intervals: HashMap::new(),
+            price_retries: 3,
+            run_retries: 10,
+            retry_base_delay: Duration::from_millis(500),
+            strict_decimals: false,
+            strict_parse: false,
+            post_scraped_at: false,
+            updated_at_timezone: Timezone::default(),
+            verify: false,
+            verify_tolerance: 0.001,
+            max_retry_after: Duration::from_secs(30),
+            since_last_success: None,
+            warmup_runs: 0,
+            bulk_post: false,
+            bulk_batch_size: 500,
+            webdriver_url: "http://localhost:4444".to_string(),
+            render_timeout: Duration::from_secs(10),
+            heartbeat_url: None,
+            heartbeat_fail_url: None,
+            reconcile: false,
+            reconcile_tolerance: 0.001,
+            alert_webhook: None,
+            alert_change_pct: 5.0,
+            breaker: None,
+            status_addr: None,
+            recent_runs: 10,
+            statsd_addr: None,
+            publish_url: None,
+            otlp_endpoint: None,
+            publish_subject: "oliepriser.prices".to_string(),
+            publish_only: false,
+            on_price: None,
+            on_price_timeout: Duration::from_secs(10),
+            preserve_provider_order: false,
+            shuffle_providers: false,
+            shuffle_seed: None,
+            sample_rate: 1.0,
+            sample_seed: None,
+            min_post_interval: None,
+            max_response_bytes: 10 * 1024 * 1024,
+            max_providers: None,
+            rotate_providers: false,
+            adaptive_concurrency: false,
+            tls: None,
+            signing_secret: None,
+            send_referer: false,
+            preflight: false,
+            trace_requests: false,
+            paths: ApiPaths::default(),
+            http: HttpConfig::default(),
+        }
+    }
+}
+
+impl ScraperConfig {
+    ///
+    /// Create a builder for the client used for the API, with the HTTP tuning and the client certificate applied
+    ///
+    /// # Returns
+    ///
+    /// ClientBuilder - The client builder
+    ///
+    pub(crate) fn api_client_builder(&self) -> ClientBuilder {
+        let builder = self.http.client_builder();
+        match &self.tls {
+            Some(tls) => tls.apply(builder),
+            None => builder,
+        }
+    }
+}
+
+///
+/// Routes of the API endpoints, appended to the base URL, `{id}` is replaced with the provider ID
+///
+/// # Fields
+///
+/// - providers: String - Lists the providers to scrape
+/// - provider_detail: String - Gets the details of a provider
+/// - prices: String - Adds a price for a provider
+/// - bulk_prices: String - Adds the prices of several providers at once
+/// - runs: String - Records a scraping run
+#[derive(Clone, Debug)]
+pub struct ApiPaths {
+    pub providers: String,
+    pub provider_detail: String,
+    pub prices: String,
+    pub bulk_prices: String,
+    pub runs: String,
+}
+
+impl Default for ApiPaths {
+    fn default() -> Self {
+        Self {
+            providers: "/scraping_runs/providers".to_string(),
+            provider_detail: "/providers/{id}".to_string(),
+            prices: "/providers/{id}/prices".to_string(),
+            bulk_prices: "/prices".to_string(),
+            runs: "/scraping_runs".to_string(),
+        }
+    }
+}
+
+impl ApiPaths {
+    ///
+    /// Build the URL of an API endpoint, the paths are checked against every base URL with `parse` by `Scraper::new`
+    ///
+    /// # Arguments
+    ///
+    /// - base_url: &str - The base URL of the backend replica
+    /// - path: &str - One of the paths, with `{id}` in place of the provider ID
+    /// - provider_id: i32 - The provider ID, ignored by paths without `{id}`
+    ///
+    /// # Returns
+    ///
+    /// Url - The URL of the endpoint
+    ///
+    pub(crate) fn url(base_url: &str, path: &str, provider_id: i32) -> Url {
+        Self::parse(base_url, path, provider_id).expect("API paths are checked by Scraper::new")
+    }
+
+    ///
+    /// Parse the URL of an API endpoint
+    ///
+    /// # Arguments
+    ///
+    /// - base_url: &str - The base URL of the backend replica
+    /// - path: &str - One of the paths, with `{id}` in place of the provider ID
+    /// - provider_id: i32 - The provider ID, ignored by paths without `{id}`
+    ///
+    /// # Returns
+    ///
+    /// Result<Url, String> - The URL of the endpoint
+    ///
+    /// # Errors
+    ///
+    /// If the path doesn't form a valid URL with the base URL, the parse error is returned
+    ///
+    pub fn parse(base_url: &str, path: &str, provider_id: i32) -> Result<Url, String> {
+        let path = path.replace("{id}", &provider_id.to_string());
+        Url::parse(&format!("{}{}", base_url, path)).map_err(|e| e.to_string())
+    }
+}
+
+///
+/// Tuning of the HTTP client, the defaults leave reqwest's own defaults in place
+///
+/// # Fields
+///
+/// - pool_max_idle_per_host: Option<usize> - Maximum idle connections kept per host
+/// - http2_prior_knowledge: bool - Speak HTTP/2 without negotiating it first
+/// - tcp_keepalive: Option<Duration> - Interval of TCP keepalive probes on open connections
+/// - request_timeout: Option<Duration> - Timeout of every request, overridden by a provider's `timeout_secs` for its page
+/// - resolve: Vec<HostOverride> - Hosts connected to at a fixed address instead of the one DNS resolves
+/// - ip_version: IpVersion - Which IP version connections are made over
+/// - local_address: Option<IpAddr> - Local address connections are made from, e.g. to pick the egress interface
+/// - accept_language: Option<String> - `Accept-Language` sent with every request, overridden by a provider's own
+#[derive(Clone, Debug, Default)]
+pub struct HttpConfig {
+    pub pool_max_idle_per_host: Option<usize>,
+    pub http2_prior_knowledge: bool,
+    pub tcp_keepalive: Option<Duration>,
+    pub request_timeout: Option<Duration>,
+    pub resolve: Vec<HostOverride>,
+    pub ip_version: IpVersion,
+    pub local_address: Option<IpAddr>,
+    pub accept_language: Option<String>,
+}
+
+///
+/// IP version connections are made over
+///
+/// # Variants
+///
+/// - Auto: Whatever addresses DNS resolves, as the system prefers them (default)
+/// - V4: Only IPv4 addresses, for sites that misbehave over IPv6
+/// - V6: Only IPv6 addresses
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum IpVersion {
+    #[default]
+    Auto,
+    V4,
+    V6,
+}
+
+impl fmt::Display for IpVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            IpVersion::Auto => "IPv4 or IPv6",
+            IpVersion::V4 => "IPv4",
+            IpVersion::V6 => "IPv6",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+///
+/// DNS override of a host, written as `host:ip` like curl's `--resolve`
+/// The port always comes from the URL, so an override applies to every port of the host
+///
+/// # Fields
+///
+/// - host: String - The host name as written in URLs, e.g. `www.example.com`
+/// - addr: IpAddr - The address connected to instead, e.g. `203.0.113.7` or `[2001:db8::7]`
+#[derive(Clone, Debug, PartialEq)]
+pub struct HostOverride {
+    pub host: String,
+    pub addr: IpAddr,
+}
+
+impl FromStr for HostOverride {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let (host, addr) = value
+            .split_once(':')
+            .filter(|(host, _)| !host.is_empty())
+            .ok_or_else(|| format!("expected host:ip, got {:?}", value))?;
+        let addr = addr
+            .trim_start_matches('[')
+            .trim_end_matches(']')
+            .parse::<IpAddr>()
+            .map_err(|e| format!("invalid address {:?}: {}", addr, e))?;
+        Ok(Self {
+            host: host.to_string(),
+            addr,
+        })
+    }
+}
+
+impl HttpConfig {
+    ///
+    /// Create a client builder with the tuning applied
+    ///
+    /// # Returns
+    ///
+    /// ClientBuilder - The client builder
+    ///
+    pub(crate) fn client_builder(&self) -> ClientBuilder {
+        let mut builder = reqwest::Client::builder();
+        // pool_max_idle_per_host -> ClientBuilder::pool_max_idle_per_host
+        if let Some(max_idle) = self.pool_max_idle_per_host {
+            builder = builder.pool_max_idle_per_host(max_idle);
+        }
+        // http2_prior_knowledge -> ClientBuilder::http2_prior_knowledge
+        if self.http2_prior_knowledge {
+            builder = builder.http2_prior_knowledge();
+        }
+        // tcp_keepalive -> ClientBuilder::tcp_keepalive
+        if let Some(keepalive) = self.tcp_keepalive {
+            builder = builder.tcp_keepalive(keepalive);
+        }
+        // request_timeout -> ClientBuilder::timeout
+        if let Some(timeout) = self.request_timeout {
+            builder = builder.timeout(timeout);
+        }
+        // resolve -> ClientBuilder::resolve, reqwest ignores the port and uses the URL's
+        for host_override in &self.resolve {
+            builder = builder.resolve(&host_override.host, SocketAddr::new(host_override.addr, 0));
+        }
+        // ip_version -> ClientBuilder::dns_resolver, reqwest has no address family option of its own
+        if self.ip_version != IpVersion::Auto {
+            builder = builder.dns_resolver(Arc::new(FamilyResolver::new(self.ip_version)));
+        }
+        // local_address -> ClientBuilder::local_address
+        if let Some(addr) = self.local_address {
+            builder = builder.local_address(addr);
+        }
+        // accept_language -> ClientBuilder::default_headers, merged with the auth header set later; invalid values are
+        // rejected when the CLI is validated
+        if let Some(language) = self
+            .accept_language
+            .as_deref()
+            .and_then(|language| HeaderValue::from_str(language).ok())
+        {
+            builder = builder.default_headers(HeaderMap::from_iter([(ACCEPT_LANGUAGE, language)]));
+        }
+        builder
+    }
+}
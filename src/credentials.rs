@@ -1,19 +1,185 @@
+use base64::prelude::{Engine, BASE64_STANDARD};
+use reqwest::header::{HeaderName, AUTHORIZATION};
 use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::path::PathBuf;
+use std::process::Command;
+use std::str::FromStr;
+
+/// Authorization schemes with a canonical casing, anything else is sent as returned by the API
+const KNOWN_SCHEMES: [&str; 3] = ["Bearer", "Basic", "Digest"];
+
+/// Scheme of tokens the API returns without a token type
+const DEFAULT_SCHEME: &str = "Bearer";
 
 #[derive(Deserialize, Serialize, Clone, Debug)]
 pub(crate) struct Token {
     pub(crate) access_token: String,
+    #[serde(default)]
     pub(crate) token_type: String,
 }
 
-pub(crate) struct Credentials {
+impl Token {
+    ///
+    /// Build the value of the `Authorization` header for the token
+    ///
+    /// # Returns
+    ///
+    /// Result<String, String> - The header value, e.g. `Bearer abc123`
+    ///
+    /// # Errors
+    ///
+    /// If the token hasn't been fetched yet, an error is returned
+    ///
+    pub(crate) fn authorization_header(&self) -> Result<String, String> {
+        if self.access_token.trim().is_empty() {
+            return Err("No access token, the token has not been fetched yet".to_string());
+        }
+        Ok(self.to_string())
+    }
+
+    ///
+    /// Normalize the token type to the canonical casing of known schemes, defaulting a missing type to `Bearer`
+    ///
+    /// # Returns
+    ///
+    /// String - The normalized scheme, e.g. `bearer` becomes `Bearer`
+    ///
+    fn scheme(&self) -> String {
+        let token_type = match self.token_type.trim() {
+            "" => DEFAULT_SCHEME,
+            token_type => token_type,
+        };
+        KNOWN_SCHEMES
+            .iter()
+            .find(|scheme| scheme.eq_ignore_ascii_case(token_type))
+            .map(|scheme| scheme.to_string())
+            .unwrap_or_else(|| token_type.to_string())
+    }
+}
+
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {}", self.scheme(), self.access_token.trim())
+    }
+}
+
+impl FromStr for Token {
+    type Err = String;
+
+    ///
+    /// Parse a token from an `Authorization` header value, e.g. `Bearer abc123`
+    ///
+    fn from_str(header: &str) -> Result<Self, Self::Err> {
+        let (token_type, access_token) = header
+            .trim()
+            .split_once(' ')
+            .ok_or_else(|| format!("Invalid authorization header: {:?}", header))?;
+        let token = Token {
+            access_token: access_token.trim().to_string(),
+            token_type: token_type.to_string(),
+        };
+        token.authorization_header()?;
+        Ok(token)
+    }
+}
+
+///
+/// How the scraper authenticates to the API
+///
+/// # Variants
+///
+/// - Bearer: Log in through `/auth/login` and send the returned token
+/// - Basic: Send the client ID and secret as HTTP basic auth, without logging in
+/// - ApiKey: Send a static key in a header, without logging in
+#[derive(Clone, Debug, PartialEq)]
+pub enum AuthScheme {
+    Bearer,
+    Basic,
+    ApiKey { header: String, key: String },
+}
+
+///
+/// Where a bearer token comes from instead of the `/auth/login` flow, read again before every run
+///
+/// # Variants
+///
+/// - File: A file holding the token, e.g. one kept up to date by a secret manager agent
+/// - Command: A shell command printing the token on stdout, e.g. a Vault or cloud CLI helper
+#[derive(Clone, Debug, PartialEq)]
+pub enum TokenSource {
+    File(PathBuf),
+    Command(String),
+}
+
+impl TokenSource {
+    ///
+    /// Read the token, either a bare access token or a full `Authorization` header value like `Bearer abc123`
+    ///
+    /// # Returns
+    ///
+    /// Result<Token, String> - The token
+    ///
+    /// # Errors
+    ///
+    /// If the file can't be read, the command fails or the output is no valid token, an error is returned
+    ///
+    pub(crate) fn read(&self) -> Result<Token, String> {
+        let output = match self {
+            TokenSource::File(path) => std::fs::read_to_string(path)
+                .map_err(|e| format!("Failed to read token file {}: {}", path.display(), e))?,
+            TokenSource::Command(command) => run_token_command(command)?,
+        };
+
+        let output = output.trim();
+        if output.contains(' ') {
+            return output.parse();
+        }
+        let token = Token {
+            access_token: output.to_string(),
+            token_type: "Bearer".to_string(),
+        };
+        token.authorization_header()?;
+        Ok(token)
+    }
+}
+
+fn run_token_command(command: &str) -> Result<String, String> {
+    let mut shell = if cfg!(windows) {
+        let mut shell = Command::new("cmd");
+        shell.arg("/C");
+        shell
+    } else {
+        let mut shell = Command::new("sh");
+        shell.arg("-c");
+        shell
+    };
+    let output = shell
+        .arg(command)
+        .output()
+        .map_err(|e| format!("Failed to run token command: {}", e))?;
+    if !output.status.success() {
+        return Err(format!(
+            "Token command exited with {}: {}",
+            output.status,
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    String::from_utf8(output.stdout)
+        .map_err(|e| format!("Token command printed invalid UTF-8: {}", e))
+}
+
+pub struct Credentials {
     pub(crate) client_id: String,
     pub(crate) client_secret: String,
     pub(crate) token: Token,
+    pub(crate) scheme: AuthScheme,
+    pub(crate) token_source: Option<TokenSource>,
+    pub(crate) token_type: Option<String>,
 }
 
 impl Credentials {
-    pub(crate) fn new(client_id: String, client_secret: String) -> Self {
+    pub fn new(client_id: String, client_secret: String) -> Self {
         Self {
             client_id,
             client_secret,
@@ -21,6 +187,189 @@ impl Credentials {
                 access_token: "".to_string(),
                 token_type: "".to_string(),
             },
+            scheme: AuthScheme::Bearer,
+            token_source: None,
+            token_type: None,
+        }
+    }
+
+    ///
+    /// Send bearer tokens with this scheme, whatever token type the API or token source returns
+    ///
+    /// # Arguments
+    ///
+    /// - token_type: String - The scheme to send, e.g. `Bearer`
+    ///
+    /// # Returns
+    ///
+    /// Credentials - The credentials forcing the scheme
+    ///
+    pub fn with_token_type(mut self, token_type: String) -> Self {
+        self.token_type = Some(token_type);
+        self
+    }
+
+    ///
+    /// Read the bearer token from a file or command before every run, instead of logging in through `/auth/login`
+    ///
+    /// # Arguments
+    ///
+    /// - source: TokenSource - Where the token comes from
+    ///
+    /// # Returns
+    ///
+    /// Credentials - The credentials using the token source
+    ///
+    pub fn with_token_source(mut self, source: TokenSource) -> Self {
+        self.token_source = Some(source);
+        self
+    }
+
+    ///
+    /// Use another authentication scheme than the default bearer token login
+    ///
+    /// # Arguments
+    ///
+    /// - scheme: AuthScheme - The scheme to authenticate with
+    ///
+    /// # Returns
+    ///
+    /// Credentials - The credentials using the scheme
+    ///
+    pub fn with_scheme(mut self, scheme: AuthScheme) -> Self {
+        self.scheme = scheme;
+        self
+    }
+
+    ///
+    /// Whether a token has to be fetched through `/auth/login` before calling the API
+    ///
+    pub(crate) fn requires_login(&self) -> bool {
+        self.scheme == AuthScheme::Bearer && self.token_source.is_none()
+    }
+
+    ///
+    /// Build the header sent with every API request
+    ///
+    /// # Returns
+    ///
+    /// Result<(HeaderName, String), String> - The header name and value
+    ///
+    /// # Errors
+    ///
+    /// If the bearer token hasn't been fetched yet or the API key header name is invalid, an error is returned
+    ///
+    pub(crate) fn auth_header(&self) -> Result<(HeaderName, String), String> {
+        match &self.scheme {
+            AuthScheme::Bearer => {
+                let token = match &self.token_type {
+                    Some(token_type) => Token {
+                        token_type: token_type.clone(),
+                        ..self.token.clone()
+                    },
+                    None => self.token.clone(),
+                };
+                Ok((AUTHORIZATION, token.authorization_header()?))
+            }
+            AuthScheme::Basic => {
+                let user_pass = format!("{}:{}", self.client_id, self.client_secret);
+                Ok((
+                    AUTHORIZATION,
+                    format!("Basic {}", BASE64_STANDARD.encode(user_pass)),
+                ))
+            }
+            AuthScheme::ApiKey { header, key } => {
+                let name = HeaderName::from_bytes(header.as_bytes())
+                    .map_err(|e| format!("Invalid API key header {:?}: {}", header, e))?;
+                Ok((name, key.clone()))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use uuid::Uuid;
+
+    #[test]
+    fn authorization_header_normalizes_scheme() {
+        let token = Token {
+            access_token: "abc123".to_string(),
+            token_type: "bearer".to_string(),
+        };
+
+        assert_eq!(token.authorization_header().unwrap(), "Bearer abc123");
+    }
+
+    #[test]
+    fn authorization_header_defaults_missing_type_and_can_be_forced() {
+        let token: Token = serde_json::from_str(r#"{ "access_token": "abc123" }"#).unwrap();
+        assert_eq!(token.authorization_header().unwrap(), "Bearer abc123");
+
+        let mut credentials = Credentials::new("client_id".to_string(), "secret".to_string());
+        credentials.token = Token {
+            access_token: "abc123".to_string(),
+            token_type: "mac".to_string(),
+        };
+        assert_eq!(credentials.auth_header().unwrap().1, "mac abc123");
+        let credentials = credentials.with_token_type("bearer".to_string());
+        assert_eq!(credentials.auth_header().unwrap().1, "Bearer abc123");
+    }
+
+    #[test]
+    fn authorization_header_errors_before_token_is_fetched() {
+        let credentials = Credentials::new("client_id".to_string(), "secret".to_string());
+
+        assert!(credentials.token.authorization_header().is_err());
+    }
+
+    #[test]
+    fn auth_header_follows_scheme() {
+        let credentials = Credentials::new("user".to_string(), "pass".to_string());
+        let basic = credentials.with_scheme(AuthScheme::Basic);
+        assert_eq!(
+            basic.auth_header().unwrap(),
+            (AUTHORIZATION, "Basic dXNlcjpwYXNz".to_string())
+        );
+
+        let api_key = basic.with_scheme(AuthScheme::ApiKey {
+            header: "X-API-Key".to_string(),
+            key: "secret".to_string(),
+        });
+        assert!(!api_key.requires_login());
+        assert_eq!(
+            api_key.auth_header().unwrap(),
+            (HeaderName::from_static("x-api-key"), "secret".to_string())
+        );
+    }
+
+    #[test]
+    fn token_source_reads_bare_tokens_and_header_values() {
+        let path = std::env::temp_dir().join(format!("oliepriser-token-test-{}", Uuid::new_v4()));
+        std::fs::write(&path, "abc123\n").unwrap();
+        let token = TokenSource::File(path.clone()).read().unwrap();
+        assert_eq!(token.to_string(), "Bearer abc123");
+
+        std::fs::write(&path, "").unwrap();
+        assert!(TokenSource::File(path.clone()).read().is_err());
+        std::fs::remove_file(&path).unwrap();
+
+        if cfg!(unix) {
+            let token = TokenSource::Command("echo 'bearer def456'".to_string())
+                .read()
+                .unwrap();
+            assert_eq!(token.to_string(), "Bearer def456");
+            assert!(TokenSource::Command("exit 1".to_string()).read().is_err());
         }
     }
+
+    #[test]
+    fn token_round_trips_through_header() {
+        let token: Token = "BASIC dXNlcjpwYXNz".parse().unwrap();
+
+        assert_eq!(token.token_type, "BASIC");
+        assert_eq!(token.to_string(), "Basic dXNlcjpwYXNz");
+        assert!("Bearer".parse::<Token>().is_err());
+    }
 }
@@ -0,0 +1,84 @@
+use reqwest::dns::{Addrs, Name, Resolve, Resolving};
+use std::net::SocketAddr;
+
+use crate::config::IpVersion;
+
+///
+/// DNS resolver that only returns the addresses of one IP version, to keep connections on IPv4 or IPv6
+/// Hosts with no address of that version fail to resolve instead of falling back to the other version
+///
+/// # Fields
+///
+/// - version: IpVersion - The IP version addresses are kept of
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct FamilyResolver {
+    version: IpVersion,
+}
+
+impl FamilyResolver {
+    pub(crate) fn new(version: IpVersion) -> Self {
+        Self { version }
+    }
+
+    ///
+    /// Check whether an address is of the resolver's IP version
+    ///
+    /// # Arguments
+    ///
+    /// - addr: &SocketAddr - The resolved address
+    ///
+    /// # Returns
+    ///
+    /// bool - True if the address is kept
+    ///
+    fn keeps(&self, addr: &SocketAddr) -> bool {
+        match self.version {
+            IpVersion::Auto => true,
+            IpVersion::V4 => addr.is_ipv4(),
+            IpVersion::V6 => addr.is_ipv6(),
+        }
+    }
+}
+
+impl Resolve for FamilyResolver {
+    fn resolve(&self, name: Name) -> Resolving {
+        let resolver = *self;
+        Box::pin(async move {
+            // The port is replaced by the URL's when connecting
+            let addrs: Vec<SocketAddr> = tokio::net::lookup_host((name.as_str(), 0))
+                .await?
+                .filter(|addr| resolver.keeps(addr))
+                .collect();
+            if addrs.is_empty() {
+                return Err(
+                    format!("{} has no {} address", name.as_str(), resolver.version).into(),
+                );
+            }
+            let addrs: Addrs = Box::new(addrs.into_iter());
+            Ok(addrs)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    #[tokio::test]
+    async fn resolves_only_addresses_of_the_chosen_version() {
+        let name = || Name::from_str("127.0.0.1").unwrap();
+
+        let v4: Vec<SocketAddr> = FamilyResolver::new(IpVersion::V4)
+            .resolve(name())
+            .await
+            .unwrap()
+            .collect();
+        assert_eq!(v4, vec!["127.0.0.1:0".parse().unwrap()]);
+
+        let Err(e) = FamilyResolver::new(IpVersion::V6).resolve(name()).await else {
+            panic!("an IPv4 address resolved over IPv6");
+        };
+        assert!(e.to_string().contains("has no IPv6 address"));
+    }
+}
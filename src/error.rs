@@ -0,0 +1,76 @@
+use std::fmt;
+
+///
+/// Errors that abort a scraping run
+///
+/// # Variants
+///
+/// - Http: A request to the API or a provider failed
+/// - Io: Reading or writing a local file failed
+/// - Config: The scraper configuration or a local input file is invalid
+/// - FailureThreshold: More providers failed than the configured threshold allows
+/// - NoPrice: A provider scraped on demand yielded no price
+/// - NoProviders: A run had no providers to scrape
+#[derive(Debug)]
+pub enum ScraperError {
+    Http(reqwest::Error),
+    Io(std::io::Error),
+    Config(String),
+    FailureThreshold {
+        failure_pct: f64,
+        threshold_pct: f64,
+    },
+    NoPrice {
+        provider_id: i32,
+        reason: String,
+    },
+    NoProviders,
+}
+
+impl fmt::Display for ScraperError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ScraperError::Http(e) => write!(f, "HTTP error: {}", e),
+            ScraperError::Io(e) => write!(f, "IO error: {}", e),
+            ScraperError::Config(message) => write!(f, "Configuration error: {}", message),
+            ScraperError::FailureThreshold {
+                failure_pct,
+                threshold_pct,
+            } => write!(
+                f,
+                "{:.1}% of providers failed, more than the {:.1}% threshold",
+                failure_pct, threshold_pct
+            ),
+            ScraperError::NoPrice {
+                provider_id,
+                reason,
+            } => write!(f, "Provider {} yielded no price: {}", provider_id, reason),
+            ScraperError::NoProviders => write!(f, "No providers to scrape"),
+        }
+    }
+}
+
+impl std::error::Error for ScraperError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ScraperError::Http(e) => Some(e),
+            ScraperError::Io(e) => Some(e),
+            ScraperError::Config(_)
+            | ScraperError::FailureThreshold { .. }
+            | ScraperError::NoPrice { .. }
+            | ScraperError::NoProviders => None,
+        }
+    }
+}
+
+impl From<reqwest::Error> for ScraperError {
+    fn from(e: reqwest::Error) -> Self {
+        ScraperError::Http(e)
+    }
+}
+
+impl From<std::io::Error> for ScraperError {
+    fn from(e: std::io::Error) -> Self {
+        ScraperError::Io(e)
+    }
+}
@@ -0,0 +1,252 @@
+use regex::Regex;
+use scraper::ElementRef;
+use std::sync::LazyLock;
+
+use crate::provider::Provider;
+
+/// The number following a label, digits with the separators and spaces prices are written with
+static LABELLED_NUMBER: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"-?\d(?:[\d.,]|\s+\d)*").unwrap());
+
+///
+/// Extraction expression of a provider: a CSS selector, then the operation that reads the price text from every
+/// matched element, separated by `|`
+///
+/// ```text
+/// expression := selector "|" operation
+/// operation  := "text" | "attr(" name ")" | "regex(" pattern ")" | "after-label(" label ")"
+/// ```
+///
+/// Arguments may be wrapped in double quotes, e.g. `.info | after-label("Pris:")`
+///
+/// # Fields
+///
+/// - selector: String - The CSS selector of the elements
+/// - operation: Operation - What is read from each element
+#[derive(Clone, Debug)]
+pub(crate) struct Expression {
+    pub(crate) selector: String,
+    pub(crate) operation: Operation,
+}
+
+///
+/// What an extraction expression reads from a matched element
+///
+/// # Variants
+///
+/// - Text: The element's text
+/// - Attr: The value of an attribute, e.g. `data-price`; elements without it don't match
+/// - Regex: The first match of a regex in the element's text, its first capture group if it has one
+/// - AfterLabel: The number following a label in the element's text, compared case-insensitively, e.g. `Pris:`
+#[derive(Clone, Debug)]
+pub(crate) enum Operation {
+    Text,
+    Attr(String),
+    Regex(Regex),
+    AfterLabel(String),
+}
+
+impl Expression {
+    ///
+    /// Parse an extraction expression
+    /// The operation starts at the first `|` followed by an operation name, so selectors and patterns may contain `|`
+    ///
+    /// # Arguments
+    ///
+    /// - expression: &str - The expression, e.g. `td.price | attr(data-value)`
+    ///
+    /// # Returns
+    ///
+    /// Result<Expression, String> - The parsed expression
+    ///
+    /// # Errors
+    ///
+    /// If the selector or operation is missing, the operation is unknown or its argument is invalid, an error describing
+    /// the problem is returned
+    ///
+    pub(crate) fn parse(expression: &str) -> Result<Self, String> {
+        let (selector, operation) = expression
+            .match_indices('|')
+            .map(|(i, _)| (&expression[..i], expression[i + 1..].trim()))
+            .find(|(_, operation)| {
+                ["text", "attr(", "regex(", "after-label("]
+                    .iter()
+                    .any(|name| operation.starts_with(name))
+            })
+            .ok_or_else(|| {
+                format!(
+                    "expected `selector | operation` with one of text, attr(..), regex(..), after-label(..) in {:?}",
+                    expression
+                )
+            })?;
+        let selector = selector.trim();
+        if selector.is_empty() {
+            return Err(format!("missing selector before `|` in {:?}", expression));
+        }
+
+        Ok(Self {
+            selector: selector.to_string(),
+            operation: Operation::parse(operation)?,
+        })
+    }
+}
+
+impl Operation {
+    ///
+    /// Parse the operation of an extraction expression
+    ///
+    /// # Arguments
+    ///
+    /// - operation: &str - The operation, e.g. `regex(([0-9,]+) kr)`
+    ///
+    /// # Returns
+    ///
+    /// Result<Operation, String> - The parsed operation
+    ///
+    /// # Errors
+    ///
+    /// If the operation is unknown, its parentheses aren't closed or its argument is empty or an invalid regex, an error
+    /// is returned
+    ///
+    fn parse(operation: &str) -> Result<Self, String> {
+        if operation == "text" {
+            return Ok(Operation::Text);
+        }
+        let (name, argument) = operation
+            .split_once('(')
+            .and_then(|(name, rest)| Some((name, rest.strip_suffix(')')?)))
+            .ok_or_else(|| format!("unclosed or unknown operation {:?}", operation))?;
+        let argument = argument.trim();
+        let argument = argument
+            .strip_prefix('"')
+            .and_then(|argument| argument.strip_suffix('"'))
+            .unwrap_or(argument);
+        if argument.is_empty() {
+            return Err(format!("{} needs an argument", name));
+        }
+
+        match name {
+            "attr" => Ok(Operation::Attr(argument.to_string())),
+            "regex" => Regex::new(argument)
+                .map(Operation::Regex)
+                .map_err(|e| format!("invalid regex {:?}: {}", argument, e)),
+            "after-label" => Ok(Operation::AfterLabel(argument.to_lowercase())),
+            name => Err(format!("unknown operation {:?}", name)),
+        }
+    }
+
+    ///
+    /// Read the price text from an element matched by the expression's selector
+    ///
+    /// # Arguments
+    ///
+    /// - provider: &Provider - The provider, which decides how the element's text is read
+    /// - element: ElementRef - The matched element
+    ///
+    /// # Returns
+    ///
+    /// Option<String> - The price text, or None if the element doesn't have what the operation reads
+    ///
+    pub(crate) fn apply(&self, provider: &Provider, element: ElementRef) -> Option<String> {
+        match self {
+            Operation::Text => Some(provider.element_text(element)),
+            Operation::Attr(name) => element.value().attr(name).map(String::from),
+            Operation::Regex(regex) => {
+                let text = provider.element_text(element);
+                let captures = regex.captures(&text)?;
+                let found = captures.get(1).or_else(|| captures.get(0))?;
+                Some(found.as_str().to_string())
+            }
+            Operation::AfterLabel(label) => {
+                let text = provider.element_text(element);
+                // Lowercasing can change byte lengths, so the label is searched for in the lowercased text throughout
+                let lowercase = text.to_lowercase();
+                let after = &lowercase[lowercase.find(label.as_str())? + label.len()..];
+                let number = LABELLED_NUMBER.find(after)?.as_str();
+                Some(number.trim_end_matches([',', '.']).to_string())
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use scraper::{Html, Selector};
+
+    fn provider() -> Provider {
+        serde_json::from_value(serde_json::json!({
+            "id": 1,
+            "name": "Test",
+            "url": "http://localhost",
+        }))
+        .unwrap()
+    }
+
+    fn apply(expression: &str, page: &str) -> Vec<String> {
+        let expression = Expression::parse(expression).unwrap();
+        let selector = Selector::parse(&expression.selector).unwrap();
+        let document = Html::parse_document(page);
+        document
+            .select(&selector)
+            .filter_map(|element| expression.operation.apply(&provider(), element))
+            .collect()
+    }
+
+    #[test]
+    fn text_reads_the_element_text() {
+        assert_eq!(
+            apply(".price | text", r#"<p class="price">12,49 kr.</p>"#),
+            vec!["12,49 kr."]
+        );
+    }
+
+    #[test]
+    fn attr_reads_an_attribute_of_elements_that_have_it() {
+        let page = r#"<span data-price="12.49">Diesel</span><span>Blyfri</span>"#;
+
+        assert_eq!(apply("span | attr(data-price)", page), vec!["12.49"]);
+        assert_eq!(apply(r#"span | attr("data-price")"#, page), vec!["12.49"]);
+    }
+
+    #[test]
+    fn regex_reads_the_first_capture_group_or_the_whole_match() {
+        let page = "<p>Diesel (pr. liter): 12,49 kr.</p>";
+
+        assert_eq!(apply(r"p | regex(: ([0-9,]+) kr)", page), vec!["12,49"]);
+        assert_eq!(apply(r"p | regex([0-9]+,[0-9]{2})", page), vec!["12,49"]);
+        assert!(apply(r"p | regex(EUR)", page).is_empty());
+    }
+
+    #[test]
+    fn after_label_reads_the_number_after_the_label() {
+        let page = "<div>Opdateret 14.05. Pris: 1 299,50 kr. Diesel</div>";
+
+        assert_eq!(apply("div | after-label(pris:)", page), vec!["1 299,50"]);
+        assert_eq!(
+            apply(r#"div | after-label("Pris:")"#, page),
+            vec!["1 299,50"]
+        );
+        assert!(apply("div | after-label(Rabat:)", page).is_empty());
+    }
+
+    #[test]
+    fn selectors_and_patterns_may_contain_pipes() {
+        let expression = Expression::parse(r#"[lang|="da"] | regex((kr|DKK))"#).unwrap();
+
+        assert_eq!(expression.selector, r#"[lang|="da"]"#);
+        assert!(matches!(expression.operation, Operation::Regex(_)));
+    }
+
+    #[test]
+    fn malformed_expressions_are_clear_errors() {
+        let error = |expression: &str| Expression::parse(expression).unwrap_err();
+
+        assert!(error(".price").contains("expected `selector | operation`"));
+        assert!(error(".price | html").contains("expected `selector | operation`"));
+        assert!(error("| text").contains("missing selector"));
+        assert!(error(".price | attr()").contains("attr needs an argument"));
+        assert!(error(".price | attr(data-price").contains("unclosed"));
+        assert!(error(".price | regex([0-9)").contains("invalid regex"));
+    }
+}
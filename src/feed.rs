@@ -0,0 +1,91 @@
+///
+/// Read the values of a column of a CSV price feed, found by its header
+/// The delimiter is a semicolon if the header has one, as in Danish feeds with decimal commas, and a comma otherwise.
+/// Fields may be quoted with double quotes, with `""` for a quote inside a field; empty lines are skipped
+///
+/// # Arguments
+///
+/// - body: &str - The CSV feed, with a header line first
+/// - column: &str - The header of the column, compared case-insensitively
+///
+/// # Returns
+///
+/// Option<Vec<String>> - The values of the column in row order, or None if the feed has no such column
+///
+pub(crate) fn csv_column(body: &str, column: &str) -> Option<Vec<String>> {
+    let mut lines = body
+        .trim_start_matches('\u{feff}')
+        .lines()
+        .filter(|line| !line.trim().is_empty());
+    let header = lines.next()?;
+    let delimiter = if header.contains(';') { ';' } else { ',' };
+    let index = csv_fields(header, delimiter)
+        .iter()
+        .position(|name| name.trim().eq_ignore_ascii_case(column.trim()))?;
+
+    Some(
+        lines
+            .filter_map(|line| csv_fields(line, delimiter).into_iter().nth(index))
+            .collect(),
+    )
+}
+
+///
+/// Split a CSV line into its fields, unquoting quoted fields
+///
+/// # Arguments
+///
+/// - line: &str - The CSV line
+/// - delimiter: char - The field delimiter
+///
+/// # Returns
+///
+/// Vec<String> - The fields of the line
+///
+fn csv_fields(line: &str, delimiter: char) -> Vec<String> {
+    let mut fields = Vec::new();
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut chars = line.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if quoted && chars.peek() == Some(&'"') => {
+                field.push('"');
+                chars.next();
+            }
+            '"' => quoted = !quoted,
+            c if c == delimiter && !quoted => fields.push(std::mem::take(&mut field)),
+            c => field.push(c),
+        }
+    }
+    fields.push(field);
+    fields
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn csv_columns_are_found_by_header() {
+        let feed =
+            "station,product,price\nAarhus,Diesel,\"12,49\"\nOdense,\"Blyfri \"\"95\"\"\",13.29\n";
+
+        assert_eq!(
+            csv_column(feed, "Price"),
+            Some(vec!["12,49".to_string(), "13.29".to_string()])
+        );
+        assert_eq!(
+            csv_column(feed, "product"),
+            Some(vec!["Diesel".to_string(), "Blyfri \"95\"".to_string()])
+        );
+        assert_eq!(csv_column(feed, "pris"), None);
+    }
+
+    #[test]
+    fn semicolon_delimited_feeds_keep_decimal_commas() {
+        let feed = "\u{feff}Produkt;Pris\r\n\r\nDiesel;12,49\r\n";
+
+        assert_eq!(csv_column(feed, "pris"), Some(vec!["12,49".to_string()]));
+    }
+}
@@ -0,0 +1,123 @@
+use std::io::Write;
+use std::process::{Command, Stdio};
+use std::time::{Duration, Instant};
+
+/// How often a running hook is checked for having exited
+const POLL_INTERVAL: Duration = Duration::from_millis(20);
+
+///
+/// Run a user's shell command, e.g. the `--on-price` hook, and wait for it to exit
+/// The arguments are passed as the positional parameters `$1`, `$2`, ... of the shell and the input is written to stdin
+/// The command runs on the blocking thread pool and is killed once the timeout passes
+///
+/// # Arguments
+///
+/// - command: &str - The shell command
+/// - args: Vec<String> - The positional arguments
+/// - input: String - What is written to the command's stdin
+/// - timeout: Duration - How long the command may run
+///
+/// # Returns
+///
+/// Result<(), String> - Ok if the command exited successfully in time
+///
+/// # Errors
+///
+/// If the command can't be started, fails or times out, an error is returned
+///
+pub(crate) async fn run_hook(
+    command: &str,
+    args: Vec<String>,
+    input: String,
+    timeout: Duration,
+) -> Result<(), String> {
+    let command = command.to_string();
+    tokio::task::spawn_blocking(move || run_blocking(&command, &args, &input, timeout))
+        .await
+        .map_err(|e| format!("hook task failed: {}", e))?
+}
+
+fn run_blocking(
+    command: &str,
+    args: &[String],
+    input: &str,
+    timeout: Duration,
+) -> Result<(), String> {
+    let mut shell = if cfg!(windows) {
+        let mut shell = Command::new("cmd");
+        shell.arg("/C").arg(command);
+        shell
+    } else {
+        let mut shell = Command::new("sh");
+        // The first argument after the command becomes $0
+        shell.arg("-c").arg(command).arg("oliepriser-hook");
+        shell
+    };
+    let mut child = shell
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .spawn()
+        .map_err(|e| format!("failed to start: {}", e))?;
+
+    // A command that doesn't read its input closes stdin early, which isn't a failure
+    if let Some(mut stdin) = child.stdin.take() {
+        let _ = stdin.write_all(input.as_bytes());
+    }
+
+    let started = Instant::now();
+    loop {
+        match child.try_wait().map_err(|e| e.to_string())? {
+            Some(status) if status.success() => return Ok(()),
+            Some(status) => return Err(format!("exited with {}", status)),
+            None if started.elapsed() >= timeout => {
+                let _ = child.kill();
+                let _ = child.wait();
+                return Err(format!("timed out after {}s", timeout.as_secs_f64()));
+            }
+            None => std::thread::sleep(POLL_INTERVAL),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use uuid::Uuid;
+
+    #[tokio::test]
+    async fn hooks_get_arguments_and_input_and_are_bounded() {
+        if !cfg!(unix) {
+            return;
+        }
+        let output =
+            std::env::temp_dir().join(format!("oliepriser-hook-test-{}.txt", Uuid::new_v4()));
+
+        let command = format!(
+            r#"echo "$1 $2" > {} && cat >> {}"#,
+            output.display(),
+            output.display()
+        );
+        run_hook(
+            &command,
+            vec!["1".to_string(), "12.49".to_string()],
+            r#"{"price":12.49}"#.to_string(),
+            Duration::from_secs(5),
+        )
+        .await
+        .unwrap();
+        assert_eq!(
+            std::fs::read_to_string(&output).unwrap(),
+            "1 12.49\n{\"price\":12.49}"
+        );
+        std::fs::remove_file(&output).unwrap();
+
+        let failed = run_hook("exit 3", vec![], String::new(), Duration::from_secs(5)).await;
+        assert!(failed.unwrap_err().contains("exit status: 3"));
+
+        let started = Instant::now();
+        let slow = run_hook("sleep 5", vec![], String::new(), Duration::from_millis(100)).await;
+        assert!(slow.unwrap_err().contains("timed out"));
+        assert!(started.elapsed() < Duration::from_secs(5));
+    }
+}
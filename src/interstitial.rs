@@ -0,0 +1,66 @@
+use reqwest::header::HeaderMap;
+
+/// Markers in the HTML of anti-bot interstitials and CAPTCHA walls, compared with the lowercased page
+const BODY_MARKERS: [(&str, &str); 10] = [
+    ("<title>just a moment...</title>", "Cloudflare challenge"),
+    ("checking your browser", "browser check"),
+    ("cf-browser-verification", "Cloudflare challenge"),
+    ("/cdn-cgi/challenge-platform/", "Cloudflare challenge"),
+    ("attention required! | cloudflare", "Cloudflare block"),
+    ("_incapsula_resource", "Imperva challenge"),
+    ("captcha-delivery.com", "DataDome CAPTCHA"),
+    ("px-captcha", "PerimeterX CAPTCHA"),
+    ("class=\"g-recaptcha\"", "reCAPTCHA"),
+    ("class=\"h-captcha\"", "hCaptcha"),
+];
+
+///
+/// Recognise an anti-bot interstitial, e.g. Cloudflare's "checking your browser" page or a CAPTCHA wall, served instead
+/// of a provider page
+/// Cloudflare marks its challenges with a `cf-mitigated` header; other pages are recognised by markers in their HTML
+///
+/// # Arguments
+///
+/// - headers: Option<&HeaderMap> - The response headers, if the response is still at hand
+/// - body: &str - The response body
+///
+/// # Returns
+///
+/// Option<&'static str> - What kind of interstitial the page is, or None for a regular page
+///
+pub(crate) fn detect(headers: Option<&HeaderMap>, body: &str) -> Option<&'static str> {
+    if headers.is_some_and(|headers| headers.contains_key("cf-mitigated")) {
+        return Some("Cloudflare challenge");
+    }
+    let body = body.to_lowercase();
+    BODY_MARKERS
+        .iter()
+        .find(|(marker, _)| body.contains(marker))
+        .map(|(_, kind)| *kind)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use reqwest::header::HeaderValue;
+
+    #[test]
+    fn interstitials_are_recognised_by_header_or_markup() {
+        let mut headers = HeaderMap::new();
+        headers.insert("cf-mitigated", HeaderValue::from_static("challenge"));
+        assert_eq!(detect(Some(&headers), ""), Some("Cloudflare challenge"));
+
+        let challenge = "<html><head><title>Just a moment...</title></head></html>";
+        assert_eq!(detect(None, challenge), Some("Cloudflare challenge"));
+        let captcha = r#"<form><div class="g-recaptcha" data-sitekey="x"></div></form>"#;
+        assert_eq!(detect(None, captcha), Some("reCAPTCHA"));
+
+        assert_eq!(
+            detect(
+                Some(&HeaderMap::new()),
+                r#"<div class="price">12,49 kr.</div>"#
+            ),
+            None
+        );
+    }
+}
@@ -0,0 +1,35 @@
+mod backend;
+mod body;
+pub mod breaker;
+mod cache;
+pub mod concurrency;
+pub mod config;
+pub mod credentials;
+mod dns;
+pub mod error;
+mod expression;
+mod feed;
+mod hook;
+mod interstitial;
+#[cfg(feature = "otel")]
+mod otel;
+pub mod price;
+pub mod profile;
+mod provider;
+#[cfg(feature = "pubsub")]
+mod pubsub;
+#[cfg(feature = "render")]
+mod render;
+pub mod report;
+mod retry;
+pub mod scraper;
+pub mod shutdown;
+mod signing;
+pub mod snapshot;
+pub mod state;
+#[cfg(feature = "statsd")]
+mod statsd;
+mod status;
+pub mod tls;
+mod trace;
+pub mod updated_at;
@@ -1,44 +1,1559 @@
-use clap::Parser;
-use credentials::Credentials;
-use scraper::Scraper;
+use chrono::{DateTime, Utc};
+use clap::{CommandFactory, Parser, Subcommand, ValueEnum};
+use oliepriser_scraper::breaker::BreakerConfig;
+use oliepriser_scraper::concurrency::Concurrency;
+use oliepriser_scraper::config::{ApiPaths, HostOverride, HttpConfig, IpVersion, ScraperConfig};
+use oliepriser_scraper::credentials::{AuthScheme, Credentials, TokenSource};
+use oliepriser_scraper::price::{
+    implied_decimal_separator, parse_with_separators, sanitize_price_string, split_price_range,
+};
+use oliepriser_scraper::profile::profile_args;
+use oliepriser_scraper::scraper::{CompareTarget, Scraper, SelftestStatus};
+use oliepriser_scraper::shutdown::Shutdown;
+use oliepriser_scraper::snapshot::SnapshotDir;
+use oliepriser_scraper::state::StateFile;
+use oliepriser_scraper::tls::ClientTls;
+use oliepriser_scraper::updated_at::Timezone;
+use std::collections::VecDeque;
+use std::io::{IsTerminal, Write};
+use std::net::{IpAddr, SocketAddr};
+use std::path::PathBuf;
 use tokio::time;
 
-mod credentials;
-mod scraper;
 // Define the command-line arguments structure
 #[derive(Parser, Debug)]
-#[clap(name = "Scraper CLI", about = "A simple web scraper CLI application.")]
+// Subcommands skip clap's required flags so parse-price works offline, validate() checks them for the others
+#[clap(
+    name = "Scraper CLI",
+    about = "A simple web scraper CLI application.",
+    subcommand_negates_reqs = true
+)]
 struct Cli {
-    /// Base URL for the API
-    #[clap(short, long)]
-    base_api_url: String,
+    #[clap(subcommand)]
+    command: Option<Command>,
 
-    /// Username for authentication
+    /// Config file with named [profiles.<name>] tables of flags
+    #[clap(long, default_value = "oliepriser.toml")]
+    config: PathBuf,
+
+    /// Profile from the config file to take default flags from, flags given on the command line take precedence
+    #[clap(long)]
+    profile: Option<String>,
+
+    /// Don't log the effective configuration at startup
     #[clap(long)]
-    client_id: String,
+    no_log_config: bool,
+
+    /// Base URL for the API, repeatable or comma-separated for failover between replicas
+    #[clap(
+        short,
+        long,
+        required_unless_present = "providers_file",
+        value_delimiter = ','
+    )]
+    base_api_url: Vec<String>,
+
+    /// Username for authentication
+    #[clap(long, required_unless_present_any = ["providers_file", "api_key", "token_file", "token_command"])]
+    client_id: Option<String>,
 
     /// Password for authentication
+    #[clap(long, required_unless_present_any = ["providers_file", "api_key", "token_file", "token_command"])]
+    client_secret: Option<String>,
+
+    /// File holding the bearer token, read before every run instead of logging in with the client ID and secret
+    #[clap(long, conflicts_with = "token_command")]
+    token_file: Option<PathBuf>,
+
+    /// Shell command printing the bearer token, run before every run instead of logging in with the client ID and secret
+    #[clap(long)]
+    token_command: Option<String>,
+
+    /// How to authenticate to the API: a token from /auth/login, basic auth with the client ID and secret, or an API key header
+    #[clap(long, value_enum, default_value_t = AuthSchemeArg::Bearer)]
+    auth_scheme: AuthSchemeArg,
+
+    /// Scheme to send bearer tokens with, e.g. Bearer, instead of the token_type the login or token source returns
+    /// A missing token_type is sent as Bearer without it
+    #[clap(long)]
+    token_type: Option<String>,
+
+    /// API key sent with --auth-scheme apikey
+    #[clap(long, required_if_eq("auth_scheme", "apikey"))]
+    api_key: Option<String>,
+
+    /// Header the API key is sent in with --auth-scheme apikey
+    #[clap(long, default_value = "X-API-Key")]
+    api_key_header: String,
+
+    /// Local JSON/TOML file with full provider entries, scraped instead of the API's providers
+    #[clap(long)]
+    providers_file: Option<PathBuf>,
+
+    /// File to append scraped prices to as JSON lines when using --providers-file, instead of printing them
+    #[clap(long, requires = "providers_file")]
+    prices_output: Option<PathBuf>,
+
+    /// File to write every run's prices and provider statuses to, appended to as JSON lines for .jsonl files
+    #[clap(long)]
+    output_json: Option<PathBuf>,
+
+    /// File every run's stats (time, run ID, duration, provider counts, prices posted) are appended to,
+    /// as CSV for .csv files and JSON lines otherwise
+    #[clap(long)]
+    output_metrics_file: Option<PathBuf>,
+
+    /// File the providers whose prices were delivered are recorded in during a run, so a run interrupted by a crash
+    /// continues under the same run ID without scraping them again; cleared once the run is over
+    #[clap(long)]
+    resume_file: Option<PathBuf>,
+
+    /// JSON file the per-provider state is restored from at startup and saved to after every run, so caches,
+    /// last prices and circuit breakers survive restarts; kept in memory only without it
+    #[clap(long)]
+    state_file: Option<PathBuf>,
+
+    /// API path listing the providers to scrape
+    #[clap(long, default_value = "/scraping_runs/providers")]
+    providers_path: String,
+
+    /// API path of a provider's details, {id} is replaced with the provider ID
+    #[clap(long, default_value = "/providers/{id}")]
+    provider_detail_path: String,
+
+    /// API path prices of a provider are posted to, {id} is replaced with the provider ID
+    #[clap(long, default_value = "/providers/{id}/prices")]
+    prices_path: String,
+
+    /// API path the prices of a bulk post are posted to
+    #[clap(long, default_value = "/prices")]
+    bulk_prices_path: String,
+
+    /// API path runs are posted to
+    #[clap(long, default_value = "/scraping_runs")]
+    runs_path: String,
+
+    /// Maximum number of price posts sent to the API at once
+    #[clap(long, default_value_t = 10)]
+    post_concurrency: usize,
+
+    /// Milliseconds to wait after logging in before calling the API, for backends with slowly propagating tokens
+    #[clap(long, default_value_t = 0)]
+    auth_warmup_ms: u64,
+
+    /// Only scrape providers in this group, repeatable or comma-separated, all providers when not set
+    #[clap(long = "group", value_name = "GROUP", value_delimiter = ',')]
+    groups: Vec<String>,
+
+    /// Minimum seconds between scrapes of the providers in a group, as GROUP=SECS, repeatable
+    /// A provider's own poll_interval_secs takes precedence with --once-per-provider-interval
+    #[clap(long = "group-interval", value_name = "GROUP=SECS", value_parser = group_interval)]
+    group_intervals: Vec<(String, u64)>,
+
+    /// Fail a run that has no providers to scrape, instead of only warning about it
+    #[clap(long)]
+    fail_on_empty: bool,
+
+    /// Fail the run when more than this percentage of the scraped providers fail
+    #[clap(long)]
+    fail_threshold_pct: Option<f64>,
+
+    /// Only scrape providers whose own poll_interval_secs has passed since they were last scraped
+    #[clap(long)]
+    once_per_provider_interval: bool,
+
+    /// How many times a failed price post is retried before the price is dropped
+    #[clap(long, default_value_t = 3)]
+    price_retries: u32,
+
+    /// How many times a failed run post is retried
+    #[clap(long, default_value_t = 10)]
+    run_retries: u32,
+
+    /// Milliseconds before the first retry of a failed post, doubled for every following retry
+    #[clap(long, default_value_t = 500)]
+    retry_base_delay_ms: u64,
+
+    /// Reject prices whose decimals don't match the provider's expected_decimals, instead of only warning
+    #[clap(long)]
+    strict_decimals: bool,
+
+    /// Fail a provider as unparseable when any element its selector matches isn't a valid price, instead of skipping
+    /// the element, so format changes surface instead of hiding behind the other matches
+    #[clap(long)]
+    strict_parse: bool,
+
+    /// Send the time each page was fetched with its price, instead of letting the API timestamp prices on receipt
+    #[clap(long)]
+    post_scraped_at: bool,
+
+    /// Timezone the update times on provider pages are read in: Europe/Copenhagen, local for the host's, UTC or an
+    /// offset like +01:00
+    #[clap(long, default_value = "Europe/Copenhagen")]
+    updated_at_timezone: Timezone,
+
+    /// Fetch every provider page a second time and only post prices that hold, skipping flickering prices
+    #[clap(long)]
+    verify: bool,
+
+    /// Largest difference between the two fetches of --verify that still counts as the same price
+    #[clap(long, default_value_t = 0.001)]
+    verify_tolerance: f64,
+
+    /// Longest Retry-After of a rate limited provider page that is waited out to retry the provider once in the run
+    #[clap(long, default_value_t = 30)]
+    max_retry_after_secs: u64,
+
+    /// Skip a run when the API already has a run that ended less than this many seconds ago, e.g. from another
+    /// instance on an overlapping schedule
+    #[clap(long, conflicts_with = "providers_file")]
+    since_last_success_secs: Option<u64>,
+
+    /// Check the API returns providers and runs of the shape the scraper expects before doing anything else, and exit
+    /// naming the field that differs if it doesn't
+    #[clap(long, conflicts_with = "providers_file")]
+    validate_schema: bool,
+
+    /// Number of runs after startup that scrape without posting anything, to warm caches and connections first
+    #[clap(long, default_value_t = 0)]
+    warmup_runs: u32,
+
+    /// Post the prices of a run in batches to the bulk prices path instead of one request per provider
+    #[clap(long, conflicts_with = "providers_file")]
+    bulk_post: bool,
+
+    /// Maximum number of prices in a single bulk post
+    #[clap(long, default_value_t = 500)]
+    bulk_batch_size: usize,
+
+    /// WebDriver endpoint of the headless browser used for providers with render enabled
+    #[clap(long, default_value = "http://localhost:4444")]
+    webdriver_url: String,
+
+    /// Seconds to wait for a rendered page to show the provider's selector
+    #[clap(long, default_value_t = 10)]
+    render_timeout_secs: u64,
+
+    /// Maximum idle connections kept open per host, unlimited when not set
+    #[clap(long)]
+    pool_max_idle_per_host: Option<usize>,
+
+    /// Speak HTTP/2 to the API and providers without negotiating it first
+    #[clap(long)]
+    http2: bool,
+
+    /// Seconds between TCP keepalive probes on open connections, off when not set
+    #[clap(long)]
+    tcp_keepalive_secs: Option<u64>,
+
+    /// Seconds a request may take before it times out, no timeout when not set
+    /// A provider's own `timeout_secs` takes precedence for its page requests
+    #[clap(long)]
+    request_timeout_secs: Option<u64>,
+
+    /// Connect to a host at a fixed address instead of resolving it, as host:ip like curl's --resolve, repeatable
+    /// Meant for checking a provider page on a new server before its DNS is switched over
+    #[clap(long = "resolve", value_name = "HOST:IP")]
+    resolve: Vec<HostOverride>,
+
+    /// IP version to connect over, e.g. v4 for provider sites that misbehave over IPv6
+    #[clap(long, value_enum, default_value_t = IpVersionArg::Auto)]
+    ip_version: IpVersionArg,
+
+    /// Local address to connect from, to pick the egress interface on multi-homed hosts
+    #[clap(long)]
+    local_address: Option<IpAddr>,
+
+    /// Accept-Language sent with every request, so sites show Danish-formatted prices the price parser expects
+    /// A provider's accept_language overrides it; empty to send none
+    #[clap(long, default_value = "da-DK")]
+    accept_language: String,
+
+    /// Shared secret to sign price and run posts with, sent as an HMAC-SHA256 of the timestamp and body in X-Signature and X-Timestamp
+    #[clap(long)]
+    signing_secret: Option<String>,
+
+    /// PEM client certificate presented to the API for mutual TLS
+    #[clap(long, requires = "tls_key")]
+    tls_cert: Option<PathBuf>,
+
+    /// PEM PKCS#8 private key of the --tls-cert client certificate
+    #[clap(long, requires = "tls_cert")]
+    tls_key: Option<PathBuf>,
+
+    /// PEM CA certificate the API's certificate is verified against, in addition to the system roots
+    #[clap(long, requires = "tls_cert")]
+    tls_ca_cert: Option<PathBuf>,
+
+    /// URL to ping with a GET after every successful run, for dead man's switch monitoring
+    #[clap(long)]
+    heartbeat_url: Option<String>,
+
+    /// URL to ping with a GET after every failed run
+    #[clap(long)]
+    heartbeat_fail_url: Option<String>,
+
+    /// Compare every scraped price with the API's latest price for the provider and report the ones that differ
+    #[clap(long, conflicts_with = "providers_file")]
+    reconcile: bool,
+
+    /// Largest difference to the API's latest price that --reconcile doesn't report
+    #[clap(long, default_value_t = 0.001)]
+    reconcile_tolerance: f64,
+
+    /// URL to post a JSON alert to when a provider's price moves more than --alert-change-pct, e.g. a Slack or Discord webhook
+    #[clap(long)]
+    alert_webhook: Option<String>,
+
+    /// Percentage a provider's price has to move from its last scraped price to post an alert
+    #[clap(long, default_value_t = 5.0)]
+    alert_change_pct: f64,
+
+    /// Skip a provider for a cooldown after this many consecutive failures
+    #[clap(long)]
+    breaker_failures: Option<u32>,
+
+    /// Seconds a provider is skipped once its circuit breaker opens, before it is tried once again
+    #[clap(long, default_value_t = 1800)]
+    breaker_cooldown_secs: u64,
+
+    /// Directory to save pages that yield no price in, for debugging selectors
+    #[clap(long)]
+    debug_dir: Option<PathBuf>,
+
+    /// Maximum size of a saved page in bytes, larger pages are truncated
+    #[clap(long, default_value_t = 1024 * 1024)]
+    debug_max_bytes: usize,
+
+    /// Maximum number of saved pages to keep in the debug directory
+    #[clap(long, default_value_t = 100)]
+    debug_max_files: usize,
+
+    /// Seconds to sleep between scraping runs
+    #[clap(long, default_value_t = 60)]
+    interval_secs: u64,
+
+    /// Start a run every interval (fixed-rate), or sleep the full interval after each run (fixed-delay)
+    #[clap(long, value_enum, default_value_t = Schedule::FixedDelay)]
+    schedule: Schedule,
+
+    /// Address to serve the reports of the last runs on as JSON at /runs, e.g. 127.0.0.1:9100
+    #[clap(long)]
+    status_addr: Option<SocketAddr>,
+
+    /// Number of run reports kept for the status server
+    #[clap(long, default_value_t = 10)]
+    recent_runs: usize,
+
+    /// StatsD or DogStatsD endpoint to send run metrics to over UDP, e.g. localhost:8125
+    /// Requires building with the statsd feature
+    #[clap(long)]
+    statsd_addr: Option<String>,
+
+    /// Redis or NATS broker every scraped price is published to as JSON, e.g. redis://localhost:6379 or nats://localhost:4222
+    /// Requires building with the pubsub feature
+    #[clap(long)]
+    publish_url: Option<String>,
+
+    /// OTLP/HTTP collector to export a trace of every run to, e.g. http://localhost:4318
+    /// Each run is one trace keyed by its run ID, with spans for fetching the providers, each provider and each post
+    /// Requires building with the otel feature
+    #[clap(long)]
+    otlp_endpoint: Option<String>,
+
+    /// Redis channel or NATS subject prices are published on
+    #[clap(long, default_value = "oliepriser.prices")]
+    publish_subject: String,
+
+    /// Only publish prices to the broker, without posting them to the API
+    #[clap(long, requires = "publish_url")]
+    publish_only: bool,
+
+    /// Shell command run for every scraped price, getting the provider ID, name and price as $1, $2 and $3 and the price
+    /// as JSON on stdin; failures are logged and don't affect the run
+    #[clap(long, value_name = "COMMAND")]
+    on_price: Option<String>,
+
+    /// Seconds the --on-price command may run before it is killed
+    #[clap(long, default_value_t = 10)]
+    on_price_timeout_secs: u64,
+
+    /// Scrape providers in the order the API lists them, instead of sorted by ID
+    #[clap(long)]
+    preserve_provider_order: bool,
+
+    /// Scrape providers in a random order every run, so the same providers aren't always scraped last
+    #[clap(long, conflicts_with_all = ["preserve_provider_order", "rotate_providers"])]
+    shuffle_providers: bool,
+
+    /// Seed of --shuffle-providers, for a reproducible order
+    #[clap(long, requires = "shuffle_providers")]
+    shuffle_seed: Option<u64>,
+
+    /// Fraction of the scraped prices posted to the API, between 0 and 1, e.g. 0.1 to post a random tenth of them
+    /// during load tests; the other providers are still scraped
+    #[clap(long, default_value_t = 1.0)]
+    sample_rate: f64,
+
+    /// Seed of --sample-rate, for reproducible sampling decisions
     #[clap(long)]
-    client_secret: String,
+    sample_seed: Option<u64>,
+
+    /// Minimum seconds between two posted prices of the same provider, even if the price changed, so overlapping or
+    /// very frequent runs don't post duplicates; a provider's own min_post_interval_secs takes precedence
+    #[clap(long)]
+    min_post_interval_secs: Option<u64>,
+
+    /// Largest provider page in bytes that is read, larger pages fail the provider instead of being read into memory
+    #[clap(long, default_value_t = 10 * 1024 * 1024)]
+    max_response_bytes: usize,
+
+    /// Scrape at most this many providers per run, for staged rollouts or to shed load
+    #[clap(long)]
+    max_providers: Option<usize>,
+
+    /// Scrape the next --max-providers providers every run, so all of them are covered over time
+    #[clap(long, requires = "max_providers")]
+    rotate_providers: bool,
+
+    /// Adapt how many providers are scraped at once between runs, starting low and backing off on timeouts and rate limiting
+    #[clap(long)]
+    concurrency_auto: bool,
+
+    /// Send every provider page request with a Referer of the page's own origin, for sites rejecting requests without one
+    /// Providers can also ask for it on their own with send_referer
+    #[clap(long)]
+    send_referer: bool,
+
+    /// Send a HEAD request before fetching each provider page and skip the page if it responds 4xx or isn't HTML (or JSON)
+    /// Saves fetching obviously broken pages at the cost of an extra request for every healthy one
+    #[clap(long)]
+    preflight: bool,
+
+    /// Log the method, URL, headers, status and start of the body of every API and provider request, with secrets redacted
+    #[clap(long)]
+    trace_requests: bool,
+
+    /// Exit with a non-zero code once more than this many runs fail in a row, retry forever when unset
+    #[clap(long)]
+    max_consecutive_failures: Option<u32>,
+}
+
+impl Cli {
+    ///
+    /// Check the parsed flags for values that would only fail deep in a run
+    ///
+    /// # Returns
+    ///
+    /// Result<(), Vec<String>> - Every problem found, so they can be reported at once
+    ///
+    fn validate(&self) -> Result<(), Vec<String>> {
+        let mut problems = vec![];
+
+        if self.providers_file.is_none() {
+            if self.base_api_url.is_empty() {
+                problems.push("--base-api-url is required without --providers-file".to_string());
+            }
+            for base_url in &self.base_api_url {
+                if let Err(e) = http_url(base_url) {
+                    problems.push(format!("--base-api-url {:?} is invalid: {}", base_url, e));
+                    continue;
+                }
+                for (flag, path) in [
+                    ("--providers-path", &self.providers_path),
+                    ("--provider-detail-path", &self.provider_detail_path),
+                    ("--prices-path", &self.prices_path),
+                    ("--bulk-prices-path", &self.bulk_prices_path),
+                    ("--runs-path", &self.runs_path),
+                ] {
+                    if let Err(e) = ApiPaths::parse(base_url, path, 0) {
+                        problems.push(format!(
+                            "{} {:?} is invalid with --base-api-url {:?}: {}",
+                            flag, path, base_url, e
+                        ));
+                    }
+                }
+            }
+            let token_source = self.token_file.is_some() || self.token_command.is_some();
+            match self.auth_scheme {
+                AuthSchemeArg::Bearer if token_source => {}
+                AuthSchemeArg::Bearer | AuthSchemeArg::Basic => {
+                    for (flag, value) in [
+                        ("--client-id", &self.client_id),
+                        ("--client-secret", &self.client_secret),
+                    ] {
+                        if value.as_deref().is_none_or(|value| value.trim().is_empty()) {
+                            problems.push(format!("{} must not be empty", flag));
+                        }
+                    }
+                }
+                AuthSchemeArg::Apikey => {
+                    if self
+                        .api_key
+                        .as_deref()
+                        .is_none_or(|key| key.trim().is_empty())
+                    {
+                        problems.push("--api-key must not be empty".to_string());
+                    }
+                }
+            }
+        }
+        if reqwest::header::HeaderValue::from_str(&self.accept_language).is_err() {
+            problems.push(format!(
+                "--accept-language {:?} isn't a valid header value",
+                self.accept_language
+            ));
+        }
+        match (self.ip_version, self.local_address) {
+            (IpVersionArg::V4, Some(addr)) if addr.is_ipv6() => problems
+                .push("--local-address must be an IPv4 address with --ip-version v4".to_string()),
+            (IpVersionArg::V6, Some(addr)) if addr.is_ipv4() => problems
+                .push("--local-address must be an IPv6 address with --ip-version v6".to_string()),
+            _ => {}
+        }
+        for (flag, url) in [
+            ("--heartbeat-url", &self.heartbeat_url),
+            ("--heartbeat-fail-url", &self.heartbeat_fail_url),
+            ("--alert-webhook", &self.alert_webhook),
+            ("--otlp-endpoint", &self.otlp_endpoint),
+        ] {
+            if let Some(Err(e)) = url.as_deref().map(http_url) {
+                problems.push(format!("{} is invalid: {}", flag, e));
+            }
+        }
+        if self.otlp_endpoint.is_some() && !cfg!(feature = "otel") {
+            problems.push("--otlp-endpoint requires building with the otel feature".to_string());
+        }
+
+        for (flag, value) in [
+            ("--post-concurrency", self.post_concurrency as u64),
+            ("--bulk-batch-size", self.bulk_batch_size as u64),
+            ("--interval-secs", self.interval_secs),
+            ("--max-response-bytes", self.max_response_bytes as u64),
+            ("--debug-max-bytes", self.debug_max_bytes as u64),
+            ("--debug-max-files", self.debug_max_files as u64),
+        ] {
+            if value == 0 {
+                problems.push(format!("{} must be greater than 0", flag));
+            }
+        }
+        for (flag, value) in [
+            ("--request-timeout-secs", self.request_timeout_secs),
+            ("--breaker-failures", self.breaker_failures.map(u64::from)),
+            ("--max-providers", self.max_providers.map(|max| max as u64)),
+        ] {
+            if value == Some(0) {
+                problems.push(format!("{} must be greater than 0", flag));
+            }
+        }
+        if let Some(pct) = self.fail_threshold_pct {
+            if !(0.0..=100.0).contains(&pct) {
+                problems.push(format!(
+                    "--fail-threshold-pct must be between 0 and 100, got {}",
+                    pct
+                ));
+            }
+        }
+        if !(0.0..=1.0).contains(&self.sample_rate) {
+            problems.push(format!(
+                "--sample-rate must be between 0 and 1, got {}",
+                self.sample_rate
+            ));
+        }
+        if self.alert_change_pct.is_nan() || self.alert_change_pct < 0.0 {
+            problems.push(format!(
+                "--alert-change-pct must not be negative, got {}",
+                self.alert_change_pct
+            ));
+        }
+        if self.reconcile_tolerance.is_nan() || self.reconcile_tolerance < 0.0 {
+            problems.push(format!(
+                "--reconcile-tolerance must not be negative, got {}",
+                self.reconcile_tolerance
+            ));
+        }
+        if self.verify_tolerance.is_nan() || self.verify_tolerance < 0.0 {
+            problems.push(format!(
+                "--verify-tolerance must not be negative, got {}",
+                self.verify_tolerance
+            ));
+        }
+
+        if problems.is_empty() {
+            Ok(())
+        } else {
+            Err(problems)
+        }
+    }
+}
+
+///
+/// Check that a URL parses and is HTTP(S)
+///
+/// # Arguments
+///
+/// - url: &str - The URL to check
+///
+/// # Returns
+///
+/// Result<(), String> - Why the URL is invalid, if it is
+///
+fn http_url(url: &str) -> Result<(), String> {
+    let parsed = reqwest::Url::parse(url).map_err(|e| e.to_string())?;
+    if !matches!(parsed.scheme(), "http" | "https") {
+        return Err(format!("unsupported scheme {:?}", parsed.scheme()));
+    }
+    Ok(())
 }
 
+///
+/// Parse a group interval given as `GROUP=SECS`
+///
+/// # Arguments
+///
+/// - value: &str - The flag value
+///
+/// # Returns
+///
+/// Result<(String, u64), String> - The group and its interval in seconds
+///
+fn group_interval(value: &str) -> Result<(String, u64), String> {
+    let (group, secs) = value
+        .split_once('=')
+        .filter(|(group, _)| !group.is_empty())
+        .ok_or_else(|| format!("expected GROUP=SECS, got {:?}", value))?;
+    let secs = secs
+        .parse()
+        .map_err(|e| format!("invalid seconds {:?}: {}", secs, e))?;
+    Ok((group.to_string(), secs))
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Check every provider's selector against its live page without posting anything
+    Selftest,
+    /// Print every provider with the configuration the scraper would use, without scraping anything
+    ListProviders {
+        /// Print a table of the main fields, or every field as JSON
+        #[clap(long, value_enum, default_value_t = ListFormat::Table)]
+        format: ListFormat,
+    },
+    /// Re-run extraction over the pages saved in --debug-dir and post the recomputed prices with their original timestamps
+    Replay {
+        /// Only replay snapshots of this provider
+        #[clap(long)]
+        provider_id: Option<i32>,
+
+        /// Only replay snapshots taken at or after this RFC 3339 time, e.g. 2024-05-01T00:00:00Z
+        #[clap(long)]
+        since: Option<DateTime<Utc>>,
+    },
+    /// Parse a raw price string like the scraper would and print the price or why it can't be parsed, offline
+    ParsePrice {
+        /// The raw price text, e.g. "1.299,50 kr."
+        price: String,
+
+        /// Decimal separator the provider declares, the separators are guessed when neither is given
+        #[clap(long)]
+        decimal_sep: Option<char>,
+
+        /// Thousands separator the provider declares
+        #[clap(long)]
+        thousands_sep: Option<char>,
+    },
+    /// Fetch a page once and print what an old and a new selector extract from it, exiting non-zero if they differ
+    CompareSelectors {
+        /// Compare on the page of this provider, with its request and parsing settings
+        #[clap(long, required_unless_present = "url", conflicts_with = "url")]
+        provider_id: Option<i32>,
+
+        /// Compare on this page, fetched with a plain GET
+        #[clap(long)]
+        url: Option<String>,
+
+        /// The selector in use
+        old: String,
+
+        /// The selector to migrate to
+        new: String,
+    },
+    /// Fetch a page once and print the elements each selector or extraction expression matches on it and the prices they
+    /// parse to; without --selector, selectors are read from stdin one per line, for trying them out interactively
+    ProbeSelectors {
+        /// Probe the page of this provider, with its request and parsing settings
+        #[clap(long, required_unless_present = "url", conflicts_with = "url")]
+        provider_id: Option<i32>,
+
+        /// Probe this page, fetched with a plain GET
+        #[clap(long)]
+        url: Option<String>,
+
+        /// A selector to probe, may be repeated
+        #[clap(long = "selector")]
+        selectors: Vec<String>,
+    },
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+enum Schedule {
+    FixedRate,
+    FixedDelay,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+enum ListFormat {
+    Table,
+    Json,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+enum IpVersionArg {
+    Auto,
+    V4,
+    V6,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+enum AuthSchemeArg {
+    Bearer,
+    Basic,
+    Apikey,
+}
+
+/// Fraction of the interval a run may take before a warning is logged
+const RUN_DURATION_WARNING_RATIO: f64 = 0.8;
+
+/// Number of errors of the last failed runs kept for the summary logged when giving up
+const RECENT_FAILURES: usize = 10;
+
 #[tokio::main]
 async fn main() {
     // Parse the command-line arguments
-    let cli = Cli::parse();
+    let cli = Cli::parse_from(with_profile_args(std::env::args().collect()));
+    if let Some(Command::ParsePrice {
+        price,
+        decimal_sep,
+        thousands_sep,
+    }) = &cli.command
+    {
+        parse_price(price, *decimal_sep, *thousands_sep);
+        return;
+    }
+    if let Err(problems) = cli.validate() {
+        let message = format!("Invalid configuration:\n  - {}", problems.join("\n  - "));
+        Cli::command()
+            .error(clap::error::ErrorKind::ValueValidation, message)
+            .exit();
+    }
     let base_api_url = cli.base_api_url.clone();
-    let client_id = cli.client_id.clone();
-    let client_secret = cli.client_secret.clone();
+    let client_id = cli.client_id.clone().unwrap_or_default();
+    let client_secret = cli.client_secret.clone().unwrap_or_default();
+    let interval = time::Duration::from_secs(cli.interval_secs);
 
     // Create a new Scraper instance
-    let credentials = Credentials::new(client_id, client_secret);
-    let mut scraper = Scraper::new(base_api_url, credentials);
+    let auth_scheme = match cli.auth_scheme {
+        AuthSchemeArg::Bearer => AuthScheme::Bearer,
+        AuthSchemeArg::Basic => AuthScheme::Basic,
+        AuthSchemeArg::Apikey => AuthScheme::ApiKey {
+            header: cli.api_key_header.clone(),
+            key: cli.api_key.clone().unwrap_or_default(),
+        },
+    };
+    let mut credentials = Credentials::new(client_id, client_secret).with_scheme(auth_scheme);
+    if let Some(path) = &cli.token_file {
+        credentials = credentials.with_token_source(TokenSource::File(path.clone()));
+    } else if let Some(command) = &cli.token_command {
+        credentials = credentials.with_token_source(TokenSource::Command(command.clone()));
+    }
+    if let Some(token_type) = &cli.token_type {
+        credentials = credentials.with_token_type(token_type.clone());
+    }
+    let tls = match (&cli.tls_cert, &cli.tls_key) {
+        (Some(cert), Some(key)) => match ClientTls::load(cert, key, cli.tls_ca_cert.as_deref()) {
+            Ok(tls) => Some(tls),
+            Err(e) => Cli::command()
+                .error(clap::error::ErrorKind::InvalidValue, e)
+                .exit(),
+        },
+        _ => None,
+    };
+    let config = ScraperConfig {
+        providers_file: cli.providers_file.clone(),
+        prices_output: cli.prices_output.clone(),
+        output_json: cli.output_json.clone(),
+        metrics_file: cli.output_metrics_file.clone(),
+        resume_file: cli.resume_file.clone(),
+        post_concurrency: cli.post_concurrency,
+        snapshots: cli.debug_dir.clone().map(|dir| SnapshotDir {
+            dir,
+            max_bytes: cli.debug_max_bytes,
+            max_files: cli.debug_max_files,
+        }),
+        auth_warmup: time::Duration::from_millis(cli.auth_warmup_ms),
+        fail_on_empty: cli.fail_on_empty,
+        fail_threshold_pct: cli.fail_threshold_pct,
+        per_provider_intervals: cli.once_per_provider_interval,
+        groups: cli.groups.clone(),
+        group_intervals: cli
+            .group_intervals
+            .iter()
+            .map(|(group, secs)| (group.clone(), time::Duration::from_secs(*secs)))
+            .collect(),
+        price_retries: cli.price_retries,
+        run_retries: cli.run_retries,
+        retry_base_delay: time::Duration::from_millis(cli.retry_base_delay_ms),
+        strict_decimals: cli.strict_decimals,
+        strict_parse: cli.strict_parse,
+        post_scraped_at: cli.post_scraped_at,
+        updated_at_timezone: cli.updated_at_timezone,
+        verify: cli.verify,
+        verify_tolerance: cli.verify_tolerance,
+        max_retry_after: time::Duration::from_secs(cli.max_retry_after_secs),
+        since_last_success: cli.since_last_success_secs.map(time::Duration::from_secs),
+        warmup_runs: cli.warmup_runs,
+        bulk_post: cli.bulk_post,
+        bulk_batch_size: cli.bulk_batch_size,
+        webdriver_url: cli.webdriver_url.clone(),
+        render_timeout: time::Duration::from_secs(cli.render_timeout_secs),
+        heartbeat_url: cli.heartbeat_url.clone(),
+        heartbeat_fail_url: cli.heartbeat_fail_url.clone(),
+        reconcile: cli.reconcile,
+        reconcile_tolerance: cli.reconcile_tolerance,
+        alert_webhook: cli.alert_webhook.clone(),
+        alert_change_pct: cli.alert_change_pct,
+        breaker: cli.breaker_failures.map(|failure_threshold| BreakerConfig {
+            failure_threshold,
+            cooldown: time::Duration::from_secs(cli.breaker_cooldown_secs),
+        }),
+        status_addr: cli.status_addr,
+        recent_runs: cli.recent_runs,
+        statsd_addr: cli.statsd_addr.clone(),
+        publish_url: cli.publish_url.clone(),
+        otlp_endpoint: cli.otlp_endpoint.clone(),
+        publish_subject: cli.publish_subject.clone(),
+        publish_only: cli.publish_only,
+        on_price: cli.on_price.clone(),
+        on_price_timeout: time::Duration::from_secs(cli.on_price_timeout_secs),
+        preserve_provider_order: cli.preserve_provider_order,
+        shuffle_providers: cli.shuffle_providers,
+        shuffle_seed: cli.shuffle_seed,
+        sample_rate: cli.sample_rate,
+        sample_seed: cli.sample_seed,
+        min_post_interval: cli.min_post_interval_secs.map(time::Duration::from_secs),
+        max_response_bytes: cli.max_response_bytes,
+        max_providers: cli.max_providers,
+        rotate_providers: cli.rotate_providers,
+        adaptive_concurrency: cli.concurrency_auto,
+        tls,
+        paths: ApiPaths {
+            providers: cli.providers_path.clone(),
+            provider_detail: cli.provider_detail_path.clone(),
+            prices: cli.prices_path.clone(),
+            bulk_prices: cli.bulk_prices_path.clone(),
+            runs: cli.runs_path.clone(),
+        },
+        signing_secret: cli.signing_secret.clone(),
+        send_referer: cli.send_referer,
+        preflight: cli.preflight,
+        trace_requests: cli.trace_requests,
+        http: HttpConfig {
+            pool_max_idle_per_host: cli.pool_max_idle_per_host,
+            http2_prior_knowledge: cli.http2,
+            tcp_keepalive: cli.tcp_keepalive_secs.map(time::Duration::from_secs),
+            request_timeout: cli.request_timeout_secs.map(time::Duration::from_secs),
+            resolve: cli.resolve.clone(),
+            ip_version: match cli.ip_version {
+                IpVersionArg::Auto => IpVersion::Auto,
+                IpVersionArg::V4 => IpVersion::V4,
+                IpVersionArg::V6 => IpVersion::V6,
+            },
+            local_address: cli.local_address,
+            accept_language: Some(cli.accept_language.clone())
+                .filter(|language| !language.is_empty()),
+        },
+    };
+    if !cli.no_log_config {
+        log_effective_config(&cli, &config);
+    }
+    let mut scraper = match Scraper::new(base_api_url, credentials, config) {
+        Ok(scraper) => scraper,
+        Err(e) => Cli::command()
+            .error(clap::error::ErrorKind::InvalidValue, e)
+            .exit(),
+    };
+    if let Some(path) = &cli.state_file {
+        scraper = match scraper.with_state_store(Box::new(StateFile::new(path.clone()))) {
+            Ok(scraper) => scraper,
+            Err(e) => Cli::command()
+                .error(clap::error::ErrorKind::InvalidValue, e)
+                .exit(),
+        };
+    }
+    if cli.validate_schema {
+        if let Err(e) = scraper.validate_schema().await {
+            eprintln!("Failed to validate the API schema: {}", e);
+            std::process::exit(1);
+        }
+    }
+
+    match cli.command {
+        Some(Command::Selftest) => selftest(&mut scraper).await,
+        Some(Command::ListProviders { format }) => list_providers(&mut scraper, format).await,
+        Some(Command::Replay { provider_id, since }) => {
+            replay(&mut scraper, provider_id, since).await
+        }
+        Some(Command::CompareSelectors {
+            provider_id,
+            url,
+            old,
+            new,
+        }) => {
+            let target = match (provider_id, url) {
+                (Some(provider_id), _) => CompareTarget::Provider(provider_id),
+                (None, url) => CompareTarget::Url(url.unwrap_or_default()),
+            };
+            compare_selectors(&mut scraper, &target, &old, &new).await
+        }
+        Some(Command::ProbeSelectors {
+            provider_id,
+            url,
+            selectors,
+        }) => {
+            let target = match (provider_id, url) {
+                (Some(provider_id), _) => CompareTarget::Provider(provider_id),
+                (None, url) => CompareTarget::Url(url.unwrap_or_default()),
+            };
+            probe_selectors(&mut scraper, &target, &selectors).await
+        }
+        // Parsed offline before the configuration is validated
+        Some(Command::ParsePrice { .. }) => unreachable!(),
+        None => {
+            if let Err(e) = scraper.start_status_server().await {
+                eprintln!("Failed to start the status server: {}", e);
+                std::process::exit(1);
+            }
+            let shutdown = Shutdown::on_signals().unwrap_or_else(|e| {
+                eprintln!(
+                    "Failed to handle termination signals, runs won't be flushed on exit: {}",
+                    e
+                );
+                Shutdown::new()
+            });
+            let mut scraper = scraper.with_shutdown(shutdown.clone());
+            scrape_loop(
+                &mut scraper,
+                &shutdown,
+                interval,
+                cli.schedule,
+                cli.max_consecutive_failures,
+            )
+            .await
+        }
+    }
+}
+
+///
+/// Insert the flags of the selected profile before the command-line arguments
+/// Flags given on the command line are left out of the profile, so they take precedence
+///
+/// # Arguments
+///
+/// - args: Vec<String> - The command-line arguments, including the program name
+///
+/// # Returns
+///
+/// Vec<String> - The arguments with the profile's flags inserted after the program name
+///
+fn with_profile_args(args: Vec<String>) -> Vec<String> {
+    let Some(profile) = flag_value(&args, "profile") else {
+        return args;
+    };
+    let config = flag_value(&args, "config").unwrap_or_else(|| "oliepriser.toml".to_string());
+    let profile_flags = match profile_args(&PathBuf::from(config), &profile) {
+        Ok(flags) => flags,
+        Err(e) => Cli::command()
+            .error(clap::error::ErrorKind::InvalidValue, e)
+            .exit(),
+    };
+
+    let command = Cli::command();
+    let mut merged = vec![args[0].clone()];
+    for (flag, value) in profile_flags {
+        let short = command
+            .get_arguments()
+            .find(|arg| arg.get_long() == Some(flag.as_str()))
+            .and_then(|arg| arg.get_short());
+        let given = args[1..].iter().any(|arg| {
+            arg == &format!("--{}", flag)
+                || arg.starts_with(&format!("--{}=", flag))
+                || short.is_some_and(|short| arg.starts_with(&format!("-{}", short)))
+        });
+        if given {
+            continue;
+        }
+        merged.push(format!("--{}", flag));
+        merged.extend(value);
+    }
+    merged.extend_from_slice(&args[1..]);
+    merged
+}
+
+///
+/// Find the value of a long flag in the command-line arguments, given as `--name value` or `--name=value`
+///
+fn flag_value(args: &[String], name: &str) -> Option<String> {
+    let flag = format!("--{}", name);
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        if arg == &flag {
+            return args.next().cloned();
+        }
+        if let Some(value) = arg.strip_prefix(&format!("{}=", flag)) {
+            return Some(value.to_string());
+        }
+    }
+    None
+}
+
+///
+/// Replay the saved snapshots, print a summary and exit non-zero if replaying fails
+///
+/// # Arguments
+///
+/// - scraper: &mut Scraper - The scraper to replay the snapshots with
+/// - provider_id: Option<i32> - Only replay snapshots of this provider
+/// - since: Option<DateTime<Utc>> - Only replay snapshots taken at or after this time
+///
+async fn replay(scraper: &mut Scraper, provider_id: Option<i32>, since: Option<DateTime<Utc>>) {
+    match scraper.replay(provider_id, since).await {
+        Ok(report) => println!(
+            "Replayed {} snapshots, {} failed, {} skipped",
+            report.replayed, report.failed, report.skipped
+        ),
+        Err(e) => {
+            eprintln!("Replay failed: {}", e);
+            std::process::exit(1);
+        }
+    }
+}
+
+///
+/// Print the price a raw price string parses to, and exit non-zero if it doesn't parse
+/// Declared separators are used like a provider's `decimal_sep` and `thousands_sep`, without them the Danish
+/// convention is assumed
+/// A price range prints both bounds, as the provider's `range_bound` picks the price
+///
+/// # Arguments
+///
+/// - price: &str - The raw price text
+/// - decimal_sep: Option<char> - The declared decimal separator
+/// - thousands_sep: Option<char> - The declared thousands separator
+///
+fn parse_price(price: &str, decimal_sep: Option<char>, thousands_sep: Option<char>) {
+    let parse = |text: &str| match implied_decimal_separator(decimal_sep, thousands_sep) {
+        Some(decimal_sep) => parse_with_separators(text, decimal_sep, thousands_sep),
+        None => sanitize_price_string(text),
+    };
+    if let Some((from, to)) = split_price_range(price) {
+        match (parse(&from), parse(&to)) {
+            (Ok(from), Ok(to)) => println!("{:?} parses to the range {} to {}", price, from, to),
+            (Err(e), _) | (_, Err(e)) => {
+                eprintln!("{:?} doesn't parse: {}", price, e);
+                std::process::exit(1);
+            }
+        }
+        return;
+    }
+    match parse(price) {
+        Ok(parsed) => println!("{:?} parses to {}", price, parsed),
+        Err(e) => {
+            eprintln!("{:?} doesn't parse: {}", price, e);
+            std::process::exit(1);
+        }
+    }
+}
+
+///
+/// Print the providers the scraper would scrape, as a table or JSON, and exit non-zero if fetching them fails
+///
+/// # Arguments
+///
+/// - scraper: &mut Scraper - The scraper to fetch the providers with
+/// - format: ListFormat - Whether to print a table or JSON
+///
+async fn list_providers(scraper: &mut Scraper, format: ListFormat) {
+    let providers = match scraper.list_providers().await {
+        Ok(providers) => providers,
+        Err(e) => {
+            eprintln!("Failed to list providers: {}", e);
+            std::process::exit(1);
+        }
+    };
+
+    if let ListFormat::Json = format {
+        println!("{}", serde_json::to_string_pretty(&providers).unwrap());
+        return;
+    }
+    println!(
+        "{:<6} {:<30} {:<8} {:<30} URL",
+        "ID", "PROVIDER", "ENABLED", "SELECTOR"
+    );
+    for provider in &providers {
+        let selector = match (&provider["csv_column"], &provider["json_pointer"]) {
+            (serde_json::Value::String(column), _) if provider["feed_type"] == "csv" => {
+                format!("csv:{}", column)
+            }
+            (_, serde_json::Value::String(pointer)) => format!("json:{}", pointer),
+            _ => provider["html_element"]
+                .as_str()
+                .unwrap_or_default()
+                .to_string(),
+        };
+        println!(
+            "{:<6} {:<30} {:<8} {:<30} {}",
+            provider["id"].to_string(),
+            provider["name"].as_str().unwrap_or_default(),
+            provider["enabled"].to_string(),
+            selector,
+            provider["url"].as_str().unwrap_or_default()
+        );
+    }
+}
+
+///
+/// Describe what a selector yielded as a status and a value column
+///
+/// # Arguments
+///
+/// - status: &SelftestStatus - What the selector yielded
+///
+/// # Returns
+///
+/// (&'static str, String) - The status and the value to print
+///
+fn status_columns(status: &SelftestStatus) -> (&'static str, String) {
+    match status {
+        SelftestStatus::Matched(price) => ("ok", price.to_string()),
+        SelftestStatus::Unparseable(text) => ("unparseable", format!("{:?}", text)),
+        SelftestStatus::NoMatch => ("no-match", String::new()),
+        SelftestStatus::Failed(e) => ("failed", e.clone()),
+    }
+}
+
+///
+/// Compare two selectors on one page, print what each yields and exit non-zero if they don't yield the same price
+///
+/// # Arguments
+///
+/// - scraper: &mut Scraper - The scraper to fetch the page with
+/// - target: &CompareTarget - The provider or URL whose page is compared on
+/// - old: &str - The selector in use
+/// - new: &str - The selector to migrate to
+///
+async fn compare_selectors(scraper: &mut Scraper, target: &CompareTarget, old: &str, new: &str) {
+    let comparison = match scraper.compare_selectors(target, old, new).await {
+        Ok(comparison) => comparison,
+        Err(e) => {
+            eprintln!("Failed to compare selectors: {}", e);
+            std::process::exit(1);
+        }
+    };
+
+    println!("Page: {}", comparison.url);
+    println!("{:<6} {:<30} {:<12} VALUE", "", "SELECTOR", "STATUS");
+    for (label, selector, status) in [("old", old, &comparison.old), ("new", new, &comparison.new)]
+    {
+        let (status, value) = status_columns(status);
+        println!("{:<6} {:<30} {:<12} {}", label, selector, status, value);
+    }
+
+    if !comparison.matches() {
+        println!("MISMATCH: the selectors don't yield the same price");
+        std::process::exit(1);
+    }
+    println!("Both selectors yield the same price");
+}
+
+///
+/// Fetch a page once and print what each selector matches on it, reading the selectors from stdin when none are given
+///
+/// # Arguments
+///
+/// - scraper: &mut Scraper - The scraper to fetch the page with
+/// - target: &CompareTarget - The provider or URL whose page is probed
+/// - selectors: &[String] - The selectors to probe
+///
+async fn probe_selectors(scraper: &mut Scraper, target: &CompareTarget, selectors: &[String]) {
+    let page = match scraper.fetch_probe_page(target).await {
+        Ok(page) => page,
+        Err(e) => {
+            eprintln!("Failed to fetch the page to probe: {}", e);
+            std::process::exit(1);
+        }
+    };
+    println!("Page: {}", page.url());
+
+    let print_probe = |selector: &str| {
+        let probe = scraper.probe_selector(&page, selector);
+        let (status, value) = status_columns(&probe.status);
+        println!("{} => {} {}", probe.selector, status, value);
+        for (index, element) in probe.elements.iter().enumerate() {
+            let price = element
+                .price
+                .map_or("not a price".to_string(), |price| price.to_string());
+            println!(
+                "  {:<4} {:<50} {}",
+                index + 1,
+                format!("{:?}", element.text),
+                price
+            );
+        }
+    };
+    if !selectors.is_empty() {
+        selectors.iter().for_each(|selector| print_probe(selector));
+        return;
+    }
+
+    let interactive = std::io::stdin().is_terminal();
+    loop {
+        if interactive {
+            print!("selector> ");
+            let _ = std::io::stdout().flush();
+        }
+        let mut line = String::new();
+        match std::io::stdin().read_line(&mut line) {
+            Ok(0) => break,
+            Ok(_) if line.trim().is_empty() => continue,
+            Ok(_) => print_probe(line.trim()),
+            Err(e) => {
+                eprintln!("Failed to read a selector: {}", e);
+                std::process::exit(1);
+            }
+        }
+    }
+}
+
+///
+/// Run the selftest, print a summary table and exit non-zero if any provider fails to yield a valid price
+///
+/// # Arguments
+///
+/// - scraper: &mut Scraper - The scraper to run the selftest with
+///
+async fn selftest(scraper: &mut Scraper) {
+    let results = match scraper.selftest().await {
+        Ok(results) => results,
+        Err(e) => {
+            eprintln!("Selftest failed: {}", e);
+            std::process::exit(1);
+        }
+    };
+
+    println!("{:<6} {:<30} {:<12} VALUE", "ID", "PROVIDER", "STATUS");
+    let mut failures = 0;
+    for result in &results {
+        let (status, value) = status_columns(&result.status);
+        if !matches!(result.status, SelftestStatus::Matched(_)) {
+            failures += 1;
+        }
+        println!(
+            "{:<6} {:<30} {:<12} {}",
+            result.provider_id, result.provider_name, status, value
+        );
+    }
 
-    // Start the scraping loop
+    println!(
+        "{} of {} providers yielded a valid price",
+        results.len() - failures,
+        results.len()
+    );
+    if failures > 0 {
+        std::process::exit(1);
+    }
+}
+
+///
+/// Log the configuration the scraper runs with after the profile and command-line flags are merged, with secrets redacted
+///
+/// # Arguments
+///
+/// - cli: &Cli - The parsed command-line arguments
+/// - config: &ScraperConfig - The scraper tunables built from them
+///
+fn log_effective_config(cli: &Cli, config: &ScraperConfig) {
+    let optional = |value: Option<String>| value.unwrap_or_else(|| "none".to_string());
+    let secs = |duration: Option<time::Duration>| {
+        optional(duration.map(|d| format!("{}s", d.as_secs_f64())))
+    };
+    let path =
+        |path: &Option<PathBuf>| optional(path.as_ref().map(|path| path.display().to_string()));
+    let url = |url: &Option<String>| optional(url.as_deref().map(redact_url));
+    let features: Vec<&str> = [
+        ("render", cfg!(feature = "render")),
+        ("statsd", cfg!(feature = "statsd")),
+        ("pubsub", cfg!(feature = "pubsub")),
+        ("otel", cfg!(feature = "otel")),
+    ]
+    .into_iter()
+    .filter_map(|(feature, enabled)| enabled.then_some(feature))
+    .collect();
+
+    let source = match &config.providers_file {
+        Some(file) => format!("providers file {}", file.display()),
+        None => {
+            let urls: Vec<String> = cli.base_api_url.iter().map(|url| redact_url(url)).collect();
+            format!("API {}", urls.join(", "))
+        }
+    };
+    let auth = match (cli.auth_scheme, &cli.token_file, &cli.token_command) {
+        (AuthSchemeArg::Bearer, Some(file), _) => format!("bearer, token file {}", file.display()),
+        (AuthSchemeArg::Bearer, None, Some(_)) => "bearer, token command <redacted>".to_string(),
+        (AuthSchemeArg::Apikey, _, _) => {
+            format!("API key in {}, key <redacted>", cli.api_key_header)
+        }
+        (scheme, _, _) => format!(
+            "{:?}, client ID {}, client secret {}",
+            scheme,
+            optional(cli.client_id.clone()),
+            if cli.client_secret.is_some() {
+                "<redacted>"
+            } else {
+                "none"
+            }
+        )
+        .to_lowercase(),
+    };
+    let concurrency = Concurrency::new(config.adaptive_concurrency);
+
+    println!("Effective configuration:");
+    for (name, value) in [
+        ("source", source),
+        ("auth", auth),
+        (
+            "interval",
+            format!("{}s, {:?}", cli.interval_secs, cli.schedule),
+        ),
+        (
+            "concurrency",
+            format!("{}, {} price posts", concurrency, config.post_concurrency),
+        ),
+        (
+            "user agent",
+            "none, reqwest sends no User-Agent".to_string(),
+        ),
+        ("request timeout", secs(config.http.request_timeout)),
+        ("max response bytes", config.max_response_bytes.to_string()),
+        (
+            "retries",
+            format!(
+                "{} per price, {} per run, from {}ms",
+                config.price_retries,
+                config.run_retries,
+                config.retry_base_delay.as_millis()
+            ),
+        ),
+        (
+            "network",
+            format!(
+                "{}, local address {}, {} resolve overrides",
+                config.http.ip_version,
+                optional(config.http.local_address.map(|addr| addr.to_string())),
+                config.http.resolve.len()
+            ),
+        ),
+        (
+            "accept language",
+            optional(config.http.accept_language.clone()),
+        ),
+        ("client certificate", path(&cli.tls_cert)),
+        (
+            "signing",
+            if config.signing_secret.is_some() {
+                "on, secret <redacted>"
+            } else {
+                "off"
+            }
+            .to_string(),
+        ),
+        ("update time zone", config.updated_at_timezone.to_string()),
+        ("state file", path(&cli.state_file)),
+        ("resume file", path(&config.resume_file)),
+        ("metrics file", path(&config.metrics_file)),
+        ("heartbeat", url(&config.heartbeat_url)),
+        ("broker", url(&config.publish_url)),
+        (
+            "price command",
+            optional(config.on_price.as_ref().map(|_| "<redacted>".to_string())),
+        ),
+        (
+            "status server",
+            optional(
+                config
+                    .status_addr
+                    .map(|addr| format!("{}, last {} runs", addr, config.recent_runs)),
+            ),
+        ),
+        (
+            "sampling",
+            if config.sample_rate < 1.0 {
+                format!("post {}% of prices", config.sample_rate * 100.0)
+            } else {
+                "off".to_string()
+            },
+        ),
+        (
+            "min post interval",
+            optional(
+                config
+                    .min_post_interval
+                    .map(|interval| format!("{}s", interval.as_secs())),
+            ),
+        ),
+        ("statsd", optional(config.statsd_addr.clone())),
+        ("otlp", url(&config.otlp_endpoint)),
+        (
+            "groups",
+            if config.groups.is_empty() {
+                "all".to_string()
+            } else {
+                config.groups.join(", ")
+            },
+        ),
+        (
+            "features",
+            if features.is_empty() {
+                "none".to_string()
+            } else {
+                features.join(", ")
+            },
+        ),
+    ] {
+        println!("  {:<20} {}", name, value);
+    }
+}
+
+///
+/// Hide the password and query of a URL, which may carry credentials
+///
+/// # Arguments
+///
+/// - url: &str - The URL
+///
+/// # Returns
+///
+/// String - The URL with its password and query replaced by `redacted`
+///
+fn redact_url(url: &str) -> String {
+    let Ok(mut parsed) = reqwest::Url::parse(url) else {
+        return "<redacted>".to_string();
+    };
+    if parsed.password().is_some() {
+        let _ = parsed.set_password(Some("redacted"));
+    }
+    if parsed.query().is_some() {
+        parsed.set_query(Some("redacted"));
+    }
+    parsed.to_string()
+}
+
+///
+/// Run the scraper in a loop, sleeping for the interval between runs, until shutdown is requested
+/// A run in progress when shutdown is requested is cut short and posted as interrupted before the loop exits
+///
+/// # Arguments
+///
+/// - scraper: &mut Scraper - The scraper to run
+/// - shutdown: &Shutdown - Stops the loop once requested
+/// - interval: time::Duration - The time to sleep between runs
+/// - schedule: Schedule - Whether the run duration counts towards the interval
+/// - max_consecutive_failures: Option<u32> - Exit non-zero once more runs than this fail in a row, retry forever when None
+///
+async fn scrape_loop(
+    scraper: &mut Scraper,
+    shutdown: &Shutdown,
+    interval: time::Duration,
+    schedule: Schedule,
+    max_consecutive_failures: Option<u32>,
+) {
+    let mut failures = 0;
+    let mut recent_failures: VecDeque<String> = VecDeque::with_capacity(RECENT_FAILURES);
     loop {
         println!("Starting scraping run");
-        scraper.run().await.unwrap();
-        println!("Scrape finished, sleeping for 60 seconds");
-        time::sleep(time::Duration::from_secs(60)).await;
+        let started = time::Instant::now();
+        let report = match scraper.run().await {
+            Ok(report) => {
+                failures = 0;
+                recent_failures.clear();
+                report.to_string()
+            }
+            Err(e) => {
+                eprintln!("Scraping run failed: {}", e);
+                if shutdown.requested() {
+                    return;
+                }
+                failures += 1;
+                if recent_failures.len() == RECENT_FAILURES {
+                    recent_failures.pop_front();
+                }
+                recent_failures.push_back(e.to_string());
+                if max_consecutive_failures.is_some_and(|max| failures > max as usize) {
+                    eprintln!("Giving up after {} consecutive failed runs:", failures);
+                    let first = failures - recent_failures.len();
+                    for (run, failure) in recent_failures.iter().enumerate() {
+                        eprintln!("  run {}: {}", first + run + 1, failure);
+                    }
+                    std::process::exit(1);
+                }
+                format!("failed, {} in a row", failures)
+            }
+        };
+        if shutdown.requested() {
+            println!("Scrape finished ({}), shutting down", report);
+            return;
+        }
+        // A failed run has no end time, so fall back to the wall clock
+        let elapsed = if failures == 0 {
+            scraper
+                .run_duration()
+                .and_then(|duration| duration.to_std().ok())
+                .unwrap_or_default()
+        } else {
+            started.elapsed()
+        };
+
+        if elapsed >= interval {
+            eprintln!(
+                "Warning: run took {:.1}s, overrunning the {}s interval; skipping sleep. Consider lowering concurrency or raising --interval-secs",
+                elapsed.as_secs_f64(),
+                interval.as_secs()
+            );
+            continue;
+        }
+        if elapsed.as_secs_f64() > interval.as_secs_f64() * RUN_DURATION_WARNING_RATIO {
+            eprintln!(
+                "Warning: run took {:.1}s, close to the {}s interval. Consider lowering concurrency or raising --interval-secs",
+                elapsed.as_secs_f64(),
+                interval.as_secs()
+            );
+        }
+
+        // Fixed-rate aims for a run every interval on the wall clock, so the run itself counts towards it
+        let sleep = match schedule {
+            Schedule::FixedRate => interval - elapsed,
+            Schedule::FixedDelay => interval,
+        };
+        println!(
+            "Scrape finished in {:.1}s ({}), sleeping for {:.1} seconds",
+            elapsed.as_secs_f64(),
+            report,
+            sleep.as_secs_f64()
+        );
+        tokio::select! {
+            _ = time::sleep(sleep) => {}
+            _ = shutdown.wait() => {
+                println!("Shutting down between runs");
+                return;
+            }
+        }
     }
 }
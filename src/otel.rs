@@ -0,0 +1,324 @@
+use chrono::{DateTime, Utc};
+use rand::Rng;
+use reqwest::Client;
+use serde_json::{json, Value};
+use std::sync::Mutex;
+use std::time::Duration;
+use uuid::Uuid;
+
+/// Name the spans are reported under, as the `service.name` resource attribute
+const SERVICE_NAME: &str = "oliepriser-scraper";
+
+/// How long exporting the spans of a run may take, so an unreachable collector never stalls the scraping loop
+const EXPORT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Span kind of the root span, which doesn't leave the scraper
+const SPAN_KIND_INTERNAL: u8 = 1;
+
+/// Span kind of every child span, each one an outgoing request
+const SPAN_KIND_CLIENT: u8 = 3;
+
+///
+/// A finished span of a run
+///
+/// # Fields
+///
+/// - name: &'static str - The name of the span, e.g. `fetch_provider`
+/// - span_id: String - The hex encoded ID of the span
+/// - parent_span_id: Option<String> - The hex encoded ID of the parent span, None for the root span
+/// - start: DateTime<Utc> - When the span started
+/// - end: DateTime<Utc> - When the span ended
+/// - attributes: Vec<(&'static str, Value)> - The attributes of the span, e.g. the provider ID
+/// - error: Option<String> - Why the span failed, None if it succeeded
+struct Span {
+    name: &'static str,
+    span_id: String,
+    parent_span_id: Option<String>,
+    start: DateTime<Utc>,
+    end: DateTime<Utc>,
+    attributes: Vec<(&'static str, Value)>,
+    error: Option<String>,
+}
+
+impl Span {
+    ///
+    /// Convert the span to its OTLP JSON form
+    ///
+    /// # Arguments
+    ///
+    /// - trace_id: &str - The hex encoded ID of the trace the span belongs to
+    ///
+    /// # Returns
+    ///
+    /// Value - The span as OTLP JSON
+    ///
+    fn to_otlp(&self, trace_id: &str) -> Value {
+        let mut span = json!({
+            "traceId": trace_id,
+            "spanId": self.span_id,
+            "name": self.name,
+            "kind": SPAN_KIND_CLIENT,
+            "startTimeUnixNano": unix_nanos(self.start),
+            "endTimeUnixNano": unix_nanos(self.end),
+            "attributes": self.attributes.iter().map(|(key, value)| attribute(key, value)).collect::<Vec<_>>(),
+            "status": match &self.error {
+                Some(message) => json!({ "code": 2, "message": message }),
+                None => json!({ "code": 1 }),
+            },
+        });
+        match &self.parent_span_id {
+            Some(parent_span_id) => span["parentSpanId"] = json!(parent_span_id),
+            None => span["kind"] = json!(SPAN_KIND_INTERNAL),
+        }
+        span
+    }
+}
+
+///
+/// Trace of a single run, collecting its spans until the run is over and they are exported together
+/// The trace ID is the run ID, so the trace can be looked up from the run record in the API and vice versa
+///
+/// # Fields
+///
+/// - trace_id: String - The hex encoded ID of the trace, the run ID without dashes
+/// - root_span_id: String - The hex encoded ID of the `run` span every other span is a child of
+/// - spans: Mutex<Vec<Span>> - The finished spans of the run
+pub(crate) struct RunTrace {
+    trace_id: String,
+    root_span_id: String,
+    spans: Mutex<Vec<Span>>,
+}
+
+impl RunTrace {
+    ///
+    /// Start the trace of a run
+    ///
+    /// # Arguments
+    ///
+    /// - run_id: Uuid - The ID of the run
+    ///
+    /// # Returns
+    ///
+    /// RunTrace - The trace, without any spans yet
+    ///
+    pub(crate) fn new(run_id: Uuid) -> Self {
+        RunTrace {
+            trace_id: run_id.simple().to_string(),
+            root_span_id: random_span_id(),
+            spans: Mutex::new(Vec::new()),
+        }
+    }
+
+    ///
+    /// Record a span of the run that ends now
+    ///
+    /// # Arguments
+    ///
+    /// - name: &'static str - The name of the span
+    /// - start: DateTime<Utc> - When the span started
+    /// - attributes: Vec<(&'static str, Value)> - The attributes of the span
+    /// - error: Option<String> - Why the span failed, None if it succeeded
+    ///
+    pub(crate) fn record(
+        &self,
+        name: &'static str,
+        start: DateTime<Utc>,
+        attributes: Vec<(&'static str, Value)>,
+        error: Option<String>,
+    ) {
+        self.spans.lock().unwrap().push(Span {
+            name,
+            span_id: random_span_id(),
+            parent_span_id: Some(self.root_span_id.clone()),
+            start,
+            end: Utc::now(),
+            attributes,
+            error,
+        });
+    }
+
+    ///
+    /// End the trace with the `run` span and build the OTLP export request for it
+    ///
+    /// # Arguments
+    ///
+    /// - start: DateTime<Utc> - When the run started
+    /// - attributes: Vec<(&'static str, Value)> - The attributes of the run span
+    /// - error: Option<String> - Why the run failed, None if it succeeded
+    ///
+    /// # Returns
+    ///
+    /// Value - The OTLP JSON export request with every span of the run
+    ///
+    pub(crate) fn finish(
+        self,
+        start: DateTime<Utc>,
+        attributes: Vec<(&'static str, Value)>,
+        error: Option<String>,
+    ) -> Value {
+        let mut spans = self.spans.into_inner().unwrap();
+        spans.push(Span {
+            name: "run",
+            span_id: self.root_span_id,
+            parent_span_id: None,
+            start,
+            end: Utc::now(),
+            attributes,
+            error,
+        });
+
+        json!({
+            "resourceSpans": [{
+                "resource": {
+                    "attributes": [attribute("service.name", &json!(SERVICE_NAME))],
+                },
+                "scopeSpans": [{
+                    "scope": { "name": SERVICE_NAME, "version": env!("CARGO_PKG_VERSION") },
+                    "spans": spans.iter().map(|span| span.to_otlp(&self.trace_id)).collect::<Vec<_>>(),
+                }],
+            }],
+        })
+    }
+}
+
+///
+/// Export the spans of a run to an OTLP/HTTP collector as JSON
+///
+/// # Arguments
+///
+/// - client: &Client - The client the spans are sent with
+/// - endpoint: &str - The collector URL, e.g. `http://localhost:4318`, `/v1/traces` is appended unless already there
+/// - request: &Value - The export request, as built by `RunTrace::finish`
+///
+/// # Returns
+///
+/// Result<(), reqwest::Error> - The result of the export request
+///
+/// # Errors
+///
+/// If the collector can't be reached or rejects the spans, an error is returned
+///
+pub(crate) async fn export(
+    client: &Client,
+    endpoint: &str,
+    request: &Value,
+) -> Result<(), reqwest::Error> {
+    let endpoint = endpoint.trim_end_matches('/');
+    let url = if endpoint.ends_with("/v1/traces") {
+        endpoint.to_string()
+    } else {
+        format!("{}/v1/traces", endpoint)
+    };
+    client
+        .post(url)
+        .timeout(EXPORT_TIMEOUT)
+        .json(request)
+        .send()
+        .await?
+        .error_for_status()?;
+    Ok(())
+}
+
+///
+/// Convert an attribute to its OTLP JSON form, integers are strings as OTLP encodes 64-bit integers that way
+///
+/// # Arguments
+///
+/// - key: &str - The name of the attribute
+/// - value: &Value - The value of the attribute
+///
+/// # Returns
+///
+/// Value - The attribute as OTLP JSON
+///
+fn attribute(key: &str, value: &Value) -> Value {
+    let value = match value {
+        Value::Bool(value) => json!({ "boolValue": value }),
+        Value::Number(number) if number.is_i64() || number.is_u64() => {
+            json!({ "intValue": number.to_string() })
+        }
+        Value::Number(number) => json!({ "doubleValue": number.as_f64() }),
+        Value::String(value) => json!({ "stringValue": value }),
+        value => json!({ "stringValue": value.to_string() }),
+    };
+    json!({ "key": key, "value": value })
+}
+
+///
+/// Generate a random span ID, never all zeroes as OTLP treats that as invalid
+///
+/// # Returns
+///
+/// String - The hex encoded span ID
+///
+fn random_span_id() -> String {
+    let id: u64 = rand::thread_rng().gen_range(1..=u64::MAX);
+    format!("{:016x}", id)
+}
+
+///
+/// Get a time as nanoseconds since the Unix epoch, as a string like OTLP encodes it
+///
+/// # Arguments
+///
+/// - time: DateTime<Utc> - The time
+///
+/// # Returns
+///
+/// String - The nanoseconds since the Unix epoch
+///
+fn unix_nanos(time: DateTime<Utc>) -> String {
+    time.timestamp_nanos_opt().unwrap_or_default().to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use wiremock::matchers::{method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    #[tokio::test]
+    async fn run_spans_are_exported_as_one_trace_keyed_by_run_id() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/v1/traces"))
+            .respond_with(ResponseTemplate::new(200))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let run_id = Uuid::new_v4();
+        let trace = RunTrace::new(run_id);
+        let start = Utc::now();
+        trace.record(
+            "fetch_provider",
+            start,
+            vec![
+                ("provider.id", json!(1)),
+                ("provider.status", json!("failed")),
+            ],
+            Some("timeout".to_string()),
+        );
+        let request = trace.finish(start, vec![("run.id", json!(run_id))], None);
+        export(&Client::new(), &server.uri(), &request)
+            .await
+            .unwrap();
+
+        let spans = &request["resourceSpans"][0]["scopeSpans"][0]["spans"];
+        let (provider, run) = (&spans[0], &spans[1]);
+        assert_eq!(provider["traceId"], json!(run_id.simple().to_string()));
+        assert_eq!(run["traceId"], provider["traceId"]);
+        assert_eq!(provider["parentSpanId"], run["spanId"]);
+        assert_eq!(run.get("parentSpanId"), None);
+        assert_eq!(
+            provider["attributes"][0],
+            json!({ "key": "provider.id", "value": { "intValue": "1" } })
+        );
+        assert_eq!(
+            provider["status"],
+            json!({ "code": 2, "message": "timeout" })
+        );
+        assert_eq!(run["status"], json!({ "code": 1 }));
+        server.verify().await;
+    }
+}
@@ -0,0 +1,498 @@
+use serde::{Deserialize, Serialize};
+
+///
+/// Decode HTML entities and normalize every Unicode whitespace variant to a plain space
+/// Scraped text is usually decoded by the HTML parser already, but double-escaped markup and
+/// JSON sources can still carry entities like `&nbsp;`
+///
+/// # Arguments
+///
+/// - text: &str - The raw price text
+///
+/// # Returns
+///
+/// String - The text with entities decoded and whitespace normalized
+///
+pub(crate) fn normalize_price_text(text: &str) -> String {
+    decode_entities(text)
+        .chars()
+        .map(|c| {
+            // Zero-width characters aren't `White_Space` but still split numbers on some sites
+            if c.is_whitespace() || matches!(c, '\u{200B}' | '\u{2060}' | '\u{FEFF}') {
+                ' '
+            } else {
+                c
+            }
+        })
+        .collect()
+}
+
+///
+/// Count the decimals written in a price string, the digits after the last decimal separator
+///
+/// # Arguments
+///
+/// - text: &str - The raw price text
+/// - decimal_sep: char - The decimal separator, `,` for Danish prices
+///
+/// # Returns
+///
+/// usize - The number of decimals, 0 for whole prices like `1.299,-`
+///
+pub(crate) fn decimal_places(text: &str, decimal_sep: char) -> usize {
+    let text = normalize_price_text(text).replace(&format!("{}-", decimal_sep), "");
+    match text.rsplit_once(decimal_sep) {
+        Some((_, decimals)) => decimals.chars().filter(char::is_ascii_digit).count(),
+        None => 0,
+    }
+}
+
+///
+/// Sanitize a price string by removing unwanted characters and whitespace and parsing it to a float value
+/// The last `,` is the decimal separator and `.` separates thousands, so `1.234,567` parses to 1234.567
+///
+/// # Arguments
+///
+/// - price_string: &str - The price string to sanitize
+///
+/// # Returns
+///
+/// Result<f64, String> - The result of the sanitization
+///
+/// # Errors
+///
+/// If the price string cannot be parsed to a float, an error is returned
+///
+pub fn sanitize_price_string(price_string: &str) -> Result<f64, String> {
+    // Remove unwanted characters and whitespace
+    let sanitized: String = normalize_price_text(price_string)
+        .replace("kr.", "")
+        .replace(",-", "")
+        .replace(|c: char| c.is_whitespace(), "");
+
+    let (integer, decimals) = sanitized.rsplit_once(',').unwrap_or((&sanitized, ""));
+    // Every group after a thousands separator has exactly three digits, anything else is ambiguous
+    let mut groups = integer.split('.');
+    let leading = groups.next().unwrap_or_default();
+    let mut integer_digits = leading.to_string();
+    for group in groups {
+        if group.len() != 3 || leading.is_empty() {
+            return Err(format!(
+                "Ambiguous thousands separator in price: {:?}",
+                price_string
+            ));
+        }
+        integer_digits.push_str(group);
+    }
+
+    let number = if decimals.is_empty() {
+        integer_digits
+    } else {
+        format!("{}.{}", integer_digits, decimals)
+    };
+    number
+        .parse::<f64>()
+        .map_err(|e| format!("Failed to parse price: {}", e))
+}
+
+///
+/// Split a price range like `12,49 – 13,20 kr.` into its bounds
+/// The bounds may be separated by a hyphen, en dash, em dash or minus sign, with or without spaces around it; a dash only
+/// separates a range between two amounts, so negative prices and whole prices like `1.299,-` aren't ranges
+///
+/// # Arguments
+///
+/// - text: &str - The raw price text
+///
+/// # Returns
+///
+/// Option<(String, String)> - The text of the lower and upper bound as written, or None if the text isn't a range
+///
+pub fn split_price_range(text: &str) -> Option<(String, String)> {
+    let text = normalize_price_text(text);
+    let (index, dash) = text.char_indices().find(|&(i, c)| {
+        matches!(c, '-' | '\u{2012}' | '\u{2013}' | '\u{2014}' | '\u{2212}')
+            && ends_with_amount(&text[..i])
+            && text[i + c.len_utf8()..]
+                .trim_start()
+                .starts_with(|c: char| c.is_ascii_digit())
+    })?;
+
+    let (low, high) = (&text[..index], &text[index + dash.len_utf8()..]);
+    Some((low.trim().to_string(), high.trim().to_string()))
+}
+
+/// Whether text ends with an amount, allowing a currency or whole-price suffix after it as in `1.299,- – 1.399,-`
+fn ends_with_amount(text: &str) -> bool {
+    let text = text.trim_end();
+    let text = ["kr.", "kr", ",-"]
+        .iter()
+        .find_map(|suffix| text.strip_suffix(suffix))
+        .unwrap_or(text);
+    text.trim_end().ends_with(|c: char| c.is_ascii_digit())
+}
+
+///
+/// Get the decimal separator of a separator convention, implied by the thousands separator when only that is given
+///
+/// # Arguments
+///
+/// - decimal_sep: Option<char> - The declared decimal separator
+/// - thousands_sep: Option<char> - The declared thousands separator
+///
+/// # Returns
+///
+/// Option<char> - The decimal separator, or None if the separators have to be guessed
+///
+pub fn implied_decimal_separator(
+    decimal_sep: Option<char>,
+    thousands_sep: Option<char>,
+) -> Option<char> {
+    match (decimal_sep, thousands_sep) {
+        (Some(decimal_sep), _) => Some(decimal_sep),
+        (None, Some(',')) => Some('.'),
+        (None, Some(_)) => Some(','),
+        (None, None) => None,
+    }
+}
+
+///
+/// Parse a price string with known separators, without guessing which separator is which
+///
+/// # Arguments
+///
+/// - text: &str - The raw price text
+/// - decimal_sep: char - The decimal separator
+/// - thousands_sep: Option<char> - The thousands separator, if the prices use one
+///
+/// # Returns
+///
+/// Result<f64, String> - The parsed price
+///
+/// # Errors
+///
+/// If the text isn't a number written with the given separators, an error is returned
+///
+pub fn parse_with_separators(
+    text: &str,
+    decimal_sep: char,
+    thousands_sep: Option<char>,
+) -> Result<f64, String> {
+    let mut sanitized: String = normalize_price_text(text)
+        .replace("kr.", "")
+        .replace(&format!("{}-", decimal_sep), "")
+        .replace(|c: char| c.is_whitespace(), "");
+    if let Some(thousands_sep) = thousands_sep {
+        sanitized = sanitized.replace(thousands_sep, "");
+    }
+
+    sanitized
+        .replace(decimal_sep, ".")
+        .parse::<f64>()
+        .map_err(|e| format!("Failed to parse price {:?}: {}", text, e))
+}
+
+///
+/// Decode named and numeric HTML entities, leaving unknown entities untouched
+///
+fn decode_entities(text: &str) -> String {
+    let mut decoded = String::with_capacity(text.len());
+    let mut rest = text;
+
+    while let Some(start) = rest.find('&') {
+        decoded.push_str(&rest[..start]);
+        rest = &rest[start..];
+
+        let entity = rest[1..]
+            .find(';')
+            .map(|end| &rest[1..end + 1])
+            .and_then(|name| decode_entity(name).map(|c| (name.len() + 2, c)));
+        match entity {
+            Some((len, c)) => {
+                decoded.push(c);
+                rest = &rest[len..];
+            }
+            None => {
+                decoded.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    decoded.push_str(rest);
+    decoded
+}
+
+fn decode_entity(name: &str) -> Option<char> {
+    if let Some(hex) = name.strip_prefix("#x").or_else(|| name.strip_prefix("#X")) {
+        return u32::from_str_radix(hex, 16).ok().and_then(char::from_u32);
+    }
+    if let Some(decimal) = name.strip_prefix('#') {
+        return decimal.parse().ok().and_then(char::from_u32);
+    }
+
+    match name {
+        "nbsp" => Some('\u{A0}'),
+        "thinsp" => Some('\u{2009}'),
+        "ensp" => Some('\u{2002}'),
+        "emsp" => Some('\u{2003}'),
+        "amp" => Some('&'),
+        "lt" => Some('<'),
+        "gt" => Some('>'),
+        "quot" => Some('"'),
+        "apos" => Some('\''),
+        "ndash" => Some('\u{2013}'),
+        "minus" => Some('\u{2212}'),
+        "euro" => Some('\u{20AC}'),
+        _ => None,
+    }
+}
+
+///
+/// A step of a provider's price transformation pipeline, written as `kind:argument` like `divide:100`
+///
+/// # Variants
+///
+/// - Multiply: Multiply the price, e.g. to convert between currencies (`multiply:7.46`)
+/// - Divide: Divide the price, e.g. prices per 100 liters to prices per liter (`divide:100`)
+/// - Add: Add a fixed amount, e.g. a delivery fee (`add:0.5`)
+/// - Subtract: Subtract a fixed amount (`subtract:0.5`)
+/// - AddVat: Add VAT at a rate, e.g. `add_vat:0.25` for 25% Danish VAT
+/// - RemoveVat: Remove VAT included at a rate (`remove_vat:0.25`)
+/// - Round: Round to a number of decimals (`round:2`)
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, PartialEq)]
+#[serde(try_from = "String", into = "String")]
+pub(crate) enum PriceTransform {
+    Multiply(f64),
+    Divide(f64),
+    Add(f64),
+    Subtract(f64),
+    AddVat(f64),
+    RemoveVat(f64),
+    Round(u32),
+}
+
+impl PriceTransform {
+    ///
+    /// Apply the transformation to a price
+    ///
+    /// # Arguments
+    ///
+    /// - price: f64 - The price before the transformation
+    ///
+    /// # Returns
+    ///
+    /// f64 - The transformed price
+    ///
+    pub(crate) fn apply(&self, price: f64) -> f64 {
+        match *self {
+            PriceTransform::Multiply(factor) => price * factor,
+            PriceTransform::Divide(divisor) => price / divisor,
+            PriceTransform::Add(amount) => price + amount,
+            PriceTransform::Subtract(amount) => price - amount,
+            PriceTransform::AddVat(rate) => price * (1.0 + rate),
+            PriceTransform::RemoveVat(rate) => price / (1.0 + rate),
+            PriceTransform::Round(decimals) => {
+                let factor = 10f64.powi(decimals as i32);
+                (price * factor).round() / factor
+            }
+        }
+    }
+}
+
+impl TryFrom<String> for PriceTransform {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        let (kind, argument) = value.split_once(':').ok_or_else(|| {
+            format!(
+                "price transform {:?} has no argument, expected kind:argument",
+                value
+            )
+        })?;
+        let number = || {
+            argument
+                .trim()
+                .parse::<f64>()
+                .ok()
+                .filter(|number| number.is_finite())
+                .ok_or_else(|| format!("price transform {:?} has an invalid number", value))
+        };
+
+        match kind.trim() {
+            "multiply" => Ok(PriceTransform::Multiply(number()?)),
+            "divide" => {
+                let divisor = number()?;
+                if divisor == 0.0 {
+                    return Err(format!("price transform {:?} divides by zero", value));
+                }
+                Ok(PriceTransform::Divide(divisor))
+            }
+            "add" => Ok(PriceTransform::Add(number()?)),
+            "subtract" => Ok(PriceTransform::Subtract(number()?)),
+            "add_vat" | "remove_vat" => {
+                let rate = number()?;
+                if !(0.0..1.0).contains(&rate) {
+                    return Err(format!(
+                        "price transform {:?} needs a VAT rate between 0 and 1, e.g. 0.25",
+                        value
+                    ));
+                }
+                Ok(if kind.trim() == "add_vat" {
+                    PriceTransform::AddVat(rate)
+                } else {
+                    PriceTransform::RemoveVat(rate)
+                })
+            }
+            "round" => argument
+                .trim()
+                .parse::<u32>()
+                .ok()
+                .filter(|decimals| *decimals <= 10)
+                .map(PriceTransform::Round)
+                .ok_or_else(|| format!("price transform {:?} needs 0 to 10 decimals", value)),
+            _ => Err(format!(
+                "unknown price transform {:?}, expected one of multiply, divide, add, subtract, add_vat, remove_vat or round",
+                kind
+            )),
+        }
+    }
+}
+
+impl From<PriceTransform> for String {
+    fn from(transform: PriceTransform) -> Self {
+        match transform {
+            PriceTransform::Multiply(factor) => format!("multiply:{}", factor),
+            PriceTransform::Divide(divisor) => format!("divide:{}", divisor),
+            PriceTransform::Add(amount) => format!("add:{}", amount),
+            PriceTransform::Subtract(amount) => format!("subtract:{}", amount),
+            PriceTransform::AddVat(rate) => format!("add_vat:{}", rate),
+            PriceTransform::RemoveVat(rate) => format!("remove_vat:{}", rate),
+            PriceTransform::Round(decimals) => format!("round:{}", decimals),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn normalize_price_text_decodes_entities_and_whitespace() {
+        assert_eq!(normalize_price_text("1&nbsp;299,50 kr."), "1 299,50 kr.");
+        assert_eq!(normalize_price_text("1\u{A0}299\u{2009}kr"), "1 299 kr");
+        assert_eq!(normalize_price_text("12&#44;49&#x20;kr"), "12,49 kr");
+        assert_eq!(normalize_price_text("A & B &unknown;"), "A & B &unknown;");
+    }
+
+    #[test]
+    fn decimal_places_counts_digits_after_the_decimal_separator() {
+        assert_eq!(decimal_places("12,499 kr.", ','), 3);
+        assert_eq!(decimal_places("1.234,56", ','), 2);
+        assert_eq!(decimal_places("1.299,-", ','), 0);
+        assert_eq!(decimal_places("1.299", ','), 0);
+        assert_eq!(decimal_places("1,234.56", '.'), 2);
+    }
+
+    #[test]
+    fn parse_with_separators_follows_the_declared_convention() {
+        assert_eq!(parse_with_separators("12.49", '.', None), Ok(12.49));
+        assert_eq!(
+            parse_with_separators("1,234.567", '.', Some(',')),
+            Ok(1234.567)
+        );
+        assert_eq!(
+            parse_with_separators("1.234,567 kr.", ',', Some('.')),
+            Ok(1234.567)
+        );
+        assert_eq!(parse_with_separators("1 299,50", ',', None), Ok(1299.5));
+        assert_eq!(
+            parse_with_separators("1'299.-", '.', Some('\'')),
+            Ok(1299.0)
+        );
+        // 1.299 is 1299 with `.` thousands, a decimal price with `.` decimals
+        assert_eq!(parse_with_separators("1.299", ',', Some('.')), Ok(1299.0));
+        assert_eq!(parse_with_separators("1.299", '.', Some(',')), Ok(1.299));
+        assert!(parse_with_separators("1,234.56", ',', None).is_err());
+    }
+
+    #[test]
+    fn price_transforms_parse_and_apply_in_order() {
+        let transforms: Vec<PriceTransform> =
+            serde_json::from_value(serde_json::json!(["divide:100", "add_vat:0.25", "round:2"]))
+                .unwrap();
+        let price = transforms
+            .iter()
+            .fold(1000.0, |price, transform| transform.apply(price));
+        assert_eq!(price, 12.5);
+        assert_eq!(
+            serde_json::to_value(&transforms).unwrap(),
+            serde_json::json!(["divide:100", "add_vat:0.25", "round:2"])
+        );
+
+        assert!(PriceTransform::try_from("remove_vat:0.25".to_string())
+            .is_ok_and(|transform| transform.apply(12.5) == 10.0));
+        for invalid in ["divide", "divide:0", "add_vat:25", "round:-1", "scale:2"] {
+            assert!(
+                PriceTransform::try_from(invalid.to_string()).is_err(),
+                "{} should be rejected",
+                invalid
+            );
+        }
+    }
+
+    #[test]
+    fn price_ranges_are_split_at_the_dash_between_amounts() {
+        let range = |low: &str, high: &str| Some((low.to_string(), high.to_string()));
+
+        assert_eq!(split_price_range("12,49 - 13,20"), range("12,49", "13,20"));
+        assert_eq!(
+            split_price_range("12,49\u{2013}13,20 kr."),
+            range("12,49", "13,20 kr.")
+        );
+        assert_eq!(
+            split_price_range("12,49 kr. \u{2014} 13,20 kr."),
+            range("12,49 kr.", "13,20 kr.")
+        );
+        // Thousands separators, whole prices and non-breaking spaces mixed within one range
+        assert_eq!(
+            split_price_range("1.299,-&nbsp;-1.349,50"),
+            range("1.299,-", "1.349,50")
+        );
+        assert_eq!(
+            split_price_range("1 299,50 \u{2212} 1.349,-"),
+            range("1 299,50", "1.349,-")
+        );
+
+        assert_eq!(split_price_range("12,49 kr."), None);
+        assert_eq!(split_price_range("1.299,-"), None);
+        assert_eq!(split_price_range("-12,49"), None);
+        assert_eq!(split_price_range("Pris: - 12,49"), None);
+    }
+
+    #[test]
+    fn sanitize_price_string_handles_danish_formats() {
+        assert_eq!(sanitize_price_string("12,49 kr."), Ok(12.49));
+        assert_eq!(sanitize_price_string("1.299,-"), Ok(1299.0));
+        assert!(sanitize_price_string("kr.").is_err());
+    }
+
+    #[test]
+    fn sanitize_price_string_keeps_mill_precision() {
+        assert_eq!(sanitize_price_string("12,499 kr."), Ok(12.499));
+        assert_eq!(sanitize_price_string("1234,567"), Ok(1234.567));
+        assert_eq!(sanitize_price_string("1.234,567 kr."), Ok(1234.567));
+        assert_eq!(sanitize_price_string("1.234.567,125"), Ok(1234567.125));
+        assert!(sanitize_price_string("12.49").is_err());
+        assert!(sanitize_price_string("1,234,567").is_err());
+    }
+
+    #[test]
+    fn sanitize_price_string_handles_entities_and_non_breaking_spaces() {
+        assert_eq!(sanitize_price_string("1&nbsp;299,50 kr."), Ok(1299.5));
+        assert_eq!(
+            sanitize_price_string("1\u{A0}299,50\u{202F}kr."),
+            Ok(1299.5)
+        );
+        assert_eq!(sanitize_price_string("2\u{2009}499,-"), Ok(2499.0));
+    }
+}
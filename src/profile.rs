@@ -0,0 +1,130 @@
+use std::path::Path;
+
+use crate::error::ScraperError;
+
+///
+/// Read a named profile from the `[profiles.<name>]` table of a TOML config file
+/// Every key is a command-line flag, e.g. `base_api_url = "http://localhost:8000"` for `--base-api-url`
+///
+/// # Arguments
+///
+/// - path: &Path - The config file
+/// - name: &str - The profile to read
+///
+/// # Returns
+///
+/// Result<Vec<(String, Option<String>)>, ScraperError> - The long flag names and their values, None for enabled boolean flags
+///
+/// # Errors
+///
+/// If the file cannot be read or parsed, or has no profile with the given name, an error is returned
+///
+pub fn profile_args(
+    path: &Path,
+    name: &str,
+) -> Result<Vec<(String, Option<String>)>, ScraperError> {
+    let contents = std::fs::read_to_string(path)?;
+    let config: toml::Table = toml::from_str(&contents).map_err(|e| {
+        ScraperError::Config(format!(
+            "Failed to parse config file {}: {}",
+            path.display(),
+            e
+        ))
+    })?;
+
+    let profiles = match config.get("profiles") {
+        Some(toml::Value::Table(profiles)) => profiles.clone(),
+        _ => toml::Table::new(),
+    };
+    let Some(toml::Value::Table(profile)) = profiles.get(name) else {
+        let available: Vec<&str> = profiles.keys().map(String::as_str).collect();
+        return Err(ScraperError::Config(format!(
+            "Unknown profile {:?} in {}, available profiles: {}",
+            name,
+            path.display(),
+            if available.is_empty() {
+                "none".to_string()
+            } else {
+                available.join(", ")
+            }
+        )));
+    };
+
+    let mut args = vec![];
+    for (key, value) in profile {
+        let flag = key.replace('_', "-");
+        let value = match value {
+            toml::Value::String(value) => Some(value.clone()),
+            toml::Value::Integer(value) => Some(value.to_string()),
+            toml::Value::Float(value) => Some(value.to_string()),
+            toml::Value::Boolean(true) => None,
+            toml::Value::Boolean(false) => continue,
+            toml::Value::Array(values) => Some(
+                values
+                    .iter()
+                    .map(|value| match value {
+                        toml::Value::String(value) => value.clone(),
+                        value => value.to_string(),
+                    })
+                    .collect::<Vec<_>>()
+                    .join(","),
+            ),
+            _ => {
+                return Err(ScraperError::Config(format!(
+                    "Unsupported value for {:?} in profile {:?}",
+                    key, name
+                )))
+            }
+        };
+        args.push((flag, value));
+    }
+    Ok(args)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use uuid::Uuid;
+
+    #[test]
+    fn profile_args_reads_named_profile() {
+        let path =
+            std::env::temp_dir().join(format!("oliepriser-profile-test-{}.toml", Uuid::new_v4()));
+        std::fs::write(
+            &path,
+            r#"
+            [profiles.staging]
+            base_api_url = ["http://staging-1", "http://staging-2"]
+            client_id = "scraper"
+            interval_secs = 300
+            bulk_post = true
+            http2 = false
+
+            [profiles.local]
+            base_api_url = "http://localhost:8000"
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            profile_args(&path, "staging").unwrap(),
+            vec![
+                (
+                    "base-api-url".to_string(),
+                    Some("http://staging-1,http://staging-2".to_string())
+                ),
+                ("bulk-post".to_string(), None),
+                ("client-id".to_string(), Some("scraper".to_string())),
+                ("interval-secs".to_string(), Some("300".to_string())),
+            ]
+        );
+
+        let error = profile_args(&path, "prod").unwrap_err().to_string();
+        assert!(
+            error.contains("available profiles: local, staging"),
+            "{}",
+            error
+        );
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,779 @@
+use reqwest::header::{HeaderValue, ACCEPT_LANGUAGE, REFERER};
+use reqwest::{Client, RequestBuilder, Url};
+use scraper::{ElementRef, Selector};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::time::Duration;
+
+use crate::error::ScraperError;
+use crate::price::{implied_decimal_separator, PriceTransform};
+
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub(crate) struct Providers {
+    pub(crate) id: i32,
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub(crate) struct Provider {
+    pub(crate) id: i32,
+    pub(crate) name: String,
+    pub(crate) url: String,
+    #[serde(default)]
+    pub(crate) html_element: String,
+    /// Extraction expression used instead of `html_element`, e.g. `.info | after-label(Pris:)`, see `Expression`
+    #[serde(default)]
+    pub(crate) expression: Option<String>,
+    /// Format of the response, an HTML page unless the provider publishes a CSV or JSON price feed
+    #[serde(default)]
+    pub(crate) feed_type: FeedType,
+    /// Scrape the response as JSON at this pointer (e.g. `/data/price`) instead of using `html_element`
+    #[serde(default)]
+    pub(crate) json_pointer: Option<String>,
+    /// Name of the column holding the price in a CSV feed, compared case-insensitively
+    #[serde(default)]
+    pub(crate) csv_column: Option<String>,
+    /// How one price is picked or aggregated from several matches, also accepted as `aggregation`
+    #[serde(default, alias = "aggregation")]
+    pub(crate) price_selection: PriceSelection,
+    /// Which bound of a price range like `12,49 – 13,20 kr.` is the price, the lower bound unless the provider says otherwise
+    #[serde(default)]
+    pub(crate) range_bound: RangeBound,
+    /// Read the price from the table cells under or beside this header in the tables matched by `html_element`
+    #[serde(default)]
+    pub(crate) table: Option<ProviderTable>,
+    /// Read when the page says the price was last updated from this selector, or JSON pointer for JSON providers or
+    /// column for CSV feeds, and post that time instead of the scrape time
+    #[serde(default)]
+    pub(crate) updated_at_selector: Option<String>,
+    /// Only read the text directly inside matched elements, ignoring text in nested elements like labels or badges
+    #[serde(default)]
+    pub(crate) direct_text: bool,
+    #[serde(default = "default_enabled")]
+    pub(crate) enabled: bool,
+    #[serde(default)]
+    pub(crate) fuel_type: Option<String>,
+    /// Text that must appear in a matched element for it to count as a price, e.g. `kr`, compared case-insensitively
+    #[serde(default)]
+    pub(crate) expected_marker: Option<String>,
+    /// Number of decimals prices are written with, e.g. 3 for prices in mills, unchecked when None
+    #[serde(default)]
+    pub(crate) expected_decimals: Option<usize>,
+    /// Decimal separator of the provider's prices, guessed from the price text when neither separator is set
+    #[serde(default)]
+    pub(crate) decimal_sep: Option<char>,
+    /// Thousands separator of the provider's prices, none when only the decimal separator is set
+    #[serde(default)]
+    pub(crate) thousands_sep: Option<char>,
+    /// Transformations applied in order to every parsed price, e.g. `["divide:100", "add_vat:0.25"]`
+    #[serde(default)]
+    pub(crate) transforms: Vec<PriceTransform>,
+    /// Currency of the scraped price, Danish kroner unless the provider says otherwise
+    #[serde(default = "default_currency")]
+    pub(crate) currency: String,
+    /// Group the provider belongs to, e.g. a region, for splitting providers across scraper processes
+    #[serde(default)]
+    pub(crate) group: Option<String>,
+    /// Minimum seconds between scrapes of this provider, scraped every run when None
+    #[serde(default)]
+    pub(crate) poll_interval_secs: Option<u64>,
+    /// Minimum seconds between posted prices of this provider, replacing the global `--min-post-interval-secs`
+    #[serde(default)]
+    pub(crate) min_post_interval_secs: Option<u64>,
+    /// Load the page in a headless browser and scrape the rendered DOM, for pages that render prices client-side
+    #[serde(default)]
+    pub(crate) render: bool,
+    #[serde(default)]
+    pub(crate) method: RequestMethod,
+    /// Form fields sent URL-encoded as the request body, e.g. a region selector that reveals the price
+    #[serde(default)]
+    pub(crate) form: Option<BTreeMap<String, String>>,
+    /// Seconds the page request may take, replacing the global `--request-timeout-secs` for this provider
+    #[serde(default)]
+    pub(crate) timeout_secs: Option<u64>,
+    /// `Accept-Language` sent for the page instead of the global `--accept-language`, e.g. `en-GB`; set `decimal_sep` and
+    /// `thousands_sep` too if the page then formats prices differently from Danish
+    #[serde(default)]
+    pub(crate) accept_language: Option<String>,
+    /// Send a `Referer` of the page's own origin, for sites that reject requests without one
+    #[serde(default)]
+    pub(crate) send_referer: bool,
+    /// Login posted before the page is fetched, for pages only shown to a logged-in session
+    #[serde(default)]
+    pub(crate) login: Option<ProviderLogin>,
+    /// API key the provider's price endpoint requires, sent only with this provider's page requests
+    #[serde(default)]
+    pub(crate) auth: Option<ProviderAuth>,
+}
+
+///
+/// API key of a provider's price endpoint, sent as a header or query parameter, like an OpenAPI `apiKey` scheme:
+/// `{"in": "header", "name": "X-Api-Key", "value": "..."}`
+/// The value is marked sensitive so traces redact it; query parameters are redacted from traces by name, so their name
+/// should look like a key, e.g. `api_key` or `token`. Pages rendered in the headless browser are loaded without it
+///
+/// # Fields
+///
+/// - location: AuthLocation - Whether the key is sent as a header or query parameter, `in` in JSON
+/// - name: String - The name of the header or query parameter
+/// - value: String - The key
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub(crate) struct ProviderAuth {
+    #[serde(rename = "in")]
+    pub(crate) location: AuthLocation,
+    pub(crate) name: String,
+    pub(crate) value: String,
+}
+
+///
+/// Where a provider's API key is sent
+///
+/// # Variants
+///
+/// - Header: As a request header
+/// - Query: As a query parameter of the page URL
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum AuthLocation {
+    Header,
+    Query,
+}
+
+///
+/// Login that establishes a session before a provider page is fetched
+/// The cookies set by the login are kept in a cookie jar of the provider's own and sent with the page request
+///
+/// # Fields
+///
+/// - url: String - The URL the login form is posted to
+/// - form: BTreeMap<String, String> - Form fields sent URL-encoded, e.g. the username and password
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub(crate) struct ProviderLogin {
+    pub(crate) url: String,
+    #[serde(default)]
+    pub(crate) form: BTreeMap<String, String>,
+}
+
+///
+/// Cell of a price table, found by the text of its header instead of its position
+///
+/// # Fields
+///
+/// - header: String - Text of the header cell, compared case-insensitively, e.g. `Pris i dag`
+/// - orientation: TableOrientation - Whether the header heads a column or a row of prices
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub(crate) struct ProviderTable {
+    pub(crate) header: String,
+    #[serde(default)]
+    pub(crate) orientation: TableOrientation,
+}
+
+///
+/// Direction in which a table header labels its prices
+///
+/// # Variants
+///
+/// - Column: The header is a column header, the prices are the cells below it (default)
+/// - Row: The header is a row header, the prices are the cells after it in the same row
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, Default, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum TableOrientation {
+    #[default]
+    Column,
+    Row,
+}
+
+///
+/// Format of a provider response, which decides how the price is extracted from it
+///
+/// # Variants
+///
+/// - Html: An HTML page, scraped with `expression` or `html_element`, or with `json_pointer` if one is set (default)
+/// - Csv: A CSV feed, the price is read from the `csv_column` column
+/// - Json: A JSON feed, the price is read at `json_pointer`
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, Default, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum FeedType {
+    #[default]
+    Html,
+    Csv,
+    Json,
+}
+
+impl Provider {
+    ///
+    /// Get the decimal separator the provider declares, implied by its thousands separator when only that is set
+    ///
+    /// # Returns
+    ///
+    /// Option<char> - The decimal separator, or None if the separators have to be guessed
+    ///
+    pub(crate) fn decimal_separator(&self) -> Option<char> {
+        implied_decimal_separator(self.decimal_sep, self.thousands_sep)
+    }
+
+    ///
+    /// Check that matched text contains the provider's expected marker, e.g. a currency, so unrelated numbers like
+    /// phone numbers or quantities aren't taken for prices
+    ///
+    /// # Arguments
+    ///
+    /// - text: &str - The matched text
+    ///
+    /// # Returns
+    ///
+    /// bool - True if the text has the marker or the provider expects none
+    ///
+    pub(crate) fn has_expected_marker(&self, text: &str) -> bool {
+        self.expected_marker
+            .as_deref()
+            .is_none_or(|marker| text.to_lowercase().contains(&marker.trim().to_lowercase()))
+    }
+
+    ///
+    /// Get the text of an element matched by the provider's selector
+    /// Text nodes are joined with a space and whitespace runs collapsed, so prices split across elements stay apart from
+    /// surrounding text; the price parsers drop the whitespace again
+    ///
+    /// # Arguments
+    ///
+    /// - element: ElementRef - The matched element
+    ///
+    /// # Returns
+    ///
+    /// String - The text of the element, only its own text nodes if the provider wants the direct text
+    ///
+    pub(crate) fn element_text(&self, element: ElementRef) -> String {
+        let nodes: Vec<&str> = if self.direct_text {
+            element
+                .children()
+                .filter_map(|child| child.value().as_text())
+                .map(|text| &**text)
+                .collect()
+        } else {
+            element.text().collect()
+        };
+        nodes
+            .iter()
+            .flat_map(|text| text.split_whitespace())
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
+    ///
+    /// Get the text of the cells labelled by a header in a matched table
+    /// Cells are counted per row without regard to `colspan`, and rows of nested tables are ignored
+    ///
+    /// # Arguments
+    ///
+    /// - table: ElementRef - The matched table
+    /// - spec: &ProviderTable - The header to look for and its orientation
+    ///
+    /// # Returns
+    ///
+    /// Vec<String> - The text of the labelled cells in document order, empty if the header isn't found
+    ///
+    pub(crate) fn table_cells(&self, table: ElementRef, spec: &ProviderTable) -> Vec<String> {
+        let header = spec.header.split_whitespace().collect::<Vec<_>>().join(" ");
+        let is_header = |cell: &ElementRef| self.element_text(*cell).eq_ignore_ascii_case(&header);
+        let row_selector = Selector::parse("tr").unwrap();
+        let rows: Vec<Vec<ElementRef>> = table
+            .select(&row_selector)
+            .filter(|row| {
+                let owner = row
+                    .ancestors()
+                    .filter_map(ElementRef::wrap)
+                    .find(|element| element.value().name() == "table");
+                owner.map(|owner| owner.id()) == Some(table.id())
+            })
+            .map(|row| {
+                row.children()
+                    .filter_map(ElementRef::wrap)
+                    .filter(|cell| matches!(cell.value().name(), "th" | "td"))
+                    .collect()
+            })
+            .collect();
+
+        match spec.orientation {
+            TableOrientation::Column => {
+                let Some((header_row, column)) = rows.iter().enumerate().find_map(|(i, cells)| {
+                    cells.iter().position(is_header).map(|column| (i, column))
+                }) else {
+                    return vec![];
+                };
+                rows[header_row + 1..]
+                    .iter()
+                    .filter_map(|cells| cells.get(column))
+                    .map(|cell| self.element_text(*cell))
+                    .collect()
+            }
+            TableOrientation::Row => rows
+                .iter()
+                .find_map(|cells| {
+                    let column = cells.iter().position(is_header)?;
+                    Some(&cells[column + 1..])
+                })
+                .unwrap_or_default()
+                .iter()
+                .map(|cell| self.element_text(*cell))
+                .collect(),
+        }
+    }
+
+    ///
+    /// Apply the provider's price transformations in order
+    ///
+    /// # Arguments
+    ///
+    /// - price: f64 - The parsed price
+    ///
+    /// # Returns
+    ///
+    /// f64 - The transformed price
+    ///
+    pub(crate) fn transform_price(&self, price: f64) -> f64 {
+        self.transforms
+            .iter()
+            .fold(price, |price, transform| transform.apply(price))
+    }
+
+    ///
+    /// Build the request for the provider page with the provider's method, form body, timeout and language
+    /// The provider's timeout takes precedence over the client's; without one the client's timeout applies
+    ///
+    /// # Arguments
+    ///
+    /// - client: &Client - The reqwest client
+    /// - url: Url - The provider page URL
+    /// - referer: bool - Send the origin of the URL as the `Referer`
+    ///
+    /// # Returns
+    ///
+    /// RequestBuilder - The request for the provider page
+    ///
+    pub(crate) fn page_request(&self, client: &Client, url: Url, referer: bool) -> RequestBuilder {
+        let origin = format!("{}/", url.origin().ascii_serialization());
+        let mut request = match self.method {
+            RequestMethod::Get => client.get(url),
+            RequestMethod::Post => client.post(url),
+        };
+        if let Some(form) = &self.form {
+            request = request.form(form);
+        }
+        if let Some(secs) = self.timeout_secs {
+            request = request.timeout(Duration::from_secs(secs));
+        }
+        if referer {
+            request = request.header(REFERER, origin);
+        }
+        if let Some(language) = &self.accept_language {
+            request = request.header(ACCEPT_LANGUAGE, language);
+        }
+        self.authorize(request)
+    }
+
+    ///
+    /// Add the provider's API key to a request for its page, if it has one
+    ///
+    /// # Arguments
+    ///
+    /// - request: RequestBuilder - The request for the page
+    ///
+    /// # Returns
+    ///
+    /// RequestBuilder - The request with the key
+    ///
+    pub(crate) fn authorize(&self, request: RequestBuilder) -> RequestBuilder {
+        let Some(auth) = &self.auth else {
+            return request;
+        };
+        match auth.location {
+            AuthLocation::Header => {
+                // An invalid value fails the request when it is built, without the value in the error
+                let value = HeaderValue::from_str(&auth.value).map(|mut value| {
+                    value.set_sensitive(true);
+                    value
+                });
+                match value {
+                    Ok(value) => request.header(auth.name.as_str(), value),
+                    Err(_) => request.header(auth.name.as_str(), auth.value.as_str()),
+                }
+            }
+            AuthLocation::Query => request.query(&[(&auth.name, &auth.value)]),
+        }
+    }
+
+    ///
+    /// Leave the URL out of an error of a request for the provider's page if the URL may carry the provider's API key
+    ///
+    /// # Arguments
+    ///
+    /// - error: reqwest::Error - The error of the request
+    ///
+    /// # Returns
+    ///
+    /// reqwest::Error - The error, safe to log
+    ///
+    pub(crate) fn redact_error(&self, error: reqwest::Error) -> reqwest::Error {
+        match &self.auth {
+            Some(auth) if auth.location == AuthLocation::Query => error.without_url(),
+            _ => error,
+        }
+    }
+}
+
+///
+/// HTTP method used to fetch a provider page
+///
+/// # Variants
+///
+/// - Get: A plain GET (default)
+/// - Post: A POST, usually with a form body
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, Default, PartialEq)]
+#[serde(rename_all = "UPPERCASE")]
+pub(crate) enum RequestMethod {
+    #[default]
+    Get,
+    Post,
+}
+
+fn default_enabled() -> bool {
+    true
+}
+
+fn default_currency() -> String {
+    "DKK".to_string()
+}
+
+///
+/// Validate a provider page URL, normalizing scheme-less URLs like `example.com/prices` or `//example.com` to HTTPS
+///
+/// # Arguments
+///
+/// - url: &str - The provider page URL
+///
+/// # Returns
+///
+/// Result<Url, String> - The normalized URL
+///
+/// # Errors
+///
+/// If the URL can't be parsed, isn't HTTP(S) or has no host, an error is returned
+///
+pub(crate) fn normalize_url(url: &str) -> Result<Url, String> {
+    let url = url.trim();
+    let url = if url.starts_with("//") {
+        format!("https:{}", url)
+    } else if url.starts_with('/') {
+        return Err("relative URL without a host".to_string());
+    } else if !url.contains("://") {
+        format!("https://{}", url)
+    } else {
+        url.to_string()
+    };
+
+    let parsed = Url::parse(&url).map_err(|e| e.to_string())?;
+    if !matches!(parsed.scheme(), "http" | "https") {
+        return Err(format!("unsupported scheme {:?}", parsed.scheme()));
+    }
+    if parsed.host_str().is_none_or(str::is_empty) {
+        return Err("no host".to_string());
+    }
+    Ok(parsed)
+}
+
+/// TOML has no top-level arrays, so a TOML providers file lists its entries under `[[providers]]`
+#[derive(Deserialize)]
+struct ProvidersFile {
+    providers: Vec<Provider>,
+}
+
+///
+/// Load full provider entries from a local JSON or TOML file, chosen by the file extension
+///
+/// # Arguments
+///
+/// - path: &Path - The path to the providers file
+///
+/// # Returns
+///
+/// Result<Vec<Provider>, ScraperError> - The providers in the file
+///
+/// # Errors
+///
+/// If the file cannot be read or parsed, an error is returned
+///
+pub(crate) fn load_providers_file(path: &Path) -> Result<Vec<Provider>, ScraperError> {
+    let contents = std::fs::read_to_string(path)?;
+    let is_toml = path
+        .extension()
+        .is_some_and(|extension| extension == "toml");
+
+    let providers = if is_toml {
+        toml::from_str::<ProvidersFile>(&contents)
+            .map(|file| file.providers)
+            .map_err(|e| e.to_string())
+    } else {
+        serde_json::from_str::<Vec<Provider>>(&contents).map_err(|e| e.to_string())
+    };
+
+    providers.map_err(|e| {
+        ScraperError::Config(format!(
+            "Failed to parse providers file {}: {}",
+            path.display(),
+            e
+        ))
+    })
+}
+
+///
+/// Strategy for picking or aggregating a single price when several elements match the provider's selector,
+/// e.g. averaging the prices of a page listing several stations
+///
+/// # Variants
+///
+/// - First: The first matched price in document order (default)
+/// - Last: The last matched price in document order
+/// - Min: The lowest matched price
+/// - Max: The highest matched price
+/// - Avg: The mean of the matched prices
+/// - Median: The middle matched price, or the mean of the two middle prices for an even number of matches
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, Default, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum PriceSelection {
+    #[default]
+    First,
+    Last,
+    Min,
+    Max,
+    Avg,
+    Median,
+}
+
+impl PriceSelection {
+    ///
+    /// Select a price from the matched prices according to the strategy
+    ///
+    /// # Arguments
+    ///
+    /// - prices: &[f64] - The matched prices in document order
+    ///
+    /// # Returns
+    ///
+    /// Option<f64> - The selected price, or None if there were no prices
+    ///
+    pub(crate) fn select(&self, prices: &[f64]) -> Option<f64> {
+        match self {
+            PriceSelection::First => prices.first().copied(),
+            PriceSelection::Last => prices.last().copied(),
+            PriceSelection::Min => prices.iter().copied().reduce(f64::min),
+            PriceSelection::Max => prices.iter().copied().reduce(f64::max),
+            PriceSelection::Avg if prices.is_empty() => None,
+            PriceSelection::Avg => Some(prices.iter().sum::<f64>() / prices.len() as f64),
+            PriceSelection::Median => {
+                let mut sorted = prices.to_vec();
+                sorted.sort_by(f64::total_cmp);
+                let middle = sorted.len() / 2;
+                match sorted.len() {
+                    0 => None,
+                    len if len % 2 == 0 => Some((sorted[middle - 1] + sorted[middle]) / 2.0),
+                    _ => Some(sorted[middle]),
+                }
+            }
+        }
+    }
+}
+
+///
+/// Which bound of a price range is taken as the price
+///
+/// # Variants
+///
+/// - Low: The lower bound (default)
+/// - High: The upper bound
+/// - Midpoint: The mean of the bounds
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, Default, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum RangeBound {
+    #[default]
+    Low,
+    High,
+    Midpoint,
+}
+
+impl RangeBound {
+    ///
+    /// Pick the price from the bounds of a range, which may be written in either order
+    ///
+    /// # Arguments
+    ///
+    /// - from: f64 - The bound written first
+    /// - to: f64 - The bound written last
+    ///
+    /// # Returns
+    ///
+    /// f64 - The picked price
+    ///
+    pub(crate) fn pick(&self, from: f64, to: f64) -> f64 {
+        match self {
+            RangeBound::Low => from.min(to),
+            RangeBound::High => from.max(to),
+            RangeBound::Midpoint => (from + to) / 2.0,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use uuid::Uuid;
+
+    #[test]
+    fn normalize_url_adds_missing_schemes_and_rejects_invalid_urls() {
+        assert_eq!(
+            normalize_url(" example.com/prices ").unwrap().as_str(),
+            "https://example.com/prices"
+        );
+        assert_eq!(
+            normalize_url("//example.com").unwrap().as_str(),
+            "https://example.com/"
+        );
+        assert_eq!(
+            normalize_url("http://example.com").unwrap().as_str(),
+            "http://example.com/"
+        );
+        assert!(normalize_url("/prices").is_err());
+        assert!(normalize_url("ftp://example.com").is_err());
+        assert!(normalize_url("http://exa mple.com").is_err());
+        assert!(normalize_url("").is_err());
+    }
+
+    #[test]
+    fn select_picks_according_to_strategy() {
+        let prices = [13.49, 12.99, 14.09];
+
+        assert_eq!(PriceSelection::First.select(&prices), Some(13.49));
+        assert_eq!(PriceSelection::Last.select(&prices), Some(14.09));
+        assert_eq!(PriceSelection::Min.select(&prices), Some(12.99));
+        assert_eq!(PriceSelection::Max.select(&prices), Some(14.09));
+        assert_eq!(PriceSelection::Max.select(&[]), None);
+    }
+
+    #[test]
+    fn select_aggregates_according_to_strategy() {
+        let odd = [13.5, 12.0, 15.0];
+        let even = [13.5, 12.0, 15.0, 12.5];
+
+        assert_eq!(PriceSelection::Avg.select(&odd), Some(13.5));
+        assert_eq!(PriceSelection::Avg.select(&even), Some(13.25));
+        assert_eq!(PriceSelection::Median.select(&odd), Some(13.5));
+        assert_eq!(PriceSelection::Median.select(&even), Some(13.0));
+        assert_eq!(PriceSelection::Median.select(&[12.49]), Some(12.49));
+        assert_eq!(PriceSelection::Avg.select(&[]), None);
+        assert_eq!(PriceSelection::Median.select(&[]), None);
+
+        let provider: Provider = serde_json::from_str(
+            r#"{ "id": 1, "name": "Test", "url": "http://localhost", "aggregation": "median" }"#,
+        )
+        .unwrap();
+        assert_eq!(provider.price_selection, PriceSelection::Median);
+    }
+
+    #[test]
+    fn element_text_joins_text_nodes_and_optionally_skips_nested_elements() {
+        let document = scraper::Html::parse_fragment(
+            "<div class=\"price\">\n  <span>12</span><span>,49</span>\n  kr. <small>inkl. moms</small></div>",
+        );
+        let selector = scraper::Selector::parse(".price").unwrap();
+        let element = document.select(&selector).next().unwrap();
+        let mut provider: Provider = serde_json::from_str(
+            r#"{ "id": 1, "name": "Test", "url": "http://localhost", "html_element": ".price" }"#,
+        )
+        .unwrap();
+
+        assert_eq!(provider.element_text(element), "12 ,49 kr. inkl. moms");
+        provider.direct_text = true;
+        assert_eq!(provider.element_text(element), "kr.");
+    }
+
+    #[test]
+    fn table_cells_follow_the_header_in_either_orientation() {
+        let document = scraper::Html::parse_fragment(
+            r#"<table class="prices">
+                <thead><tr><th>Produkt</th><th>Pris i går</th><th> Pris  i dag </th></tr></thead>
+                <tbody>
+                    <tr><th>Fyringsolie</th><td>12,19</td><td>12,49</td></tr>
+                    <tr><th>Diesel</th><td>13,29</td><td>13,09</td></tr>
+                    <tr><td colspan="3"><table><tr><td>Pris i dag</td><td>99,99</td></tr></table></td></tr>
+                </tbody>
+            </table>"#,
+        );
+        let selector = scraper::Selector::parse("table.prices").unwrap();
+        let table = document.select(&selector).next().unwrap();
+        let provider: Provider = serde_json::from_str(
+            r#"{ "id": 1, "name": "Test", "url": "http://localhost", "html_element": "table.prices" }"#,
+        )
+        .unwrap();
+        let spec = |header: &str, orientation| ProviderTable {
+            header: header.to_string(),
+            orientation,
+        };
+
+        assert_eq!(
+            provider.table_cells(table, &spec("pris i dag", TableOrientation::Column)),
+            vec!["12,49", "13,09"]
+        );
+        assert_eq!(
+            provider.table_cells(table, &spec("Diesel", TableOrientation::Row)),
+            vec!["13,29", "13,09"]
+        );
+        assert!(provider
+            .table_cells(table, &spec("Pris i morgen", TableOrientation::Column))
+            .is_empty());
+    }
+
+    #[test]
+    fn optional_fields_have_defaults() {
+        let provider: Provider = serde_json::from_str(
+            r#"{ "id": 1, "name": "Test", "url": "http://localhost", "html_element": ".price" }"#,
+        )
+        .unwrap();
+
+        assert_eq!(provider.price_selection, PriceSelection::First);
+        assert_eq!(provider.feed_type, FeedType::Html);
+        assert!(provider.enabled);
+    }
+
+    #[test]
+    fn load_providers_file_reads_json_and_toml() {
+        let dir = std::env::temp_dir();
+        let json_path = dir.join(format!("oliepriser-providers-test-{}.json", Uuid::new_v4()));
+        let toml_path = dir.join(format!("oliepriser-providers-test-{}.toml", Uuid::new_v4()));
+        std::fs::write(
+            &json_path,
+            r#"[{ "id": 1, "name": "Json", "url": "http://localhost", "html_element": ".price" }]"#,
+        )
+        .unwrap();
+        std::fs::write(
+            &toml_path,
+            r#"
+[[providers]]
+id = 2
+name = "Toml"
+url = "http://localhost"
+html_element = ".price"
+price_selection = "max"
+"#,
+        )
+        .unwrap();
+
+        let json_providers = load_providers_file(&json_path).unwrap();
+        let toml_providers = load_providers_file(&toml_path).unwrap();
+
+        assert_eq!(json_providers[0].name, "Json");
+        assert_eq!(toml_providers[0].id, 2);
+        assert_eq!(toml_providers[0].price_selection, PriceSelection::Max);
+        std::fs::remove_file(&json_path).unwrap();
+        std::fs::remove_file(&toml_path).unwrap();
+    }
+}
@@ -0,0 +1,258 @@
+use reqwest::Url;
+use std::future::Future;
+use std::io::{Error, ErrorKind};
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::TcpStream;
+
+/// How long connecting to the broker or a single command may take, so a stalled broker can't hold up posting
+const BROKER_TIMEOUT: Duration = Duration::from_secs(5);
+
+///
+/// Connection to a message broker scraped prices are published to
+/// Speaks just enough of each protocol to publish, so no broker client library is needed
+///
+/// # Variants
+///
+/// - Redis: A Redis server, prices are sent with `PUBLISH` and every reply is checked
+/// - Nats: A NATS server, prices are sent with `PUB`, which the server doesn't acknowledge
+pub(crate) enum Publisher {
+    Redis(BufReader<TcpStream>),
+    Nats(BufReader<TcpStream>),
+}
+
+impl Publisher {
+    ///
+    /// Connect to the broker at a URL, logging in with the credentials in the URL if there are any
+    ///
+    /// # Arguments
+    ///
+    /// - url: &str - The broker URL, e.g. `redis://:password@localhost:6379` or `nats://localhost:4222`
+    ///
+    /// # Returns
+    ///
+    /// Result<Publisher, Error> - The connected publisher
+    ///
+    /// # Errors
+    ///
+    /// If the URL is invalid, the broker can't be reached, doesn't answer in time or rejects the credentials, an error
+    /// is returned
+    ///
+    pub(crate) async fn connect(url: &str) -> Result<Self, Error> {
+        within_timeout(Self::open(url)).await
+    }
+
+    async fn open(url: &str) -> Result<Self, Error> {
+        let url = Url::parse(url).map_err(|e| Error::new(ErrorKind::InvalidInput, e))?;
+        let host = url
+            .host_str()
+            .ok_or_else(|| Error::new(ErrorKind::InvalidInput, "broker URL has no host"))?;
+        let user = (!url.username().is_empty()).then(|| url.username());
+        match url.scheme() {
+            "redis" => {
+                let stream = TcpStream::connect((host, url.port().unwrap_or(6379))).await?;
+                let mut publisher = Publisher::Redis(BufReader::new(stream));
+                if let Some(password) = url.password() {
+                    let mut auth = vec!["AUTH"];
+                    auth.extend(user);
+                    auth.push(password);
+                    publisher.redis_command(&auth).await?;
+                }
+                Ok(publisher)
+            }
+            "nats" => {
+                let stream = TcpStream::connect((host, url.port().unwrap_or(4222))).await?;
+                let mut stream = BufReader::new(stream);
+                // The server greets with its INFO before anything else
+                read_line(&mut stream).await?;
+                let mut options = serde_json::json!({ "verbose": false, "pedantic": false });
+                if let (Some(user), Some(password)) = (user, url.password()) {
+                    options["user"] = user.into();
+                    options["pass"] = password.into();
+                }
+                let connect = format!("CONNECT {}\r\nPING\r\n", options);
+                stream.get_mut().write_all(connect.as_bytes()).await?;
+                // Answered with PONG once the server accepted the connection, or -ERR if it didn't
+                let reply = read_line(&mut stream).await?;
+                if reply != "PONG" {
+                    return Err(Error::other(format!(
+                        "NATS refused the connection: {}",
+                        reply
+                    )));
+                }
+                Ok(Publisher::Nats(stream))
+            }
+            scheme => Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!(
+                    "unsupported broker scheme {:?}, expected redis or nats",
+                    scheme
+                ),
+            )),
+        }
+    }
+
+    ///
+    /// Publish a message on a channel (Redis) or subject (NATS)
+    ///
+    /// # Arguments
+    ///
+    /// - subject: &str - The channel or subject, e.g. `oliepriser.prices`
+    /// - payload: &str - The message
+    ///
+    /// # Errors
+    ///
+    /// If the connection fails, the broker doesn't answer in time or Redis replies with an error, an error is returned
+    ///
+    pub(crate) async fn publish(&mut self, subject: &str, payload: &str) -> Result<(), Error> {
+        within_timeout(self.send(subject, payload)).await
+    }
+
+    async fn send(&mut self, subject: &str, payload: &str) -> Result<(), Error> {
+        match self {
+            Publisher::Redis(_) => self.redis_command(&["PUBLISH", subject, payload]).await,
+            Publisher::Nats(stream) => {
+                let message = format!("PUB {} {}\r\n{}\r\n", subject, payload.len(), payload);
+                stream.get_mut().write_all(message.as_bytes()).await
+            }
+        }
+    }
+
+    ///
+    /// Send a command to Redis and check its reply isn't an error
+    ///
+    /// # Arguments
+    ///
+    /// - args: &[&str] - The command and its arguments
+    ///
+    /// # Errors
+    ///
+    /// If the connection fails or Redis replies with an error, an error is returned
+    ///
+    async fn redis_command(&mut self, args: &[&str]) -> Result<(), Error> {
+        let Publisher::Redis(stream) = self else {
+            unreachable!("Redis commands are only sent to Redis");
+        };
+        let mut command = format!("*{}\r\n", args.len());
+        for arg in args {
+            command.push_str(&format!("${}\r\n{}\r\n", arg.len(), arg));
+        }
+        stream.get_mut().write_all(command.as_bytes()).await?;
+
+        let reply = read_line(stream).await?;
+        match reply.strip_prefix('-') {
+            Some(error) => Err(Error::other(format!("Redis replied {}", error))),
+            None => Ok(()),
+        }
+    }
+}
+
+async fn within_timeout<T>(operation: impl Future<Output = Result<T, Error>>) -> Result<T, Error> {
+    tokio::time::timeout(BROKER_TIMEOUT, operation)
+        .await
+        .map_err(|_| Error::new(ErrorKind::TimedOut, "broker timed out"))?
+}
+
+async fn read_line(stream: &mut BufReader<TcpStream>) -> Result<String, Error> {
+    let mut line = String::new();
+    if stream.read_line(&mut line).await? == 0 {
+        return Err(Error::new(
+            ErrorKind::UnexpectedEof,
+            "broker closed the connection",
+        ));
+    }
+    Ok(line.trim_end().to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::AsyncReadExt;
+    use tokio::net::TcpListener;
+
+    ///
+    /// Accept one connection, send the greeting and replies, and return everything the client sent
+    ///
+    async fn fake_broker(
+        greeting: &'static str,
+        replies: &'static str,
+    ) -> (String, tokio::task::JoinHandle<String>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+        let received = tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            socket.write_all(greeting.as_bytes()).await.unwrap();
+            socket.write_all(replies.as_bytes()).await.unwrap();
+            let mut received = String::new();
+            socket.read_to_string(&mut received).await.unwrap();
+            received
+        });
+        (addr, received)
+    }
+
+    #[tokio::test]
+    async fn publishes_to_redis_after_authenticating() {
+        let (addr, received) = fake_broker("", "+OK\r\n:1\r\n").await;
+
+        let mut publisher = Publisher::connect(&format!("redis://:secret@{}", addr))
+            .await
+            .unwrap();
+        publisher
+            .publish("prices", r#"{"price":12.49}"#)
+            .await
+            .unwrap();
+        drop(publisher);
+
+        assert_eq!(
+            received.await.unwrap(),
+            "*2\r\n$4\r\nAUTH\r\n$6\r\nsecret\r\n*3\r\n$7\r\nPUBLISH\r\n$6\r\nprices\r\n$15\r\n{\"price\":12.49}\r\n"
+        );
+    }
+
+    #[tokio::test]
+    async fn redis_errors_fail_the_publish() {
+        let (addr, _received) = fake_broker("", "-NOAUTH Authentication required.\r\n").await;
+
+        let mut publisher = Publisher::connect(&format!("redis://{}", addr))
+            .await
+            .unwrap();
+        let error = publisher.publish("prices", "{}").await.unwrap_err();
+
+        assert!(error.to_string().contains("NOAUTH"));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn stalled_brokers_time_out() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        // Accepts the connection but never greets
+        let _stalled = tokio::spawn(async move {
+            let (socket, _) = listener.accept().await.unwrap();
+            std::future::pending::<()>().await;
+            drop(socket);
+        });
+
+        let error = Publisher::connect(&format!("nats://{}", addr))
+            .await
+            .err()
+            .unwrap();
+
+        assert_eq!(error.kind(), ErrorKind::TimedOut);
+    }
+
+    #[tokio::test]
+    async fn publishes_to_nats_once_the_connection_is_accepted() {
+        let (addr, received) = fake_broker("INFO {}\r\n", "PONG\r\n").await;
+
+        let mut publisher = Publisher::connect(&format!("nats://{}", addr))
+            .await
+            .unwrap();
+        publisher.publish("oliepriser.prices", "{}").await.unwrap();
+        drop(publisher);
+
+        assert!(received
+            .await
+            .unwrap()
+            .ends_with("PING\r\nPUB oliepriser.prices 2\r\n{}\r\n"));
+    }
+}
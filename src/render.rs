@@ -0,0 +1,59 @@
+use fantoccini::{ClientBuilder, Locator};
+use serde_json::json;
+use std::time::Duration;
+
+///
+/// Load a page in a headless browser through WebDriver and return its rendered DOM
+/// Waits for the selector to appear first, so prices rendered client-side are present
+///
+/// # Arguments
+///
+/// - webdriver_url: &str - The WebDriver endpoint, e.g. a chromedriver or geckodriver
+/// - url: &str - The page to load
+/// - selector: &str - The CSS selector to wait for, not waited for when empty
+/// - timeout: Duration - How long to wait for the selector
+///
+/// # Returns
+///
+/// Result<String, String> - The rendered page HTML
+///
+/// # Errors
+///
+/// If the browser can't be reached, the page doesn't load or the selector doesn't appear in time, an error is returned
+///
+pub(crate) async fn render_page(
+    webdriver_url: &str,
+    url: &str,
+    selector: &str,
+    timeout: Duration,
+) -> Result<String, String> {
+    let capabilities = json!({
+        "goog:chromeOptions": { "args": ["--headless", "--disable-gpu"] },
+        "moz:firefoxOptions": { "args": ["-headless"] },
+    });
+    let serde_json::Value::Object(capabilities) = capabilities else {
+        unreachable!("capabilities are a JSON object");
+    };
+    let client = ClientBuilder::native()
+        .capabilities(capabilities)
+        .connect(webdriver_url)
+        .await
+        .map_err(|e| format!("Failed to connect to WebDriver at {}: {}", webdriver_url, e))?;
+
+    // Close the session even when loading the page fails
+    let result = async {
+        client.goto(url).await?;
+        if !selector.is_empty() {
+            client
+                .wait()
+                .at_most(timeout)
+                .for_element(Locator::Css(selector))
+                .await?;
+        }
+        client.source().await
+    }
+    .await;
+    let _ = client.close().await;
+
+    result.map_err(|e| e.to_string())
+}
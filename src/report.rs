@@ -0,0 +1,503 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fmt;
+use uuid::Uuid;
+
+use crate::provider::Provider;
+
+///
+/// Why a provider failed to yield a price
+///
+/// # Variants
+///
+/// - Connection: The provider page couldn't be reached, e.g. a DNS or connection error
+/// - Tls: The TLS handshake with the provider failed
+/// - Http4xx: The provider page responded with a 4xx status
+/// - RateLimited: The provider page responded with 429 Too Many Requests
+/// - Http5xx: The provider page responded with a 5xx status
+/// - Timeout: The request for the provider page timed out
+/// - InvalidUrl: The provider's URL is malformed
+/// - InvalidSelector: The provider's selector could not be parsed
+/// - NoMatch: The selector or JSON pointer matched nothing
+/// - Unparseable: The matched value isn't a valid price
+/// - OutOfRange: The matched price isn't a plausible price, e.g. zero or negative
+/// - Render: The page couldn't be rendered in the headless browser
+/// - TooLarge: The provider page is larger than the response size limit
+/// - Unstable: The price changed when the page was fetched again to verify it
+/// - Login: The provider's session login failed
+/// - ContentType: The preflight request found the page isn't of the type the provider is scraped as
+/// - Suspicious: Every match lacks the provider's expected marker, so the selector likely grabs an unrelated number
+/// - Blocked: An anti-bot interstitial or CAPTCHA page was served instead of the provider page
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FailureKind {
+    Connection,
+    Tls,
+    #[serde(rename = "http_4xx")]
+    Http4xx,
+    #[serde(rename = "http_429")]
+    RateLimited,
+    #[serde(rename = "http_5xx")]
+    Http5xx,
+    Timeout,
+    InvalidUrl,
+    InvalidSelector,
+    NoMatch,
+    Unparseable,
+    OutOfRange,
+    Render,
+    TooLarge,
+    Unstable,
+    Login,
+    ContentType,
+    Suspicious,
+    Blocked,
+}
+
+impl FailureKind {
+    ///
+    /// Classify a failed request for a provider page
+    ///
+    /// # Arguments
+    ///
+    /// - error: &reqwest::Error - The request error
+    ///
+    /// # Returns
+    ///
+    /// FailureKind - The kind of the failure
+    ///
+    pub(crate) fn from_error(error: &reqwest::Error) -> Self {
+        if error.is_timeout() {
+            return FailureKind::Timeout;
+        }
+        if let Some(status) = error.status() {
+            return if status == reqwest::StatusCode::TOO_MANY_REQUESTS {
+                FailureKind::RateLimited
+            } else if status.is_server_error() {
+                FailureKind::Http5xx
+            } else {
+                FailureKind::Http4xx
+            };
+        }
+
+        // reqwest doesn't expose TLS errors as such, so look for them in the error chain
+        let mut source = error.source();
+        while let Some(e) = source {
+            let message = e.to_string().to_lowercase();
+            if ["tls", "ssl", "certificate", "handshake"]
+                .iter()
+                .any(|needle| message.contains(needle))
+            {
+                return FailureKind::Tls;
+            }
+            source = e.source();
+        }
+        FailureKind::Connection
+    }
+}
+
+impl fmt::Display for FailureKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            FailureKind::Connection => "connection error",
+            FailureKind::Tls => "TLS error",
+            FailureKind::Http4xx => "HTTP 4xx",
+            FailureKind::RateLimited => "rate limited",
+            FailureKind::Http5xx => "HTTP 5xx",
+            FailureKind::Timeout => "timeout",
+            FailureKind::InvalidUrl => "invalid URL",
+            FailureKind::InvalidSelector => "invalid selector",
+            FailureKind::NoMatch => "no match",
+            FailureKind::Unparseable => "unparseable",
+            FailureKind::OutOfRange => "out of range",
+            FailureKind::Render => "render error",
+            FailureKind::TooLarge => "response too large",
+            FailureKind::Unstable => "unstable price",
+            FailureKind::Login => "login failed",
+            FailureKind::ContentType => "unexpected content type",
+            FailureKind::Suspicious => "suspicious match",
+            FailureKind::Blocked => "blocked by anti-bot page",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+///
+/// Outcome of scraping a single provider
+///
+/// # Variants
+///
+/// - Scraped: A price was found and posted, with the API's stored price if reconciling found it to differ
+/// - Unchanged: The provider page was not modified since the last run
+/// - Failed: The provider failed to yield a price
+/// - Skipped: The provider was not scraped, e.g. because it is disabled
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) enum ProviderOutcome {
+    Scraped {
+        price: f64,
+        discrepancy: Option<f64>,
+    },
+    Unchanged,
+    Failed(FailureKind),
+    Skipped,
+}
+
+///
+/// Status of a provider in a run, as written to the run artifact
+///
+/// # Variants
+///
+/// - Scraped: A price was found and posted
+/// - Unchanged: The provider page was not modified since the last run
+/// - Failed: The provider failed to yield a price
+/// - Skipped: The provider was not scraped
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PriceStatus {
+    Scraped,
+    Unchanged,
+    Failed,
+    Skipped,
+}
+
+///
+/// What a run did with a single provider
+///
+/// # Fields
+///
+/// - provider_id: i32 - The ID of the provider
+/// - name: String - The name of the provider
+/// - price: Option<f64> - The scraped price, if one was found
+/// - currency: String - The currency of the price
+/// - status: PriceStatus - What happened to the provider
+/// - failure: Option<FailureKind> - Why the provider failed, if it did
+/// - stored_price: Option<f64> - The API's latest price, if reconciling found it to differ from the scraped price
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct PriceRecord {
+    pub provider_id: i32,
+    pub name: String,
+    pub price: Option<f64>,
+    pub currency: String,
+    pub status: PriceStatus,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub failure: Option<FailureKind>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stored_price: Option<f64>,
+}
+
+///
+/// How a run ended, posted with the run so the API can tell clean runs from cut-short ones
+///
+/// # Variants
+///
+/// - Completed: Every provider was attempted (default)
+/// - Interrupted: Shutdown was requested during the run, so the providers not started on were skipped
+/// - Failed: Too many providers failed, over the failure threshold
+#[derive(Clone, Copy, Debug, Default, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RunStatus {
+    #[default]
+    Completed,
+    Interrupted,
+    Failed,
+}
+
+///
+/// Summary of a scraping run
+///
+/// # Fields
+///
+/// - run_id: Uuid - The client-generated ID of the run
+/// - status: RunStatus - How the run ended, posted alongside the summary
+/// - scraped: usize - Providers a price was posted for
+/// - unchanged: usize - Providers whose page was not modified
+/// - failed: usize - Providers that failed to yield a price
+/// - failures: BTreeMap<FailureKind, usize> - The failed providers by kind of failure
+/// - skipped: usize - Providers that were not scraped
+/// - discrepancies: usize - Scraped prices that differ from the API's latest price, when reconciling
+/// - posted: usize - Prices the API accepted, or that were recorded locally with a providers file
+/// - throttled: usize - Scraped prices not posted because their provider's minimum post interval hadn't passed
+/// - records: Vec<PriceRecord> - What the run did with each provider, not part of the summary posted to the API
+#[derive(Clone, Debug, Default, PartialEq, Serialize)]
+pub struct RunReport {
+    #[serde(skip)]
+    pub run_id: Uuid,
+    #[serde(skip)]
+    pub status: RunStatus,
+    pub scraped: usize,
+    pub unchanged: usize,
+    pub failed: usize,
+    pub failures: BTreeMap<FailureKind, usize>,
+    pub skipped: usize,
+    pub discrepancies: usize,
+    pub posted: usize,
+    pub throttled: usize,
+    #[serde(skip)]
+    pub records: Vec<PriceRecord>,
+}
+
+impl ProviderOutcome {
+    ///
+    /// Get the status the outcome is recorded with
+    ///
+    /// # Returns
+    ///
+    /// PriceStatus - The status of the provider
+    ///
+    pub(crate) fn status(&self) -> PriceStatus {
+        match self {
+            ProviderOutcome::Scraped { .. } => PriceStatus::Scraped,
+            ProviderOutcome::Unchanged => PriceStatus::Unchanged,
+            ProviderOutcome::Failed(_) => PriceStatus::Failed,
+            ProviderOutcome::Skipped => PriceStatus::Skipped,
+        }
+    }
+}
+
+impl RunReport {
+    ///
+    /// Record the outcome of a provider in the report
+    ///
+    /// # Arguments
+    ///
+    /// - provider: &Provider - The provider the outcome belongs to
+    /// - outcome: &ProviderOutcome - The outcome to record
+    ///
+    pub(crate) fn record(&mut self, provider: &Provider, outcome: &ProviderOutcome) {
+        let mut stored_price = None;
+        let (status, price, failure) = match outcome {
+            ProviderOutcome::Scraped { price, discrepancy } => {
+                self.scraped += 1;
+                if discrepancy.is_some() {
+                    self.discrepancies += 1;
+                    stored_price = *discrepancy;
+                }
+                (PriceStatus::Scraped, Some(*price), None)
+            }
+            ProviderOutcome::Unchanged => {
+                self.unchanged += 1;
+                (PriceStatus::Unchanged, None, None)
+            }
+            ProviderOutcome::Failed(kind) => {
+                self.failed += 1;
+                *self.failures.entry(*kind).or_default() += 1;
+                (PriceStatus::Failed, None, Some(*kind))
+            }
+            ProviderOutcome::Skipped => {
+                self.skipped += 1;
+                (PriceStatus::Skipped, None, None)
+            }
+        };
+        self.records.push(PriceRecord {
+            provider_id: provider.id,
+            name: provider.name.clone(),
+            price,
+            currency: provider.currency.clone(),
+            status,
+            failure,
+            stored_price,
+        });
+    }
+
+    ///
+    /// Record a provider whose details couldn't be fetched as failed, under its ID as the name is unknown
+    ///
+    /// # Arguments
+    ///
+    /// - provider_id: i32 - The ID of the provider
+    /// - kind: FailureKind - Why fetching the provider failed
+    ///
+    pub(crate) fn record_unfetched(&mut self, provider_id: i32, kind: FailureKind) {
+        self.failed += 1;
+        *self.failures.entry(kind).or_default() += 1;
+        self.records.push(PriceRecord {
+            provider_id,
+            name: provider_id.to_string(),
+            price: None,
+            currency: String::new(),
+            status: PriceStatus::Failed,
+            failure: Some(kind),
+            stored_price: None,
+        });
+    }
+
+    pub fn total(&self) -> usize {
+        self.scraped + self.unchanged + self.failed + self.skipped
+    }
+
+    ///
+    /// Get the percentage of attempted providers that failed, skipped providers are not counted
+    ///
+    /// # Returns
+    ///
+    /// f64 - The failure percentage, 0 when no provider was attempted
+    ///
+    pub fn failure_pct(&self) -> f64 {
+        let attempted = self.total() - self.skipped;
+        if attempted == 0 {
+            return 0.0;
+        }
+        self.failed as f64 / attempted as f64 * 100.0
+    }
+}
+
+///
+/// Stats of a run as appended to the metrics file, the counts are None for failed runs
+///
+/// # Fields
+///
+/// - timestamp: DateTime<Utc> - When the run ended, or failed
+/// - run_id: Uuid - The client-generated ID of the run
+/// - status: &'static str - `ok` or `failed`
+/// - duration_secs: Option<f64> - How long the run took
+/// - providers: Option<usize> - Providers in the run, including skipped ones
+/// - succeeded: Option<usize> - Providers that yielded a price, scraped or unchanged
+/// - failed: Option<usize> - Providers that failed to yield a price
+/// - prices_posted: Option<usize> - Prices sent to the API
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub(crate) struct RunMetrics {
+    pub(crate) timestamp: DateTime<Utc>,
+    pub(crate) run_id: Uuid,
+    pub(crate) status: &'static str,
+    pub(crate) duration_secs: Option<f64>,
+    pub(crate) providers: Option<usize>,
+    pub(crate) succeeded: Option<usize>,
+    pub(crate) failed: Option<usize>,
+    pub(crate) prices_posted: Option<usize>,
+}
+
+impl RunMetrics {
+    pub(crate) const CSV_HEADER: &'static str =
+        "timestamp,run_id,status,duration_secs,providers,succeeded,failed,prices_posted";
+
+    ///
+    /// Format the stats as a CSV line matching `CSV_HEADER`, missing counts are left empty
+    ///
+    /// # Returns
+    ///
+    /// String - The CSV line, without a line break
+    ///
+    pub(crate) fn csv_line(&self) -> String {
+        fn field<T: ToString>(value: Option<T>) -> String {
+            value.map(|value| value.to_string()).unwrap_or_default()
+        }
+        [
+            self.timestamp.to_rfc3339(),
+            self.run_id.to_string(),
+            self.status.to_string(),
+            field(self.duration_secs),
+            field(self.providers),
+            field(self.succeeded),
+            field(self.failed),
+            field(self.prices_posted),
+        ]
+        .join(",")
+    }
+}
+
+///
+/// A past run as served by the status server, its stats with what happened to each provider
+///
+/// # Fields
+///
+/// - metrics: RunMetrics - The stats of the run, flattened into the run
+/// - error: Option<String> - Why the run failed, if it did
+/// - failures: BTreeMap<FailureKind, usize> - The failed providers by kind of failure
+/// - records: Vec<PriceRecord> - What the run did with each provider
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub(crate) struct RecentRun {
+    #[serde(flatten)]
+    pub(crate) metrics: RunMetrics,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) error: Option<String>,
+    pub(crate) failures: BTreeMap<FailureKind, usize>,
+    pub(crate) records: Vec<PriceRecord>,
+}
+
+impl RecentRun {
+    pub(crate) fn new<E: Error>(metrics: RunMetrics, result: &Result<RunReport, E>) -> Self {
+        match result {
+            Ok(report) => Self {
+                metrics,
+                error: None,
+                failures: report.failures.clone(),
+                records: report.records.clone(),
+            },
+            Err(e) => Self {
+                metrics,
+                error: Some(e.to_string()),
+                failures: BTreeMap::new(),
+                records: vec![],
+            },
+        }
+    }
+}
+
+impl fmt::Display for RunReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "run {}, {} providers: {} scraped, {} unchanged, {} failed",
+            self.run_id,
+            self.total(),
+            self.scraped,
+            self.unchanged,
+            self.failed,
+        )?;
+        if !self.failures.is_empty() {
+            let kinds: Vec<String> = self
+                .failures
+                .iter()
+                .map(|(kind, count)| format!("{} {}", count, kind))
+                .collect();
+            write!(f, " ({})", kinds.join(", "))?;
+        }
+        write!(f, ", {} skipped", self.skipped)?;
+        if self.discrepancies > 0 {
+            write!(f, ", {} differing from the API", self.discrepancies)?;
+        }
+        if self.throttled > 0 {
+            write!(f, ", {} throttled", self.throttled)?;
+        }
+        if self.status == RunStatus::Interrupted {
+            write!(f, ", interrupted")?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn skipped_providers_are_not_failures() {
+        let provider: Provider = serde_json::from_value(serde_json::json!({
+            "id": 1,
+            "name": "Test provider",
+            "url": "http://localhost",
+        }))
+        .unwrap();
+        let mut report = RunReport::default();
+        report.record(
+            &provider,
+            &ProviderOutcome::Scraped {
+                price: 12.49,
+                discrepancy: None,
+            },
+        );
+        report.record(&provider, &ProviderOutcome::Skipped);
+        report.record(&provider, &ProviderOutcome::Failed(FailureKind::NoMatch));
+
+        assert_eq!(report.failed, 1);
+        assert_eq!(report.skipped, 1);
+        assert_eq!(
+            report.to_string(),
+            "run 00000000-0000-0000-0000-000000000000, 3 providers: 1 scraped, 0 unchanged, 1 failed (1 no match), 1 skipped"
+        );
+        assert_eq!(report.failure_pct(), 50.0);
+        assert_eq!(report.records[2].failure, Some(FailureKind::NoMatch));
+    }
+}
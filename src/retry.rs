@@ -0,0 +1,200 @@
+use reqwest::header::{HeaderMap, RETRY_AFTER};
+use reqwest::{Response, StatusCode};
+use std::future::Future;
+use std::time::Duration;
+
+/// Upper bound for a single backoff delay, including delays requested through `Retry-After`
+const MAX_DELAY: Duration = Duration::from_secs(300);
+
+///
+/// Retry policy for writes to the API
+/// Writes the API can't deduplicate are only retried when they certainly weren't processed: on errors before a
+/// response, like refused connections, DNS or TLS handshake failures, and on explicit 5xx and 429 responses
+/// A timeout or a connection dropped mid-request may come after the API stored the write, so retrying it could
+/// store it twice
+///
+/// # Fields
+///
+/// - max_retries: u32 - How many times a failed request is retried
+/// - base_delay: Duration - The delay before the first retry, doubled for every following retry
+/// - idempotent: bool - Whether the API deduplicates the write, e.g. runs by their run ID, so any failure may be retried
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct RetryPolicy {
+    pub(crate) max_retries: u32,
+    pub(crate) base_delay: Duration,
+    pub(crate) idempotent: bool,
+}
+
+impl RetryPolicy {
+    ///
+    /// Send a request, retrying on transient failures with exponential backoff
+    /// 5xx and 429 responses are retried, honoring `Retry-After` when present, and so are network errors the policy
+    /// deems safe to retry
+    ///
+    /// # Arguments
+    ///
+    /// - description: &str - What is being sent, for logging
+    /// - send: F - Sends one attempt of the request
+    ///
+    /// # Returns
+    ///
+    /// Result<Response, reqwest::Error> - The first non-transient response, or the last one once retries are exhausted
+    ///
+    /// # Errors
+    ///
+    /// If the last attempt fails with a network error, that error is returned
+    ///
+    pub(crate) async fn send<F, Fut>(
+        &self,
+        description: &str,
+        mut send: F,
+    ) -> Result<Response, reqwest::Error>
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = Result<Response, reqwest::Error>>,
+    {
+        let mut attempt = 0;
+        loop {
+            let result = send().await;
+            let retry_after = match &result {
+                Ok(response) if is_transient(response.status()) => {
+                    parse_retry_after(response.headers())
+                }
+                Ok(_) => return result,
+                Err(e) if self.idempotent || is_pre_response(e) => None,
+                Err(e) => {
+                    eprintln!(
+                        "Not retrying {}, the API may have processed it before failing: {}",
+                        description, e
+                    );
+                    return result;
+                }
+            };
+            if attempt >= self.max_retries {
+                return result;
+            }
+
+            let delay = retry_after
+                .unwrap_or_else(|| self.base_delay.saturating_mul(2u32.saturating_pow(attempt)))
+                .min(MAX_DELAY);
+            match &result {
+                Ok(response) => eprintln!(
+                    "Failed to send {} ({}), retrying in {:.1}s",
+                    description,
+                    response.status(),
+                    delay.as_secs_f64()
+                ),
+                Err(e) => eprintln!(
+                    "Failed to send {} ({}), retrying in {:.1}s",
+                    description,
+                    e,
+                    delay.as_secs_f64()
+                ),
+            }
+            tokio::time::sleep(delay).await;
+            attempt += 1;
+        }
+    }
+}
+
+fn is_transient(status: StatusCode) -> bool {
+    status.is_server_error() || status == StatusCode::TOO_MANY_REQUESTS
+}
+
+///
+/// Check whether a request failed before it reached the API
+/// reqwest reports DNS, refused connection and TLS handshake failures as connect errors
+///
+fn is_pre_response(error: &reqwest::Error) -> bool {
+    error.is_connect()
+}
+
+///
+/// Parse the `Retry-After` header, given either as seconds or as an HTTP date
+///
+/// # Arguments
+///
+/// - headers: &HeaderMap - The response headers
+///
+/// # Returns
+///
+/// Option<Duration> - How long to wait, or None if the header is missing or invalid
+///
+pub(crate) fn parse_retry_after(headers: &HeaderMap) -> Option<Duration> {
+    let value = headers.get(RETRY_AFTER)?.to_str().ok()?.trim();
+    if let Ok(seconds) = value.parse::<u64>() {
+        return Some(Duration::from_secs(seconds));
+    }
+
+    let date = chrono::DateTime::parse_from_rfc2822(value).ok()?;
+    let delay = date.with_timezone(&chrono::Utc) - chrono::Utc::now();
+    Some(delay.to_std().unwrap_or(Duration::ZERO))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use reqwest::header::HeaderValue;
+    use std::sync::atomic::{AtomicU32, Ordering};
+    use wiremock::matchers::method;
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    #[tokio::test]
+    async fn only_idempotent_writes_are_retried_after_a_timeout() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(201).set_delay(Duration::from_millis(500)))
+            .mount(&server)
+            .await;
+        let client = reqwest::Client::builder()
+            .timeout(Duration::from_millis(50))
+            .build()
+            .unwrap();
+        let closed_port = std::net::TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port();
+
+        let attempts = |idempotent: bool, url: String| {
+            let client = client.clone();
+            async move {
+                let policy = RetryPolicy {
+                    max_retries: 2,
+                    base_delay: Duration::from_millis(1),
+                    idempotent,
+                };
+                let count = AtomicU32::new(0);
+                let result = policy
+                    .send("price", || {
+                        count.fetch_add(1, Ordering::Relaxed);
+                        client.post(&url).send()
+                    })
+                    .await;
+                assert!(result.is_err());
+                count.load(Ordering::Relaxed)
+            }
+        };
+
+        assert_eq!(attempts(false, server.uri()).await, 1);
+        assert_eq!(attempts(true, server.uri()).await, 3);
+        let refused = format!("http://127.0.0.1:{}", closed_port);
+        assert_eq!(attempts(false, refused).await, 3);
+    }
+
+    #[test]
+    fn parse_retry_after_accepts_seconds_and_dates() {
+        let mut headers = HeaderMap::new();
+        headers.insert(RETRY_AFTER, HeaderValue::from_static("120"));
+        assert_eq!(parse_retry_after(&headers), Some(Duration::from_secs(120)));
+
+        headers.insert(
+            RETRY_AFTER,
+            HeaderValue::from_static("Wed, 21 Oct 2015 07:28:00 GMT"),
+        );
+        assert_eq!(parse_retry_after(&headers), Some(Duration::ZERO));
+
+        headers.insert(RETRY_AFTER, HeaderValue::from_static("soon"));
+        assert_eq!(parse_retry_after(&headers), None);
+    }
+}
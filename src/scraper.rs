@@ -1,27 +1,120 @@
-use chrono::DateTime;
-use futures::stream;
-use futures::stream::StreamExt;
-use reqwest::header::{HeaderMap, HeaderValue, AUTHORIZATION};
-use reqwest::{Client, Url};
+use chrono::{DateTime, TimeDelta};
+use futures::channel::mpsc;
+use futures::stream::{self, BoxStream, Stream, StreamExt};
+use futures::{future, FutureExt, SinkExt};
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::{Rng, SeedableRng};
+use reqwest::cookie::Jar;
+use reqwest::header::{HeaderMap, HeaderValue};
+use reqwest::{Client, StatusCode, Url};
 use scraper::{Html, Selector};
-use serde::{Deserialize, Serialize};
+use serde::Serialize;
 use serde_json::json;
-use std::io::{Error, ErrorKind};
-use std::sync::Arc;
+use std::collections::{HashMap, HashSet};
+use std::fs::OpenOptions;
+use std::io::{Error, Write};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use uuid::Uuid;
 
+/// How long a heartbeat ping or alert webhook may take, so a slow monitoring service can't stall the scraping loop
+const HEARTBEAT_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(10);
+
+/// Route of the login endpoint, appended to the base URL
+const LOGIN_PATH: &str = "/auth/login";
+
+use crate::backend::Backend;
+use crate::body::read_capped;
+use crate::breaker::CircuitBreaker;
+use crate::cache::CachedPage;
+use crate::concurrency::Concurrency;
+use crate::config::{ApiPaths, ScraperConfig};
 use crate::credentials::{Credentials, Token};
+use crate::error::ScraperError;
+use crate::expression::{Expression, Operation};
+use crate::feed;
+use crate::interstitial;
+use crate::price::{
+    decimal_places, parse_with_separators, sanitize_price_string, split_price_range,
+};
+use crate::provider::{
+    load_providers_file, normalize_url, FeedType, Provider, ProviderLogin, Providers, RequestMethod,
+};
+use crate::report::{FailureKind, ProviderOutcome, RecentRun, RunMetrics, RunReport, RunStatus};
+use crate::retry::RetryPolicy;
+use crate::shutdown::Shutdown;
+use crate::signing;
+use crate::state::{ScraperState, StateStore};
+use crate::status::RecentRuns;
+use crate::trace;
+use crate::updated_at::parse_updated_at;
+
+///
+/// A price scraped from a provider page, waiting to be posted
+///
+/// # Fields
+///
+/// - provider_id: i32 - The ID of the provider
+/// - provider_name: String - The name of the provider
+/// - fuel_type: Option<String> - The fuel type the price is for, if the provider declares one
+/// - price: f64 - The scraped price
+/// - scraped_at: DateTime<chrono::Utc> - When the provider page was fetched
+/// - updated_at: Option<DateTime<chrono::Utc>> - When the provider says the price was last updated, if it publishes that
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct ScrapedPrice {
+    pub provider_id: i32,
+    pub provider_name: String,
+    pub fuel_type: Option<String>,
+    pub price: f64,
+    pub scraped_at: DateTime<chrono::Utc>,
+    pub updated_at: Option<DateTime<chrono::Utc>>,
+}
 
-#[derive(Deserialize, Serialize, Clone, Debug)]
-pub(crate) struct Providers {
-    id: i32,
+impl ScrapedPrice {
+    fn new(provider: &Provider, price: f64, scraped_at: DateTime<chrono::Utc>) -> Self {
+        Self {
+            provider_id: provider.id,
+            provider_name: provider.name.clone(),
+            fuel_type: provider.fuel_type.clone(),
+            price,
+            scraped_at,
+            updated_at: None,
+        }
+    }
+
+    ///
+    /// Get the timestamp to post the price with
+    ///
+    /// # Arguments
+    ///
+    /// - post_scraped_at: bool - Whether prices are posted with their scrape time
+    ///
+    /// # Returns
+    ///
+    /// Option<DateTime<chrono::Utc>> - The provider's update time, else the scrape time if posted, else None to let the
+    /// API timestamp the price on receipt
+    ///
+    fn timestamp(&self, post_scraped_at: bool) -> Option<DateTime<chrono::Utc>> {
+        self.updated_at
+            .or(post_scraped_at.then_some(self.scraped_at))
+    }
 }
 
-#[derive(Deserialize, Serialize)]
-pub(crate) struct Provider {
-    id: i32,
-    name: String,
-    url: String,
-    html_element: String,
+///
+/// How a price is extracted from a provider response
+///
+/// # Variants
+///
+/// - Selector: Parse the response as HTML and select elements with a CSS selector
+/// - JsonPointer: Parse the response as JSON and read the value at a JSON pointer
+/// - CsvColumn: Parse the response as CSV and read the values of a column
+/// - Expression: Parse the response as HTML, select elements with a CSS selector and read the price with an operation
+enum Extractor {
+    Selector(Selector),
+    Expression(Selector, Operation),
+    JsonPointer(String),
+    CsvColumn(String),
 }
 
 ///
@@ -29,78 +122,290 @@ pub(crate) struct Provider {
 ///
 /// # Fields
 ///
-/// - providers: Vec<Providers> - A vector of providers
+/// - providers: Vec<Provider> - The providers of the current run, prefetched from the API or loaded from the providers file
+/// - unfetched: Vec<(i32, FailureKind)> - Providers of the current run whose details couldn't be fetched from the API, recorded as failed
 /// - credentials: Credentials - The credentials for the scraper
-/// - client: Client - The reqwest client
-/// - base_url: String - The base URL for the API
+/// - client: Client - The reqwest client for the API, sending the auth header
+/// - page_client: Client - The reqwest client for provider pages, which never get the API's auth header or client certificate
+/// - external_client: Client - The reqwest client for heartbeats, price alerts and trace exports, a plain client so the API credentials are never sent to these services
+/// - backend: Backend - The backend API replicas
+/// - run_id: Uuid - The client-generated ID of the current run, sent to the API so the run record can be deduplicated
 /// - run_start: DateTime<chrono::Utc> - The start time of the run
 /// - run_end: Option<DateTime<chrono::Utc>> - The end time of the run
-pub(crate) struct Scraper {
-    providers: Vec<Providers>,
+/// - page_cache: Mutex<HashMap<i32, CachedPage>> - Cache validators for provider pages, keyed by provider ID
+/// - selector_cache: Mutex<HashMap<String, Selector>> - Compiled selectors, keyed by selector string
+/// - last_scraped: Mutex<HashMap<i32, DateTime<chrono::Utc>>> - When each provider was last scraped, keyed by provider ID
+/// - breakers: Mutex<HashMap<i32, CircuitBreaker>> - Circuit breaker state of each provider, keyed by provider ID
+/// - last_prices: Mutex<HashMap<i32, f64>> - The last price scraped for each provider, keyed by provider ID
+/// - last_posted: Mutex<HashMap<i32, DateTime<chrono::Utc>>> - When a price of each provider was last posted, keyed by provider ID
+/// - resumed: HashSet<i32> - Providers an interrupted run already delivered prices for, skipped when the run is resumed
+/// - recent_runs: RecentRuns - The reports of the last runs, served by the status server
+/// - rng: StdRng - Random source for shuffling the providers, seeded from the config when a seed is set
+/// - sample_rng: StdRng - Random source for sampling the prices posted, seeded from the config when a seed is set
+/// - sampled_out: HashSet<i32> - Providers whose price isn't posted this run, when sampling
+/// - rotation_offset: usize - Where the next run's window of providers starts when rotating through a provider cap
+/// - warmup_runs_left: u32 - Runs left that scrape without posting, to warm caches and connections after startup
+/// - concurrency: Concurrency - How many providers are scraped at once, adapted between runs in adaptive mode
+/// - state_store: Option<Box<dyn StateStore>> - Where the per-provider state is kept between restarts, in memory only when None
+/// - shutdown: Shutdown - Requested to cut the current run short, skipping the providers not started on yet
+/// - run_trace: Option<RunTrace> - Spans of the current run while an OTLP endpoint is configured, requires the `otel` feature
+/// - config: ScraperConfig - The scraper tunables
+pub struct Scraper {
+    providers: Vec<Provider>,
+    unfetched: Vec<(i32, FailureKind)>,
     credentials: Credentials,
     client: Client,
-    base_url: String,
+    page_client: Client,
+    external_client: Client,
+    backend: Backend,
+    run_id: Uuid,
     run_start: DateTime<chrono::Utc>,
     run_end: Option<DateTime<chrono::Utc>>,
+    page_cache: Mutex<HashMap<i32, CachedPage>>,
+    selector_cache: Mutex<HashMap<String, Selector>>,
+    last_scraped: Mutex<HashMap<i32, DateTime<chrono::Utc>>>,
+    breakers: Mutex<HashMap<i32, CircuitBreaker>>,
+    last_prices: Mutex<HashMap<i32, f64>>,
+    last_posted: Mutex<HashMap<i32, DateTime<chrono::Utc>>>,
+    resumed: HashSet<i32>,
+    recent_runs: RecentRuns,
+    rng: StdRng,
+    sample_rng: StdRng,
+    sampled_out: HashSet<i32>,
+    rotation_offset: usize,
+    warmup_runs_left: u32,
+    concurrency: Concurrency,
+    state_store: Option<Box<dyn StateStore>>,
+    shutdown: Shutdown,
+    #[cfg(feature = "otel")]
+    run_trace: Option<crate::otel::RunTrace>,
+    config: ScraperConfig,
 }
 
 impl Scraper {
-    pub(crate) fn new(base_url: String, credentials: Credentials) -> Self {
-        Self {
+    ///
+    /// Create a scraper for the given backend replicas
+    ///
+    /// # Arguments
+    ///
+    /// - base_urls: Vec<String> - The base URLs of the backend replicas, in order of preference
+    /// - credentials: Credentials - The credentials of the API
+    /// - config: ScraperConfig - The scraper configuration
+    ///
+    /// # Returns
+    ///
+    /// Result<Scraper, ScraperError> - The scraper
+    ///
+    /// # Errors
+    ///
+    /// If an API path doesn't form a valid URL with one of the base URLs or an HTTP client can't be built, a config error
+    /// is returned
+    ///
+    pub fn new(
+        base_urls: Vec<String>,
+        credentials: Credentials,
+        config: ScraperConfig,
+    ) -> Result<Self, ScraperError> {
+        for base_url in &base_urls {
+            for path in [
+                &config.paths.providers,
+                &config.paths.provider_detail,
+                &config.paths.prices,
+                &config.paths.bulk_prices,
+                &config.paths.runs,
+                LOGIN_PATH,
+            ] {
+                ApiPaths::parse(base_url, path, 0).map_err(|e| {
+                    ScraperError::Config(format!(
+                        "API path {:?} is invalid with base URL {:?}: {}",
+                        path, base_url, e
+                    ))
+                })?;
+            }
+        }
+        let client_error = |e: reqwest::Error| {
+            ScraperError::Config(format!("Failed to build the HTTP client: {}", e))
+        };
+        let client = config.api_client_builder().build().map_err(client_error)?;
+        let page_client = config.http.client_builder().build().map_err(client_error)?;
+        let external_client = Client::builder().build().map_err(client_error)?;
+        Ok(Self {
             providers: vec![],
-            client: Client::new(),
+            unfetched: vec![],
+            page_client,
+            external_client,
+            client,
             credentials,
-            base_url,
+            backend: Backend::new(base_urls, config.trace_requests),
+            run_id: Uuid::nil(),
             run_start: chrono::Utc::now(),
             run_end: None,
+            page_cache: Mutex::new(HashMap::new()),
+            selector_cache: Mutex::new(HashMap::new()),
+            last_scraped: Mutex::new(HashMap::new()),
+            breakers: Mutex::new(HashMap::new()),
+            last_prices: Mutex::new(HashMap::new()),
+            last_posted: Mutex::new(HashMap::new()),
+            resumed: HashSet::new(),
+            recent_runs: RecentRuns::new(config.recent_runs),
+            rng: match config.shuffle_seed {
+                Some(seed) => StdRng::seed_from_u64(seed),
+                None => StdRng::from_entropy(),
+            },
+            sample_rng: match config.sample_seed {
+                Some(seed) => StdRng::seed_from_u64(seed),
+                None => StdRng::from_entropy(),
+            },
+            sampled_out: HashSet::new(),
+            rotation_offset: 0,
+            warmup_runs_left: config.warmup_runs,
+            concurrency: Concurrency::new(config.adaptive_concurrency),
+            state_store: None,
+            shutdown: Shutdown::new(),
+            #[cfg(feature = "otel")]
+            run_trace: None,
+            config,
+        })
+    }
+
+    ///
+    /// Keep the per-provider state in a state store, restoring the state saved in it
+    /// The state is saved to the store after every run
+    ///
+    /// # Arguments
+    ///
+    /// - store: Box<dyn StateStore> - The state store
+    ///
+    /// # Returns
+    ///
+    /// Result<Scraper, ScraperError> - The scraper with the restored state
+    ///
+    /// # Errors
+    ///
+    /// If the saved state can't be loaded, an error is returned
+    ///
+    pub fn with_state_store(mut self, store: Box<dyn StateStore>) -> Result<Self, ScraperError> {
+        if let Some(state) = store.load()? {
+            println!("Restored the provider state of the previous run");
+            self.page_cache = Mutex::new(state.pages);
+            self.last_scraped = Mutex::new(state.last_scraped);
+            self.breakers = Mutex::new(state.breakers);
+            self.last_prices = Mutex::new(state.last_prices);
+            self.last_posted = Mutex::new(state.last_posted);
+        }
+        self.state_store = Some(store);
+        Ok(self)
+    }
+
+    ///
+    /// Cut runs short when shutdown is requested, posting what they did so far as an interrupted run
+    ///
+    /// # Arguments
+    ///
+    /// - shutdown: Shutdown - The shutdown, e.g. requested by signals
+    ///
+    /// # Returns
+    ///
+    /// Scraper - The scraper watching the shutdown
+    ///
+    pub fn with_shutdown(mut self, shutdown: Shutdown) -> Self {
+        self.shutdown = shutdown;
+        self
+    }
+
+    ///
+    /// Save the per-provider state to the state store, if there is one
+    /// A failed save is logged rather than failing the run, the state is saved again after the next run
+    ///
+    fn save_state(&self) {
+        let Some(store) = &self.state_store else {
+            return;
+        };
+        let state = ScraperState {
+            pages: self.page_cache.lock().unwrap().clone(),
+            last_scraped: self.last_scraped.lock().unwrap().clone(),
+            breakers: self.breakers.lock().unwrap().clone(),
+            last_prices: self.last_prices.lock().unwrap().clone(),
+            last_posted: self.last_posted.lock().unwrap().clone(),
+        };
+        if let Err(e) = store.save(&state) {
+            eprintln!("Failed to save the scraper state: {}", e);
         }
     }
 
     ///
     /// Post the run to the API
     ///
+    /// # Arguments
+    ///
+    /// - report: &RunReport - The summary of the run
+    ///
     /// # Returns
     ///
     ///  Result<(), reqwest::Error> - The result of the post request
     ///
-    async fn post_run(&self) -> Result<(), reqwest::Error> {
+    async fn post_run(&self, report: &RunReport) -> Result<(), reqwest::Error> {
         let now = chrono::Utc::now();
         let json_body = json!({
+            "run_id": self.run_id,
             "start_time": self.run_start,
             "end_time": self.run_end.unwrap_or(now),
+            "status": report.status,
+            "summary": report,
         });
-        let url = Url::parse(&format!("{}/scraping_runs", self.base_url)).unwrap();
-        self.client.post(url).json(&json_body).send().await?;
+        let retry = RetryPolicy {
+            max_retries: self.config.run_retries,
+            base_delay: self.config.retry_base_delay,
+            // The API deduplicates runs by their run ID
+            idempotent: true,
+        };
+        let response = retry
+            .send("run", || {
+                self.backend.send(|base_url| {
+                    let url = ApiPaths::url(base_url, &self.config.paths.runs, 0);
+                    signing::json_body(self.client.post(url), &json_body, self.signing_secret())
+                })
+            })
+            .await?;
+
+        if !response.status().is_success() {
+            eprintln!("Failed to post run {}: {}", self.run_id, response.status());
+        }
         Ok(())
     }
 
+    fn signing_secret(&self) -> Option<&str> {
+        self.config.signing_secret.as_deref()
+    }
+
     ///
-    /// Fetch the providers from the API
+    /// Fetch the providers from the API, sorted by ID so runs and logs are reproducible unless the API order is preserved
     ///
     /// # Returns
     ///
     /// Result<Vec<Providers>, Error> - The result of the fetch request for the providers
     ///
     async fn fetch_providers(&self) -> Result<Vec<Providers>, Error> {
-        let url = Url::parse(&format!("{}/scraping_runs/providers", self.base_url)).unwrap();
         let response = self
-            .client
-            .get(url)
-            .send()
+            .backend
+            .send(|base_url| {
+                let url = ApiPaths::url(base_url, &self.config.paths.providers, 0);
+                self.client.get(url)
+            })
             .await
-            .map_err(|e| Error::new(ErrorKind::Other, e))?;
+            .map_err(Error::other)?;
 
         let status = response.status();
-        let body = response
-            .text()
-            .await
-            .map_err(|e| Error::new(ErrorKind::Other, e))?;
+        let body = response.text().await.map_err(Error::other)?;
 
         if status.is_success() {
-            let providers = serde_json::from_str::<Vec<Providers>>(&body)?;
+            let mut providers = serde_json::from_str::<Vec<Providers>>(&body)?;
+            if !self.config.preserve_provider_order {
+                providers.sort_by_key(|provider| provider.id);
+            }
             Ok(providers)
         } else {
-            Err(Error::new(ErrorKind::Other, "Failed to fetch providers"))
+            Err(Error::other("Failed to fetch providers"))
         }
     }
 
@@ -111,6 +416,7 @@ impl Scraper {
     ///
     /// - provider_id: i32 - The ID of the provider
     /// - price: f64 - The price to add
+    /// - timestamp: Option<DateTime<chrono::Utc>> - When the price was observed, None to let the API timestamp it on receipt
     ///
     /// # Returns
     ///
@@ -118,33 +424,50 @@ impl Scraper {
     ///
     /// # Errors
     ///
-    /// If the request fails, an error is returned
+    /// If the request fails or the API rejects the price with a non-success status, an error is returned
     ///
     /// # Example
     ///
-    /// ```no_run
-    /// let scraper = Scraper::new("http://localhost:8000", Credentials::new("client_id", "client_secret"));
-    /// scraper.add_price_for_provider(1, 100.0).await;
+    /// ```ignore
+    /// let scraper = Scraper::new(vec!["http://localhost:8000"], Credentials::new("client_id", "client_secret"));
+    /// scraper.add_price_for_provider(1, 100.0, None).await;
     /// ```
     ///
     async fn add_price_for_provider(
         &self,
         provider_id: i32,
         price: f64,
+        timestamp: Option<DateTime<chrono::Utc>>,
     ) -> Result<(), reqwest::Error> {
-        let url = Url::parse(&format!(
-            "{}/providers/{}/prices",
-            self.base_url, provider_id
-        ))
-        .unwrap();
-        let json_price = json!({ "price": price });
-        let response = self.client.post(url).json(&json_price).send().await?;
+        if self.config.providers_file.is_some() {
+            self.record_local_price(
+                provider_id,
+                price,
+                timestamp.unwrap_or_else(chrono::Utc::now),
+            );
+            return Ok(());
+        }
+
+        let mut json_price = json!({ "price": price });
+        if let Some(timestamp) = timestamp {
+            json_price["timestamp"] = json!(timestamp);
+        }
+        let retry = RetryPolicy {
+            max_retries: self.config.price_retries,
+            base_delay: self.config.retry_base_delay,
+            idempotent: false,
+        };
+        let response = retry
+            .send(&format!("price for provider {}", provider_id), || {
+                self.backend.send(|base_url| {
+                    let url = ApiPaths::url(base_url, &self.config.paths.prices, provider_id);
+                    signing::json_body(self.client.post(url), &json_price, self.signing_secret())
+                })
+            })
+            .await?;
         let status = response.status();
 
-        if response.status().is_success() {
-            let body = response.text().await?;
-            println!("Added price for provider {}: {}", provider_id, body);
-        } else {
+        if let Err(e) = response.error_for_status_ref() {
             let body = response
                 .text()
                 .await
@@ -153,200 +476,2671 @@ impl Scraper {
                 "Failed to add price for provider {}: {} {}",
                 provider_id, status, body
             );
+            return Err(e);
+        }
+        // The status decides success, a failed body read must not turn the POST into a failure
+        match response.text().await {
+            Ok(body) => println!("Added price for provider {}: {}", provider_id, body),
+            Err(e) => println!(
+                "Added price for provider {}, but failed to read the response body: {}",
+                provider_id, e
+            ),
         }
         Ok(())
     }
 
     ///
-    /// Sanitize a price string by removing unwanted characters and whitespace and parsing it to a float value
+    /// Add the prices of several providers to the API in a single request to the bulk endpoint
     ///
     /// # Arguments
     ///
-    /// - price_string: String - The price string to sanitize
+    /// - prices: &[ScrapedPrice] - The prices to add
     ///
     /// # Returns
     ///
-    /// Result<f64, String> - The result of the sanitization
+    /// Result<(), reqwest::Error> - The result of the post request
     ///
     /// # Errors
     ///
-    /// If the price string cannot be parsed to a float, an error is returned
+    /// If the request fails or the API rejects the prices with a non-success status, an error is returned
     ///
-    fn sanitize_price_string(&self, price_string: String) -> Result<f64, String> {
-        // Remove unwanted characters and whitespace
-        let sanitized: String = price_string
-            .replace("kr.", "")
-            .replace(",-", "")
-            .replace('.', "")
-            .replace(',', ".")
-            .replace(|c: char| c.is_whitespace(), "");
+    async fn add_prices_bulk(&self, prices: &[ScrapedPrice]) -> Result<(), reqwest::Error> {
+        let json_prices = prices
+            .iter()
+            .map(|scraped| {
+                json!({
+                    "provider_id": scraped.provider_id,
+                    "price": scraped.price,
+                    "timestamp": scraped.updated_at.unwrap_or(scraped.scraped_at),
+                })
+            })
+            .collect::<serde_json::Value>();
+        let retry = RetryPolicy {
+            max_retries: self.config.price_retries,
+            base_delay: self.config.retry_base_delay,
+            idempotent: false,
+        };
+        let response = retry
+            .send(&format!("{} prices", prices.len()), || {
+                self.backend.send(|base_url| {
+                    // Parsed by reqwest, so an invalid path is returned as an error of the request
+                    let url = format!("{}{}", base_url, self.config.paths.bulk_prices);
+                    signing::json_body(self.client.post(url), &json_prices, self.signing_secret())
+                })
+            })
+            .await?;
+        let status = response.status();
 
-        sanitized
-            .parse::<f64>()
-            .map_err(|e| format!("Failed to parse price: {}", e))
+        if let Err(e) = response.error_for_status_ref() {
+            let body = response
+                .text()
+                .await
+                .unwrap_or_else(|_| "No response body".to_string());
+            eprintln!("Failed to add {} prices: {} {}", prices.len(), status, body);
+            return Err(e);
+        }
+        println!("Added {} prices", prices.len());
+        Ok(())
     }
 
     ///
-    /// Handle the scraping of the providers by fetching the provider data, scraping the price and adding it to the API
-    /// Uses a concurrency limit of 10 to prevent too many concurrent requests to the API
-    /// Also uses an Arc to share the Scraper struct between async blocks
-    ///
-    /// # Returns
-    ///
-    /// Result<(), reqwest::Error> - The result of the scraping operation
+    /// Record a price scraped from a providers file provider, by printing it or appending it to the prices output
     ///
-    /// # Errors
+    /// # Arguments
     ///
-    /// If the request fails, an error is returned
+    /// - provider_id: i32 - The ID of the provider
+    /// - price: f64 - The scraped price
+    /// - scraped_at: DateTime<chrono::Utc> - When the price was observed
     ///
-    async fn handle_scraping(&self) -> Result<(), reqwest::Error> {
-        let self_arc = Arc::new(self); // Wrap self in Arc
-
-        let tasks = self_arc.providers.iter().map(|provider| {
-            let client = self_arc.client.clone(); // Clone Arc for each async block
-
-            let self_arc_clone = Arc::clone(&self_arc); // Clone Arc for usage in the async block
-            async move {
-                let provider = self.get_provider(provider, &client).await?;
-                println!("Scraping provider: {}", provider.name);
+    fn record_local_price(&self, provider_id: i32, price: f64, scraped_at: DateTime<chrono::Utc>) {
+        let Some(path) = &self.config.prices_output else {
+            println!("Scraped price for provider {}: {}", provider_id, price);
+            return;
+        };
 
-                let selector = Selector::parse(&provider.html_element).unwrap();
-                let provider_url = Url::parse(&provider.url).unwrap();
-
-                let response = client.get(provider_url).send().await?;
-                let body = response.text().await?;
-                let document = Html::parse_document(&body);
-                self_arc_clone
-                    .extract_price(provider, document, &selector)
-                    .await; // Call using the cloned Arc
-                Ok::<_, reqwest::Error>(())
-            }
+        let line = json!({
+            "provider_id": provider_id,
+            "price": price,
+            "scraped_at": scraped_at,
         });
+        let result = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .and_then(|mut file| writeln!(file, "{}", line));
+        if let Err(e) = result {
+            eprintln!(
+                "Failed to write price for provider {} to {}: {}",
+                provider_id,
+                path.display(),
+                e
+            );
+        }
+    }
 
-        let results: Vec<Result<(), reqwest::Error>> = stream::iter(tasks)
-            .buffer_unordered(10) // Set a concurrency limit
-            .collect()
-            .await;
+    ///
+    /// Write the summary of a run with every provider's price and status to the output JSON file, if enabled
+    /// `.jsonl` files get a line appended per run, any other file is overwritten with the latest run
+    /// Failing to write the file is logged and doesn't fail the run
+    ///
+    /// # Arguments
+    ///
+    /// - report: &RunReport - The report of the run
+    ///
+    fn write_run_artifact(&self, report: &RunReport) {
+        let Some(path) = &self.config.output_json else {
+            return;
+        };
 
-        for result in results {
-            result?;
+        let artifact = json!({
+            "run_id": self.run_id,
+            "start_time": self.run_start,
+            "end_time": self.run_end,
+            "prices": report.records,
+        });
+        let is_jsonl = path
+            .extension()
+            .is_some_and(|extension| extension == "jsonl");
+        let result = if is_jsonl {
+            OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(path)
+                .and_then(|mut file| writeln!(file, "{}", artifact))
+        } else {
+            serde_json::to_string_pretty(&artifact)
+                .map_err(Error::other)
+                .and_then(|contents| std::fs::write(path, contents))
+        };
+        if let Err(e) = result {
+            eprintln!(
+                "Failed to write run {} to {}: {}",
+                self.run_id,
+                path.display(),
+                e
+            );
         }
-        Ok(())
     }
 
     ///
-    /// Get a provider from the API by ID
+    /// Summarize the stats of a run, failed runs are summarized without counts
     ///
     /// # Arguments
     ///
-    /// - provider: &Providers - The provider to fetch
-    /// - client: &Client - The reqwest client
+    /// - result: &Result<RunReport, ScraperError> - The result of the run
     ///
     /// # Returns
     ///
-    /// Result<Provider, reqwest::Error> - The result of the fetch request for the provider
+    /// RunMetrics - The stats of the run
     ///
-    /// # Errors
+    fn run_metrics(&self, result: &Result<RunReport, ScraperError>) -> RunMetrics {
+        let report = result.as_ref().ok();
+        // Prices from a providers file are only recorded locally, not sent to the API
+        let posts_prices = self.config.providers_file.is_none();
+        RunMetrics {
+            timestamp: self
+                .run_end
+                .filter(|_| report.is_some())
+                .unwrap_or_else(chrono::Utc::now),
+            run_id: self.run_id,
+            status: match report.map(|report| report.status) {
+                Some(RunStatus::Interrupted) => "interrupted",
+                Some(_) => "ok",
+                None => "failed",
+            },
+            duration_secs: report
+                .and(self.run_duration())
+                .map(|duration| duration.num_milliseconds() as f64 / 1000.0),
+            providers: report.map(RunReport::total),
+            succeeded: report.map(|report| report.scraped + report.unchanged),
+            failed: report.map(|report| report.failed),
+            prices_posted: report.map(|report| if posts_prices { report.posted } else { 0 }),
+        }
+    }
+
     ///
-    /// If the request fails, an error is returned
+    /// Append the stats of a run to the metrics file, if enabled
+    /// `.csv` files get a header when they are created, any other file gets a JSON line per run
+    /// Failing to write the file is logged and doesn't fail the run
     ///
-    async fn get_provider(
-        &self,
-        provider: &Providers,
-        client: &Client,
-    ) -> Result<Provider, reqwest::Error> {
-        let provider = client
-            .get(Url::parse(&format!("{}/providers/{}", self.base_url, provider.id)).unwrap())
-            .send()
-            .await?
-            .json::<Provider>()
-            .await?;
+    /// # Arguments
+    ///
+    /// - record: &RunMetrics - The stats of the run
+    ///
+    fn append_run_metrics(&self, record: &RunMetrics) {
+        let Some(path) = &self.config.metrics_file else {
+            return;
+        };
 
-        Ok(provider)
+        let is_csv = path.extension().is_some_and(|extension| extension == "csv");
+        let result = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .and_then(|mut file| {
+                if !is_csv {
+                    return writeln!(file, "{}", json!(record));
+                }
+                if file.metadata()?.len() == 0 {
+                    writeln!(file, "{}", RunMetrics::CSV_HEADER)?;
+                }
+                writeln!(file, "{}", record.csv_line())
+            });
+        if let Err(e) = result {
+            eprintln!(
+                "Failed to append metrics of run {} to {}: {}",
+                self.run_id,
+                path.display(),
+                e
+            );
+        }
     }
 
     ///
-    /// Extract the price from the HTML document using the provided selector, and sanitize the price string
+    /// Parse a price string of a provider with the separators it declares, guessing them when it declares none
+    /// A price range is parsed bound by bound, and the provider's range bound picks the price
     ///
     /// # Arguments
     ///
-    /// - provider: Provider - The provider to extract the price for
-    /// - document: Html - The HTML document to extract the price from
+    /// - provider: &Provider - The provider the price belongs to
+    /// - price_string: String - The raw price text
     ///
     /// # Returns
     ///
-    /// Result<(), reqwest::Error> - The result of the price extraction
+    /// Result<f64, String> - The parsed price
     ///
     /// # Errors
     ///
-    /// If the price string cannot be sanitized, an error is returned
+    /// If the price string cannot be parsed to a float, an error is returned
+    ///
+    fn parse_price(&self, provider: &Provider, price_string: String) -> Result<f64, String> {
+        let parse = |text: &str| match provider.decimal_separator() {
+            Some(decimal_sep) => parse_with_separators(text, decimal_sep, provider.thousands_sep),
+            None => sanitize_price_string(text),
+        };
+        match split_price_range(&price_string) {
+            Some((from, to)) => Ok(provider.range_bound.pick(parse(&from)?, parse(&to)?)),
+            None => parse(&price_string),
+        }
+    }
+
+    ///
+    /// Handle the scraping of the prefetched providers by scraping their prices and adding them to the API
+    /// Scraped prices go through a bounded queue to a separate posting stream limited by the post concurrency,
+    /// so writes to the API are paced independently of scraping
+    /// With bulk posting, the prices are collected instead and posted in batches once scraping is done
+    /// During warmup runs the prices are dropped instead of posted
+    /// With a resume file, every provider whose price was delivered is recorded in it: accepted by the API with a success
+    /// status, or taken by the broker with publish-only
+    /// With a price command configured, it is run for every price alongside posting, so a slow command never holds up
+    /// the posts; the run still waits for the commands to finish
+    /// With a broker configured, every price is published to it on its way to the API, or only published with publish-only
+    /// When sampling, the prices of the providers sampled out are dropped before posting
+    /// The report counts the prices the API accepted, so rejected, failed and dropped posts aren't counted as posted,
+    /// and the prices throttled by the minimum post interval
     ///
-    async fn extract_price(&self, provider: Provider, document: Html, selector: &Selector) {
-        for element in document.select(selector) {
-            let price_string = element.text().collect::<String>();
-            match self.sanitize_price_string(price_string) {
-                Ok(price) if price > 0.0 => {
-                    if let Err(e) = self.add_price_for_provider(provider.id, price).await {
-                        eprintln!("Error adding price for provider {}: {}", provider.name, e);
+    /// # Returns
+    ///
+    /// RunReport - The report of the scraping operation
+    ///
+    async fn handle_scraping(&self) -> RunReport {
+        let (sender, receiver) = mpsc::channel::<ScrapedPrice>(self.config.post_concurrency);
+        // Unbounded, so prices are handed to the price command without waiting for it
+        let (hooks, hook_queue) = mpsc::unbounded::<ScrapedPrice>();
+        let (delivered, throttled) = (&AtomicUsize::new(0), &AtomicUsize::new(0));
+
+        // Owns the hook queue's sender, so the queue closes once posting is done
+        let posting = async move {
+            if self.warmup_runs_left > 0 {
+                receiver.for_each(|_| future::ready(())).await;
+                return;
+            }
+            let receiver = self.publish_prices(receiver).await.inspect(|(scraped, _)| {
+                if self.config.on_price.is_some() {
+                    // The queue is only closed once posting is done, so this can't fail
+                    let _ = hooks.unbounded_send(scraped.clone());
+                }
+            });
+            if self.config.publish_only {
+                receiver
+                    .for_each(|(scraped, published)| {
+                        // Only a price the broker took is delivered, the others are scraped again on resume
+                        if published {
+                            self.mark_completed(scraped.provider_id);
+                        }
+                        future::ready(())
+                    })
+                    .await;
+                return;
+            }
+            let receiver = receiver
+                .map(|(scraped, _)| scraped)
+                .filter(|scraped| future::ready(!self.sampled_out.contains(&scraped.provider_id)))
+                .filter(|scraped| {
+                    let is_throttled = self.is_throttled(scraped);
+                    if is_throttled {
+                        throttled.fetch_add(1, Ordering::Relaxed);
+                    }
+                    future::ready(!is_throttled)
+                });
+            if self.config.bulk_post && self.config.providers_file.is_none() {
+                let prices: Vec<ScrapedPrice> = receiver.collect().await;
+                for batch in prices.chunks(self.config.bulk_batch_size.max(1)) {
+                    let start = chrono::Utc::now();
+                    let posted = self.add_prices_bulk(batch).await;
+                    let attributes = vec![("prices.count", json!(batch.len()))];
+                    let error = posted.as_ref().err().map(ToString::to_string);
+                    self.record_span("post_prices", start, attributes, error);
+                    match posted {
+                        Ok(()) => {
+                            delivered.fetch_add(batch.len(), Ordering::Relaxed);
+                            batch.iter().for_each(|scraped| {
+                                self.mark_posted(scraped.provider_id);
+                                self.mark_completed(scraped.provider_id);
+                            })
+                        }
+                        Err(e) => eprintln!("Error adding {} prices: {}", batch.len(), e),
                     }
-                    return; // Price found, exit the function
                 }
-                _ => {}
+                return;
             }
-        }
-        println!("No price found for provider: {}", provider.name);
+
+            receiver
+                .for_each_concurrent(self.config.post_concurrency, |scraped| async move {
+                    let timestamp = scraped.timestamp(self.config.post_scraped_at);
+                    let start = chrono::Utc::now();
+                    let posted = self
+                        .add_price_for_provider(scraped.provider_id, scraped.price, timestamp)
+                        .await;
+                    let attributes = vec![
+                        ("provider.id", json!(scraped.provider_id)),
+                        ("price", json!(scraped.price)),
+                    ];
+                    let error = posted.as_ref().err().map(ToString::to_string);
+                    self.record_span("post_price", start, attributes, error);
+                    match posted {
+                        Ok(()) => {
+                            delivered.fetch_add(1, Ordering::Relaxed);
+                            self.mark_posted(scraped.provider_id);
+                            self.mark_completed(scraped.provider_id);
+                        }
+                        Err(e) => eprintln!(
+                            "Error adding price for provider {}: {}",
+                            scraped.provider_name, e
+                        ),
+                    }
+                })
+                .await;
+        };
+        let (mut report, (), ()) = futures::join!(
+            self.scrape_providers(sender),
+            posting,
+            self.run_price_hooks(hook_queue)
+        );
+        report.posted = delivered.load(Ordering::Relaxed);
+        report.throttled = throttled.load(Ordering::Relaxed);
+        report
     }
 
     ///
-    /// Get a token from the API
+    /// Scrape every provider and send the scraped prices to the given queue
+    /// A fixed pool of workers pulls providers from a bounded queue, one worker per allowed concurrent request; the
+    /// concurrency limit is fixed at 10 or adapted between runs
+    ///
+    /// Disabled providers are skipped and recorded as such in the report
+    ///
+    /// # Arguments
+    ///
+    /// - prices: mpsc::Sender<ScrapedPrice> - The queue to send scraped prices to
     ///
     /// # Returns
     ///
-    /// Result<Token, reqwest::Error> - The result of the token request
+    /// RunReport - The report of the scraping operation
     ///
-    async fn get_token(&mut self) -> Result<Token, reqwest::Error> {
-        let url = Url::parse(&format!("{}{}", self.base_url, "/auth/login")).unwrap();
-        let response = self
-            .client
-            .post(url)
-            .json(&json!({
-                "client_id": self.credentials.client_id,
-                "client_secret": self.credentials.client_secret,
-            }))
-            .send()
-            .await?
-            .json()
-            .await?;
+    async fn scrape_providers(&self, prices: mpsc::Sender<ScrapedPrice>) -> RunReport {
+        let workers = self.concurrency.limit().max(1);
+        // Bounded, so providers are handed out as workers free up instead of queued all at once
+        let (mut queue, receiver) = mpsc::channel::<&Provider>(workers);
+        let receiver = futures::lock::Mutex::new(receiver);
 
-        Ok(response)
+        let feeding = async move {
+            for provider in &self.providers {
+                if queue.send(provider).await.is_err() {
+                    break;
+                }
+            }
+            // Dropping the sender closes the queue, so idle workers stop once it is drained
+        };
+        let working = future::join_all((0..workers).map(|_| {
+            let prices = prices.clone();
+            let receiver = &receiver;
+            async move {
+                let mut results = Vec::new();
+                // The lock is only held while taking the next provider, not while scraping it
+                while let Some(provider) = receiver.lock().await.next().await {
+                    let start = chrono::Utc::now();
+                    let outcome = self
+                        .scrape_provider(provider, &self.page_client, prices.clone())
+                        .await;
+                    self.record_breaker(provider.id, &outcome);
+                    let error = match &outcome {
+                        ProviderOutcome::Failed(kind) => Some(kind.to_string()),
+                        _ => None,
+                    };
+                    let attributes = vec![
+                        ("provider.id", json!(provider.id)),
+                        ("provider.name", json!(provider.name)),
+                        ("provider.status", json!(outcome.status())),
+                    ];
+                    self.record_span("fetch_provider", start, attributes, error);
+                    results.push((provider, outcome));
+                }
+                results
+            }
+        }));
+
+        // Each worker owns its own sender, so the price queue closes once every worker is done
+        drop(prices);
+        let ((), results) = futures::join!(feeding, working);
+
+        let mut report = RunReport::default();
+        for (provider, outcome) in results.into_iter().flatten() {
+            report.record(provider, &outcome);
+        }
+        for (provider_id, kind) in &self.unfetched {
+            report.record_unfetched(*provider_id, *kind);
+        }
+        if self.shutdown.requested() {
+            report.status = RunStatus::Interrupted;
+        }
+        report
     }
 
     ///
-    /// Configure the client with the necessary headers
+    /// Scrape all providers and yield the prices as they are extracted, without posting them
+    /// Authenticates and fetches the providers first, like `run`, but doesn't record a run in the API
     ///
     /// # Returns
     ///
-    /// Result<(), Box<dyn std::error::Error>> - The result of the configuration
-    async fn configure_client(&mut self) -> Result<(), Box<dyn std::error::Error>> {
-        let mut headers = HeaderMap::new();
-        let auth_value = format!(
-            "{} {}",
-            self.credentials.token.token_type, self.credentials.token.access_token
-        );
-        headers.insert(AUTHORIZATION, HeaderValue::from_str(&auth_value)?);
+    /// Result<impl Stream<Item = ScrapedPrice>, ScraperError> - The stream of scraped prices
+    ///
+    /// # Errors
+    ///
+    /// If authenticating or fetching the providers fails, an error is returned
+    ///
+    pub async fn scraped_prices(
+        &mut self,
+    ) -> Result<impl Stream<Item = ScrapedPrice> + '_, ScraperError> {
+        self.prepare_run().await?;
+        let this = &*self;
+        let (sender, receiver) = mpsc::channel::<ScrapedPrice>(this.config.post_concurrency);
 
-        self.client = Client::builder().default_headers(headers).build()?;
-        Ok(())
+        // Drive the scraping alongside the receiver, the queue closes once scraping is done
+        let scraping = this
+            .scrape_providers(sender)
+            .map(|report| println!("Scrape finished ({})", report))
+            .into_stream()
+            .filter_map(|()| future::ready(None));
+        Ok(stream::select(receiver, scraping))
     }
 
-    pub(crate) async fn run(&mut self) -> Result<(), reqwest::Error> {
-        self.run_start = chrono::Utc::now();
-        self.credentials.token = self.get_token().await?;
-        self.configure_client().await.unwrap();
-        self.providers = self.fetch_providers().await.unwrap();
-        self.handle_scraping().await?;
-        self.run_end = Some(chrono::Utc::now());
-        self.post_run().await?;
-        Ok(())
+    ///
+    /// Send the request for a provider page, logging a failure
+    ///
+    /// # Arguments
+    ///
+    /// - provider: &Provider - The provider the page belongs to
+    /// - request: reqwest::RequestBuilder - The request for the page
+    ///
+    /// # Returns
+    ///
+    /// Result<reqwest::Response, FailureKind> - The response, or the kind of the failure
+    ///
+    async fn send_page(
+        &self,
+        provider: &Provider,
+        request: reqwest::RequestBuilder,
+    ) -> Result<reqwest::Response, FailureKind> {
+        trace::send(
+            request,
+            self.config.trace_requests,
+            Some(self.config.max_response_bytes),
+        )
+        .await
+        .map_err(|e| {
+            let e = provider.redact_error(e);
+            let kind = FailureKind::from_error(&e);
+            eprintln!(
+                "Failed to fetch provider {} ({}): {}",
+                provider.name, kind, e
+            );
+            kind
+        })
     }
-}
+
+    ///
+    /// Scrape a single provider: fetch its page, extract the price and queue it for posting
+    /// Failures to fetch or scrape the page are recorded in the outcome instead of failing the run
+    ///
+    /// # Arguments
+    ///
+    /// - provider: &Provider - The provider to scrape
+    /// - client: &Client - The reqwest client
+    /// - prices: mpsc::Sender<ScrapedPrice> - The queue of prices waiting to be posted
+    ///
+    /// # Returns
+    ///
+    /// ProviderOutcome - The outcome of scraping the provider
+    ///
+    async fn scrape_provider(
+        &self,
+        provider: &Provider,
+        client: &Client,
+        mut prices: mpsc::Sender<ScrapedPrice>,
+    ) -> ProviderOutcome {
+        if self.shutdown.requested() {
+            println!("Skipping provider {}, shutting down", provider.name);
+            return ProviderOutcome::Skipped;
+        }
+        if !provider.enabled {
+            println!("Skipping disabled provider: {}", provider.name);
+            return ProviderOutcome::Skipped;
+        }
+        if !self.is_due(provider) {
+            println!(
+                "Skipping provider {} until its poll interval has passed",
+                provider.name
+            );
+            return ProviderOutcome::Skipped;
+        }
+        if !self.breaker_allows(provider.id) {
+            println!(
+                "Skipping provider {} while its circuit breaker is open",
+                provider.name
+            );
+            return ProviderOutcome::Skipped;
+        }
+        println!("Scraping provider: {}", provider.name);
+
+        let extractor = match self.extractor(provider) {
+            Ok(extractor) => extractor,
+            Err(e) => {
+                eprintln!(
+                    "Invalid selector {:?} for provider {}: {}",
+                    provider
+                        .expression
+                        .as_deref()
+                        .unwrap_or(&provider.html_element),
+                    provider.name,
+                    e
+                );
+                return ProviderOutcome::Failed(FailureKind::InvalidSelector);
+            }
+        };
+        if provider.render {
+            let scraped_at = chrono::Utc::now();
+            let body = match self.render_page(provider).await {
+                Ok(body) => body,
+                Err(e) => {
+                    eprintln!("Failed to render provider {}: {}", provider.name, e);
+                    return ProviderOutcome::Failed(FailureKind::Render);
+                }
+            };
+            return self
+                .process_page(provider, &extractor, &body, None, scraped_at, prices)
+                .await;
+        }
+
+        // Invalid URLs were already logged when the providers were loaded
+        let Ok(provider_url) = normalize_url(&provider.url) else {
+            return ProviderOutcome::Failed(FailureKind::InvalidUrl);
+        };
+        let session;
+        let client = match &provider.login {
+            Some(login) => match self.login_session(provider, login).await {
+                Ok(client) => {
+                    session = client;
+                    &session
+                }
+                Err(e) => {
+                    eprintln!("Failed to log in to provider {}: {}", provider.name, e);
+                    return ProviderOutcome::Failed(FailureKind::Login);
+                }
+            },
+            None => client,
+        };
+
+        // Conditional requests only make sense for plain GETs
+        let cached = match provider.method {
+            RequestMethod::Get => self.cached_page(provider.id),
+            RequestMethod::Post => None,
+        };
+        if self.config.preflight && provider.method == RequestMethod::Get {
+            if let Err(kind) = self.preflight(provider, client, &provider_url).await {
+                return ProviderOutcome::Failed(kind);
+            }
+        }
+        let mut request = self.page_request(provider, client, provider_url);
+        if let Some(cached) = &cached {
+            request = cached.apply(request);
+        }
+
+        let retry_request = request.try_clone();
+        let mut scraped_at = chrono::Utc::now();
+        let mut response = match self.send_page(provider, request).await {
+            Ok(response) => response,
+            Err(kind) => return ProviderOutcome::Failed(kind),
+        };
+
+        // Wait out a rate limit once, a provider that is still rate limited fails and counts towards its breaker
+        let retry_after = crate::retry::parse_retry_after(response.headers());
+        if let (StatusCode::TOO_MANY_REQUESTS, Some(delay), Some(retry_request)) =
+            (response.status(), retry_after, retry_request)
+        {
+            if delay <= self.config.max_retry_after {
+                println!(
+                    "Provider {} is rate limited, retrying in {}s",
+                    provider.name,
+                    delay.as_secs_f64()
+                );
+                tokio::time::sleep(delay).await;
+                scraped_at = chrono::Utc::now();
+                response = match self.send_page(provider, retry_request).await {
+                    Ok(response) => response,
+                    Err(kind) => return ProviderOutcome::Failed(kind),
+                };
+            } else {
+                println!(
+                    "Provider {} is rate limited for {}s, longer than the {}s allowed, not retrying",
+                    provider.name,
+                    delay.as_secs(),
+                    self.config.max_retry_after.as_secs()
+                );
+            }
+        }
+        let status = response.status();
+        // Challenges are served as 403 or 503, so those are checked for an interstitial before counting as HTTP errors
+        if matches!(
+            status,
+            StatusCode::FORBIDDEN | StatusCode::SERVICE_UNAVAILABLE
+        ) {
+            let headers = response.headers().clone();
+            let body = read_capped(response, self.config.max_response_bytes)
+                .await
+                .ok()
+                .flatten()
+                .unwrap_or_default();
+            if let Some(interstitial) = interstitial::detect(Some(&headers), &body) {
+                eprintln!(
+                    "Provider {} is blocked by a {} ({})",
+                    provider.name, interstitial, status
+                );
+                return ProviderOutcome::Failed(FailureKind::Blocked);
+            }
+            eprintln!("Failed to fetch provider {}: {}", provider.name, status);
+            return ProviderOutcome::Failed(if status.is_server_error() {
+                FailureKind::Http5xx
+            } else {
+                FailureKind::Http4xx
+            });
+        }
+        if status.is_client_error() || status.is_server_error() {
+            let kind = if status.is_server_error() {
+                FailureKind::Http5xx
+            } else if status == StatusCode::TOO_MANY_REQUESTS {
+                FailureKind::RateLimited
+            } else {
+                FailureKind::Http4xx
+            };
+            eprintln!("Failed to fetch provider {}: {}", provider.name, status);
+            return ProviderOutcome::Failed(kind);
+        }
+        if status == StatusCode::NOT_MODIFIED {
+            println!("Page unchanged for provider: {}", provider.name);
+            self.mark_scraped(provider.id, scraped_at);
+            if let Some(price) = cached.and_then(|cached| cached.price) {
+                // Waits for room in the queue, so scraping slows down when posting falls behind
+                let scraped = ScrapedPrice::new(provider, price, scraped_at);
+                let _ = prices.send(scraped).await;
+            }
+            return ProviderOutcome::Unchanged;
+        }
+
+        let validators = CachedPage::from_headers(response.headers());
+        let body = match read_capped(response, self.config.max_response_bytes).await {
+            Ok(Some(body)) => body,
+            Ok(None) => {
+                eprintln!(
+                    "Page of provider {} is larger than {} bytes",
+                    provider.name, self.config.max_response_bytes
+                );
+                return ProviderOutcome::Failed(FailureKind::TooLarge);
+            }
+            Err(e) => {
+                let kind = FailureKind::from_error(&e);
+                eprintln!(
+                    "Failed to read provider {} ({}): {}",
+                    provider.name, kind, e
+                );
+                return ProviderOutcome::Failed(kind);
+            }
+        };
+        self.process_page(provider, &extractor, &body, validators, scraped_at, prices)
+            .await
+    }
+
+    ///
+    /// Log in to a provider with a fresh cookie jar, so the page can be fetched with the session cookies
+    /// The session client doesn't carry the API credentials, only the provider's own cookies
+    ///
+    /// # Arguments
+    ///
+    /// - provider: &Provider - The provider to log in to
+    /// - login: &ProviderLogin - The login URL and form
+    ///
+    /// # Returns
+    ///
+    /// Result<Client, String> - A client sending the cookies of the session
+    ///
+    /// # Errors
+    ///
+    /// If the login URL is invalid, the request fails or the login is rejected, an error is returned
+    ///
+    async fn login_session(
+        &self,
+        provider: &Provider,
+        login: &ProviderLogin,
+    ) -> Result<Client, String> {
+        let url = normalize_url(&login.url).map_err(|e| format!("invalid login URL: {}", e))?;
+        let client = self
+            .config
+            .http
+            .client_builder()
+            .cookie_provider(Arc::new(Jar::default()))
+            .build()
+            .map_err(|e| e.to_string())?;
+
+        let mut request = client.post(url).form(&login.form);
+        if let Some(secs) = provider.timeout_secs {
+            request = request.timeout(std::time::Duration::from_secs(secs));
+        }
+        trace::send(
+            request,
+            self.config.trace_requests,
+            Some(self.config.max_response_bytes),
+        )
+        .await
+        .and_then(|response| response.error_for_status())
+        .map_err(|e| e.to_string())?;
+        Ok(client)
+    }
+
+    ///
+    /// Extract the price from a fetched provider page, remember the page validators and queue the price for posting
+    ///
+    /// # Arguments
+    ///
+    /// - provider: &Provider - The provider the page belongs to
+    /// - extractor: &Extractor - How the price is extracted from the page
+    /// - body: &str - The page body
+    /// - validators: Option<CachedPage> - The cache validators of the response, if any
+    /// - scraped_at: DateTime<chrono::Utc> - When the page was fetched
+    /// - prices: mpsc::Sender<ScrapedPrice> - The queue of prices waiting to be posted
+    ///
+    /// # Returns
+    ///
+    /// ProviderOutcome - The outcome of scraping the provider
+    ///
+    async fn process_page(
+        &self,
+        provider: &Provider,
+        extractor: &Extractor,
+        body: &str,
+        validators: Option<CachedPage>,
+        scraped_at: DateTime<chrono::Utc>,
+        mut prices: mpsc::Sender<ScrapedPrice>,
+    ) -> ProviderOutcome {
+        let price = match self.extract(provider, extractor, body) {
+            Ok(price) if self.config.verify => self.verify_price(provider, extractor, price).await,
+            price => price,
+        };
+
+        if let Some(mut validators) = validators {
+            validators.price = price.ok();
+            self.cache_page(provider.id, validators);
+        }
+        match price {
+            Ok(price) => {
+                self.mark_scraped(provider.id, scraped_at);
+                self.track_price_change(provider, price).await;
+                let scraped = ScrapedPrice {
+                    updated_at: self.updated_at(provider, extractor, body, scraped_at),
+                    ..ScrapedPrice::new(provider, price, scraped_at)
+                };
+                // Before queueing the price, so the stored price can't already be the one just posted
+                let discrepancy = self.reconcile(provider, price).await;
+                let _ = prices.send(scraped).await;
+                ProviderOutcome::Scraped { price, discrepancy }
+            }
+            Err(kind) => {
+                // A challenge served with a success status only shows once its page yields no price
+                let kind = match interstitial::detect(None, body) {
+                    Some(interstitial) if kind != FailureKind::Unstable => {
+                        eprintln!(
+                            "Provider {} is blocked by a {}",
+                            provider.name, interstitial
+                        );
+                        FailureKind::Blocked
+                    }
+                    _ => kind,
+                };
+                println!("No price found for provider {}: {}", provider.name, kind);
+                self.save_snapshot(provider, scraped_at, body);
+                ProviderOutcome::Failed(kind)
+            }
+        }
+    }
+
+    ///
+    /// Compare a scraped price with the latest price the API stores for the provider, when reconciling
+    /// Reconciling is best-effort, failing to fetch the stored price is logged and counts as no discrepancy
+    ///
+    /// # Arguments
+    ///
+    /// - provider: &Provider - The provider the price belongs to
+    /// - price: f64 - The scraped price
+    ///
+    /// # Returns
+    ///
+    /// Option<f64> - The stored price, if it differs from the scraped price by more than the tolerance
+    ///
+    async fn reconcile(&self, provider: &Provider, price: f64) -> Option<f64> {
+        if !self.config.reconcile || self.config.providers_file.is_some() {
+            return None;
+        }
+
+        let stored = match self.stored_price(provider.id).await {
+            Ok(stored) => stored?,
+            Err(e) => {
+                eprintln!(
+                    "Failed to fetch the stored price of provider {}: {}",
+                    provider.name, e
+                );
+                return None;
+            }
+        };
+        if (stored - price).abs() <= self.config.reconcile_tolerance {
+            return None;
+        }
+        eprintln!(
+            "Scraped price {} of provider {} differs from the stored price {}",
+            price, provider.name, stored
+        );
+        Some(stored)
+    }
+
+    ///
+    /// Get the latest price the API stores for a provider
+    ///
+    /// # Arguments
+    ///
+    /// - provider_id: i32 - The ID of the provider
+    ///
+    /// # Returns
+    ///
+    /// Result<Option<f64>, reqwest::Error> - The stored price, or None if the API has no price for the provider
+    ///
+    /// # Errors
+    ///
+    /// If the request fails or the API responds with an error other than 404, an error is returned
+    ///
+    async fn stored_price(&self, provider_id: i32) -> Result<Option<f64>, reqwest::Error> {
+        let response = self
+            .backend
+            .send(|base_url| {
+                let mut url = ApiPaths::url(base_url, &self.config.paths.prices, provider_id);
+                url.set_query(Some("latest"));
+                self.client.get(url)
+            })
+            .await?;
+        if response.status() == StatusCode::NOT_FOUND {
+            return Ok(None);
+        }
+
+        // A single price, or a list of prices with the latest first
+        let json = response
+            .error_for_status()?
+            .json::<serde_json::Value>()
+            .await?;
+        let latest = match &json {
+            serde_json::Value::Array(prices) => prices.first(),
+            _ => Some(&json),
+        };
+        Ok(latest.and_then(|latest| latest["price"].as_f64()))
+    }
+
+    ///
+    /// Remember the price of a provider and post an alert to the webhook when it moved more than the alert threshold
+    /// The first price of a provider has nothing to compare with, so it never alerts
+    /// Posting the alert is best-effort, failures are logged and don't affect the run
+    ///
+    /// # Arguments
+    ///
+    /// - provider: &Provider - The provider the price belongs to
+    /// - price: f64 - The scraped price
+    ///
+    async fn track_price_change(&self, provider: &Provider, price: f64) {
+        let previous = self.last_prices.lock().unwrap().insert(provider.id, price);
+        let (Some(url), Some(previous)) = (&self.config.alert_webhook, previous) else {
+            return;
+        };
+        let change_pct = (price - previous) / previous * 100.0;
+        if change_pct.abs() <= self.config.alert_change_pct {
+            return;
+        }
+
+        println!(
+            "Price of provider {} moved {:+.1}% from {} to {}",
+            provider.name, change_pct, previous, price
+        );
+        let alert = json!({
+            "provider_id": provider.id,
+            "provider_name": provider.name,
+            "old_price": previous,
+            "new_price": price,
+            "change_pct": change_pct,
+        });
+        let result = self
+            .external_client
+            .post(url)
+            .timeout(HEARTBEAT_TIMEOUT)
+            .json(&alert)
+            .send()
+            .await
+            .and_then(|response| response.error_for_status());
+        if let Err(e) = result {
+            eprintln!("Failed to post price alert to {}: {}", url, e);
+        }
+    }
+
+    ///
+    /// Extract the price from a provider page with the provider's extractor
+    ///
+    /// # Arguments
+    ///
+    /// - provider: &Provider - The provider the page belongs to
+    /// - extractor: &Extractor - How the price is extracted from the page
+    /// - body: &str - The page body
+    ///
+    /// # Returns
+    ///
+    /// Result<f64, FailureKind> - The extracted price
+    ///
+    fn extract(
+        &self,
+        provider: &Provider,
+        extractor: &Extractor,
+        body: &str,
+    ) -> Result<f64, FailureKind> {
+        match extractor {
+            Extractor::Selector(selector) => {
+                let document = Html::parse_document(body);
+                self.extract_price(provider, &document, selector)
+            }
+            Extractor::Expression(selector, operation) => {
+                let document = Html::parse_document(body);
+                self.extract_expression_price(provider, &document, selector, operation)
+            }
+            Extractor::JsonPointer(pointer) => self.extract_json_price(provider, body, pointer),
+            Extractor::CsvColumn(column) => self.extract_csv_price(provider, body, column),
+        }
+    }
+
+    ///
+    /// Fetch the provider page once more and check it still yields the same price, to avoid posting flickering prices
+    ///
+    /// # Arguments
+    ///
+    /// - provider: &Provider - The provider the price belongs to
+    /// - extractor: &Extractor - How the price is extracted from the page
+    /// - price: f64 - The price extracted from the first fetch
+    ///
+    /// # Returns
+    ///
+    /// Result<f64, FailureKind> - The price if the second fetch yields it within the tolerance
+    ///
+    /// # Errors
+    ///
+    /// If the second fetch fails or yields another price, `FailureKind::Unstable` is returned
+    ///
+    async fn verify_price(
+        &self,
+        provider: &Provider,
+        extractor: &Extractor,
+        price: f64,
+    ) -> Result<f64, FailureKind> {
+        let body = if provider.render {
+            self.render_page(provider).await
+        } else {
+            self.fetch_page_body(provider)
+                .await
+                .map_err(|e| e.to_string())
+        };
+        let verified = match body {
+            Ok(body) => self.extract(provider, extractor, &body),
+            Err(e) => {
+                eprintln!(
+                    "Failed to refetch provider {} to verify its price: {}",
+                    provider.name, e
+                );
+                return Err(FailureKind::Unstable);
+            }
+        };
+
+        match verified {
+            Ok(verified) if (verified - price).abs() <= self.config.verify_tolerance => Ok(price),
+            verified => {
+                eprintln!(
+                    "Price {} of provider {} didn't hold on a second fetch ({:?}), not posting it",
+                    price, provider.name, verified
+                );
+                Err(FailureKind::Unstable)
+            }
+        }
+    }
+
+    ///
+    /// Build the request for a provider page, with a `Referer` of the page's origin if the provider or config asks for it
+    ///
+    /// # Arguments
+    ///
+    /// - provider: &Provider - The provider the page belongs to
+    /// - client: &Client - The client to send the request with
+    /// - url: Url - The normalized provider page URL
+    ///
+    /// # Returns
+    ///
+    /// reqwest::RequestBuilder - The request for the provider page
+    ///
+    fn page_request(
+        &self,
+        provider: &Provider,
+        client: &Client,
+        url: Url,
+    ) -> reqwest::RequestBuilder {
+        let referer = provider.send_referer || self.config.send_referer;
+        if referer {
+            println!(
+                "Sending Referer {} to provider {}",
+                url.origin().ascii_serialization(),
+                provider.name
+            );
+        }
+        provider.page_request(client, url, referer)
+    }
+
+    ///
+    /// Check a provider page with a HEAD request before fetching it
+    /// Pages responding 4xx or with a content type other than the one the provider is scraped as fail without a GET;
+    /// servers that don't allow HEAD, fail with 5xx or can't be reached are left for the GET to classify
+    ///
+    /// # Arguments
+    ///
+    /// - provider: &Provider - The provider the page belongs to
+    /// - client: &Client - The client to send the request with, the provider's session if it logs in
+    /// - url: &Url - The URL of the page
+    ///
+    /// # Returns
+    ///
+    /// Result<(), FailureKind> - Ok if the page should be fetched
+    ///
+    /// # Errors
+    ///
+    /// If the page is gone or isn't the expected content type, the kind of failure is returned
+    ///
+    async fn preflight(
+        &self,
+        provider: &Provider,
+        client: &Client,
+        url: &Url,
+    ) -> Result<(), FailureKind> {
+        let mut request = provider.authorize(client.head(url.clone()));
+        if let Some(secs) = provider.timeout_secs {
+            request = request.timeout(std::time::Duration::from_secs(secs));
+        }
+        let response = match trace::send(
+            request,
+            self.config.trace_requests,
+            Some(self.config.max_response_bytes),
+        )
+        .await
+        {
+            Ok(response) => response,
+            Err(e) => {
+                eprintln!(
+                    "Preflight of provider {} failed, fetching anyway: {}",
+                    provider.name,
+                    provider.redact_error(e)
+                );
+                return Ok(());
+            }
+        };
+
+        let status = response.status();
+        if status.is_client_error() && status != StatusCode::METHOD_NOT_ALLOWED {
+            eprintln!("Preflight of provider {} failed: {}", provider.name, status);
+            return Err(if status == StatusCode::TOO_MANY_REQUESTS {
+                FailureKind::RateLimited
+            } else {
+                FailureKind::Http4xx
+            });
+        }
+        if !status.is_success() {
+            return Ok(());
+        }
+
+        // CSV feeds are as often served as text/plain as text/csv, so their content type isn't checked
+        let expected = match provider.feed_type {
+            FeedType::Csv => return Ok(()),
+            FeedType::Json => "json",
+            FeedType::Html if provider.json_pointer.is_some() => "json",
+            FeedType::Html => "html",
+        };
+        let content_type = response
+            .headers()
+            .get(reqwest::header::CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+            .map(str::to_lowercase);
+        match content_type {
+            Some(content_type) if !content_type.contains(expected) => {
+                eprintln!(
+                    "Preflight of provider {} failed: content type {} isn't {}",
+                    provider.name, content_type, expected
+                );
+                Err(FailureKind::ContentType)
+            }
+            _ => Ok(()),
+        }
+    }
+
+    ///
+    /// Fetch a provider page without cache validators, for tools that inspect the live page and price verification
+    /// Providers with a login get a fresh session first
+    ///
+    /// # Arguments
+    ///
+    /// - provider: &Provider - The provider to fetch
+    ///
+    /// # Returns
+    ///
+    /// Result<String, ScraperError> - The page body
+    ///
+    async fn fetch_page_body(&self, provider: &Provider) -> Result<String, ScraperError> {
+        let url = normalize_url(&provider.url)
+            .map_err(|e| ScraperError::Config(format!("Invalid URL {:?}: {}", provider.url, e)))?;
+        let client = match &provider.login {
+            Some(login) => self
+                .login_session(provider, login)
+                .await
+                .map_err(|e| ScraperError::Config(format!("Failed to log in: {}", e)))?,
+            None => self.page_client.clone(),
+        };
+        let response = trace::send(
+            self.page_request(provider, &client, url),
+            self.config.trace_requests,
+            Some(self.config.max_response_bytes),
+        )
+        .await
+        .map_err(|e| provider.redact_error(e))?;
+        read_capped(response, self.config.max_response_bytes)
+            .await?
+            .ok_or_else(|| {
+                ScraperError::Config(format!(
+                    "Page is larger than {} bytes",
+                    self.config.max_response_bytes
+                ))
+            })
+    }
+
+    ///
+    /// Build the extractor of a provider according to its feed type
+    /// HTML providers use their JSON pointer if they have one, then their expression, and their selector otherwise
+    ///
+    /// # Arguments
+    ///
+    /// - provider: &Provider - The provider
+    ///
+    /// # Returns
+    ///
+    /// Result<Extractor, String> - The extractor, or the reason the selector is invalid or missing
+    ///
+    fn extractor(&self, provider: &Provider) -> Result<Extractor, String> {
+        match (provider.feed_type, &provider.json_pointer) {
+            (FeedType::Csv, _) => provider
+                .csv_column
+                .clone()
+                .map(Extractor::CsvColumn)
+                .ok_or_else(|| "CSV feeds need a csv_column".to_string()),
+            (_, Some(pointer)) => Ok(Extractor::JsonPointer(pointer.clone())),
+            (FeedType::Json, None) => Err("JSON feeds need a json_pointer".to_string()),
+            (FeedType::Html, None) if provider.expression.is_some() => {
+                let expression =
+                    Expression::parse(provider.expression.as_deref().unwrap_or_default())?;
+                let selector = self.selector(&expression.selector)?;
+                Ok(Extractor::Expression(selector, expression.operation))
+            }
+            (FeedType::Html, None) => self
+                .selector(&provider.html_element)
+                .map(Extractor::Selector),
+        }
+    }
+
+    ///
+    /// Render a provider page in a headless browser and return the rendered DOM
+    ///
+    /// # Arguments
+    ///
+    /// - provider: &Provider - The provider to render
+    ///
+    /// # Returns
+    ///
+    /// Result<String, String> - The rendered page HTML
+    ///
+    /// # Errors
+    ///
+    /// If the browser can't be reached, the page doesn't load or the selector doesn't appear in time, an error is returned
+    ///
+    #[cfg(feature = "render")]
+    async fn render_page(&self, provider: &Provider) -> Result<String, String> {
+        // The browser waits for the elements the price is read from
+        let selector = provider
+            .expression
+            .as_deref()
+            .and_then(|expression| Expression::parse(expression).ok())
+            .map(|expression| expression.selector)
+            .unwrap_or_else(|| provider.html_element.clone());
+        crate::render::render_page(
+            &self.config.webdriver_url,
+            &provider.url,
+            &selector,
+            self.config.render_timeout,
+        )
+        .await
+    }
+
+    #[cfg(not(feature = "render"))]
+    async fn render_page(&self, _provider: &Provider) -> Result<String, String> {
+        Err("the scraper was built without the render feature".to_string())
+    }
+
+    ///
+    /// Save the page of a provider that yielded no price to the debug directory, if enabled
+    ///
+    /// # Arguments
+    ///
+    /// - provider: &Provider - The provider the page belongs to
+    /// - scraped_at: DateTime<chrono::Utc> - When the page was fetched
+    /// - body: &str - The page HTML
+    ///
+    fn save_snapshot(&self, provider: &Provider, scraped_at: DateTime<chrono::Utc>, body: &str) {
+        let Some(snapshots) = &self.config.snapshots else {
+            return;
+        };
+
+        match snapshots.save(provider.id, scraped_at, body) {
+            Ok(path) => println!(
+                "Saved page of provider {} to {}",
+                provider.name,
+                path.display()
+            ),
+            Err(e) => eprintln!("Failed to save page of provider {}: {}", provider.name, e),
+        }
+    }
+
+    ///
+    /// Check whether a provider's poll interval has passed since it was last scraped
+    ///
+    /// # Arguments
+    ///
+    /// - provider: &Provider - The provider to check
+    ///
+    /// # Returns
+    ///
+    /// bool - True if the provider should be scraped in this run
+    ///
+    fn is_due(&self, provider: &Provider) -> bool {
+        let Some(poll_interval) = self.poll_interval(provider) else {
+            return true;
+        };
+        match self.last_scraped.lock().unwrap().get(&provider.id) {
+            Some(last_scraped) => {
+                let poll_interval =
+                    TimeDelta::from_std(poll_interval).unwrap_or(TimeDelta::max_value());
+                chrono::Utc::now() - *last_scraped >= poll_interval
+            }
+            None => true,
+        }
+    }
+
+    ///
+    /// Check whether a scraped price is throttled because a price of its provider was posted less than the minimum
+    /// post interval ago, logging it if so
+    /// Unlike the poll interval this applies to posting, so overlapping runs can't post the same provider twice
+    ///
+    /// # Arguments
+    ///
+    /// - scraped: &ScrapedPrice - The scraped price
+    ///
+    /// # Returns
+    ///
+    /// bool - True if the price shouldn't be posted
+    ///
+    fn is_throttled(&self, scraped: &ScrapedPrice) -> bool {
+        let own = self
+            .providers
+            .iter()
+            .find(|provider| provider.id == scraped.provider_id)
+            .and_then(|provider| provider.min_post_interval_secs)
+            .map(std::time::Duration::from_secs);
+        let Some(interval) = own.or(self.config.min_post_interval) else {
+            return false;
+        };
+        let Some(last_posted) = self
+            .last_posted
+            .lock()
+            .unwrap()
+            .get(&scraped.provider_id)
+            .copied()
+        else {
+            return false;
+        };
+
+        let since = chrono::Utc::now() - last_posted;
+        if since >= TimeDelta::from_std(interval).unwrap_or(TimeDelta::max_value()) {
+            return false;
+        }
+        println!(
+            "Throttled price {} of provider {}, a price was posted {}s ago, within the {}s minimum post interval",
+            scraped.price,
+            scraped.provider_name,
+            since.num_seconds(),
+            interval.as_secs()
+        );
+        true
+    }
+
+    ///
+    /// Remember when a price of a provider was posted, for the minimum post interval
+    ///
+    /// # Arguments
+    ///
+    /// - provider_id: i32 - The ID of the provider
+    ///
+    fn mark_posted(&self, provider_id: i32) {
+        self.last_posted
+            .lock()
+            .unwrap()
+            .insert(provider_id, chrono::Utc::now());
+    }
+
+    ///
+    /// Get the poll interval of a provider: its own when per-provider intervals are enabled, otherwise its group's
+    ///
+    /// # Arguments
+    ///
+    /// - provider: &Provider - The provider
+    ///
+    /// # Returns
+    ///
+    /// Option<Duration> - The poll interval, or None if the provider is scraped every run
+    ///
+    fn poll_interval(&self, provider: &Provider) -> Option<std::time::Duration> {
+        let own = provider
+            .poll_interval_secs
+            .filter(|_| self.config.per_provider_intervals)
+            .map(std::time::Duration::from_secs);
+        own.or_else(|| {
+            let group = provider.group.as_ref()?;
+            self.config.group_intervals.get(group).copied()
+        })
+    }
+
+    ///
+    /// Check whether a provider's circuit breaker lets it be scraped in this run
+    ///
+    /// # Arguments
+    ///
+    /// - provider_id: i32 - The ID of the provider
+    ///
+    /// # Returns
+    ///
+    /// bool - True if the breaker is off, closed or half-open
+    ///
+    fn breaker_allows(&self, provider_id: i32) -> bool {
+        let Some(config) = &self.config.breaker else {
+            return true;
+        };
+        self.breakers
+            .lock()
+            .unwrap()
+            .get(&provider_id)
+            .is_none_or(|breaker| breaker.allows(config, chrono::Utc::now()))
+    }
+
+    ///
+    /// Record the outcome of scraping a provider in its circuit breaker
+    /// Skipped providers leave the breaker untouched
+    ///
+    /// # Arguments
+    ///
+    /// - provider_id: i32 - The ID of the provider
+    /// - outcome: &ProviderOutcome - The outcome of scraping the provider
+    ///
+    fn record_breaker(&self, provider_id: i32, outcome: &ProviderOutcome) {
+        let Some(config) = &self.config.breaker else {
+            return;
+        };
+        let success = match outcome {
+            ProviderOutcome::Skipped => return,
+            ProviderOutcome::Scraped { .. } | ProviderOutcome::Unchanged => true,
+            ProviderOutcome::Failed(_) => false,
+        };
+        self.breakers
+            .lock()
+            .unwrap()
+            .entry(provider_id)
+            .or_default()
+            .record(config, success, chrono::Utc::now());
+    }
+
+    ///
+    /// Remember when a provider was last scraped successfully
+    ///
+    /// # Arguments
+    ///
+    /// - provider_id: i32 - The ID of the provider
+    /// - scraped_at: DateTime<chrono::Utc> - When the provider page was fetched
+    ///
+    fn mark_scraped(&self, provider_id: i32, scraped_at: DateTime<chrono::Utc>) {
+        self.last_scraped
+            .lock()
+            .unwrap()
+            .insert(provider_id, scraped_at);
+    }
+
+    ///
+    /// Get the cache validators remembered for a provider page
+    ///
+    /// # Arguments
+    ///
+    /// - provider_id: i32 - The ID of the provider
+    ///
+    /// # Returns
+    ///
+    /// Option<CachedPage> - The cached validators, if the page has been fetched before
+    ///
+    fn cached_page(&self, provider_id: i32) -> Option<CachedPage> {
+        self.page_cache.lock().unwrap().get(&provider_id).cloned()
+    }
+
+    ///
+    /// Remember the cache validators of a provider page for the next run
+    ///
+    /// # Arguments
+    ///
+    /// - provider_id: i32 - The ID of the provider
+    /// - page: CachedPage - The validators and price of the page
+    ///
+    fn cache_page(&self, provider_id: i32, page: CachedPage) {
+        self.page_cache.lock().unwrap().insert(provider_id, page);
+    }
+
+    ///
+    /// Get the compiled selector for a selector string, compiling and caching it on first use
+    ///
+    /// # Arguments
+    ///
+    /// - html_element: &str - The CSS selector string
+    ///
+    /// # Returns
+    ///
+    /// Result<Selector, String> - The compiled selector
+    ///
+    /// # Errors
+    ///
+    /// If the selector cannot be parsed, an error is returned
+    ///
+    fn selector(&self, html_element: &str) -> Result<Selector, String> {
+        let mut cache = self.selector_cache.lock().unwrap();
+        if let Some(selector) = cache.get(html_element) {
+            return Ok(selector.clone());
+        }
+
+        let selector = Selector::parse(html_element).map_err(|e| e.to_string())?;
+        cache.insert(html_element.to_string(), selector.clone());
+        Ok(selector)
+    }
+
+    ///
+    /// Fetch the details of every provider up front, with the same concurrency limit as scraping
+    /// Keeps fetching metadata from the API separate from scraping the provider pages; a provider whose details can't
+    /// be fetched fails on its own instead of failing the run
+    ///
+    /// # Arguments
+    ///
+    /// - providers: &[Providers] - The providers to fetch
+    ///
+    /// # Returns
+    ///
+    /// (Vec<Provider>, Vec<(i32, FailureKind)>) - The provider details in the order of the providers, and the IDs of
+    /// the providers that couldn't be fetched with the kind of failure
+    ///
+    async fn prefetch_providers(
+        &self,
+        providers: &[Providers],
+    ) -> (Vec<Provider>, Vec<(i32, FailureKind)>) {
+        let fetched = stream::iter(providers)
+            .map(|provider| async move { (provider.id, self.get_provider(provider).await) })
+            .buffered(self.concurrency.limit().max(1))
+            .collect::<Vec<_>>()
+            .await;
+
+        let mut details = Vec::new();
+        let mut unfetched = Vec::new();
+        for (provider_id, result) in fetched {
+            match result {
+                Ok(provider) => details.push(provider),
+                Err(e) => {
+                    eprintln!("Failed to fetch provider {}: {}", provider_id, e);
+                    let kind = if e.is_decode() {
+                        FailureKind::Unparseable
+                    } else {
+                        FailureKind::from_error(&e)
+                    };
+                    unfetched.push((provider_id, kind));
+                }
+            }
+        }
+        (details, unfetched)
+    }
+
+    ///
+    /// Get a provider from the API by ID
+    ///
+    /// # Arguments
+    ///
+    /// - provider: &Providers - The provider to fetch
+    ///
+    /// # Returns
+    ///
+    /// Result<Provider, reqwest::Error> - The result of the fetch request for the provider
+    ///
+    /// # Errors
+    ///
+    /// If the request fails or the API responds with an error status, an error is returned
+    ///
+    async fn get_provider(&self, provider: &Providers) -> Result<Provider, reqwest::Error> {
+        let provider = self
+            .backend
+            .send(|base_url| {
+                self.client.get(ApiPaths::url(
+                    base_url,
+                    &self.config.paths.provider_detail,
+                    provider.id,
+                ))
+            })
+            .await?
+            .error_for_status()?
+            .json::<Provider>()
+            .await?;
+
+        Ok(provider)
+    }
+
+    ///
+    /// Extract the price from the HTML document using the provided selector, and sanitize the price string
+    /// Every parsed price goes through the provider's transformations before it is checked to be positive
+    /// Matches without the provider's expected marker are rejected before parsing, as they are likely unrelated numbers
+    /// Matches that aren't a valid price are skipped, or fail the provider in strict parse mode
+    /// When several elements match, the provider's price selection strategy decides which price is used
+    /// Providers with a table header read the cells the header labels in the matched tables instead of the matched elements
+    ///
+    /// # Arguments
+    ///
+    /// - provider: &Provider - The provider to extract the price for
+    /// - document: &Html - The HTML document to extract the price from
+    ///
+    /// # Returns
+    ///
+    /// Result<f64, FailureKind> - The extracted price
+    ///
+    /// # Errors
+    ///
+    /// If nothing matches, no match has the expected marker, no match is a valid price or no price is positive, the kind
+    /// of failure is returned
+    ///
+    fn extract_price(
+        &self,
+        provider: &Provider,
+        document: &Html,
+        selector: &Selector,
+    ) -> Result<f64, FailureKind> {
+        let matches: Vec<String> = match &provider.table {
+            Some(table) => document
+                .select(selector)
+                .flat_map(|element| provider.table_cells(element, table))
+                .collect(),
+            None => document
+                .select(selector)
+                .map(|element| provider.element_text(element))
+                .collect(),
+        };
+        if matches.is_empty() {
+            return Err(FailureKind::NoMatch);
+        }
+        let matches = self.with_expected_marker(provider, matches, String::clone)?;
+        self.select_price(provider, matches)
+    }
+
+    ///
+    /// Extract the price from the HTML document with the provider's extraction expression
+    /// Every element the expression's selector matches is read with its operation, elements the operation finds nothing
+    /// in are left out, and the texts read are picked from like the texts of matched elements
+    /// The expected marker is looked for in the text of the whole element, as the operation usually cuts it off
+    ///
+    /// # Arguments
+    ///
+    /// - provider: &Provider - The provider to extract the price for
+    /// - document: &Html - The HTML document to extract the price from
+    /// - selector: &Selector - The expression's selector
+    /// - operation: &Operation - The expression's operation
+    ///
+    /// # Returns
+    ///
+    /// Result<f64, FailureKind> - The extracted price
+    ///
+    /// # Errors
+    ///
+    /// If no element matches, no element has the expected marker, the operation finds nothing in any of them, or no text
+    /// is a valid positive price, the kind of failure is returned
+    ///
+    fn extract_expression_price(
+        &self,
+        provider: &Provider,
+        document: &Html,
+        selector: &Selector,
+        operation: &Operation,
+    ) -> Result<f64, FailureKind> {
+        let elements: Vec<_> = document.select(selector).collect();
+        if elements.is_empty() {
+            return Err(FailureKind::NoMatch);
+        }
+        let elements = self.with_expected_marker(provider, elements, |element| {
+            provider.element_text(*element)
+        })?;
+        let matches: Vec<String> = elements
+            .into_iter()
+            .filter_map(|element| operation.apply(provider, element))
+            .collect();
+        if matches.is_empty() {
+            return Err(FailureKind::NoMatch);
+        }
+        self.select_price(provider, matches)
+    }
+
+    ///
+    /// Keep the matches whose text has the provider's expected marker, logging the others
+    ///
+    /// # Arguments
+    ///
+    /// - provider: &Provider - The provider the matches belong to
+    /// - matches: Vec<T> - The matches, at least one
+    /// - text: impl Fn(&T) -> String - The text of a match the marker is looked for in
+    ///
+    /// # Returns
+    ///
+    /// Result<Vec<T>, FailureKind> - The matches with the marker, all of them when the provider expects none
+    ///
+    /// # Errors
+    ///
+    /// If no match has the expected marker, `FailureKind::Suspicious` is returned
+    ///
+    fn with_expected_marker<T>(
+        &self,
+        provider: &Provider,
+        matches: Vec<T>,
+        text: impl Fn(&T) -> String,
+    ) -> Result<Vec<T>, FailureKind> {
+        let matches: Vec<T> = matches
+            .into_iter()
+            .filter(|found| {
+                let text = text(found);
+                let has_marker = provider.has_expected_marker(&text);
+                if !has_marker {
+                    eprintln!(
+                        "Matched text {:?} of provider {} lacks the expected marker {:?}",
+                        text,
+                        provider.name,
+                        provider.expected_marker.as_deref().unwrap_or_default()
+                    );
+                }
+                has_marker
+            })
+            .collect();
+        if matches.is_empty() {
+            return Err(FailureKind::Suspicious);
+        }
+        Ok(matches)
+    }
+
+    ///
+    /// Pick the price from the texts matched on a provider page or feed, once they passed the expected marker check
+    /// The texts are parsed and transformed, and the provider's price selection strategy decides which positive price
+    /// is used
+    ///
+    /// # Arguments
+    ///
+    /// - provider: &Provider - The provider to pick the price for
+    /// - matches: Vec<String> - The matched texts, at least one
+    ///
+    /// # Returns
+    ///
+    /// Result<f64, FailureKind> - The selected price
+    ///
+    /// # Errors
+    ///
+    /// If no match is a valid price or no price is positive, the kind of failure is returned
+    ///
+    fn select_price(&self, provider: &Provider, matches: Vec<String>) -> Result<f64, FailureKind> {
+        let mut parsed = Vec::new();
+        for price_string in matches {
+            match self.parse_price(provider, price_string.clone()) {
+                Ok(price) => {
+                    if self.decimals_match(provider, &price_string) {
+                        parsed.push(provider.transform_price(price));
+                    }
+                }
+                Err(e) if self.config.strict_parse => {
+                    eprintln!(
+                        "Matched text {:?} of provider {} isn't a valid price: {}",
+                        price_string, provider.name, e
+                    );
+                    return Err(FailureKind::Unparseable);
+                }
+                Err(_) => {}
+            }
+        }
+        if parsed.is_empty() {
+            return Err(FailureKind::Unparseable);
+        }
+
+        let prices: Vec<f64> = parsed.into_iter().filter(|price| *price > 0.0).collect();
+        provider
+            .price_selection
+            .select(&prices)
+            .ok_or(FailureKind::OutOfRange)
+    }
+
+    ///
+    /// Check a price string against the decimals the provider expects, warning on a mismatch
+    /// A mismatch usually means a thousands separator was read as a decimal separator or the other way around
+    ///
+    /// # Arguments
+    ///
+    /// - provider: &Provider - The provider the price belongs to
+    /// - price_string: &str - The raw price text
+    ///
+    /// # Returns
+    ///
+    /// bool - False if the decimals don't match and strict decimals are enabled
+    ///
+    fn decimals_match(&self, provider: &Provider, price_string: &str) -> bool {
+        let Some(expected) = provider.expected_decimals else {
+            return true;
+        };
+        let decimals = decimal_places(price_string, provider.decimal_separator().unwrap_or(','));
+        if decimals == expected {
+            return true;
+        }
+
+        eprintln!(
+            "Price {:?} of provider {} has {} decimals, expected {}",
+            price_string.trim(),
+            provider.name,
+            decimals,
+            expected
+        );
+        !self.config.strict_decimals
+    }
+
+    ///
+    /// Extract the price from a JSON response at the provider's JSON pointer
+    /// Numbers are used as-is and go through the provider's transformations, strings go through the same expected
+    /// marker and decimals checks and price selection as matched HTML text
+    ///
+    /// # Arguments
+    ///
+    /// - provider: &Provider - The provider to extract the price for
+    /// - body: &str - The JSON response body
+    /// - pointer: &str - The JSON pointer to the price, e.g. `/data/price`
+    ///
+    /// # Returns
+    ///
+    /// Result<f64, FailureKind> - The extracted price
+    ///
+    /// # Errors
+    ///
+    /// If the pointer matches nothing, the value isn't a valid price or the price isn't positive, the kind of failure is returned
+    ///
+    fn extract_json_price(
+        &self,
+        provider: &Provider,
+        body: &str,
+        pointer: &str,
+    ) -> Result<f64, FailureKind> {
+        let json = serde_json::from_str::<serde_json::Value>(body).map_err(|e| {
+            eprintln!("Invalid JSON from provider {}: {}", provider.name, e);
+            FailureKind::Unparseable
+        })?;
+        let price = match json.pointer(pointer) {
+            None => return Err(FailureKind::NoMatch),
+            Some(serde_json::Value::Number(number)) => {
+                number.as_f64().map(|price| provider.transform_price(price))
+            }
+            Some(serde_json::Value::String(price_string)) => {
+                let matches =
+                    self.with_expected_marker(provider, vec![price_string.clone()], String::clone)?;
+                return self.select_price(provider, matches);
+            }
+            Some(_) => None,
+        };
+
+        match price {
+            Some(price) if price > 0.0 => Ok(price),
+            Some(_) => Err(FailureKind::OutOfRange),
+            None => Err(FailureKind::Unparseable),
+        }
+    }
+
+    ///
+    /// Extract the price from a CSV feed, from the values of the provider's price column
+    /// Every row's value is a match, so the provider's price selection strategy picks among the rows like among
+    /// several matched elements
+    ///
+    /// # Arguments
+    ///
+    /// - provider: &Provider - The provider to extract the price for
+    /// - body: &str - The CSV feed
+    /// - column: &str - The header of the price column
+    ///
+    /// # Returns
+    ///
+    /// Result<f64, FailureKind> - The extracted price
+    ///
+    /// # Errors
+    ///
+    /// If the feed has no such column or no rows, or no value is a valid positive price, the kind of failure is returned
+    ///
+    fn extract_csv_price(
+        &self,
+        provider: &Provider,
+        body: &str,
+        column: &str,
+    ) -> Result<f64, FailureKind> {
+        match feed::csv_column(body, column) {
+            Some(values) if !values.is_empty() => {
+                let values = self.with_expected_marker(provider, values, String::clone)?;
+                self.select_price(provider, values)
+            }
+            _ => Err(FailureKind::NoMatch),
+        }
+    }
+
+    ///
+    /// Read when the page says the price was last updated, for providers with an updated-at selector
+    /// An element's `datetime` attribute is preferred over its text, so `<time>` elements are read exactly
+    ///
+    /// # Arguments
+    ///
+    /// - provider: &Provider - The provider the page belongs to
+    /// - extractor: &Extractor - How the price was extracted, which decides how the selector is applied
+    /// - body: &str - The page body
+    /// - scraped_at: DateTime<chrono::Utc> - When the page was fetched, used when the update time can't be read
+    ///
+    /// # Returns
+    ///
+    /// Option<DateTime<chrono::Utc>> - The update time, the scrape time if it can't be read, or None without a selector
+    ///
+    fn updated_at(
+        &self,
+        provider: &Provider,
+        extractor: &Extractor,
+        body: &str,
+        scraped_at: DateTime<chrono::Utc>,
+    ) -> Option<DateTime<chrono::Utc>> {
+        let updated_at_selector = provider.updated_at_selector.as_deref()?;
+        let text = match extractor {
+            Extractor::Selector(_) | Extractor::Expression(..) => self
+                .selector(updated_at_selector)
+                .ok()
+                .and_then(|selector| {
+                    let document = Html::parse_document(body);
+                    let element = document.select(&selector).next()?;
+                    Some(match element.value().attr("datetime") {
+                        Some(datetime) => datetime.to_string(),
+                        None => provider.element_text(element),
+                    })
+                }),
+            Extractor::JsonPointer(_) => serde_json::from_str::<serde_json::Value>(body)
+                .ok()
+                .and_then(|json| {
+                    json.pointer(updated_at_selector)?
+                        .as_str()
+                        .map(String::from)
+                }),
+            Extractor::CsvColumn(_) => feed::csv_column(body, updated_at_selector)
+                .and_then(|values| values.into_iter().next()),
+        };
+
+        let updated_at = text.and_then(|text| {
+            parse_updated_at(&text, chrono::Utc::now(), self.config.updated_at_timezone)
+        });
+        if updated_at.is_none() {
+            eprintln!(
+                "Failed to read the update time of provider {} at {:?}, using the scrape time",
+                provider.name, updated_at_selector
+            );
+        }
+        Some(updated_at.unwrap_or(scraped_at))
+    }
+
+    ///
+    /// Get a token from the API
+    ///
+    /// # Returns
+    ///
+    /// Result<Token, reqwest::Error> - The result of the token request
+    ///
+    async fn get_token(&mut self) -> Result<Token, reqwest::Error> {
+        let json_credentials = json!({
+            "client_id": self.credentials.client_id,
+            "client_secret": self.credentials.client_secret,
+        });
+        let response = self
+            .backend
+            .send(|base_url| {
+                let url = ApiPaths::url(base_url, LOGIN_PATH, 0);
+                self.client.post(url).json(&json_credentials)
+            })
+            .await?
+            .json()
+            .await?;
+
+        Ok(response)
+    }
+
+    ///
+    /// Configure the client with the necessary headers
+    /// The client is only replaced once the new one is built, so a failure keeps the previous working client
+    ///
+    /// # Returns
+    ///
+    /// Result<(), ScraperError> - The result of the configuration
+    ///
+    /// # Errors
+    ///
+    /// If the auth header can't be built, e.g. the token has bytes not allowed in a header, or the client can't be built,
+    /// an error is returned
+    ///
+    async fn configure_client(&mut self) -> Result<(), ScraperError> {
+        let mut headers = HeaderMap::new();
+        let (auth_header, auth_value) = self
+            .credentials
+            .auth_header()
+            .map_err(ScraperError::Config)?;
+        // The value is a secret, so only the reason it's invalid is reported
+        let auth_value = HeaderValue::from_str(&auth_value).map_err(|e| {
+            ScraperError::Config(format!("Invalid {} header value: {}", auth_header, e))
+        })?;
+        headers.insert(auth_header, auth_value);
+
+        self.client = self
+            .config
+            .api_client_builder()
+            .default_headers(headers)
+            .build()?;
+        Ok(())
+    }
+
+    ///
+    /// Get the duration of the last completed run
+    ///
+    /// # Returns
+    ///
+    /// Option<TimeDelta> - The duration of the run, or None if no run has completed
+    ///
+    pub fn run_duration(&self) -> Option<TimeDelta> {
+        self.run_end.map(|run_end| run_end - self.run_start)
+    }
+
+    ///
+    /// Start serving the reports of the last runs as JSON at `/runs`, if a status address is configured
+    /// The server runs in the background for as long as the process does
+    ///
+    /// # Returns
+    ///
+    /// Result<Option<SocketAddr>, ScraperError> - The address the server listens on, None without a status address
+    ///
+    /// # Errors
+    ///
+    /// If the address can't be bound, an error is returned
+    ///
+    pub async fn start_status_server(&self) -> Result<Option<std::net::SocketAddr>, ScraperError> {
+        let Some(addr) = self.config.status_addr else {
+            return Ok(None);
+        };
+        let listener = tokio::net::TcpListener::bind(addr).await?;
+        let addr = listener.local_addr()?;
+        tokio::spawn(crate::status::serve(listener, self.recent_runs.clone()));
+        println!("Serving recent runs at http://{}/runs", addr);
+        Ok(Some(addr))
+    }
+
+    ///
+    /// Run a full scrape: authenticate, fetch the providers, scrape them and post the run
+    /// With a providers file, the providers are read from the file and nothing is sent to the API
+    ///
+    /// # Returns
+    ///
+    /// Result<RunReport, ScraperError> - The report of the run
+    ///
+    pub async fn run(&mut self) -> Result<RunReport, ScraperError> {
+        let result = self.execute_run().await;
+        self.ping_heartbeat(result.is_ok()).await;
+        self.send_metrics(&result);
+        self.export_trace(&result).await;
+        result
+    }
+
+    ///
+    /// Start the trace of the current run, if an OTLP endpoint is configured
+    ///
+    #[cfg(feature = "otel")]
+    fn start_trace(&mut self) {
+        self.run_trace = self
+            .config
+            .otlp_endpoint
+            .as_ref()
+            .map(|_| crate::otel::RunTrace::new(self.run_id));
+    }
+
+    #[cfg(not(feature = "otel"))]
+    fn start_trace(&mut self) {}
+
+    ///
+    /// Record a span of the current run that ends now, if the run is traced
+    ///
+    /// # Arguments
+    ///
+    /// - name: &'static str - The name of the span, e.g. `fetch_provider`
+    /// - start: DateTime<chrono::Utc> - When the span started
+    /// - attributes: Vec<(&'static str, serde_json::Value)> - The attributes of the span, e.g. the provider ID
+    /// - error: Option<String> - Why the span failed, None if it succeeded
+    ///
+    #[cfg(feature = "otel")]
+    fn record_span(
+        &self,
+        name: &'static str,
+        start: DateTime<chrono::Utc>,
+        attributes: Vec<(&'static str, serde_json::Value)>,
+        error: Option<String>,
+    ) {
+        if let Some(trace) = &self.run_trace {
+            trace.record(name, start, attributes, error);
+        }
+    }
+
+    #[cfg(not(feature = "otel"))]
+    fn record_span(
+        &self,
+        _name: &'static str,
+        _start: DateTime<chrono::Utc>,
+        _attributes: Vec<(&'static str, serde_json::Value)>,
+        _error: Option<String>,
+    ) {
+    }
+
+    ///
+    /// End the trace of a run and export it to the OTLP endpoint, if one is configured
+    /// Exporting is best-effort, failures are logged and don't affect the run
+    ///
+    /// # Arguments
+    ///
+    /// - result: &Result<RunReport, ScraperError> - The result of the run
+    ///
+    #[cfg(feature = "otel")]
+    async fn export_trace(&mut self, result: &Result<RunReport, ScraperError>) {
+        let (Some(endpoint), Some(trace)) = (&self.config.otlp_endpoint, self.run_trace.take())
+        else {
+            return;
+        };
+        let mut attributes = vec![("run.id", json!(self.run_id))];
+        if let Ok(report) = result {
+            attributes.extend([
+                ("run.status", json!(report.status)),
+                ("providers.scraped", json!(report.scraped)),
+                ("providers.unchanged", json!(report.unchanged)),
+                ("providers.skipped", json!(report.skipped)),
+                ("providers.failed", json!(report.failed)),
+            ]);
+        }
+        let error = result.as_ref().err().map(ToString::to_string);
+        let request = trace.finish(self.run_start, attributes, error);
+        if let Err(e) = crate::otel::export(&self.external_client, endpoint, &request).await {
+            eprintln!("Failed to export the trace of run {}: {}", self.run_id, e);
+        }
+    }
+
+    #[cfg(not(feature = "otel"))]
+    async fn export_trace(&mut self, _result: &Result<RunReport, ScraperError>) {}
+
+    ///
+    /// Send the metrics of a run to the StatsD endpoint, if one is configured
+    /// Sending is best-effort, failures are logged and don't affect the run
+    ///
+    /// # Arguments
+    ///
+    /// - result: &Result<RunReport, ScraperError> - The result of the run
+    ///
+    #[cfg(feature = "statsd")]
+    fn send_metrics(&self, result: &Result<RunReport, ScraperError>) {
+        let Some(addr) = &self.config.statsd_addr else {
+            return;
+        };
+        // A failed run has no end time of its own, so its duration would be stale
+        let duration = match result {
+            Ok(_) => self
+                .run_duration()
+                .and_then(|duration| duration.to_std().ok()),
+            Err(_) => None,
+        };
+        if let Err(e) = crate::statsd::send_run_metrics(addr, result, duration) {
+            eprintln!("Failed to send metrics to {}: {}", addr, e);
+        }
+    }
+
+    #[cfg(not(feature = "statsd"))]
+    fn send_metrics(&self, _result: &Result<RunReport, ScraperError>) {
+        if self.config.statsd_addr.is_some() {
+            eprintln!("Not sending metrics, the scraper was built without the statsd feature");
+        }
+    }
+
+    ///
+    /// Run the price command for every price in a queue until it closes, if one is configured
+    /// Commands for several prices run at once, up to the post concurrency; failures are logged
+    ///
+    /// # Arguments
+    ///
+    /// - prices: mpsc::UnboundedReceiver<ScrapedPrice> - The queue of prices to run the command for
+    ///
+    async fn run_price_hooks(&self, prices: mpsc::UnboundedReceiver<ScrapedPrice>) {
+        let Some(command) = &self.config.on_price else {
+            return;
+        };
+
+        prices
+            .for_each_concurrent(self.config.post_concurrency.max(1), |scraped| async move {
+                let args = vec![
+                    scraped.provider_id.to_string(),
+                    scraped.provider_name.clone(),
+                    scraped.price.to_string(),
+                ];
+                let input = serde_json::to_string(&scraped).unwrap();
+                let ran = crate::hook::run_hook(command, args, input, self.config.on_price_timeout);
+                if let Err(e) = ran.await {
+                    eprintln!(
+                        "Price command for provider {} failed: {}",
+                        scraped.provider_name, e
+                    );
+                }
+            })
+            .await;
+    }
+
+    ///
+    /// Publish every price passing through a stream to the broker, if one is configured
+    /// Publishing is best-effort, failures are logged and the prices pass on regardless; after a failed publish, e.g. a
+    /// broker that stopped answering, the rest of the run's prices aren't published so they aren't held up by it
+    ///
+    /// # Arguments
+    ///
+    /// - prices: mpsc::Receiver<ScrapedPrice> - The queue of scraped prices
+    ///
+    /// # Returns
+    ///
+    /// BoxStream<(ScrapedPrice, bool)> - The same prices as they pass, with whether the broker took them
+    ///
+    #[cfg(feature = "pubsub")]
+    async fn publish_prices(
+        &self,
+        prices: mpsc::Receiver<ScrapedPrice>,
+    ) -> BoxStream<'_, (ScrapedPrice, bool)> {
+        let Some(url) = &self.config.publish_url else {
+            return prices.map(|scraped| (scraped, false)).boxed();
+        };
+        let publisher = match crate::pubsub::Publisher::connect(url).await {
+            Ok(publisher) => Arc::new(futures::lock::Mutex::new(Some(publisher))),
+            Err(e) => {
+                eprintln!("Failed to connect to broker {}: {}", url, e);
+                return prices.map(|scraped| (scraped, false)).boxed();
+            }
+        };
+
+        prices
+            .then(move |scraped| {
+                let publisher = Arc::clone(&publisher);
+                async move {
+                    let mut publisher = publisher.lock().await;
+                    let Some(connection) = publisher.as_mut() else {
+                        return (scraped, false);
+                    };
+                    let payload = serde_json::to_string(&scraped).unwrap();
+                    let published = connection
+                        .publish(&self.config.publish_subject, &payload)
+                        .await;
+                    if let Err(e) = &published {
+                        eprintln!(
+                            "Failed to publish price for provider {}, not publishing the rest of the run: {}",
+                            scraped.provider_name, e
+                        );
+                        *publisher = None;
+                    }
+                    (scraped, published.is_ok())
+                }
+            })
+            .boxed()
+    }
+
+    #[cfg(not(feature = "pubsub"))]
+    async fn publish_prices(
+        &self,
+        prices: mpsc::Receiver<ScrapedPrice>,
+    ) -> BoxStream<'_, (ScrapedPrice, bool)> {
+        if self.config.publish_url.is_some() {
+            eprintln!("Not publishing prices, the scraper was built without the pubsub feature");
+        }
+        prices.map(|scraped| (scraped, false)).boxed()
+    }
+
+    ///
+    /// Ping the heartbeat URL after a successful run, or the failure URL after a failed one
+    /// Pinging is best-effort, failures are logged and don't affect the run
+    ///
+    /// # Arguments
+    ///
+    /// - success: bool - Whether the run succeeded
+    ///
+    async fn ping_heartbeat(&self, success: bool) {
+        let url = if success {
+            &self.config.heartbeat_url
+        } else {
+            &self.config.heartbeat_fail_url
+        };
+        let Some(url) = url else {
+            return;
+        };
+
+        let response = self
+            .external_client
+            .get(url)
+            .timeout(HEARTBEAT_TIMEOUT)
+            .send()
+            .await;
+        if let Err(e) = response.and_then(|response| response.error_for_status()) {
+            eprintln!("Failed to ping heartbeat {}: {}", url, e);
+        }
+    }
+
+    async fn execute_run(&mut self) -> Result<RunReport, ScraperError> {
+        self.run_start = chrono::Utc::now();
+        // Cleared so a run that fails early isn't timed against the end of the previous run
+        self.run_end = None;
+        self.run_id = Uuid::new_v4();
+        if self.warmup_runs_left == 0 {
+            self.resume_interrupted_run();
+        }
+        // After resuming, which takes over the interrupted run's ID
+        self.start_trace();
+        if self.warmup_runs_left > 0 {
+            println!(
+                "Starting warmup run {}, {} warmup runs left before posting",
+                self.run_id, self.warmup_runs_left
+            );
+        } else {
+            println!("Starting run {}", self.run_id);
+        }
+        let result = self.scrape_run().await;
+        self.clear_resume_file();
+        let metrics = self.run_metrics(&result);
+        self.append_run_metrics(&metrics);
+        self.recent_runs.push(RecentRun::new(metrics, &result));
+        self.save_state();
+        if self.warmup_runs_left > 0 {
+            self.warmup_runs_left -= 1;
+            if self.warmup_runs_left == 0 {
+                println!("Warmup done, posting from the next run");
+            }
+        }
+        result
+    }
+
+    async fn scrape_run(&mut self) -> Result<RunReport, ScraperError> {
+        if self.config.providers_file.is_none() {
+            self.authenticate().await?;
+            if let Some(window) = self.config.since_last_success {
+                if let Some(end_time) = self.fresh_run(window).await {
+                    println!(
+                        "Skipping run {}, the API already has a run that ended at {}",
+                        self.run_id, end_time
+                    );
+                    self.run_end = Some(chrono::Utc::now());
+                    return Ok(RunReport {
+                        run_id: self.run_id,
+                        ..RunReport::default()
+                    });
+                }
+            }
+        }
+        self.load_providers().await?;
+        if self.providers.is_empty() {
+            eprintln!(
+                "WARNING: run {} has no providers to scrape, check the providers endpoint or file",
+                self.run_id
+            );
+            if self.config.fail_on_empty {
+                return Err(ScraperError::NoProviders);
+            }
+        }
+        self.shuffle_providers();
+        self.cap_providers();
+        self.skip_resumed_providers();
+        self.sample_providers();
+        let mut report = self.handle_scraping().await;
+        report.run_id = self.run_id;
+        let previous_limit = self.concurrency.limit();
+        self.concurrency.adjust(&report);
+        if self.concurrency.limit() != previous_limit {
+            println!(
+                "Adjusted scraping concurrency from {} to {}",
+                previous_limit,
+                self.concurrency.limit()
+            );
+        }
+        self.run_end = Some(chrono::Utc::now());
+        let failure_pct = report.failure_pct();
+        let over_threshold = self
+            .config
+            .fail_threshold_pct
+            .filter(|&threshold_pct| failure_pct > threshold_pct);
+        if over_threshold.is_some() {
+            report.status = RunStatus::Failed;
+        }
+        self.write_run_artifact(&report);
+        if self.config.providers_file.is_none() && self.warmup_runs_left == 0 {
+            self.post_run(&report).await?;
+        }
+
+        if let Some(threshold_pct) = over_threshold {
+            return Err(ScraperError::FailureThreshold {
+                failure_pct,
+                threshold_pct,
+            });
+        }
+        Ok(report)
+    }
+
+    ///
+    /// Load the providers for a run, either from the providers file or by authenticating and fetching them and their details from the API
+    ///
+    /// # Returns
+    ///
+    /// Result<(), ScraperError> - The result of loading the providers
+    ///
+    async fn prepare_run(&mut self) -> Result<(), ScraperError> {
+        if self.config.providers_file.is_none() {
+            self.authenticate().await?;
+        }
+        self.load_providers().await
+    }
+
+    ///
+    /// Load the providers for a run from the providers file or from the API, which must be authenticated with already
+    ///
+    /// # Returns
+    ///
+    /// Result<(), ScraperError> - The result of loading the providers
+    ///
+    async fn load_providers(&mut self) -> Result<(), ScraperError> {
+        self.unfetched.clear();
+        if let Some(path) = &self.config.providers_file {
+            // Re-read every run so selector changes are picked up without a restart
+            self.providers = load_providers_file(path)?;
+        } else {
+            let start = chrono::Utc::now();
+            let fetched = self.fetch_providers().await;
+            let attributes = match &fetched {
+                Ok(providers) => vec![("providers.count", json!(providers.len()))],
+                Err(_) => vec![],
+            };
+            let error = fetched.as_ref().err().map(ToString::to_string);
+            self.record_span("fetch_providers", start, attributes, error);
+            let providers = fetched?;
+            (self.providers, self.unfetched) = self.prefetch_providers(&providers).await;
+        }
+        self.select_groups();
+        self.normalize_provider_urls();
+        Ok(())
+    }
+
+    ///
+    /// Get the end time of the latest completed run the API has, if it ended within the freshness window
+    /// Checking is best-effort, a failed check is logged and treated as no recent run
+    ///
+    /// # Arguments
+    ///
+    /// - window: Duration - How recent a run has to be to count as fresh
+    ///
+    /// # Returns
+    ///
+    /// Option<DateTime<chrono::Utc>> - The end time of the fresh run, or None if there is none
+    ///
+    async fn fresh_run(&self, window: std::time::Duration) -> Option<DateTime<chrono::Utc>> {
+        let end_time = match self.latest_run_end().await {
+            Ok(end_time) => end_time?,
+            Err(e) => {
+                eprintln!("Failed to check the latest run, scraping anyway: {}", e);
+                return None;
+            }
+        };
+        let window = TimeDelta::from_std(window).unwrap_or(TimeDelta::max_value());
+        (chrono::Utc::now() - end_time < window).then_some(end_time)
+    }
+
+    ///
+    /// Get the end time of the latest completed run the API stores
+    /// Failed and interrupted runs, e.g. of another instance, don't count, so they never suppress scraping
+    ///
+    /// # Returns
+    ///
+    /// Result<Option<DateTime<chrono::Utc>>, reqwest::Error> - The end time, or None if the API has no completed runs
+    ///
+    /// # Errors
+    ///
+    /// If the request fails or the API responds with an error other than 404, an error is returned
+    ///
+    async fn latest_run_end(&self) -> Result<Option<DateTime<chrono::Utc>>, reqwest::Error> {
+        let runs = self.latest_runs().await?;
+        Ok(runs
+            .into_iter()
+            .filter_map(|run| serde_json::from_value::<StoredRun>(run).ok())
+            .find(|run| run.status == Some(RunStatus::Completed))
+            .map(|run| run.end_time))
+    }
+
+    ///
+    /// Get the latest run the API stores
+    ///
+    /// # Returns
+    ///
+    /// Result<Option<serde_json::Value>, reqwest::Error> - The run, or None if the API has no runs
+    ///
+    /// # Errors
+    ///
+    /// If the request fails or the API responds with an error other than 404, an error is returned
+    ///
+    async fn latest_run(&self) -> Result<Option<serde_json::Value>, reqwest::Error> {
+        Ok(self.latest_runs().await?.into_iter().next())
+    }
+
+    ///
+    /// Get the latest runs the API stores, latest first
+    ///
+    /// # Returns
+    ///
+    /// Result<Vec<serde_json::Value>, reqwest::Error> - The runs, empty if the API has none
+    ///
+    /// # Errors
+    ///
+    /// If the request fails or the API responds with an error other than 404, an error is returned
+    ///
+    async fn latest_runs(&self) -> Result<Vec<serde_json::Value>, reqwest::Error> {
+        let response = self
+            .backend
+            .send(|base_url| {
+                let mut url = ApiPaths::url(base_url, &self.config.paths.runs, 0);
+                url.set_query(Some("latest"));
+                self.client.get(url)
+            })
+            .await?;
+        if response.status() == StatusCode::NOT_FOUND {
+            return Ok(vec![]);
+        }
+
+        // A single run, or a list of runs with the latest first
+        let json = response
+            .error_for_status()?
+            .json::<serde_json::Value>()
+            .await?;
+        Ok(match json {
+            serde_json::Value::Array(runs) => runs,
+            run => vec![run],
+        })
+    }
+
+    ///
+    /// Read the token from its source or log in to the API if the scheme requires it, and configure the client with the auth header
+    ///
+    /// # Returns
+    ///
+    /// Result<(), ScraperError> - The result of authenticating
+    ///
+    async fn authenticate(&mut self) -> Result<(), ScraperError> {
+        if let Some(source) = &self.credentials.token_source {
+            self.credentials.token = source.read().map_err(ScraperError::Config)?;
+        } else if self.credentials.requires_login() {
+            self.credentials.token = self.get_token().await?;
+        }
+        if let Err(e) = self.configure_client().await {
+            eprintln!(
+                "Failed to configure the API client, keeping the previous one until the next run: {}",
+                e
+            );
+            return Err(e);
+        }
+        if !self.config.auth_warmup.is_zero() {
+            tokio::time::sleep(self.config.auth_warmup).await;
+        }
+        Ok(())
+    }
+
+    ///
+    /// Keep only the providers in the configured groups, if any are configured
+    ///
+    fn select_groups(&mut self) {
+        let groups = &self.config.groups;
+        if groups.is_empty() {
+            return;
+        }
+        let total = self.providers.len();
+        self.providers.retain(|provider| {
+            provider
+                .group
+                .as_ref()
+                .is_some_and(|group| groups.contains(group))
+        });
+        println!(
+            "Scraping {} of {} providers, in groups {}",
+            self.providers.len(),
+            total,
+            groups.join(", ")
+        );
+    }
+
+    ///
+    /// Put the providers of a run in a random order, if shuffling is enabled
+    /// Runs cut short then don't always starve the same providers, and capped runs cover a random subset
+    ///
+    fn shuffle_providers(&mut self) {
+        if self.config.shuffle_providers {
+            self.providers.shuffle(&mut self.rng);
+        }
+    }
+
+    ///
+    /// Decide which providers of a run have their price posted, when posting only a sample of the prices
+    /// Each provider is posted with the probability of the sample rate; the others are still scraped, reported and
+    /// published, only the write to the API is skipped
+    ///
+    fn sample_providers(&mut self) {
+        self.sampled_out.clear();
+        if self.config.sample_rate >= 1.0 {
+            return;
+        }
+        let mut skipped = vec![];
+        for provider in &self.providers {
+            if !self.sample_rng.gen_bool(self.config.sample_rate.max(0.0)) {
+                self.sampled_out.insert(provider.id);
+                skipped.push(provider.name.as_str());
+            }
+        }
+        println!(
+            "Sampling at {}, not posting the prices of {} of {} providers in run {}: {}",
+            self.config.sample_rate,
+            self.sampled_out.len(),
+            self.providers.len(),
+            self.run_id,
+            if skipped.is_empty() {
+                "none".to_string()
+            } else {
+                skipped.join(", ")
+            }
+        );
+    }
+
+    ///
+    /// Limit the providers of a run to the provider cap, if one is set
+    /// When rotating, each run takes the next window of providers, wrapping around, so every provider is covered over time
+    ///
+    fn cap_providers(&mut self) {
+        let Some(max_providers) = self.config.max_providers else {
+            return;
+        };
+        let total = self.providers.len();
+        if total <= max_providers {
+            return;
+        }
+
+        if self.config.rotate_providers {
+            let offset = self.rotation_offset % total;
+            self.providers.rotate_left(offset);
+            self.rotation_offset = (offset + max_providers) % total;
+        }
+        self.providers.truncate(max_providers);
+        println!("Scraping {} of {} providers", max_providers, total);
+    }
+
+    ///
+    /// Normalize the URL of every provider, logging the malformed ones
+    /// Providers with malformed URLs are kept, so they are counted as failures when scraped
+    ///
+    fn normalize_provider_urls(&mut self) {
+        for provider in &mut self.providers {
+            match normalize_url(&provider.url) {
+                Ok(url) => provider.url = url.to_string(),
+                Err(e) => eprintln!(
+                    "Invalid URL {:?} for provider {}: {}",
+                    provider.url, provider.name, e
+                ),
+            }
+        }
+    }
+}
+
+mod compare;
+mod list;
+mod probe;
+mod replay;
+mod resume;
+mod schema;
+mod selftest;
+mod single;
+
+pub use compare::{CompareTarget, SelectorComparison};
+pub use probe::{ProbePage, ProbedElement, SelectorProbe};
+pub use replay::ReplayReport;
+use schema::StoredRun;
+pub use selftest::{SelftestResult, SelftestStatus};
+
+#[cfg(test)]
+mod tests;
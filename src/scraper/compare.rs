@@ -0,0 +1,165 @@
+use super::*;
+
+///
+/// Page two selectors are compared on
+///
+/// # Variants
+///
+/// - Provider: The page of a provider, fetched with the provider's request settings
+/// - Url: Any page, fetched with a plain GET
+#[derive(Clone, Debug, PartialEq)]
+pub enum CompareTarget {
+    Provider(i32),
+    Url(String),
+}
+
+///
+/// What two selectors yield on the same page
+///
+/// # Fields
+///
+/// - url: String - The URL of the compared page
+/// - old: SelftestStatus - What the old selector yields
+/// - new: SelftestStatus - What the new selector yields
+#[derive(Clone, Debug, PartialEq)]
+pub struct SelectorComparison {
+    pub url: String,
+    pub old: SelftestStatus,
+    pub new: SelftestStatus,
+}
+
+impl SelectorComparison {
+    ///
+    /// Check whether both selectors yield the same price
+    ///
+    /// # Returns
+    ///
+    /// bool - True if both selectors matched the same price
+    ///
+    pub fn matches(&self) -> bool {
+        matches!(
+            (&self.old, &self.new),
+            (SelftestStatus::Matched(old), SelftestStatus::Matched(new)) if old == new
+        )
+    }
+}
+
+impl Scraper {
+    ///
+    /// Fetch a page once and extract the price with an old and a new selector, without posting anything
+    /// Both selectors go through the provider's parsing settings, so only the selector differs
+    ///
+    /// # Arguments
+    ///
+    /// - target: &CompareTarget - The provider or URL whose page is compared on
+    /// - old: &str - The selector in use
+    /// - new: &str - The selector to migrate to
+    ///
+    /// # Returns
+    ///
+    /// Result<SelectorComparison, ScraperError> - What each selector yields
+    ///
+    /// # Errors
+    ///
+    /// If the provider can't be loaded, the URL is invalid or the page can't be fetched, an error is returned
+    ///
+    pub async fn compare_selectors(
+        &mut self,
+        target: &CompareTarget,
+        old: &str,
+        new: &str,
+    ) -> Result<SelectorComparison, ScraperError> {
+        let provider = self.target_provider(target).await?;
+        let with_selector = |selector: &str| Provider {
+            html_element: selector.to_string(),
+            feed_type: FeedType::Html,
+            expression: None,
+            json_pointer: None,
+            ..provider.clone()
+        };
+
+        // Rendered pages wait for the old selector, which is expected to be on the page
+        let body = self.live_page(&with_selector(old)).await.map_err(|e| {
+            ScraperError::Config(format!("Failed to fetch {}: {}", provider.url, e))
+        })?;
+        Ok(SelectorComparison {
+            url: provider.url.clone(),
+            old: self.classify_page(&with_selector(old), &body),
+            new: self.classify_page(&with_selector(new), &body),
+        })
+    }
+
+    ///
+    /// Get the provider whose settings a page is fetched and parsed with, a bare provider for a URL
+    ///
+    /// # Arguments
+    ///
+    /// - target: &CompareTarget - The provider or URL
+    ///
+    /// # Returns
+    ///
+    /// Result<Provider, ScraperError> - The provider
+    ///
+    /// # Errors
+    ///
+    /// If the provider can't be loaded or the URL is invalid, an error is returned
+    ///
+    pub(super) async fn target_provider(
+        &mut self,
+        target: &CompareTarget,
+    ) -> Result<Provider, ScraperError> {
+        match target {
+            CompareTarget::Provider(provider_id) => self.load_provider(*provider_id).await,
+            CompareTarget::Url(url) => {
+                let url = normalize_url(url)
+                    .map_err(|e| ScraperError::Config(format!("Invalid URL {:?}: {}", url, e)))?;
+                let provider = serde_json::from_value(json!({
+                    "id": 0,
+                    "name": url.as_str(),
+                    "url": url.as_str(),
+                }))
+                .map_err(Error::other)?;
+                Ok(provider)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use wiremock::matchers::{method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    #[tokio::test]
+    async fn compare_selectors_extracts_with_both_selectors_from_one_fetch() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/prices"))
+            .respond_with(ResponseTemplate::new(200).set_body_string(
+                r#"<div class="price">12,49 kr.</div><span id="today">12,49</span><span id="yesterday">12,19</span>"#,
+            ))
+            .expect(2)
+            .mount(&server)
+            .await;
+
+        let credentials = Credentials::new("".to_string(), "".to_string());
+        let mut scraper = Scraper::new(vec![], credentials, ScraperConfig::default()).unwrap();
+        let target = CompareTarget::Url(format!("{}/prices", server.uri()));
+
+        let same = scraper
+            .compare_selectors(&target, ".price", "#today")
+            .await
+            .unwrap();
+        assert!(same.matches());
+        assert_eq!(same.new, SelftestStatus::Matched(12.49));
+
+        let different = scraper
+            .compare_selectors(&target, ".price", "#yesterday")
+            .await
+            .unwrap();
+        assert!(!different.matches());
+        assert_eq!(different.new, SelftestStatus::Matched(12.19));
+        server.verify().await;
+    }
+}
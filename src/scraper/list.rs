@@ -0,0 +1,81 @@
+use super::*;
+
+impl Scraper {
+    ///
+    /// Authenticate and fetch every provider with its full details, without scraping anything
+    /// The values of login forms and API keys are redacted, since they hold the credentials of provider portals and APIs
+    ///
+    /// # Returns
+    ///
+    /// Result<Vec<serde_json::Value>, ScraperError> - The configuration of each provider, in scraping order
+    ///
+    /// # Errors
+    ///
+    /// If authenticating or fetching the providers fails, an error is returned
+    ///
+    pub async fn list_providers(&mut self) -> Result<Vec<serde_json::Value>, ScraperError> {
+        self.prepare_run().await?;
+
+        let mut providers = vec![];
+        for provider in &self.providers {
+            let mut provider = provider.clone();
+            if let Some(login) = &mut provider.login {
+                login
+                    .form
+                    .values_mut()
+                    .for_each(|value| *value = "<redacted>".to_string());
+            }
+            if let Some(auth) = &mut provider.auth {
+                auth.value = "<redacted>".to_string();
+            }
+            providers.push(serde_json::to_value(provider).map_err(Error::other)?);
+        }
+        Ok(providers)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn list_providers_redacts_login_forms_and_api_keys() {
+        let providers_file = std::env::temp_dir().join(format!(
+            "oliepriser-list-providers-test-{}.json",
+            Uuid::new_v4()
+        ));
+        std::fs::write(
+            &providers_file,
+            json!([{
+                "id": 1,
+                "name": "Member provider",
+                "url": "example.com/prices",
+                "html_element": ".price",
+                "login": {
+                    "url": "https://example.com/login",
+                    "form": { "username": "member", "password": "secret" },
+                },
+                "auth": { "in": "header", "name": "X-Api-Key", "value": "secret" },
+            }])
+            .to_string(),
+        )
+        .unwrap();
+        let config = ScraperConfig {
+            providers_file: Some(providers_file.clone()),
+            ..ScraperConfig::default()
+        };
+        let credentials = Credentials::new("".to_string(), "".to_string());
+        let mut scraper = Scraper::new(vec![], credentials, config).unwrap();
+
+        let providers = scraper.list_providers().await.unwrap();
+
+        assert_eq!(providers[0]["url"], "https://example.com/prices");
+        assert_eq!(providers[0]["enabled"], true);
+        assert_eq!(
+            providers[0]["login"]["form"],
+            json!({ "username": "<redacted>", "password": "<redacted>" })
+        );
+        assert_eq!(providers[0]["auth"]["value"], "<redacted>");
+        std::fs::remove_file(&providers_file).unwrap();
+    }
+}
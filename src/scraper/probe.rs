@@ -0,0 +1,183 @@
+use super::*;
+
+///
+/// A page fetched once so selectors can be probed against it without refetching
+///
+/// # Fields
+///
+/// - provider: Provider - The provider whose parsing settings the probed selectors use
+/// - body: String - The page body
+#[derive(Clone, Debug)]
+pub struct ProbePage {
+    provider: Provider,
+    body: String,
+}
+
+impl ProbePage {
+    pub fn url(&self) -> &str {
+        &self.provider.url
+    }
+}
+
+///
+/// What a selector matches on a probed page
+///
+/// # Fields
+///
+/// - selector: String - The probed selector or extraction expression
+/// - elements: Vec<ProbedElement> - The matched elements in document order
+/// - status: SelftestStatus - The price the provider would extract with the selector
+#[derive(Clone, Debug, PartialEq)]
+pub struct SelectorProbe {
+    pub selector: String,
+    pub elements: Vec<ProbedElement>,
+    pub status: SelftestStatus,
+}
+
+///
+/// An element matched by a probed selector
+///
+/// # Fields
+///
+/// - text: String - The text read from the element
+/// - price: Option<f64> - The text parsed as a price, None if it isn't one
+#[derive(Clone, Debug, PartialEq)]
+pub struct ProbedElement {
+    pub text: String,
+    pub price: Option<f64>,
+}
+
+impl Scraper {
+    ///
+    /// Fetch a page to probe selectors against
+    ///
+    /// # Arguments
+    ///
+    /// - target: &CompareTarget - The provider or URL whose page is probed
+    ///
+    /// # Returns
+    ///
+    /// Result<ProbePage, ScraperError> - The fetched page
+    ///
+    /// # Errors
+    ///
+    /// If the provider can't be loaded, the URL is invalid or the page can't be fetched, an error is returned
+    ///
+    pub async fn fetch_probe_page(
+        &mut self,
+        target: &CompareTarget,
+    ) -> Result<ProbePage, ScraperError> {
+        let provider = self.target_provider(target).await?;
+        let body = self.live_page(&provider).await.map_err(|e| {
+            ScraperError::Config(format!("Failed to fetch {}: {}", provider.url, e))
+        })?;
+        Ok(ProbePage { provider, body })
+    }
+
+    ///
+    /// Evaluate a selector against a fetched page, listing every element it matches and the price extracted with it
+    /// Extraction expressions like `.info | after-label(Pris:)` are probed as expressions, anything else as a CSS
+    /// selector; both go through the provider's parsing settings
+    ///
+    /// # Arguments
+    ///
+    /// - page: &ProbePage - The fetched page
+    /// - selector: &str - The selector or expression to probe
+    ///
+    /// # Returns
+    ///
+    /// SelectorProbe - What the selector matches
+    ///
+    pub fn probe_selector(&self, page: &ProbePage, selector: &str) -> SelectorProbe {
+        let expression = Expression::parse(selector).ok();
+        let provider = Provider {
+            html_element: selector.to_string(),
+            feed_type: FeedType::Html,
+            expression: expression.as_ref().map(|_| selector.to_string()),
+            json_pointer: None,
+            ..page.provider.clone()
+        };
+
+        let document = Html::parse_document(&page.body);
+        let css = expression
+            .as_ref()
+            .map_or(selector, |expression| &expression.selector);
+        let texts: Vec<String> = match Selector::parse(css) {
+            Ok(css) => document
+                .select(&css)
+                .filter_map(|element| match &expression {
+                    Some(expression) => expression.operation.apply(&provider, element),
+                    None => Some(provider.element_text(element)),
+                })
+                .collect(),
+            Err(_) => vec![],
+        };
+
+        SelectorProbe {
+            selector: selector.to_string(),
+            elements: texts
+                .into_iter()
+                .map(|text| ProbedElement {
+                    price: self.parse_price(&provider, text.clone()).ok(),
+                    text: text.trim().to_string(),
+                })
+                .collect(),
+            status: self.classify_page(&provider, &page.body),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use wiremock::matchers::{method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    #[tokio::test]
+    async fn selectors_are_probed_against_one_fetch() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/prices"))
+            .respond_with(ResponseTemplate::new(200).set_body_string(
+                r#"<ul><li class="station">13,50 kr.</li><li class="station">Lukket</li></ul><p>Pris: 12,49</p>"#,
+            ))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let credentials = Credentials::new("".to_string(), "".to_string());
+        let mut scraper = Scraper::new(vec![], credentials, ScraperConfig::default()).unwrap();
+        let target = CompareTarget::Url(format!("{}/prices", server.uri()));
+        let page = scraper.fetch_probe_page(&target).await.unwrap();
+
+        let stations = scraper.probe_selector(&page, ".station");
+        assert_eq!(
+            stations.elements,
+            vec![
+                ProbedElement {
+                    text: "13,50 kr.".to_string(),
+                    price: Some(13.5),
+                },
+                ProbedElement {
+                    text: "Lukket".to_string(),
+                    price: None,
+                },
+            ]
+        );
+        assert_eq!(stations.status, SelftestStatus::Matched(13.5));
+
+        let labelled = scraper.probe_selector(&page, "p | after-label(Pris:)");
+        assert_eq!(labelled.elements[0].price, Some(12.49));
+        assert_eq!(labelled.status, SelftestStatus::Matched(12.49));
+
+        assert!(scraper
+            .probe_selector(&page, "#missing")
+            .elements
+            .is_empty());
+        assert!(matches!(
+            scraper.probe_selector(&page, "li[").status,
+            SelftestStatus::Failed(_)
+        ));
+        server.verify().await;
+    }
+}
@@ -0,0 +1,210 @@
+use super::*;
+
+///
+/// Summary of replaying saved snapshots
+///
+/// # Fields
+///
+/// - replayed: usize - Snapshots that yielded a price, which was posted with the snapshot's timestamp
+/// - failed: usize - Snapshots that still yield no price, or whose price couldn't be posted
+/// - skipped: usize - Snapshots of providers that are unknown or disabled
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ReplayReport {
+    pub replayed: usize,
+    pub failed: usize,
+    pub skipped: usize,
+}
+
+impl Scraper {
+    ///
+    /// Re-run extraction over the pages saved in the snapshot directory and post the recomputed prices
+    /// Each price is posted with the time its snapshot was taken, oldest first, so a fixed selector can backfill missed data
+    ///
+    /// # Arguments
+    ///
+    /// - provider_id: Option<i32> - Only replay snapshots of this provider
+    /// - since: Option<DateTime<chrono::Utc>> - Only replay snapshots taken at or after this time
+    ///
+    /// # Returns
+    ///
+    /// Result<ReplayReport, ScraperError> - How many snapshots were replayed, failed and skipped
+    ///
+    /// # Errors
+    ///
+    /// If no snapshot directory is configured, it can't be read, or authenticating or fetching the providers fails, an error is returned
+    ///
+    pub async fn replay(
+        &mut self,
+        provider_id: Option<i32>,
+        since: Option<DateTime<chrono::Utc>>,
+    ) -> Result<ReplayReport, ScraperError> {
+        let Some(snapshots) = self.config.snapshots.clone() else {
+            return Err(ScraperError::Config(
+                "Replaying requires a snapshot directory".to_string(),
+            ));
+        };
+        self.prepare_run().await?;
+
+        let mut saved = snapshots.list(provider_id)?;
+        saved.retain(|(_, _, scraped_at)| since.is_none_or(|since| *scraped_at >= since));
+        saved.sort_by_key(|(_, _, scraped_at)| *scraped_at);
+
+        let mut report = ReplayReport::default();
+        for (path, id, scraped_at) in saved {
+            let Some(provider) = self.providers.iter().find(|provider| provider.id == id) else {
+                println!(
+                    "Skipping snapshot {} of unknown provider {}",
+                    path.display(),
+                    id
+                );
+                report.skipped += 1;
+                continue;
+            };
+            if !provider.enabled {
+                println!(
+                    "Skipping snapshot {} of disabled provider {}",
+                    path.display(),
+                    provider.name
+                );
+                report.skipped += 1;
+                continue;
+            }
+
+            let body = std::fs::read_to_string(&path)?;
+            let price = match self.extractor(provider) {
+                Ok(extractor) => self.extract(provider, &extractor, &body),
+                Err(_) => Err(FailureKind::InvalidSelector),
+            };
+            let price = match price {
+                Ok(price) => price,
+                Err(kind) => {
+                    println!("No price found in snapshot {}: {}", path.display(), kind);
+                    report.failed += 1;
+                    continue;
+                }
+            };
+
+            match self
+                .add_price_for_provider(provider.id, price, Some(scraped_at))
+                .await
+            {
+                Ok(()) => report.replayed += 1,
+                Err(e) => {
+                    eprintln!(
+                        "Error adding replayed price for provider {}: {}",
+                        provider.name, e
+                    );
+                    report.failed += 1;
+                }
+            }
+        }
+        Ok(report)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::snapshot::SnapshotDir;
+    use chrono::SubsecRound;
+    use wiremock::matchers::{body_json, method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    #[tokio::test]
+    async fn replay_posts_snapshot_prices_with_their_timestamps() {
+        let server = MockServer::start().await;
+        let snapshots = SnapshotDir {
+            dir: std::env::temp_dir().join(format!("oliepriser-replay-test-{}", Uuid::new_v4())),
+            max_bytes: 1024,
+            max_files: 10,
+        };
+
+        let old = chrono::Utc::now() - TimeDelta::days(2);
+        let recent = chrono::Utc::now() - TimeDelta::hours(1);
+        let page = r#"<div class="price">12,49 kr.</div>"#;
+        snapshots.save(1, old, page).unwrap();
+        snapshots.save(1, recent, page).unwrap();
+        snapshots.save(2, recent, page).unwrap();
+        snapshots
+            .save(1, recent + TimeDelta::seconds(1), "<div>Udsolgt</div>")
+            .unwrap();
+
+        let providers = json!([{
+            "id": 1,
+            "name": "Provider 1",
+            "url": format!("{}/pages/1", server.uri()),
+            "html_element": ".price",
+        }]);
+        let providers_file = std::env::temp_dir().join(format!(
+            "oliepriser-replay-providers-{}.json",
+            Uuid::new_v4()
+        ));
+        std::fs::write(&providers_file, providers.to_string()).unwrap();
+        let prices_output =
+            std::env::temp_dir().join(format!("oliepriser-replay-prices-{}.jsonl", Uuid::new_v4()));
+
+        Mock::given(method("GET"))
+            .and(path("/pages/1"))
+            .respond_with(ResponseTemplate::new(200))
+            .expect(0)
+            .mount(&server)
+            .await;
+
+        let config = ScraperConfig {
+            providers_file: Some(providers_file.clone()),
+            prices_output: Some(prices_output.clone()),
+            snapshots: Some(snapshots.clone()),
+            ..ScraperConfig::default()
+        };
+        let credentials = Credentials::new("".to_string(), "".to_string());
+        let mut scraper = Scraper::new(vec![], credentials, config).unwrap();
+
+        let report = scraper
+            .replay(None, Some(chrono::Utc::now() - TimeDelta::days(1)))
+            .await
+            .unwrap();
+
+        assert_eq!(
+            report,
+            ReplayReport {
+                replayed: 1,
+                failed: 1,
+                skipped: 1,
+            }
+        );
+        let line: serde_json::Value =
+            serde_json::from_str(std::fs::read_to_string(&prices_output).unwrap().trim()).unwrap();
+        assert_eq!(
+            line,
+            json!({ "provider_id": 1, "price": 12.49, "scraped_at": recent.trunc_subsecs(3) })
+        );
+        server.verify().await;
+        std::fs::remove_dir_all(&snapshots.dir).unwrap();
+        std::fs::remove_file(&providers_file).unwrap();
+        std::fs::remove_file(&prices_output).unwrap();
+    }
+
+    #[tokio::test]
+    async fn replayed_prices_carry_their_timestamp_to_the_api() {
+        let server = MockServer::start().await;
+        let scraped_at = chrono::Utc::now() - TimeDelta::hours(1);
+        Mock::given(method("POST"))
+            .and(path("/providers/1/prices"))
+            .and(body_json(
+                json!({ "price": 12.49, "timestamp": scraped_at }),
+            ))
+            .respond_with(ResponseTemplate::new(201))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let credentials = Credentials::new("".to_string(), "".to_string());
+        let scraper =
+            Scraper::new(vec![server.uri()], credentials, ScraperConfig::default()).unwrap();
+        scraper
+            .add_price_for_provider(1, 12.49, Some(scraped_at))
+            .await
+            .unwrap();
+        server.verify().await;
+    }
+}
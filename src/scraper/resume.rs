@@ -0,0 +1,187 @@
+use super::*;
+
+/// Oldest interrupted run that is resumed, older resume files are left over from runs nobody restarted in time
+const MAX_RESUME_AGE: TimeDelta = TimeDelta::days(1);
+
+impl Scraper {
+    ///
+    /// Continue the run recorded in the resume file, if the previous process was interrupted during it
+    /// The run keeps its ID and start time, and the providers whose prices were already delivered are skipped
+    /// Records of other runs and runs older than a day are ignored
+    ///
+    pub(super) fn resume_interrupted_run(&mut self) {
+        self.resumed.clear();
+        let Some(path) = &self.config.resume_file else {
+            return;
+        };
+        let contents = match std::fs::read_to_string(path) {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return,
+            Err(e) => {
+                eprintln!("Failed to read resume file {}: {}", path.display(), e);
+                return;
+            }
+        };
+
+        let records: Vec<serde_json::Value> = contents
+            .lines()
+            .filter_map(|line| serde_json::from_str(line).ok())
+            .collect();
+        // The last record belongs to the run that was interrupted
+        let Some(last) = records.last() else {
+            return;
+        };
+        let (Ok(run_id), Ok(run_start)) = (
+            serde_json::from_value::<Uuid>(last["run_id"].clone()),
+            serde_json::from_value::<DateTime<chrono::Utc>>(last["run_start"].clone()),
+        ) else {
+            return;
+        };
+        if chrono::Utc::now() - run_start > MAX_RESUME_AGE {
+            println!(
+                "Not resuming run {}, it started at {} and is too old",
+                run_id, run_start
+            );
+            return;
+        }
+
+        self.run_id = run_id;
+        self.run_start = run_start;
+        self.resumed = records
+            .iter()
+            .filter(|record| record["run_id"] == last["run_id"])
+            .filter_map(|record| record["provider_id"].as_i64())
+            .map(|provider_id| provider_id as i32)
+            .collect();
+        println!(
+            "Resuming interrupted run {}, {} providers are already done",
+            run_id,
+            self.resumed.len()
+        );
+    }
+
+    ///
+    /// Drop the providers a resumed run already delivered prices for from the providers of the run
+    ///
+    pub(super) fn skip_resumed_providers(&mut self) {
+        if self.resumed.is_empty() {
+            return;
+        }
+        let resumed = &self.resumed;
+        self.providers
+            .retain(|provider| !resumed.contains(&provider.id));
+    }
+
+    ///
+    /// Record in the resume file that a provider's price was delivered, so a restarted run doesn't scrape it again
+    /// Failing to write the file is logged and only costs scraping the provider again after a crash
+    ///
+    /// # Arguments
+    ///
+    /// - provider_id: i32 - The ID of the provider
+    ///
+    pub(super) fn mark_completed(&self, provider_id: i32) {
+        let Some(path) = &self.config.resume_file else {
+            return;
+        };
+
+        let line = json!({
+            "run_id": self.run_id,
+            "run_start": self.run_start,
+            "provider_id": provider_id,
+        });
+        let result = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .and_then(|mut file| writeln!(file, "{}", line));
+        if let Err(e) = result {
+            eprintln!(
+                "Failed to record provider {} in resume file {}: {}",
+                provider_id,
+                path.display(),
+                e
+            );
+        }
+    }
+
+    ///
+    /// Remove the resume file once a run is over, so the next run starts from scratch
+    ///
+    pub(super) fn clear_resume_file(&self) {
+        let Some(path) = &self.config.resume_file else {
+            return;
+        };
+        match std::fs::remove_file(path) {
+            Ok(()) => {}
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => eprintln!("Failed to clear resume file {}: {}", path.display(), e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use wiremock::matchers::{method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    #[tokio::test]
+    async fn interrupted_runs_resume_without_the_providers_already_done() {
+        let server = MockServer::start().await;
+        for id in 1..=3 {
+            Mock::given(method("GET"))
+                .and(path(format!("/pages/{}", id)))
+                .respond_with(
+                    ResponseTemplate::new(200)
+                        .set_body_string(r#"<div class="price">12,49 kr.</div>"#),
+                )
+                .expect(if id == 2 { 0 } else { 1 })
+                .mount(&server)
+                .await;
+        }
+        let providers: Vec<serde_json::Value> = (1..=3)
+            .map(|id| {
+                json!({
+                    "id": id,
+                    "name": format!("Provider {}", id),
+                    "url": format!("{}/pages/{}", server.uri(), id),
+                    "html_element": ".price",
+                })
+            })
+            .collect();
+        let providers_file = std::env::temp_dir().join(format!(
+            "oliepriser-resume-providers-test-{}.json",
+            Uuid::new_v4()
+        ));
+        std::fs::write(&providers_file, json!(providers).to_string()).unwrap();
+
+        // Provider 2 was done by the interrupted run, provider 3 only by an older run
+        let resume_file =
+            std::env::temp_dir().join(format!("oliepriser-resume-test-{}.jsonl", Uuid::new_v4()));
+        let interrupted = Uuid::new_v4();
+        let run_start = chrono::Utc::now() - TimeDelta::minutes(5);
+        let records = [
+            json!({ "run_id": Uuid::new_v4(), "run_start": run_start, "provider_id": 3 }),
+            json!({ "run_id": interrupted, "run_start": run_start, "provider_id": 2 }),
+        ];
+        let lines: Vec<String> = records.iter().map(|record| record.to_string()).collect();
+        std::fs::write(&resume_file, lines.join("\n")).unwrap();
+
+        let config = ScraperConfig {
+            providers_file: Some(providers_file.clone()),
+            resume_file: Some(resume_file.clone()),
+            ..ScraperConfig::default()
+        };
+        let credentials = Credentials::new("".to_string(), "".to_string());
+        let mut scraper = Scraper::new(vec![], credentials, config).unwrap();
+
+        let report = scraper.run().await.unwrap();
+
+        assert_eq!(report.run_id, interrupted);
+        assert_eq!(report.scraped, 2);
+        assert!(!resume_file.exists());
+        server.verify().await;
+        std::fs::remove_file(&providers_file).unwrap();
+    }
+}
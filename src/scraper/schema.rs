@@ -0,0 +1,147 @@
+use super::*;
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+
+///
+/// A stored run as the scraper posts it, as far as `--since-last-success-secs` reads it back
+///
+/// # Fields
+///
+/// - end_time: DateTime<chrono::Utc> - When the run ended
+/// - status: Option<RunStatus> - How the run ended, None for runs stored without a status
+#[derive(Deserialize)]
+pub(super) struct StoredRun {
+    pub(super) end_time: DateTime<chrono::Utc>,
+    #[serde(default)]
+    pub(super) status: Option<RunStatus>,
+}
+
+impl Scraper {
+    ///
+    /// Check the API returns the shapes the scraper expects before running, so a backend of another version fails at
+    /// startup with the field that differs instead of with a deserialize error deep in a run
+    /// Fetches the providers list, the details of its first provider and the latest run, and checks each of them
+    ///
+    /// # Returns
+    ///
+    /// Result<(), ScraperError> - Ok if every response has the expected shape
+    ///
+    /// # Errors
+    ///
+    /// If authenticating or a request fails, or a response is missing a field or has one of another type, an error
+    /// naming the endpoint and field is returned
+    ///
+    pub async fn validate_schema(&mut self) -> Result<(), ScraperError> {
+        if self.config.providers_file.is_some() {
+            println!("Not validating the API schema, providers are read from the providers file");
+            return Ok(());
+        }
+        self.authenticate().await?;
+
+        let providers = self.fetch_json(&self.config.paths.providers, 0).await?;
+        let serde_json::Value::Array(providers) = providers else {
+            return Err(unexpected_shape("providers list", "expected an array"));
+        };
+        for (index, provider) in providers.iter().enumerate() {
+            if let Some(e) = shape_error::<Providers>(provider) {
+                return Err(unexpected_shape(
+                    "providers list",
+                    &format!("item {}: {}", index, e),
+                ));
+            }
+        }
+
+        match providers
+            .first()
+            .and_then(|provider| provider["id"].as_i64())
+        {
+            Some(id) => {
+                let provider = self
+                    .fetch_json(&self.config.paths.provider_detail, id as i32)
+                    .await?;
+                if let Some(e) = shape_error::<Provider>(&provider) {
+                    return Err(unexpected_shape("provider", &e));
+                }
+            }
+            None => println!("No providers to validate the provider details of"),
+        }
+
+        match self.latest_run().await? {
+            Some(run) => {
+                if let Some(e) = shape_error::<StoredRun>(&run) {
+                    return Err(unexpected_shape("run", &e));
+                }
+            }
+            None => println!("No runs to validate the run shape of"),
+        }
+        println!("The API schema matches what the scraper expects");
+        Ok(())
+    }
+
+    ///
+    /// Get an API endpoint as JSON
+    ///
+    /// # Arguments
+    ///
+    /// - path: &str - The route of the endpoint
+    /// - provider_id: i32 - The provider ID, for routes with `{id}`
+    ///
+    /// # Returns
+    ///
+    /// Result<serde_json::Value, ScraperError> - The response
+    ///
+    async fn fetch_json(
+        &self,
+        path: &str,
+        provider_id: i32,
+    ) -> Result<serde_json::Value, ScraperError> {
+        let response = self
+            .backend
+            .send(|base_url| self.client.get(ApiPaths::url(base_url, path, provider_id)))
+            .await?
+            .error_for_status()?;
+        let body = response.text().await?;
+        serde_json::from_str(&body)
+            .map_err(|e| ScraperError::Config(format!("{} didn't return JSON: {}", path, e)))
+    }
+}
+
+fn unexpected_shape(what: &str, problem: &str) -> ScraperError {
+    ScraperError::Config(format!(
+        "The API returned an unexpected {} shape: {}",
+        what, problem
+    ))
+}
+
+///
+/// Check a JSON value deserializes into a type, naming the offending field if it doesn't
+/// serde doesn't say which field has the wrong type, so each field is left out in turn until the error changes
+///
+/// # Arguments
+///
+/// - value: &serde_json::Value - The value returned by the API
+///
+/// # Returns
+///
+/// Option<String> - What is wrong with the value, or None if it deserializes
+///
+fn shape_error<T: DeserializeOwned>(value: &serde_json::Value) -> Option<String> {
+    let error = serde_json::from_value::<T>(value.clone())
+        .err()?
+        .to_string();
+    if error.starts_with("missing field") {
+        return Some(error);
+    }
+    let culprit = value.as_object().and_then(|object| {
+        object.keys().find(|key| {
+            let mut without = object.clone();
+            without.remove(*key);
+            serde_json::from_value::<T>(serde_json::Value::Object(without))
+                .map_or_else(|e| e.to_string() != error, |_| true)
+        })
+    });
+    Some(match culprit {
+        Some(field) => format!("field `{}`: {}", field, error),
+        None => error,
+    })
+}
@@ -0,0 +1,256 @@
+use super::*;
+
+///
+/// Result of checking a provider's selector against its live page
+///
+/// # Variants
+///
+/// - Matched: The selector matched and yielded a valid price
+/// - Unparseable: The selector matched, but the matched text isn't a valid price
+/// - NoMatch: The selector matched nothing on the page
+/// - Failed: The provider couldn't be checked, e.g. the page couldn't be fetched
+#[derive(Clone, Debug, PartialEq)]
+pub enum SelftestStatus {
+    Matched(f64),
+    Unparseable(String),
+    NoMatch,
+    Failed(String),
+}
+
+///
+/// Selftest result for a single provider
+///
+/// # Fields
+///
+/// - provider_id: i32 - The ID of the provider
+/// - provider_name: String - The name of the provider
+/// - status: SelftestStatus - What the selector yielded
+#[derive(Clone, Debug, PartialEq)]
+pub struct SelftestResult {
+    pub provider_id: i32,
+    pub provider_name: String,
+    pub status: SelftestStatus,
+}
+
+impl Scraper {
+    ///
+    /// Fetch every provider page once and check what its selector or JSON pointer yields, without posting anything
+    ///
+    /// # Returns
+    ///
+    /// Result<Vec<SelftestResult>, ScraperError> - The result for each provider, ordered by provider ID
+    ///
+    /// # Errors
+    ///
+    /// If authenticating or fetching the providers fails, an error is returned
+    ///
+    pub async fn selftest(&mut self) -> Result<Vec<SelftestResult>, ScraperError> {
+        self.prepare_run().await?;
+
+        let checks = self
+            .providers
+            .iter()
+            .map(|provider| self.selftest_provider(provider));
+        let mut results: Vec<SelftestResult> = stream::iter(checks)
+            .buffer_unordered(self.concurrency.limit()) // Same concurrency limit as a scraping run
+            .collect()
+            .await;
+        results.sort_by_key(|result| result.provider_id);
+        Ok(results)
+    }
+
+    async fn selftest_provider(&self, provider: &Provider) -> SelftestResult {
+        SelftestResult {
+            provider_id: provider.id,
+            provider_name: provider.name.clone(),
+            status: self.check_provider(provider).await,
+        }
+    }
+
+    ///
+    /// Fetch a provider page and classify what its selector or JSON pointer yields
+    ///
+    /// # Arguments
+    ///
+    /// - provider: &Provider - The provider to check
+    ///
+    /// # Returns
+    ///
+    /// SelftestStatus - What the provider yielded
+    ///
+    async fn check_provider(&self, provider: &Provider) -> SelftestStatus {
+        match self.live_page(provider).await {
+            Ok(body) => self.classify_page(provider, &body),
+            Err(e) => SelftestStatus::Failed(e),
+        }
+    }
+
+    ///
+    /// Fetch the live page of a provider, rendered in the headless browser if the provider needs it
+    ///
+    /// # Arguments
+    ///
+    /// - provider: &Provider - The provider to fetch
+    ///
+    /// # Returns
+    ///
+    /// Result<String, String> - The page body, or why it couldn't be fetched
+    ///
+    pub(super) async fn live_page(&self, provider: &Provider) -> Result<String, String> {
+        if provider.render {
+            self.render_page(provider).await
+        } else {
+            self.fetch_page_body(provider)
+                .await
+                .map_err(|e| e.to_string())
+        }
+    }
+
+    ///
+    /// Classify what a provider's selector, expression, JSON pointer or CSV column yields on a fetched page
+    ///
+    /// # Arguments
+    ///
+    /// - provider: &Provider - The provider to check
+    /// - body: &str - The page body
+    ///
+    /// # Returns
+    ///
+    /// SelftestStatus - What the provider yielded
+    ///
+    pub(super) fn classify_page(&self, provider: &Provider, body: &str) -> SelftestStatus {
+        if let Ok(Extractor::CsvColumn(column)) = self.extractor(provider) {
+            let first_value =
+                feed::csv_column(body, &column).and_then(|values| values.into_iter().next());
+            return match first_value {
+                None => SelftestStatus::NoMatch,
+                Some(value) => match self.extract_csv_price(provider, body, &column) {
+                    Ok(price) => SelftestStatus::Matched(price),
+                    Err(_) => SelftestStatus::Unparseable(value.trim().to_string()),
+                },
+            };
+        }
+        if let Some(pointer) = &provider.json_pointer {
+            let value = serde_json::from_str::<serde_json::Value>(body)
+                .ok()
+                .and_then(|json| json.pointer(pointer).cloned());
+            return match value {
+                None => SelftestStatus::NoMatch,
+                Some(value) => match self.extract_json_price(provider, body, pointer) {
+                    Ok(price) => SelftestStatus::Matched(price),
+                    Err(_) => SelftestStatus::Unparseable(value.to_string()),
+                },
+            };
+        }
+        if provider.expression.is_some() {
+            let (selector, operation) = match self.extractor(provider) {
+                Ok(Extractor::Expression(selector, operation)) => (selector, operation),
+                Ok(_) => unreachable!("HTML providers with an expression are extracted with it"),
+                Err(e) => return SelftestStatus::Failed(format!("Invalid expression: {}", e)),
+            };
+            let document = Html::parse_document(body);
+            let first_match = document
+                .select(&selector)
+                .find_map(|element| operation.apply(provider, element));
+            return match first_match {
+                None => SelftestStatus::NoMatch,
+                Some(text) => {
+                    match self.extract_expression_price(provider, &document, &selector, &operation)
+                    {
+                        Ok(price) => SelftestStatus::Matched(price),
+                        Err(_) => SelftestStatus::Unparseable(text.trim().to_string()),
+                    }
+                }
+            };
+        }
+
+        let selector = match self.selector(&provider.html_element) {
+            Ok(selector) => selector,
+            Err(e) => return SelftestStatus::Failed(format!("Invalid selector: {}", e)),
+        };
+        let document = Html::parse_document(body);
+        let first_match = document
+            .select(&selector)
+            .next()
+            .map(|element| provider.element_text(element));
+
+        match first_match {
+            None => SelftestStatus::NoMatch,
+            Some(text) => match self.extract_price(provider, &document, &selector) {
+                Ok(price) => SelftestStatus::Matched(price),
+                Err(_) => SelftestStatus::Unparseable(text.trim().to_string()),
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use wiremock::matchers::{method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    #[tokio::test]
+    async fn selftest_classifies_each_provider() {
+        let server = MockServer::start().await;
+        let pages = [
+            ("/pages/1", r#"<div class="price">12,49 kr.</div>"#),
+            ("/pages/2", r#"<div class="price">Udsolgt</div>"#),
+            ("/pages/3", r#"<div class="other">12,49 kr.</div>"#),
+        ];
+        for (page, html) in pages {
+            Mock::given(method("GET"))
+                .and(path(page))
+                .respond_with(ResponseTemplate::new(200).set_body_string(html))
+                .mount(&server)
+                .await;
+        }
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(201))
+            .expect(0)
+            .mount(&server)
+            .await;
+
+        let providers: Vec<serde_json::Value> = (1..=3)
+            .map(|id| {
+                json!({
+                    "id": id,
+                    "name": format!("Provider {}", id),
+                    "url": format!("{}/pages/{}", server.uri(), id),
+                    "html_element": ".price",
+                })
+            })
+            .collect();
+        let providers_file = std::env::temp_dir().join(format!(
+            "oliepriser-selftest-providers-{}.json",
+            Uuid::new_v4()
+        ));
+        std::fs::write(&providers_file, json!(providers).to_string()).unwrap();
+
+        let config = ScraperConfig {
+            providers_file: Some(providers_file.clone()),
+            ..ScraperConfig::default()
+        };
+        let credentials = Credentials::new("".to_string(), "".to_string());
+        let mut scraper = Scraper::new(vec![], credentials, config).unwrap();
+
+        let statuses: Vec<SelftestStatus> = scraper
+            .selftest()
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|result| result.status)
+            .collect();
+
+        assert_eq!(
+            statuses,
+            vec![
+                SelftestStatus::Matched(12.49),
+                SelftestStatus::Unparseable("Udsolgt".to_string()),
+                SelftestStatus::NoMatch,
+            ]
+        );
+        server.verify().await;
+        std::fs::remove_file(&providers_file).unwrap();
+    }
+}
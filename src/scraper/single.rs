@@ -0,0 +1,98 @@
+use super::*;
+
+impl Scraper {
+    ///
+    /// Scrape a single provider on demand, without running the whole batch or recording a run
+    /// The provider goes through the same fetch and extraction path as in a run, so disabled providers
+    /// and providers behind an open circuit breaker are not scraped
+    ///
+    /// # Arguments
+    ///
+    /// - provider_id: i32 - The ID of the provider to scrape
+    /// - post: bool - Whether to post the scraped price like a run does
+    ///
+    /// # Returns
+    ///
+    /// Result<ScrapedPrice, ScraperError> - The scraped price
+    ///
+    /// # Errors
+    ///
+    /// If authenticating or fetching the provider fails, the provider yields no price or posting the price fails, an error is returned
+    ///
+    pub async fn scrape_one(
+        &mut self,
+        provider_id: i32,
+        post: bool,
+    ) -> Result<ScrapedPrice, ScraperError> {
+        let provider = self.load_provider(provider_id).await?;
+
+        let (sender, mut receiver) = mpsc::channel::<ScrapedPrice>(1);
+        let outcome = self
+            .scrape_provider(&provider, &self.page_client, sender)
+            .await;
+        self.record_breaker(provider.id, &outcome);
+        let reason = match outcome {
+            ProviderOutcome::Scraped { .. } | ProviderOutcome::Unchanged => None,
+            ProviderOutcome::Failed(kind) => Some(kind.to_string()),
+            ProviderOutcome::Skipped => Some("the provider was skipped".to_string()),
+        };
+        let scraped = match (reason, receiver.next().await) {
+            (None, Some(scraped)) => scraped,
+            (reason, _) => {
+                return Err(ScraperError::NoPrice {
+                    provider_id,
+                    reason: reason
+                        .unwrap_or_else(|| "page unchanged without a cached price".to_string()),
+                })
+            }
+        };
+
+        if post {
+            let timestamp = scraped.timestamp(self.config.post_scraped_at);
+            self.add_price_for_provider(scraped.provider_id, scraped.price, timestamp)
+                .await?;
+        }
+        Ok(scraped)
+    }
+
+    ///
+    /// Load a single provider from the providers file, or authenticate and fetch its details from the API
+    ///
+    /// # Arguments
+    ///
+    /// - provider_id: i32 - The ID of the provider
+    ///
+    /// # Returns
+    ///
+    /// Result<Provider, ScraperError> - The provider, with its URL normalized
+    ///
+    /// # Errors
+    ///
+    /// If the provider isn't in the providers file, or authenticating or fetching it fails, an error is returned
+    ///
+    pub(super) async fn load_provider(
+        &mut self,
+        provider_id: i32,
+    ) -> Result<Provider, ScraperError> {
+        let mut provider = match &self.config.providers_file {
+            Some(path) => load_providers_file(path)?
+                .into_iter()
+                .find(|provider| provider.id == provider_id)
+                .ok_or_else(|| {
+                    ScraperError::Config(format!(
+                        "Provider {} is not in {}",
+                        provider_id,
+                        path.display()
+                    ))
+                })?,
+            None => {
+                self.authenticate().await?;
+                self.get_provider(&Providers { id: provider_id }).await?
+            }
+        };
+        if let Ok(url) = normalize_url(&provider.url) {
+            provider.url = url.to_string();
+        }
+        Ok(provider)
+    }
+}
@@ -0,0 +1,3050 @@
+use super::*;
+use crate::config::{HostOverride, HttpConfig, IpVersion};
+use crate::credentials::AuthScheme;
+use crate::price::PriceTransform;
+use crate::provider::{PriceSelection, RangeBound};
+use crate::report::{RunMetrics, RunStatus};
+use crate::state::StateFile;
+use wiremock::matchers::{
+    body_json, body_partial_json, body_string, header, header_exists, method, path, query_param,
+};
+use wiremock::{Mock, MockServer, ResponseTemplate};
+
+const PROVIDER_PAGE: &str = r#"
+<html>
+    <body>
+        <div class="price">12,49 kr.</div>
+    </body>
+</html>
+"#;
+
+///
+/// The provider details served by the mock API, pointing at the mock provider page
+///
+fn test_provider(server: &MockServer) -> serde_json::Value {
+    json!({
+        "id": 1,
+        "name": "Test provider",
+        "url": format!("{}/pages/1", server.uri()),
+        "html_element": ".price",
+    })
+}
+
+///
+/// Mount stubs for logging in and fetching a single provider, expecting the given number of runs
+///
+/// # Arguments
+///
+/// - server: &MockServer - The mock server to mount the stubs on
+/// - runs: u64 - The number of runs expected against the backend
+/// - provider: serde_json::Value - The provider details to serve
+///
+async fn mount_provider_api(server: &MockServer, runs: u64, provider: serde_json::Value) {
+    Mock::given(method("POST"))
+        .and(path("/auth/login"))
+        .and(body_json(json!({
+            "client_id": "client_id",
+            "client_secret": "client_secret",
+        })))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "access_token": "access_token",
+            "token_type": "Bearer",
+        })))
+        .expect(runs)
+        .mount(server)
+        .await;
+
+    Mock::given(method("GET"))
+        .and(path("/scraping_runs/providers"))
+        .and(header("authorization", "Bearer access_token"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!([{ "id": provider["id"] }])))
+        .expect(runs)
+        .mount(server)
+        .await;
+
+    Mock::given(method("GET"))
+        .and(path(format!("/providers/{}", provider["id"])))
+        .respond_with(ResponseTemplate::new(200).set_body_json(provider))
+        .expect(runs)
+        .mount(server)
+        .await;
+}
+
+///
+/// Mount stubs for every backend endpoint used by a run, expecting the given number of runs
+///
+/// # Arguments
+///
+/// - server: &MockServer - The mock server to mount the stubs on
+/// - runs: u64 - The number of runs expected against the backend
+///
+async fn mount_backend(server: &MockServer, runs: u64) {
+    mount_provider_api(server, runs, test_provider(server)).await;
+
+    Mock::given(method("POST"))
+        .and(path("/providers/1/prices"))
+        .and(body_json(json!({ "price": 12.49 })))
+        .respond_with(ResponseTemplate::new(201))
+        .expect(runs)
+        .mount(server)
+        .await;
+
+    Mock::given(method("POST"))
+        .and(path("/scraping_runs"))
+        .respond_with(ResponseTemplate::new(201))
+        .expect(runs)
+        .mount(server)
+        .await;
+}
+
+///
+/// Start a mock API with stubs for every endpoint used by a full run, including the provider page
+///
+/// # Returns
+///
+/// MockServer - The running mock server
+///
+async fn mock_api() -> MockServer {
+    let server = MockServer::start().await;
+    mount_backend(&server, 1).await;
+
+    Mock::given(method("GET"))
+        .and(path("/pages/1"))
+        .respond_with(ResponseTemplate::new(200).set_body_string(PROVIDER_PAGE))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    server
+}
+
+fn test_scraper(server: &MockServer) -> Scraper {
+    let credentials = Credentials::new("client_id".to_string(), "client_secret".to_string());
+    Scraper::new(vec![server.uri()], credentials, ScraperConfig::default()).unwrap()
+}
+
+fn offline_scraper() -> Scraper {
+    Scraper::new(
+        vec!["http://localhost".to_string()],
+        Credentials::new("".to_string(), "".to_string()),
+        ScraperConfig::default(),
+    )
+    .unwrap()
+}
+
+#[tokio::test]
+async fn run_posts_scraped_price_and_run_times() {
+    let server = mock_api().await;
+    let mut scraper = test_scraper(&server);
+
+    scraper.run().await.unwrap();
+
+    let requests = server.received_requests().await.unwrap();
+    let run_request = requests
+        .iter()
+        .find(|request| request.url.path() == "/scraping_runs")
+        .unwrap();
+    let body: serde_json::Value = serde_json::from_slice(&run_request.body).unwrap();
+    let start_time: DateTime<chrono::Utc> =
+        serde_json::from_value(body["start_time"].clone()).unwrap();
+    let end_time: DateTime<chrono::Utc> = serde_json::from_value(body["end_time"].clone()).unwrap();
+
+    assert!(start_time <= end_time);
+    assert_eq!(body["summary"]["scraped"], 1);
+    assert_eq!(body["status"], "completed");
+    assert_eq!(body["run_id"], json!(scraper.run_id));
+    assert!(!scraper.run_id.is_nil());
+    assert_eq!(Some(end_time), scraper.run_end);
+    assert_eq!(start_time, scraper.run_start);
+
+    server.verify().await;
+}
+
+#[tokio::test]
+async fn run_reuses_cached_price_when_page_is_not_modified() {
+    let server = MockServer::start().await;
+    mount_backend(&server, 2).await;
+
+    Mock::given(method("GET"))
+        .and(path("/pages/1"))
+        .and(header("if-none-match", "\"v1\""))
+        .respond_with(ResponseTemplate::new(304))
+        .with_priority(1)
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    Mock::given(method("GET"))
+        .and(path("/pages/1"))
+        .respond_with(
+            ResponseTemplate::new(200)
+                .insert_header("etag", "\"v1\"")
+                .set_body_string(PROVIDER_PAGE),
+        )
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let mut scraper = test_scraper(&server);
+    scraper.run().await.unwrap();
+    scraper.run().await.unwrap();
+
+    server.verify().await;
+}
+
+#[tokio::test]
+async fn state_file_keeps_page_cache_across_restarts() {
+    let server = MockServer::start().await;
+    mount_backend(&server, 2).await;
+
+    Mock::given(method("GET"))
+        .and(path("/pages/1"))
+        .and(header("if-none-match", "\"v1\""))
+        .respond_with(ResponseTemplate::new(304))
+        .with_priority(1)
+        .expect(1)
+        .mount(&server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/pages/1"))
+        .respond_with(
+            ResponseTemplate::new(200)
+                .insert_header("etag", "\"v1\"")
+                .set_body_string(PROVIDER_PAGE),
+        )
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let state_file = std::env::temp_dir().join(format!(
+        "oliepriser-scraper-state-test-{}.json",
+        Uuid::new_v4()
+    ));
+    let _ = std::fs::remove_file(&state_file);
+    for _ in 0..2 {
+        let mut scraper = test_scraper(&server)
+            .with_state_store(Box::new(StateFile::new(state_file.clone())))
+            .unwrap();
+        scraper.run().await.unwrap();
+    }
+    std::fs::remove_file(&state_file).unwrap();
+
+    server.verify().await;
+}
+
+#[tokio::test]
+async fn referer_of_the_page_origin_is_sent_when_asked_for() {
+    let server = MockServer::start().await;
+    let mut provider = test_provider(&server);
+    provider["send_referer"] = json!(true);
+    mount_provider_api(&server, 1, provider).await;
+    Mock::given(method("POST"))
+        .respond_with(ResponseTemplate::new(201))
+        .mount(&server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/pages/1"))
+        .and(header("referer", format!("{}/", server.uri())))
+        .respond_with(ResponseTemplate::new(200).set_body_string(PROVIDER_PAGE))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let mut scraper = test_scraper(&server);
+    assert_eq!(scraper.run().await.unwrap().scraped, 1);
+    server.verify().await;
+}
+
+#[tokio::test]
+async fn run_retries_rate_limited_provider_after_retry_after() {
+    let server = MockServer::start().await;
+    mount_backend(&server, 1).await;
+
+    Mock::given(method("GET"))
+        .and(path("/pages/1"))
+        .respond_with(ResponseTemplate::new(429).insert_header("retry-after", "0"))
+        .up_to_n_times(1)
+        .with_priority(1)
+        .expect(1)
+        .mount(&server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/pages/1"))
+        .respond_with(ResponseTemplate::new(200).set_body_string(PROVIDER_PAGE))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let mut scraper = test_scraper(&server);
+    let report = scraper.run().await.unwrap();
+
+    assert_eq!(report.scraped, 1);
+    server.verify().await;
+}
+
+#[tokio::test]
+async fn run_fails_provider_rate_limited_beyond_max_retry_after() {
+    let server = MockServer::start().await;
+    mount_provider_api(&server, 1, test_provider(&server)).await;
+    Mock::given(method("POST"))
+        .and(path("/scraping_runs"))
+        .respond_with(ResponseTemplate::new(201))
+        .mount(&server)
+        .await;
+
+    Mock::given(method("GET"))
+        .and(path("/pages/1"))
+        .respond_with(ResponseTemplate::new(429).insert_header("retry-after", "3600"))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let mut scraper = test_scraper(&server);
+    let report = scraper.run().await.unwrap();
+
+    assert_eq!(report.failures.get(&FailureKind::RateLimited), Some(&1));
+    server.verify().await;
+}
+
+#[tokio::test]
+async fn metrics_file_gets_a_line_appended_per_run() {
+    let server = MockServer::start().await;
+    mount_backend(&server, 2).await;
+    Mock::given(method("GET"))
+        .and(path("/pages/1"))
+        .respond_with(ResponseTemplate::new(200).set_body_string(PROVIDER_PAGE))
+        .mount(&server)
+        .await;
+
+    let dir = std::env::temp_dir();
+    let csv_file = dir.join(format!("oliepriser-metrics-test-{}.csv", Uuid::new_v4()));
+    let jsonl_file = dir.join(format!("oliepriser-metrics-test-{}.jsonl", Uuid::new_v4()));
+    let _ = std::fs::remove_file(&csv_file);
+    let _ = std::fs::remove_file(&jsonl_file);
+    let mut scraper = test_scraper(&server);
+    for metrics_file in [&csv_file, &jsonl_file] {
+        scraper.config.metrics_file = Some(metrics_file.clone());
+        scraper.run().await.unwrap();
+    }
+
+    let csv = std::fs::read_to_string(&csv_file).unwrap();
+    let lines: Vec<&str> = csv.lines().collect();
+    assert_eq!(lines[0], RunMetrics::CSV_HEADER);
+    assert_eq!(lines.len(), 2);
+    assert!(lines[1].contains(",ok,"));
+    assert!(lines[1].ends_with(",1,1,0,1"));
+
+    let line: serde_json::Value =
+        serde_json::from_str(std::fs::read_to_string(&jsonl_file).unwrap().trim()).unwrap();
+    assert_eq!(line["run_id"], json!(scraper.run_id));
+    assert_eq!(line["status"], "ok");
+    assert_eq!(line["prices_posted"], 1);
+
+    std::fs::remove_file(&csv_file).unwrap();
+    std::fs::remove_file(&jsonl_file).unwrap();
+    server.verify().await;
+}
+
+#[tokio::test]
+async fn run_is_skipped_while_the_latest_completed_api_run_is_fresh() {
+    let server = MockServer::start().await;
+    // Both runs log in to check the latest run, only the second one gets further
+    Mock::given(method("POST"))
+        .and(path("/auth/login"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "access_token": "access_token",
+            "token_type": "Bearer",
+        })))
+        .expect(1)
+        .with_priority(1)
+        .up_to_n_times(1)
+        .mount(&server)
+        .await;
+    mount_backend(&server, 1).await;
+    Mock::given(method("GET"))
+        .and(path("/pages/1"))
+        .respond_with(ResponseTemplate::new(200).set_body_string(PROVIDER_PAGE))
+        .expect(1)
+        .mount(&server)
+        .await;
+    let end_time = chrono::Utc::now() - TimeDelta::seconds(30);
+    Mock::given(method("GET"))
+        .and(path("/scraping_runs"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!([
+            { "end_time": end_time + TimeDelta::seconds(25), "status": "failed" },
+            { "end_time": end_time, "status": "completed" },
+            { "end_time": end_time - TimeDelta::hours(1), "status": "completed" },
+        ])))
+        .expect(2)
+        .mount(&server)
+        .await;
+
+    let mut scraper = test_scraper(&server);
+    scraper.config.since_last_success = Some(std::time::Duration::from_secs(60));
+    let skipped = scraper.run().await.unwrap();
+    assert_eq!(skipped.total(), 0);
+    assert!(scraper
+        .run_duration()
+        .is_some_and(|duration| duration >= TimeDelta::zero()));
+
+    // Fresh for 60 seconds, stale for 10, so the second run scrapes and posts even though a run failed 5 seconds ago
+    scraper.config.since_last_success = Some(std::time::Duration::from_secs(10));
+    assert_eq!(scraper.run().await.unwrap().scraped, 1);
+
+    server.verify().await;
+}
+
+#[tokio::test]
+async fn empty_provider_list_warns_or_fails_the_run() {
+    let providers_file = std::env::temp_dir().join(format!(
+        "oliepriser-empty-providers-test-{}.json",
+        Uuid::new_v4()
+    ));
+    std::fs::write(&providers_file, "[]").unwrap();
+    let config = ScraperConfig {
+        providers_file: Some(providers_file.clone()),
+        ..ScraperConfig::default()
+    };
+    let credentials = Credentials::new("".to_string(), "".to_string());
+    let mut scraper = Scraper::new(vec![], credentials, config).unwrap();
+
+    assert_eq!(scraper.run().await.unwrap().total(), 0);
+
+    scraper.config.fail_on_empty = true;
+    assert!(matches!(
+        scraper.run().await,
+        Err(ScraperError::NoProviders)
+    ));
+    std::fs::remove_file(&providers_file).unwrap();
+}
+
+// `<div class="price">12,49 kr.</div>`, gzip-compressed
+const GZIPPED_PAGE: &[u8] = &[
+    0x1f, 0x8b, 0x08, 0x00, 0x00, 0x00, 0x00, 0x00, 0x02, 0x03, 0xb3, 0x49, 0xc9, 0x2c, 0x53, 0x48,
+    0xce, 0x49, 0x2c, 0x2e, 0xb6, 0x55, 0x2a, 0x28, 0xca, 0x4c, 0x4e, 0x55, 0xb2, 0x33, 0x34, 0xd2,
+    0x31, 0xb1, 0x54, 0xc8, 0x2e, 0xd2, 0xb3, 0xd1, 0x07, 0x4a, 0xda, 0x01, 0x00, 0x3d, 0x7f, 0xcf,
+    0x81, 0x22, 0x00, 0x00, 0x00,
+];
+
+#[tokio::test]
+async fn compressed_pages_are_decoded() {
+    let server = MockServer::start().await;
+    mount_provider_api(&server, 1, test_provider(&server)).await;
+    Mock::given(method("GET"))
+        .and(path("/pages/1"))
+        .and(header_exists("accept-encoding"))
+        .respond_with(
+            ResponseTemplate::new(200)
+                .insert_header("content-encoding", "gzip")
+                .set_body_bytes(GZIPPED_PAGE),
+        )
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let mut scraper = test_scraper(&server);
+    let prices: Vec<ScrapedPrice> = scraper.scraped_prices().await.unwrap().collect().await;
+
+    assert_eq!(prices.len(), 1);
+    assert_eq!(prices[0].price, 12.49);
+    server.verify().await;
+}
+
+#[tokio::test]
+async fn pages_are_decoded_with_their_charset() {
+    let server = MockServer::start().await;
+    mount_provider_api(&server, 1, test_provider(&server)).await;
+    // 0xA0 is a no-break space in ISO-8859-1, but not valid UTF-8 on its own
+    let mut page = b"<div class=\"price\">1".to_vec();
+    page.push(0xA0);
+    page.extend_from_slice(b"299,50 kr.</div>");
+    Mock::given(method("GET"))
+        .and(path("/pages/1"))
+        .respond_with(
+            ResponseTemplate::new(200).set_body_raw(page, "text/html; charset=iso-8859-1"),
+        )
+        .mount(&server)
+        .await;
+
+    let mut scraper = test_scraper(&server);
+    let prices: Vec<ScrapedPrice> = scraper.scraped_prices().await.unwrap().collect().await;
+
+    assert_eq!(prices.len(), 1);
+    assert_eq!(prices[0].price, 1299.5);
+    server.verify().await;
+}
+
+#[tokio::test]
+async fn scraped_prices_yields_prices_without_posting() {
+    let server = MockServer::start().await;
+    let mut provider = test_provider(&server);
+    provider["fuel_type"] = json!("diesel");
+    mount_provider_api(&server, 1, provider).await;
+
+    Mock::given(method("GET"))
+        .and(path("/pages/1"))
+        .respond_with(ResponseTemplate::new(200).set_body_string(PROVIDER_PAGE))
+        .mount(&server)
+        .await;
+    Mock::given(method("POST"))
+        .respond_with(ResponseTemplate::new(201))
+        .expect(0)
+        .mount(&server)
+        .await;
+
+    let mut scraper = test_scraper(&server);
+    let prices: Vec<ScrapedPrice> = scraper.scraped_prices().await.unwrap().collect().await;
+
+    assert_eq!(prices.len(), 1);
+    assert_eq!(prices[0].provider_id, 1);
+    assert_eq!(prices[0].fuel_type.as_deref(), Some("diesel"));
+    assert_eq!(prices[0].price, 12.49);
+    server.verify().await;
+}
+
+#[tokio::test]
+async fn run_fails_when_failures_exceed_threshold() {
+    let server = MockServer::start().await;
+    mount_provider_api(&server, 1, test_provider(&server)).await;
+
+    Mock::given(method("GET"))
+        .and(path("/pages/1"))
+        .respond_with(ResponseTemplate::new(200).set_body_string("<html></html>"))
+        .mount(&server)
+        .await;
+    Mock::given(method("POST"))
+        .and(path("/scraping_runs"))
+        .and(body_partial_json(
+            json!({ "status": "failed", "summary": { "failed": 1 } }),
+        ))
+        .respond_with(ResponseTemplate::new(201))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let credentials = Credentials::new("client_id".to_string(), "client_secret".to_string());
+    let config = ScraperConfig {
+        fail_threshold_pct: Some(50.0),
+        ..ScraperConfig::default()
+    };
+    let mut scraper = Scraper::new(vec![server.uri()], credentials, config).unwrap();
+
+    let result = scraper.run().await;
+
+    assert!(matches!(
+        result,
+        Err(ScraperError::FailureThreshold { failure_pct, .. }) if failure_pct == 100.0
+    ));
+    server.verify().await;
+}
+
+#[tokio::test]
+async fn shutdown_during_a_run_posts_it_as_interrupted() {
+    let server = MockServer::start().await;
+    mount_provider_api(&server, 1, test_provider(&server)).await;
+    Mock::given(method("GET"))
+        .and(path("/pages/1"))
+        .respond_with(ResponseTemplate::new(200).set_body_string(PROVIDER_PAGE))
+        .expect(0)
+        .mount(&server)
+        .await;
+    Mock::given(method("POST"))
+        .and(path("/scraping_runs"))
+        .and(body_partial_json(
+            json!({ "status": "interrupted", "summary": { "scraped": 0, "skipped": 1 } }),
+        ))
+        .respond_with(ResponseTemplate::new(201))
+        .expect(1)
+        .mount(&server)
+        .await;
+    let shutdown = Shutdown::new();
+    let mut scraper = test_scraper(&server).with_shutdown(shutdown.clone());
+
+    // Requested before any provider is started on, so every provider is skipped but the run is still posted
+    shutdown.request();
+    let report = scraper.run().await.unwrap();
+
+    assert_eq!(report.status, RunStatus::Interrupted);
+    assert!(report.to_string().ends_with("interrupted"));
+    server.verify().await;
+}
+
+#[tokio::test]
+async fn run_skips_providers_until_their_poll_interval_has_passed() {
+    let server = MockServer::start().await;
+    let mut provider = test_provider(&server);
+    provider["poll_interval_secs"] = json!(3600);
+    mount_provider_api(&server, 2, provider).await;
+
+    Mock::given(method("GET"))
+        .and(path("/pages/1"))
+        .respond_with(ResponseTemplate::new(200).set_body_string(PROVIDER_PAGE))
+        .expect(1)
+        .mount(&server)
+        .await;
+    Mock::given(method("POST"))
+        .respond_with(ResponseTemplate::new(201))
+        .mount(&server)
+        .await;
+
+    let credentials = Credentials::new("client_id".to_string(), "client_secret".to_string());
+    let config = ScraperConfig {
+        per_provider_intervals: true,
+        ..ScraperConfig::default()
+    };
+    let mut scraper = Scraper::new(vec![server.uri()], credentials, config).unwrap();
+
+    let first = scraper.run().await.unwrap();
+    let second = scraper.run().await.unwrap();
+
+    assert_eq!(first.scraped, 1);
+    assert_eq!(second.skipped, 1);
+    server.verify().await;
+}
+
+#[tokio::test]
+async fn run_throttles_posts_within_the_minimum_post_interval() {
+    let server = MockServer::start().await;
+    mount_provider_api(&server, 2, test_provider(&server)).await;
+
+    Mock::given(method("GET"))
+        .and(path("/pages/1"))
+        .respond_with(ResponseTemplate::new(200).set_body_string(PROVIDER_PAGE))
+        .expect(2)
+        .mount(&server)
+        .await;
+    Mock::given(method("POST"))
+        .and(path("/providers/1/prices"))
+        .respond_with(ResponseTemplate::new(201))
+        .expect(1)
+        .mount(&server)
+        .await;
+    Mock::given(method("POST"))
+        .and(path("/scraping_runs"))
+        .respond_with(ResponseTemplate::new(201))
+        .expect(2)
+        .mount(&server)
+        .await;
+
+    let credentials = Credentials::new("client_id".to_string(), "client_secret".to_string());
+    let config = ScraperConfig {
+        min_post_interval: Some(std::time::Duration::from_secs(3600)),
+        ..ScraperConfig::default()
+    };
+    let mut scraper = Scraper::new(vec![server.uri()], credentials, config).unwrap();
+
+    let first = scraper.run().await.unwrap();
+    let second = scraper.run().await.unwrap();
+
+    assert_eq!((first.posted, first.throttled), (1, 0));
+    assert_eq!((second.posted, second.throttled), (0, 1));
+    assert!(second.to_string().ends_with(", 1 throttled"));
+    server.verify().await;
+}
+
+#[tokio::test]
+async fn rejected_posts_do_not_start_the_minimum_post_interval() {
+    let server = MockServer::start().await;
+    mount_provider_api(&server, 2, test_provider(&server)).await;
+
+    Mock::given(method("GET"))
+        .and(path("/pages/1"))
+        .respond_with(ResponseTemplate::new(200).set_body_string(PROVIDER_PAGE))
+        .expect(2)
+        .mount(&server)
+        .await;
+    Mock::given(method("POST"))
+        .and(path("/providers/1/prices"))
+        .respond_with(ResponseTemplate::new(422))
+        .up_to_n_times(1)
+        .with_priority(1)
+        .expect(1)
+        .mount(&server)
+        .await;
+    Mock::given(method("POST"))
+        .and(path("/providers/1/prices"))
+        .respond_with(ResponseTemplate::new(201))
+        .expect(1)
+        .mount(&server)
+        .await;
+    Mock::given(method("POST"))
+        .and(path("/scraping_runs"))
+        .respond_with(ResponseTemplate::new(201))
+        .expect(2)
+        .mount(&server)
+        .await;
+
+    let credentials = Credentials::new("client_id".to_string(), "client_secret".to_string());
+    let config = ScraperConfig {
+        min_post_interval: Some(std::time::Duration::from_secs(3600)),
+        ..ScraperConfig::default()
+    };
+    let mut scraper = Scraper::new(vec![server.uri()], credentials, config).unwrap();
+
+    let first = scraper.run().await.unwrap();
+    let second = scraper.run().await.unwrap();
+
+    assert_eq!((first.posted, first.throttled), (0, 0));
+    assert_eq!((second.posted, second.throttled), (1, 0));
+    server.verify().await;
+}
+
+#[cfg(feature = "otel")]
+#[tokio::test]
+async fn run_exports_a_trace_with_spans_for_each_step() {
+    let server = mock_api().await;
+    Mock::given(method("POST"))
+        .and(path("/v1/traces"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let credentials = Credentials::new("client_id".to_string(), "client_secret".to_string());
+    let config = ScraperConfig {
+        otlp_endpoint: Some(server.uri()),
+        ..ScraperConfig::default()
+    };
+    let mut scraper = Scraper::new(vec![server.uri()], credentials, config).unwrap();
+
+    let report = scraper.run().await.unwrap();
+
+    let requests = server.received_requests().await.unwrap();
+    let export = requests
+        .iter()
+        .find(|request| request.url.path() == "/v1/traces")
+        .unwrap();
+    let export: serde_json::Value = serde_json::from_slice(&export.body).unwrap();
+    let spans = export["resourceSpans"][0]["scopeSpans"][0]["spans"]
+        .as_array()
+        .unwrap();
+    let mut names: Vec<&str> = spans
+        .iter()
+        .map(|span| span["name"].as_str().unwrap())
+        .collect();
+    // Prices are posted while other providers are still being scraped, so only the run span comes last
+    names.sort();
+    assert_eq!(
+        names,
+        vec!["fetch_provider", "fetch_providers", "post_price", "run"]
+    );
+    let trace_id = report.run_id.simple().to_string();
+    assert!(spans.iter().all(|span| span["traceId"] == json!(trace_id)));
+    server.verify().await;
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn price_commands_do_not_hold_up_posting() {
+    let server = mock_api().await;
+    let marker = std::env::temp_dir().join(format!("oliepriser-hook-marker-{}", Uuid::new_v4()));
+    let credentials = Credentials::new("client_id".to_string(), "client_secret".to_string());
+    let config = ScraperConfig {
+        // Blocks until the price was posted, which would never happen if posting waited for the command
+        on_price: Some(format!(
+            "while [ ! -f {} ]; do sleep 0.05; done",
+            marker.display()
+        )),
+        on_price_timeout: std::time::Duration::from_secs(10),
+        ..ScraperConfig::default()
+    };
+    let mut scraper = Scraper::new(vec![server.uri()], credentials, config).unwrap();
+
+    let started = std::time::Instant::now();
+    let mark_once_posted = async {
+        loop {
+            let requests = server.received_requests().await.unwrap();
+            if requests
+                .iter()
+                .any(|request| request.url.path() == "/providers/1/prices")
+            {
+                std::fs::write(&marker, "").unwrap();
+                break;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+        }
+    };
+    let (result, ()) = futures::join!(scraper.run(), mark_once_posted);
+    let _ = std::fs::remove_file(&marker);
+
+    result.unwrap();
+    assert!(started.elapsed() < std::time::Duration::from_secs(5));
+    server.verify().await;
+}
+
+#[tokio::test]
+async fn rejected_prices_are_not_recorded_as_delivered() {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path("/pages/1"))
+        .respond_with(ResponseTemplate::new(200).set_body_string(PROVIDER_PAGE))
+        .expect(1)
+        .mount(&server)
+        .await;
+    Mock::given(method("POST"))
+        .and(path("/providers/1/prices"))
+        .respond_with(ResponseTemplate::new(422).set_body_string("price out of range"))
+        .expect(2)
+        .mount(&server)
+        .await;
+
+    let resume_file =
+        std::env::temp_dir().join(format!("oliepriser-rejected-{}.jsonl", Uuid::new_v4()));
+    let credentials = Credentials::new("client_id".to_string(), "client_secret".to_string());
+    let config = ScraperConfig {
+        resume_file: Some(resume_file.clone()),
+        ..ScraperConfig::default()
+    };
+    let mut scraper = Scraper::new(vec![server.uri()], credentials, config).unwrap();
+    scraper.providers = vec![serde_json::from_value(test_provider(&server)).unwrap()];
+
+    let report = scraper.handle_scraping().await;
+
+    assert_eq!(report.scraped, 1);
+    assert_eq!(report.posted, 0);
+    // A delivered price would have been recorded, so a resumed run would never retry it
+    let recorded = resume_file.exists();
+    let _ = std::fs::remove_file(&resume_file);
+    assert!(!recorded);
+    assert!(scraper
+        .add_price_for_provider(1, 12.49, None)
+        .await
+        .is_err());
+}
+
+#[cfg(feature = "otel")]
+#[tokio::test]
+async fn resumed_runs_are_traced_under_the_interrupted_run_id() {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path("/pages/1"))
+        .respond_with(ResponseTemplate::new(200).set_body_string(PROVIDER_PAGE))
+        .mount(&server)
+        .await;
+    Mock::given(method("POST"))
+        .and(path("/v1/traces"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let dir = std::env::temp_dir();
+    let providers_file = dir.join(format!(
+        "oliepriser-trace-providers-{}.json",
+        Uuid::new_v4()
+    ));
+    std::fs::write(&providers_file, json!([test_provider(&server)]).to_string()).unwrap();
+    let resume_file = dir.join(format!("oliepriser-trace-resume-{}.jsonl", Uuid::new_v4()));
+    let interrupted = Uuid::new_v4();
+    let record =
+        json!({ "run_id": interrupted, "run_start": chrono::Utc::now(), "provider_id": 2 });
+    std::fs::write(&resume_file, record.to_string()).unwrap();
+
+    let config = ScraperConfig {
+        providers_file: Some(providers_file.clone()),
+        resume_file: Some(resume_file.clone()),
+        otlp_endpoint: Some(server.uri()),
+        ..ScraperConfig::default()
+    };
+    let mut scraper = Scraper::new(
+        vec![],
+        Credentials::new("".to_string(), "".to_string()),
+        config,
+    )
+    .unwrap();
+
+    let report = scraper.run().await;
+    let _ = std::fs::remove_file(&providers_file);
+    let _ = std::fs::remove_file(&resume_file);
+
+    assert_eq!(report.unwrap().run_id, interrupted);
+    let requests = server.received_requests().await.unwrap();
+    let export = requests
+        .iter()
+        .find(|request| request.url.path() == "/v1/traces")
+        .unwrap();
+    let export: serde_json::Value = serde_json::from_slice(&export.body).unwrap();
+    let spans = export["resourceSpans"][0]["scopeSpans"][0]["spans"]
+        .as_array()
+        .unwrap();
+    let trace_id = interrupted.simple().to_string();
+    assert!(spans.iter().all(|span| span["traceId"] == json!(trace_id)));
+    server.verify().await;
+}
+
+#[tokio::test]
+async fn run_retries_transient_post_failures() {
+    let server = mock_api().await;
+
+    Mock::given(method("POST"))
+        .and(path("/providers/1/prices"))
+        .respond_with(ResponseTemplate::new(503).insert_header("retry-after", "0"))
+        .up_to_n_times(1)
+        .with_priority(1)
+        .expect(1)
+        .mount(&server)
+        .await;
+    Mock::given(method("POST"))
+        .and(path("/scraping_runs"))
+        .respond_with(ResponseTemplate::new(502))
+        .up_to_n_times(2)
+        .with_priority(1)
+        .expect(2)
+        .mount(&server)
+        .await;
+
+    let credentials = Credentials::new("client_id".to_string(), "client_secret".to_string());
+    let config = ScraperConfig {
+        retry_base_delay: std::time::Duration::from_millis(1),
+        ..ScraperConfig::default()
+    };
+    let mut scraper = Scraper::new(vec![server.uri()], credentials, config).unwrap();
+
+    scraper.run().await.unwrap();
+
+    // The fallback mocks from `mock_api` expect exactly one successful post of each
+    server.verify().await;
+}
+
+#[tokio::test]
+async fn run_fails_over_to_next_backend_when_unreachable() {
+    let server = mock_api().await;
+    let credentials = Credentials::new("client_id".to_string(), "client_secret".to_string());
+    // Nothing listens on port 1, so the first replica refuses the connection
+    let mut scraper = Scraper::new(
+        vec!["http://127.0.0.1:1".to_string(), server.uri()],
+        credentials,
+        ScraperConfig::default(),
+    )
+    .unwrap();
+
+    scraper.run().await.unwrap();
+
+    server.verify().await;
+}
+
+#[tokio::test]
+async fn run_with_providers_file_writes_prices_locally() {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path("/pages/1"))
+        .respond_with(ResponseTemplate::new(200).set_body_string(PROVIDER_PAGE))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let dir = std::env::temp_dir();
+    let providers_file = dir.join(format!(
+        "oliepriser-run-providers-test-{}.json",
+        Uuid::new_v4()
+    ));
+    let prices_output = dir.join(format!(
+        "oliepriser-run-prices-test-{}.jsonl",
+        Uuid::new_v4()
+    ));
+    let _ = std::fs::remove_file(&prices_output);
+    std::fs::write(
+        &providers_file,
+        json!([{
+            "id": 1,
+            "name": "Local provider",
+            "url": format!("{}/pages/1", server.uri()),
+            "html_element": ".price",
+        }])
+        .to_string(),
+    )
+    .unwrap();
+
+    let config = ScraperConfig {
+        providers_file: Some(providers_file.clone()),
+        prices_output: Some(prices_output.clone()),
+        ..ScraperConfig::default()
+    };
+    let credentials = Credentials::new("".to_string(), "".to_string());
+    let mut scraper = Scraper::new(vec![], credentials, config).unwrap();
+
+    let report = scraper.run().await.unwrap();
+
+    let output = std::fs::read_to_string(&prices_output).unwrap();
+    let line: serde_json::Value = serde_json::from_str(output.trim()).unwrap();
+    assert_eq!(report.scraped, 1);
+    assert_eq!(line["provider_id"], 1);
+    assert_eq!(line["price"], 12.49);
+    server.verify().await;
+    std::fs::remove_file(&providers_file).unwrap();
+    std::fs::remove_file(&prices_output).unwrap();
+}
+
+#[test]
+fn strict_decimals_reject_mismatched_prices() {
+    let provider: Provider = serde_json::from_value(json!({
+        "id": 1,
+        "name": "Mill provider",
+        "url": "http://localhost",
+        "html_element": ".price",
+        "expected_decimals": 3,
+    }))
+    .unwrap();
+    let document = Html::parse_document(r#"<div class="price">1.299 kr.</div>"#);
+    let selector = Selector::parse(".price").unwrap();
+
+    let lenient = offline_scraper();
+    assert_eq!(
+        lenient.extract_price(&provider, &document, &selector),
+        Ok(1299.0)
+    );
+
+    let mut strict = offline_scraper();
+    strict.config.strict_decimals = true;
+    assert_eq!(
+        strict.extract_price(&provider, &document, &selector),
+        Err(FailureKind::Unparseable)
+    );
+}
+
+#[test]
+fn strict_parse_fails_on_any_unparseable_match() {
+    let provider: Provider = serde_json::from_value(json!({
+        "id": 1,
+        "name": "Reformatted provider",
+        "url": "http://localhost",
+        "html_element": ".price",
+    }))
+    .unwrap();
+    let document = Html::parse_document(
+        r#"<div class="price">Ring for pris</div><div class="price">12,49 kr.</div>"#,
+    );
+    let selector = Selector::parse(".price").unwrap();
+
+    let lenient = offline_scraper();
+    assert_eq!(
+        lenient.extract_price(&provider, &document, &selector),
+        Ok(12.49)
+    );
+
+    let mut strict = offline_scraper();
+    strict.config.strict_parse = true;
+    assert_eq!(
+        strict.extract_price(&provider, &document, &selector),
+        Err(FailureKind::Unparseable)
+    );
+}
+
+#[test]
+fn matches_without_the_expected_marker_are_rejected() {
+    let mut provider: Provider = serde_json::from_value(json!({
+        "id": 1,
+        "name": "Relayouted provider",
+        "url": "http://localhost",
+        "html_element": ".info",
+        "expected_marker": "KR",
+    }))
+    .unwrap();
+    let selector = Selector::parse(".info").unwrap();
+    let scraper = offline_scraper();
+
+    // A phone number and a quantity aren't prices, even if they parse
+    let relayouted = Html::parse_document(
+        r#"<div class="info">70 20 30 40</div><div class="info">1.000 liter</div>"#,
+    );
+    assert_eq!(
+        scraper.extract_price(&provider, &relayouted, &selector),
+        Err(FailureKind::Suspicious)
+    );
+
+    let mixed = Html::parse_document(
+        r#"<div class="info">1.000 liter</div><div class="info">12,49 kr.</div>"#,
+    );
+    assert_eq!(
+        scraper.extract_price(&provider, &mixed, &selector),
+        Ok(12.49)
+    );
+
+    // Without the marker check the phone number is taken for the price
+    provider.expected_marker = None;
+    assert_eq!(
+        scraper.extract_price(&provider, &relayouted, &selector),
+        Ok(70203040.0)
+    );
+}
+
+#[test]
+fn prices_split_across_elements_are_extracted() {
+    let provider: Provider = serde_json::from_value(json!({
+        "id": 1,
+        "name": "Styled provider",
+        "url": "http://localhost",
+        "html_element": ".price",
+    }))
+    .unwrap();
+    let selector = Selector::parse(".price").unwrap();
+    let scraper = offline_scraper();
+
+    for html in [
+        r#"<div class="price"><span>12</span><span>,49</span> kr.</div>"#,
+        r#"<div class="price"><span class="int">12</span><sup>,49</sup>
+            <span class="currency">kr.</span></div>"#,
+        r#"<div class="price">12<span class="decimals">,49</span>&nbsp;kr.</div>"#,
+    ] {
+        let document = Html::parse_document(html);
+        assert_eq!(
+            scraper.extract_price(&provider, &document, &selector),
+            Ok(12.49),
+            "{}",
+            html
+        );
+    }
+}
+
+#[test]
+fn declared_separators_override_the_guessed_convention() {
+    let document = Html::parse_document(
+        r#"<div class="price">1,299.50 kr.</div><div class="price">12.49</div>"#,
+    );
+    let selector = Selector::parse(".price").unwrap();
+    let mut provider: Provider = serde_json::from_value(json!({
+        "id": 1,
+        "name": "English provider",
+        "url": "http://localhost",
+        "html_element": ".price",
+        "price_selection": "min",
+    }))
+    .unwrap();
+    let scraper = offline_scraper();
+
+    // Guessed as Danish, `12.49` is an ambiguous thousands group and `1,299.50` isn't a number
+    assert_eq!(
+        scraper.extract_price(&provider, &document, &selector),
+        Err(FailureKind::Unparseable)
+    );
+
+    provider.thousands_sep = Some(',');
+    assert_eq!(
+        scraper.extract_price(&provider, &document, &selector),
+        Ok(12.49)
+    );
+    provider.price_selection = PriceSelection::Max;
+    assert_eq!(
+        scraper.extract_price(&provider, &document, &selector),
+        Ok(1299.5)
+    );
+
+    provider.decimal_sep = Some(',');
+    provider.thousands_sep = Some('.');
+    assert_eq!(
+        scraper.extract_json_price(&provider, r#"{ "price": "1.299,50" }"#, "/price"),
+        Ok(1299.5)
+    );
+}
+
+#[test]
+fn matched_station_prices_are_aggregated() {
+    let document = Html::parse_document(
+        r#"<ul>
+            <li class="station">13,50</li>
+            <li class="station">12,00</li>
+            <li class="station">Lukket</li>
+            <li class="station">15,00</li>
+            <li class="station">12,50</li>
+        </ul>"#,
+    );
+    let selector = Selector::parse(".station").unwrap();
+    let mut provider: Provider = serde_json::from_value(json!({
+        "id": 1,
+        "name": "Station provider",
+        "url": "http://localhost",
+        "html_element": ".station",
+    }))
+    .unwrap();
+    let scraper = offline_scraper();
+
+    for (selection, price) in [
+        (PriceSelection::First, 13.5),
+        (PriceSelection::Min, 12.0),
+        (PriceSelection::Max, 15.0),
+        (PriceSelection::Avg, 13.25),
+        (PriceSelection::Median, 13.0),
+    ] {
+        provider.price_selection = selection;
+        assert_eq!(
+            scraper.extract_price(&provider, &document, &selector),
+            Ok(price),
+            "{:?}",
+            selection
+        );
+    }
+}
+
+#[test]
+fn price_ranges_are_parsed_to_the_configured_bound() {
+    let document = Html::parse_document("<div class=\"price\">13,00 \u{2013} 12,50 kr.</div>");
+    let selector = Selector::parse(".price").unwrap();
+    let mut provider: Provider = serde_json::from_value(json!({
+        "id": 1,
+        "name": "Range provider",
+        "url": "http://localhost",
+        "html_element": ".price",
+        "expected_marker": "kr",
+    }))
+    .unwrap();
+    let scraper = offline_scraper();
+
+    for (bound, price) in [
+        (RangeBound::Low, 12.5),
+        (RangeBound::High, 13.0),
+        (RangeBound::Midpoint, 12.75),
+    ] {
+        provider.range_bound = bound;
+        assert_eq!(
+            scraper.extract_price(&provider, &document, &selector),
+            Ok(price),
+            "{:?}",
+            bound
+        );
+    }
+
+    provider.range_bound = RangeBound::High;
+    provider.decimal_sep = Some('.');
+    let document = Html::parse_document(r#"<div class="price">12.49-13.20 kr.</div>"#);
+    assert_eq!(
+        scraper.extract_price(&provider, &document, &selector),
+        Ok(13.2)
+    );
+}
+
+#[test]
+fn transforms_apply_to_parsed_prices_before_validation() {
+    let document = Html::parse_document(r#"<div class="price">1.249</div>"#);
+    let selector = Selector::parse(".price").unwrap();
+    let mut provider: Provider = serde_json::from_value(json!({
+        "id": 1,
+        "name": "Provider in øre",
+        "url": "http://localhost",
+        "html_element": ".price",
+        "decimal_sep": ",",
+        "thousands_sep": ".",
+        "transforms": ["divide:100", "add_vat:0.25", "round:4"],
+    }))
+    .unwrap();
+    let scraper = offline_scraper();
+
+    assert_eq!(
+        scraper.extract_price(&provider, &document, &selector),
+        Ok(15.6125)
+    );
+    assert_eq!(
+        scraper.extract_json_price(&provider, r#"{ "price": 1249 }"#, "/price"),
+        Ok(15.6125)
+    );
+
+    provider.transforms = vec![PriceTransform::Subtract(2000.0)];
+    assert_eq!(
+        scraper.extract_price(&provider, &document, &selector),
+        Err(FailureKind::OutOfRange)
+    );
+
+    let unknown = serde_json::from_value::<Provider>(json!({
+        "id": 1,
+        "name": "Provider",
+        "url": "http://localhost",
+        "transforms": ["scale:2"],
+    }));
+    assert!(unknown
+        .unwrap_err()
+        .to_string()
+        .contains("unknown price transform"));
+}
+
+#[test]
+fn extract_price_reads_the_cell_under_a_table_header() {
+    let document = Html::parse_document(
+        r#"<table class="prices">
+            <tr><th>Produkt</th><th>Pris i går</th><th>Pris i dag</th></tr>
+            <tr><td>Fyringsolie</td><td>12,19 kr.</td><td>12,49 kr.</td></tr>
+            <tr><td>Diesel</td><td>13,29 kr.</td><td>13,09 kr.</td></tr>
+        </table>"#,
+    );
+    let selector = Selector::parse("table.prices").unwrap();
+    let mut provider: Provider = serde_json::from_value(json!({
+        "id": 1,
+        "name": "Table provider",
+        "url": "http://localhost",
+        "html_element": "table.prices",
+        "table": { "header": "Pris i dag" },
+    }))
+    .unwrap();
+    let scraper = offline_scraper();
+
+    assert_eq!(
+        scraper.extract_price(&provider, &document, &selector),
+        Ok(12.49)
+    );
+    provider.price_selection = PriceSelection::Max;
+    assert_eq!(
+        scraper.extract_price(&provider, &document, &selector),
+        Ok(13.09)
+    );
+
+    provider.table =
+        serde_json::from_value(json!({ "header": "Diesel", "orientation": "row" })).unwrap();
+    provider.price_selection = PriceSelection::Last;
+    assert_eq!(
+        scraper.extract_price(&provider, &document, &selector),
+        Ok(13.09)
+    );
+
+    provider.table = serde_json::from_value(json!({ "header": "Pris i morgen" })).unwrap();
+    assert_eq!(
+        scraper.extract_price(&provider, &document, &selector),
+        Err(FailureKind::NoMatch)
+    );
+}
+
+#[test]
+fn extract_json_price_reads_numbers_and_strings() {
+    let scraper = offline_scraper();
+    let provider: Provider = serde_json::from_value(json!({
+        "id": 1,
+        "name": "Json provider",
+        "url": "http://localhost",
+        "json_pointer": "/data/price",
+    }))
+    .unwrap();
+
+    assert_eq!(
+        scraper.extract_json_price(&provider, r#"{"data":{"price":12.49}}"#, "/data/price"),
+        Ok(12.49)
+    );
+    assert_eq!(
+        scraper.extract_json_price(
+            &provider,
+            r#"{"data":{"price":"13,09 kr."}}"#,
+            "/data/price"
+        ),
+        Ok(13.09)
+    );
+    assert_eq!(
+        scraper.extract_json_price(&provider, r#"{"data":{}}"#, "/data/price"),
+        Err(FailureKind::NoMatch)
+    );
+    assert_eq!(
+        scraper.extract_json_price(&provider, "<html>", "/data/price"),
+        Err(FailureKind::Unparseable)
+    );
+    assert_eq!(
+        scraper.extract_json_price(&provider, r#"{"data":{"price":0}}"#, "/data/price"),
+        Err(FailureKind::OutOfRange)
+    );
+}
+
+#[test]
+fn json_price_strings_get_the_same_checks_as_matched_text() {
+    let provider: Provider = serde_json::from_value(json!({
+        "id": 1,
+        "name": "Json provider",
+        "url": "http://localhost",
+        "json_pointer": "/price",
+        "expected_marker": "kr",
+        "expected_decimals": 3,
+    }))
+    .unwrap();
+
+    let mut scraper = offline_scraper();
+    assert_eq!(
+        scraper.extract_json_price(&provider, r#"{ "price": "12,49 EUR" }"#, "/price"),
+        Err(FailureKind::Suspicious)
+    );
+    assert_eq!(
+        scraper.extract_json_price(&provider, r#"{ "price": "12,490 kr." }"#, "/price"),
+        Ok(12.49)
+    );
+    scraper.config.strict_decimals = true;
+    assert_eq!(
+        scraper.extract_json_price(&provider, r#"{ "price": "12,49 kr." }"#, "/price"),
+        Err(FailureKind::Unparseable)
+    );
+}
+
+#[test]
+fn feeds_are_extracted_according_to_their_feed_type() {
+    let scraper = offline_scraper();
+    let feed_provider = |feed: serde_json::Value| -> Provider {
+        let mut provider = json!({ "id": 1, "name": "Feed provider", "url": "http://localhost" });
+        provider
+            .as_object_mut()
+            .unwrap()
+            .extend(feed.as_object().unwrap().clone());
+        serde_json::from_value(provider).unwrap()
+    };
+    let extract = |provider: &Provider, body: &str| {
+        let extractor = scraper.extractor(provider).unwrap();
+        scraper.extract(provider, &extractor, body)
+    };
+    let csv =
+        "Produkt;Pris;Opdateret\nBlyfri 95;13,29;14.05.2024 12:30\nDiesel;12,49;14.05.2024 12:30\n";
+    let json = r#"{"products":[{"name":"Diesel","price":"12,49"}]}"#;
+
+    let csv_provider = feed_provider(json!({ "feed_type": "csv", "csv_column": "pris" }));
+    assert_eq!(extract(&csv_provider, csv), Ok(13.29));
+    let cheapest = feed_provider(json!({
+        "feed_type": "csv",
+        "csv_column": "Pris",
+        "price_selection": "min",
+    }));
+    assert_eq!(extract(&cheapest, csv), Ok(12.49));
+    let missing_column = feed_provider(json!({ "feed_type": "csv", "csv_column": "Price" }));
+    assert_eq!(extract(&missing_column, csv), Err(FailureKind::NoMatch));
+
+    let json_provider = feed_provider(json!({
+        "feed_type": "json",
+        "json_pointer": "/products/0/price",
+    }));
+    assert_eq!(extract(&json_provider, json), Ok(12.49));
+
+    assert!(scraper
+        .extractor(&feed_provider(json!({ "feed_type": "csv" })))
+        .is_err());
+    assert!(scraper
+        .extractor(&feed_provider(json!({ "feed_type": "json" })))
+        .is_err());
+
+    let scraped_at = chrono::Utc::now();
+    let with_updated_at = Provider {
+        updated_at_selector: Some("opdateret".to_string()),
+        ..csv_provider.clone()
+    };
+    let extractor = scraper.extractor(&with_updated_at).unwrap();
+    assert_ne!(
+        scraper.updated_at(&with_updated_at, &extractor, csv, scraped_at),
+        Some(scraped_at)
+    );
+}
+
+#[test]
+fn expressions_replace_the_selector_of_html_providers() {
+    let scraper = offline_scraper();
+    let provider = |expression: &str| -> Provider {
+        serde_json::from_value(json!({
+            "id": 1,
+            "name": "Expression provider",
+            "url": "http://localhost",
+            "html_element": ".unused",
+            "expression": expression,
+        }))
+        .unwrap()
+    };
+    let page = r#"<ul><li class="info">Blyfri 95 - Pris: 13,29 kr.</li><li class="info">Diesel - Pris: 12,49 kr.</li></ul>"#;
+
+    let labelled = provider(".info | after-label(Pris:)");
+    let extractor = scraper.extractor(&labelled).unwrap();
+    assert_eq!(scraper.extract(&labelled, &extractor, page), Ok(13.29));
+
+    let unlabelled = provider(".info | after-label(Rabat:)");
+    let extractor = scraper.extractor(&unlabelled).unwrap();
+    assert_eq!(
+        scraper.extract(&unlabelled, &extractor, page),
+        Err(FailureKind::NoMatch)
+    );
+
+    assert!(scraper.extractor(&provider(".info | html")).is_err());
+    assert!(scraper.extractor(&provider("li[ | text")).is_err());
+}
+
+#[test]
+fn expressions_check_the_expected_marker_against_the_whole_element() {
+    let scraper = offline_scraper();
+    let provider = |marker: &str| -> Provider {
+        serde_json::from_value(json!({
+            "id": 1,
+            "name": "Expression provider",
+            "url": "http://localhost",
+            "html_element": ".unused",
+            "expression": ".info | after-label(Pris:)",
+            "expected_marker": marker,
+        }))
+        .unwrap()
+    };
+    let page = r#"<ul><li class="info">Rabat: 2,00</li><li class="info">Diesel - Pris: 12,49 kr.</li></ul>"#;
+
+    // The after-label operation reads only the number, the marker is in the rest of the element
+    let marked = provider("kr");
+    let extractor = scraper.extractor(&marked).unwrap();
+    assert_eq!(scraper.extract(&marked, &extractor, page), Ok(12.49));
+
+    let unmarked = provider("EUR");
+    let extractor = scraper.extractor(&unmarked).unwrap();
+    assert_eq!(
+        scraper.extract(&unmarked, &extractor, page),
+        Err(FailureKind::Suspicious)
+    );
+}
+
+#[tokio::test]
+async fn invalid_auth_headers_fail_the_run_instead_of_panicking() {
+    let credentials =
+        Credentials::new("".to_string(), "".to_string()).with_scheme(AuthScheme::ApiKey {
+            header: "X-Api-Key".to_string(),
+            key: "abc\ndef".to_string(),
+        });
+    let mut scraper = Scraper::new(
+        vec!["http://localhost".to_string()],
+        credentials,
+        ScraperConfig::default(),
+    )
+    .unwrap();
+
+    let error = scraper.run().await.unwrap_err();
+
+    assert!(
+        matches!(&error, ScraperError::Config(message) if message.contains("Invalid x-api-key header value"))
+    );
+    assert!(!error.to_string().contains("abc"));
+}
+
+#[test]
+fn selector_is_cached_and_invalid_selectors_are_errors() {
+    let scraper = offline_scraper();
+
+    assert!(scraper.selector(".price").is_ok());
+    assert!(scraper.selector(".price").is_ok());
+    assert_eq!(scraper.selector_cache.lock().unwrap().len(), 1);
+    assert!(scraper.selector("div[").is_err());
+}
+
+#[tokio::test]
+async fn providers_whose_details_fail_are_reported_without_aborting_the_run() {
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(path("/auth/login"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "access_token": "access_token",
+            "token_type": "Bearer",
+        })))
+        .mount(&server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/scraping_runs/providers"))
+        .respond_with(
+            ResponseTemplate::new(200)
+                .set_body_json(json!([{ "id": 1 }, { "id": 2 }, { "id": 3 }])),
+        )
+        .mount(&server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/providers/1"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(test_provider(&server)))
+        .mount(&server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/providers/2"))
+        .respond_with(ResponseTemplate::new(503))
+        .mount(&server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/providers/3"))
+        .respond_with(ResponseTemplate::new(200).set_body_string("not json"))
+        .mount(&server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/pages/1"))
+        .respond_with(ResponseTemplate::new(200).set_body_string(PROVIDER_PAGE))
+        .mount(&server)
+        .await;
+    Mock::given(method("POST"))
+        .and(path("/providers/1/prices"))
+        .respond_with(ResponseTemplate::new(201))
+        .expect(1)
+        .mount(&server)
+        .await;
+    Mock::given(method("POST"))
+        .and(path("/scraping_runs"))
+        .respond_with(ResponseTemplate::new(201))
+        .mount(&server)
+        .await;
+
+    let mut scraper = test_scraper(&server);
+    let report = scraper.run().await.unwrap();
+
+    assert_eq!(report.scraped, 1);
+    assert_eq!(report.failed, 2);
+    assert_eq!(report.failures.get(&FailureKind::Http5xx), Some(&1));
+    assert_eq!(report.failures.get(&FailureKind::Unparseable), Some(&1));
+    server.verify().await;
+}
+
+#[tokio::test]
+async fn bulk_post_sends_all_prices_in_one_request() {
+    let server = MockServer::start().await;
+    mount_provider_api(&server, 1, test_provider(&server)).await;
+    Mock::given(method("GET"))
+        .and(path("/pages/1"))
+        .respond_with(ResponseTemplate::new(200).set_body_string(PROVIDER_PAGE))
+        .mount(&server)
+        .await;
+    Mock::given(method("POST"))
+        .and(path("/prices"))
+        .and(body_partial_json(
+            json!([{ "provider_id": 1, "price": 12.49 }]),
+        ))
+        .respond_with(ResponseTemplate::new(201))
+        .expect(1)
+        .mount(&server)
+        .await;
+    Mock::given(method("POST"))
+        .and(path("/providers/1/prices"))
+        .respond_with(ResponseTemplate::new(201))
+        .expect(0)
+        .mount(&server)
+        .await;
+    Mock::given(method("POST"))
+        .and(path("/scraping_runs"))
+        .respond_with(ResponseTemplate::new(201))
+        .mount(&server)
+        .await;
+
+    let credentials = Credentials::new("client_id".to_string(), "client_secret".to_string());
+    let config = ScraperConfig {
+        bulk_post: true,
+        ..ScraperConfig::default()
+    };
+    let mut scraper = Scraper::new(vec![server.uri()], credentials, config).unwrap();
+
+    scraper.run().await.unwrap();
+    server.verify().await;
+}
+
+#[tokio::test]
+async fn bulk_posts_go_to_the_configured_path() {
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(path("/api/prices/bulk"))
+        .respond_with(ResponseTemplate::new(201))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let credentials = Credentials::new("".to_string(), "".to_string());
+    let mut config = ScraperConfig::default();
+    config.paths.bulk_prices = "/api/prices/bulk".to_string();
+    let scraper = Scraper::new(vec![server.uri()], credentials, config).unwrap();
+    let prices = [ScrapedPrice::new(
+        &serde_json::from_value(test_provider(&server)).unwrap(),
+        12.49,
+        chrono::Utc::now(),
+    )];
+    scraper.add_prices_bulk(&prices).await.unwrap();
+    server.verify().await;
+
+    let mut config = ScraperConfig::default();
+    config.paths.bulk_prices = ":not a path".to_string();
+    let credentials = Credentials::new("".to_string(), "".to_string());
+    assert!(matches!(
+        Scraper::new(vec![server.uri()], credentials, config),
+        Err(ScraperError::Config(_))
+    ));
+}
+
+#[test]
+fn sampling_is_reproducible_with_a_seed() {
+    let sampled_out = |sample_rate: f64, seed: u64| {
+        let config = ScraperConfig {
+            sample_rate,
+            sample_seed: Some(seed),
+            ..ScraperConfig::default()
+        };
+        let credentials = Credentials::new("".to_string(), "".to_string());
+        let mut scraper = Scraper::new(vec![], credentials, config).unwrap();
+        scraper.providers = (1..=100)
+            .map(|id| {
+                serde_json::from_value(
+                    json!({ "id": id, "name": id.to_string(), "url": "http://localhost" }),
+                )
+                .unwrap()
+            })
+            .collect();
+        scraper.sample_providers();
+        scraper.sampled_out
+    };
+
+    let half = sampled_out(0.5, 7);
+    assert_eq!(half, sampled_out(0.5, 7));
+    assert!(
+        (25..=75).contains(&half.len()),
+        "{} sampled out",
+        half.len()
+    );
+    assert!(sampled_out(1.0, 7).is_empty());
+    assert_eq!(sampled_out(0.0, 7).len(), 100);
+}
+
+#[tokio::test]
+async fn sampled_out_prices_are_scraped_but_not_posted() {
+    let server = MockServer::start().await;
+    mount_provider_api(&server, 1, test_provider(&server)).await;
+    Mock::given(method("GET"))
+        .and(path("/pages/1"))
+        .respond_with(ResponseTemplate::new(200).set_body_string(PROVIDER_PAGE))
+        .expect(1)
+        .mount(&server)
+        .await;
+    Mock::given(method("POST"))
+        .and(path("/providers/1/prices"))
+        .respond_with(ResponseTemplate::new(201))
+        .expect(0)
+        .mount(&server)
+        .await;
+    Mock::given(method("POST"))
+        .and(path("/scraping_runs"))
+        .respond_with(ResponseTemplate::new(201))
+        .mount(&server)
+        .await;
+
+    let credentials = Credentials::new("client_id".to_string(), "client_secret".to_string());
+    let config = ScraperConfig {
+        sample_rate: 0.0,
+        ..ScraperConfig::default()
+    };
+    let mut scraper = Scraper::new(vec![server.uri()], credentials, config).unwrap();
+
+    let report = scraper.run().await.unwrap();
+
+    assert_eq!(report.scraped, 1);
+    server.verify().await;
+}
+
+#[tokio::test]
+async fn run_report_classifies_provider_http_errors() {
+    let server = MockServer::start().await;
+    mount_provider_api(&server, 1, test_provider(&server)).await;
+    Mock::given(method("GET"))
+        .and(path("/pages/1"))
+        .respond_with(ResponseTemplate::new(503))
+        .mount(&server)
+        .await;
+    Mock::given(method("POST"))
+        .and(path("/scraping_runs"))
+        .and(body_partial_json(json!({
+            "summary": { "failed": 1, "failures": { "http_5xx": 1 } },
+        })))
+        .respond_with(ResponseTemplate::new(201))
+        .expect(1)
+        .mount(&server)
+        .await;
+    let mut scraper = test_scraper(&server);
+
+    let report = scraper.run().await.unwrap();
+
+    assert_eq!(report.failures.get(&FailureKind::Http5xx), Some(&1));
+    server.verify().await;
+}
+
+#[tokio::test]
+async fn anti_bot_interstitials_are_reported_as_blocked() {
+    for (status, page) in [
+        (
+            403,
+            "<html><head><title>Just a moment...</title></head></html>",
+        ),
+        (
+            200,
+            r#"<form><div class="g-recaptcha" data-sitekey="x"></div></form>"#,
+        ),
+    ] {
+        let server = MockServer::start().await;
+        mount_provider_api(&server, 1, test_provider(&server)).await;
+        Mock::given(method("GET"))
+            .and(path("/pages/1"))
+            .respond_with(ResponseTemplate::new(status).set_body_string(page))
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path("/scraping_runs"))
+            .and(body_partial_json(json!({
+                "summary": { "failed": 1, "failures": { "blocked": 1 } },
+            })))
+            .respond_with(ResponseTemplate::new(201))
+            .expect(1)
+            .mount(&server)
+            .await;
+        let mut scraper = test_scraper(&server);
+
+        let report = scraper.run().await.unwrap();
+
+        assert_eq!(report.failures.get(&FailureKind::Blocked), Some(&1));
+        server.verify().await;
+    }
+}
+
+#[tokio::test]
+async fn accept_language_is_sent_unless_a_provider_overrides_it() {
+    let server = MockServer::start().await;
+    for (id, language) in [(1, "da-DK"), (2, "en-GB")] {
+        Mock::given(method("GET"))
+            .and(path(format!("/pages/{}", id)))
+            .and(header("accept-language", language))
+            .respond_with(ResponseTemplate::new(200).set_body_string(PROVIDER_PAGE))
+            .expect(1)
+            .mount(&server)
+            .await;
+    }
+    let providers = json!([
+        { "id": 1, "name": "Danish", "url": format!("{}/pages/1", server.uri()), "html_element": ".price" },
+        {
+            "id": 2,
+            "name": "English",
+            "url": format!("{}/pages/2", server.uri()),
+            "html_element": ".price",
+            "accept_language": "en-GB",
+        },
+    ]);
+    let providers_file = std::env::temp_dir().join(format!(
+        "oliepriser-accept-language-test-{}.json",
+        Uuid::new_v4()
+    ));
+    std::fs::write(&providers_file, providers.to_string()).unwrap();
+
+    let config = ScraperConfig {
+        providers_file: Some(providers_file.clone()),
+        http: HttpConfig {
+            accept_language: Some("da-DK".to_string()),
+            ..HttpConfig::default()
+        },
+        ..ScraperConfig::default()
+    };
+    let credentials = Credentials::new("".to_string(), "".to_string());
+    let mut scraper = Scraper::new(vec![], credentials, config).unwrap();
+
+    let report = scraper.run().await.unwrap();
+
+    assert_eq!(report.scraped, 2);
+    server.verify().await;
+    std::fs::remove_file(&providers_file).unwrap();
+}
+
+#[tokio::test]
+async fn provider_api_keys_are_sent_only_to_their_provider_without_the_backend_token() {
+    let server = MockServer::start().await;
+    let mut provider = test_provider(&server);
+    provider["auth"] =
+        json!({ "in": "header", "name": "X-Partner-Key", "value": "partner-secret" });
+    mount_provider_api(&server, 1, provider).await;
+    for route in ["/providers/1/prices", "/scraping_runs"] {
+        Mock::given(method("POST"))
+            .and(path(route))
+            .respond_with(ResponseTemplate::new(201))
+            .expect(1)
+            .mount(&server)
+            .await;
+    }
+    Mock::given(method("GET"))
+        .and(path("/pages/1"))
+        .and(header("x-partner-key", "partner-secret"))
+        .respond_with(ResponseTemplate::new(200).set_body_string(PROVIDER_PAGE))
+        .expect(1)
+        .mount(&server)
+        .await;
+    let mut scraper = test_scraper(&server);
+
+    assert_eq!(scraper.run().await.unwrap().scraped, 1);
+
+    let requests = server.received_requests().await.unwrap();
+    let page = requests
+        .iter()
+        .find(|request| request.url.path() == "/pages/1")
+        .unwrap();
+    assert!(!page.headers.contains_key("authorization"));
+    let api = requests
+        .iter()
+        .find(|request| request.url.path() == "/providers/1/prices")
+        .unwrap();
+    assert!(!api.headers.contains_key("x-partner-key"));
+    server.verify().await;
+}
+
+#[tokio::test]
+async fn provider_api_keys_can_be_sent_as_query_parameters() {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path("/pages/1"))
+        .and(query_param("api_key", "partner-secret"))
+        .respond_with(ResponseTemplate::new(200).set_body_string(PROVIDER_PAGE))
+        .expect(1)
+        .mount(&server)
+        .await;
+    let mut provider = test_provider(&server);
+    provider["url"] = json!(format!("{}/pages/1?station=12", server.uri()));
+    provider["auth"] = json!({ "in": "query", "name": "api_key", "value": "partner-secret" });
+    let providers_file = std::env::temp_dir().join(format!(
+        "oliepriser-provider-auth-test-{}.json",
+        Uuid::new_v4()
+    ));
+    std::fs::write(&providers_file, json!([provider]).to_string()).unwrap();
+    let config = ScraperConfig {
+        providers_file: Some(providers_file.clone()),
+        ..ScraperConfig::default()
+    };
+    let mut scraper = Scraper::new(
+        vec![],
+        Credentials::new("".to_string(), "".to_string()),
+        config,
+    )
+    .unwrap();
+
+    assert_eq!(scraper.run().await.unwrap().scraped, 1);
+    let requests = server.received_requests().await.unwrap();
+    assert_eq!(
+        requests[0].url.query(),
+        Some("station=12&api_key=partner-secret")
+    );
+    server.verify().await;
+    std::fs::remove_file(&providers_file).unwrap();
+}
+
+#[tokio::test]
+async fn post_providers_submit_their_form() {
+    let server = MockServer::start().await;
+    let mut provider = test_provider(&server);
+    provider["method"] = json!("POST");
+    provider["form"] = json!({ "region": "east" });
+    mount_provider_api(&server, 1, provider).await;
+    Mock::given(method("POST"))
+        .and(path("/pages/1"))
+        .and(body_string("region=east"))
+        .respond_with(ResponseTemplate::new(200).set_body_string(PROVIDER_PAGE))
+        .expect(1)
+        .mount(&server)
+        .await;
+    Mock::given(method("POST"))
+        .and(path("/providers/1/prices"))
+        .and(body_json(json!({ "price": 12.49 })))
+        .respond_with(ResponseTemplate::new(201))
+        .expect(1)
+        .mount(&server)
+        .await;
+    Mock::given(method("POST"))
+        .and(path("/scraping_runs"))
+        .respond_with(ResponseTemplate::new(201))
+        .mount(&server)
+        .await;
+    let mut scraper = test_scraper(&server);
+
+    let report = scraper.run().await.unwrap();
+
+    assert_eq!(report.scraped, 1);
+    server.verify().await;
+}
+
+#[tokio::test]
+async fn run_appends_artifact_with_every_provider() {
+    let server = mock_api().await;
+    let output_json = std::env::temp_dir().join(format!(
+        "oliepriser-run-artifact-test-{}.jsonl",
+        Uuid::new_v4()
+    ));
+    let _ = std::fs::remove_file(&output_json);
+    let credentials = Credentials::new("client_id".to_string(), "client_secret".to_string());
+    let config = ScraperConfig {
+        output_json: Some(output_json.clone()),
+        ..ScraperConfig::default()
+    };
+    let mut scraper = Scraper::new(vec![server.uri()], credentials, config).unwrap();
+
+    let report = scraper.run().await.unwrap();
+
+    let output = std::fs::read_to_string(&output_json).unwrap();
+    let artifact: serde_json::Value = serde_json::from_str(output.trim()).unwrap();
+    assert_eq!(artifact["run_id"], json!(report.run_id));
+    assert_eq!(
+        artifact["prices"],
+        json!([{
+            "provider_id": 1,
+            "name": "Test provider",
+            "price": 12.49,
+            "currency": "DKK",
+            "status": "scraped",
+        }])
+    );
+    std::fs::remove_file(&output_json).unwrap();
+}
+
+#[tokio::test]
+async fn successful_run_pings_heartbeat_without_credentials() {
+    let server = mock_api().await;
+    Mock::given(method("GET"))
+        .and(path("/heartbeat"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&server)
+        .await;
+    let credentials = Credentials::new("client_id".to_string(), "client_secret".to_string());
+    let config = ScraperConfig {
+        heartbeat_url: Some(format!("{}/heartbeat", server.uri())),
+        heartbeat_fail_url: Some(format!("{}/heartbeat/fail", server.uri())),
+        ..ScraperConfig::default()
+    };
+    let mut scraper = Scraper::new(vec![server.uri()], credentials, config).unwrap();
+
+    scraper.run().await.unwrap();
+
+    let requests = server.received_requests().await.unwrap();
+    let ping = requests
+        .iter()
+        .find(|request| request.url.path() == "/heartbeat")
+        .unwrap();
+    assert!(!ping.headers.contains_key("authorization"));
+    server.verify().await;
+}
+
+#[tokio::test]
+async fn provider_timeout_overrides_the_global_timeout() {
+    let server = MockServer::start().await;
+    for page in ["/pages/1", "/pages/2"] {
+        Mock::given(method("GET"))
+            .and(path(page))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .set_body_string(PROVIDER_PAGE)
+                    .set_delay(std::time::Duration::from_millis(500)),
+            )
+            .mount(&server)
+            .await;
+    }
+
+    let providers_file = std::env::temp_dir().join(format!(
+        "oliepriser-timeout-providers-test-{}.json",
+        Uuid::new_v4()
+    ));
+    std::fs::write(
+        &providers_file,
+        json!([
+            {
+                "id": 1,
+                "name": "Slow provider",
+                "url": format!("{}/pages/1", server.uri()),
+                "html_element": ".price",
+                "timeout_secs": 5,
+            },
+            {
+                "id": 2,
+                "name": "Fast provider",
+                "url": format!("{}/pages/2", server.uri()),
+                "html_element": ".price",
+            },
+        ])
+        .to_string(),
+    )
+    .unwrap();
+
+    let config = ScraperConfig {
+        providers_file: Some(providers_file.clone()),
+        http: HttpConfig {
+            request_timeout: Some(std::time::Duration::from_millis(100)),
+            ..HttpConfig::default()
+        },
+        ..ScraperConfig::default()
+    };
+    let credentials = Credentials::new("".to_string(), "".to_string());
+    let mut scraper = Scraper::new(vec![], credentials, config).unwrap();
+
+    let report = scraper.run().await.unwrap();
+
+    assert_eq!(report.scraped, 1);
+    assert_eq!(report.failures.get(&FailureKind::Timeout), Some(&1));
+    std::fs::remove_file(&providers_file).unwrap();
+}
+
+#[tokio::test]
+async fn resolve_overrides_connect_hosts_to_fixed_addresses() {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path("/pages/1"))
+        .and(header(
+            "host",
+            format!("staging.provider.invalid:{}", server.address().port()),
+        ))
+        .respond_with(ResponseTemplate::new(200).set_body_string(PROVIDER_PAGE))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let providers_file = std::env::temp_dir().join(format!(
+        "oliepriser-resolve-providers-test-{}.json",
+        Uuid::new_v4()
+    ));
+    std::fs::write(
+        &providers_file,
+        json!([{
+            "id": 1,
+            "name": "Staging provider",
+            "url": format!("http://staging.provider.invalid:{}/pages/1", server.address().port()),
+            "html_element": ".price",
+        }])
+        .to_string(),
+    )
+    .unwrap();
+
+    let config = ScraperConfig {
+        providers_file: Some(providers_file.clone()),
+        http: HttpConfig {
+            resolve: vec!["staging.provider.invalid:127.0.0.1".parse().unwrap()],
+            ..HttpConfig::default()
+        },
+        ..ScraperConfig::default()
+    };
+    let credentials = Credentials::new("".to_string(), "".to_string());
+    let mut scraper = Scraper::new(vec![], credentials, config).unwrap();
+
+    assert_eq!(scraper.run().await.unwrap().scraped, 1);
+    assert!("staging.provider.invalid".parse::<HostOverride>().is_err());
+    assert!(":127.0.0.1".parse::<HostOverride>().is_err());
+    assert_eq!(
+        "staging.provider.invalid:[::1]"
+            .parse::<HostOverride>()
+            .unwrap()
+            .addr,
+        std::net::Ipv6Addr::LOCALHOST
+    );
+    server.verify().await;
+    std::fs::remove_file(&providers_file).unwrap();
+}
+
+#[tokio::test]
+async fn ip_version_and_local_address_shape_provider_connections() {
+    // Bound to IPv4 loopback, so only a client that connects over IPv4 reaches it
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path("/pages/1"))
+        .respond_with(ResponseTemplate::new(200).set_body_string(PROVIDER_PAGE))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let providers_file = std::env::temp_dir().join(format!(
+        "oliepriser-ip-version-providers-test-{}.json",
+        Uuid::new_v4()
+    ));
+    std::fs::write(
+        &providers_file,
+        json!([{
+            "id": 1,
+            "name": "Dual-stack provider",
+            "url": format!("http://localhost:{}/pages/1", server.address().port()),
+            "html_element": ".price",
+        }])
+        .to_string(),
+    )
+    .unwrap();
+
+    let config = ScraperConfig {
+        providers_file: Some(providers_file.clone()),
+        http: HttpConfig {
+            ip_version: IpVersion::V4,
+            local_address: Some(std::net::Ipv4Addr::LOCALHOST.into()),
+            ..HttpConfig::default()
+        },
+        ..ScraperConfig::default()
+    };
+    let credentials = Credentials::new("".to_string(), "".to_string());
+    let mut scraper = Scraper::new(vec![], credentials, config).unwrap();
+
+    assert_eq!(scraper.run().await.unwrap().scraped, 1);
+    server.verify().await;
+    std::fs::remove_file(&providers_file).unwrap();
+}
+
+#[tokio::test]
+async fn providers_are_sorted_by_id_unless_api_order_is_preserved() {
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(path("/auth/login"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "access_token": "access_token",
+            "token_type": "Bearer",
+        })))
+        .mount(&server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/scraping_runs/providers"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!([
+            { "id": 3 },
+            { "id": 1 },
+            { "id": 2 },
+        ])))
+        .mount(&server)
+        .await;
+    for id in 1..=3 {
+        Mock::given(method("GET"))
+            .and(path(format!("/providers/{}", id)))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "id": id,
+                "name": format!("Provider {}", id),
+                "url": format!("{}/pages/{}", server.uri(), id),
+                "html_element": ".price",
+            })))
+            .mount(&server)
+            .await;
+    }
+
+    let mut scraper = test_scraper(&server);
+    scraper.prepare_run().await.unwrap();
+    let ids: Vec<i32> = scraper
+        .providers
+        .iter()
+        .map(|provider| provider.id)
+        .collect();
+    assert_eq!(ids, vec![1, 2, 3]);
+
+    scraper.config.preserve_provider_order = true;
+    scraper.prepare_run().await.unwrap();
+    let ids: Vec<i32> = scraper
+        .providers
+        .iter()
+        .map(|provider| provider.id)
+        .collect();
+    assert_eq!(ids, vec![3, 1, 2]);
+}
+
+#[tokio::test]
+async fn login_providers_fetch_their_page_with_the_session_cookie() {
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(path("/login"))
+        .and(body_string("password=secret&username=member"))
+        .respond_with(ResponseTemplate::new(200).insert_header("set-cookie", "session=abc123"))
+        .expect(1)
+        .mount(&server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/pages/1"))
+        .and(header("cookie", "session=abc123"))
+        .respond_with(ResponseTemplate::new(200).set_body_string(PROVIDER_PAGE))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let providers_file = std::env::temp_dir().join(format!(
+        "oliepriser-login-providers-test-{}.json",
+        Uuid::new_v4()
+    ));
+    std::fs::write(
+        &providers_file,
+        json!([{
+            "id": 1,
+            "name": "Member provider",
+            "url": format!("{}/pages/1", server.uri()),
+            "html_element": ".price",
+            "login": {
+                "url": format!("{}/login", server.uri()),
+                "form": { "username": "member", "password": "secret" },
+            },
+        }])
+        .to_string(),
+    )
+    .unwrap();
+
+    let config = ScraperConfig {
+        providers_file: Some(providers_file.clone()),
+        ..ScraperConfig::default()
+    };
+    let credentials = Credentials::new("".to_string(), "".to_string());
+    let mut scraper = Scraper::new(vec![], credentials, config).unwrap();
+
+    let report = scraper.run().await.unwrap();
+
+    assert_eq!(report.scraped, 1);
+    server.verify().await;
+    std::fs::remove_file(&providers_file).unwrap();
+}
+
+#[tokio::test]
+async fn scrape_one_scrapes_and_posts_a_single_provider() {
+    let server = MockServer::start().await;
+    let provider = test_provider(&server);
+    Mock::given(method("POST"))
+        .and(path("/auth/login"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "access_token": "access_token",
+            "token_type": "Bearer",
+        })))
+        .mount(&server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/providers/1"))
+        .and(header("authorization", "Bearer access_token"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(provider))
+        .mount(&server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/pages/1"))
+        .respond_with(ResponseTemplate::new(200).set_body_string(PROVIDER_PAGE))
+        .mount(&server)
+        .await;
+    Mock::given(method("POST"))
+        .and(path("/providers/1/prices"))
+        .and(body_json(json!({ "price": 12.49 })))
+        .respond_with(ResponseTemplate::new(201))
+        .expect(1)
+        .mount(&server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/scraping_runs/providers"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&server)
+        .await;
+    let mut scraper = test_scraper(&server);
+
+    let scraped = scraper.scrape_one(1, false).await.unwrap();
+    assert_eq!(scraped.provider_id, 1);
+    assert_eq!(scraped.price, 12.49);
+    scraper.scrape_one(1, true).await.unwrap();
+
+    server.verify().await;
+}
+
+#[tokio::test]
+async fn pages_larger_than_the_response_limit_fail_the_provider() {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path("/pages/1"))
+        .respond_with(ResponseTemplate::new(200).set_body_string(PROVIDER_PAGE))
+        .mount(&server)
+        .await;
+
+    let providers_file = std::env::temp_dir().join(format!(
+        "oliepriser-large-providers-test-{}.json",
+        Uuid::new_v4()
+    ));
+    std::fs::write(
+        &providers_file,
+        json!([{
+            "id": 1,
+            "name": "Large provider",
+            "url": format!("{}/pages/1", server.uri()),
+            "html_element": ".price",
+        }])
+        .to_string(),
+    )
+    .unwrap();
+
+    // Traced pages are read through a separate path, which has to keep to the limit as well
+    for trace_requests in [false, true] {
+        let config = ScraperConfig {
+            providers_file: Some(providers_file.clone()),
+            max_response_bytes: 16,
+            trace_requests,
+            ..ScraperConfig::default()
+        };
+        let credentials = Credentials::new("".to_string(), "".to_string());
+        let mut scraper = Scraper::new(vec![], credentials, config).unwrap();
+
+        let report = scraper.run().await.unwrap();
+
+        assert_eq!(report.failures.get(&FailureKind::TooLarge), Some(&1));
+    }
+    std::fs::remove_file(&providers_file).unwrap();
+}
+
+#[tokio::test]
+async fn prices_are_posted_with_the_scrape_time_when_enabled() {
+    let server = MockServer::start().await;
+    mount_provider_api(&server, 1, test_provider(&server)).await;
+    Mock::given(method("GET"))
+        .and(path("/pages/1"))
+        .respond_with(ResponseTemplate::new(200).set_body_string(PROVIDER_PAGE))
+        .mount(&server)
+        .await;
+    Mock::given(method("POST"))
+        .and(path("/providers/1/prices"))
+        .and(body_partial_json(json!({ "price": 12.49 })))
+        .respond_with(ResponseTemplate::new(201))
+        .expect(1)
+        .mount(&server)
+        .await;
+    Mock::given(method("POST"))
+        .and(path("/scraping_runs"))
+        .respond_with(ResponseTemplate::new(201))
+        .mount(&server)
+        .await;
+    let credentials = Credentials::new("client_id".to_string(), "client_secret".to_string());
+    let config = ScraperConfig {
+        post_scraped_at: true,
+        ..ScraperConfig::default()
+    };
+    let mut scraper = Scraper::new(vec![server.uri()], credentials, config).unwrap();
+
+    scraper.run().await.unwrap();
+
+    let requests = server.received_requests().await.unwrap();
+    let post = requests
+        .iter()
+        .find(|request| request.url.path() == "/providers/1/prices")
+        .unwrap();
+    let body: serde_json::Value = serde_json::from_slice(&post.body).unwrap();
+    let timestamp: DateTime<chrono::Utc> =
+        serde_json::from_value(body["timestamp"].clone()).unwrap();
+    assert!(timestamp >= scraper.run_start && Some(timestamp) <= scraper.run_end);
+    server.verify().await;
+}
+
+#[tokio::test]
+async fn prices_are_posted_with_the_update_time_the_page_reports() {
+    let server = MockServer::start().await;
+    let mut provider = test_provider(&server);
+    provider["updated_at_selector"] = json!(".updated");
+    mount_provider_api(&server, 2, provider).await;
+    Mock::given(method("GET"))
+        .and(path("/pages/1"))
+        .respond_with(ResponseTemplate::new(200).set_body_string(
+            r#"<div class="price">12,49 kr.</div><time class="updated" datetime="2024-05-14T10:30:00Z">i dag</time>"#,
+        ))
+        .up_to_n_times(1)
+        .with_priority(1)
+        .mount(&server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/pages/1"))
+        .respond_with(ResponseTemplate::new(200).set_body_string(
+            r#"<div class="price">12,49 kr.</div><span class="updated">Opdateret i dag</span>"#,
+        ))
+        .mount(&server)
+        .await;
+    Mock::given(method("POST"))
+        .and(path("/providers/1/prices"))
+        .respond_with(ResponseTemplate::new(201))
+        .expect(2)
+        .mount(&server)
+        .await;
+    Mock::given(method("POST"))
+        .and(path("/scraping_runs"))
+        .respond_with(ResponseTemplate::new(201))
+        .mount(&server)
+        .await;
+    let credentials = Credentials::new("client_id".to_string(), "client_secret".to_string());
+    let mut scraper =
+        Scraper::new(vec![server.uri()], credentials, ScraperConfig::default()).unwrap();
+
+    scraper.run().await.unwrap();
+    scraper.run().await.unwrap();
+
+    let requests = server.received_requests().await.unwrap();
+    let timestamps: Vec<DateTime<chrono::Utc>> = requests
+        .iter()
+        .filter(|request| request.url.path() == "/providers/1/prices")
+        .map(|post| {
+            let body: serde_json::Value = serde_json::from_slice(&post.body).unwrap();
+            serde_json::from_value(body["timestamp"].clone()).unwrap()
+        })
+        .collect();
+    assert_eq!(timestamps[0].to_rfc3339(), "2024-05-14T10:30:00+00:00");
+    // The second page's update time can't be read, so the scrape time is posted instead
+    assert!(timestamps[1] >= scraper.run_start && Some(timestamps[1]) <= scraper.run_end);
+    server.verify().await;
+}
+
+#[tokio::test]
+async fn preflight_skips_pages_that_are_gone_or_not_html() {
+    let server = MockServer::start().await;
+    for (id, head) in [
+        (1, ResponseTemplate::new(404)),
+        (
+            2,
+            ResponseTemplate::new(200).insert_header("content-type", "application/pdf"),
+        ),
+        (3, ResponseTemplate::new(405)),
+    ] {
+        Mock::given(method("HEAD"))
+            .and(path(format!("/pages/{}", id)))
+            .respond_with(head)
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path(format!("/pages/{}", id)))
+            .respond_with(ResponseTemplate::new(200).set_body_string(PROVIDER_PAGE))
+            .expect(if id == 3 { 1 } else { 0 })
+            .mount(&server)
+            .await;
+    }
+
+    let providers: Vec<serde_json::Value> = (1..=3)
+        .map(|id| {
+            json!({
+                "id": id,
+                "name": format!("Provider {}", id),
+                "url": format!("{}/pages/{}", server.uri(), id),
+                "html_element": ".price",
+            })
+        })
+        .collect();
+    let providers_file = std::env::temp_dir().join(format!(
+        "oliepriser-preflight-providers-test-{}.json",
+        Uuid::new_v4()
+    ));
+    std::fs::write(&providers_file, json!(providers).to_string()).unwrap();
+
+    let config = ScraperConfig {
+        providers_file: Some(providers_file.clone()),
+        preflight: true,
+        ..ScraperConfig::default()
+    };
+    let credentials = Credentials::new("".to_string(), "".to_string());
+    let mut scraper = Scraper::new(vec![], credentials, config).unwrap();
+
+    let report = scraper.run().await.unwrap();
+
+    assert_eq!(report.scraped, 1);
+    assert_eq!(report.failures.get(&FailureKind::Http4xx), Some(&1));
+    assert_eq!(report.failures.get(&FailureKind::ContentType), Some(&1));
+    server.verify().await;
+    std::fs::remove_file(&providers_file).unwrap();
+}
+
+#[tokio::test]
+async fn verify_skips_prices_that_change_on_a_second_fetch() {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path("/pages/1"))
+        .respond_with(ResponseTemplate::new(200).set_body_string(PROVIDER_PAGE))
+        .expect(2)
+        .mount(&server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/pages/2"))
+        .respond_with(ResponseTemplate::new(200).set_body_string(PROVIDER_PAGE))
+        .up_to_n_times(1)
+        .with_priority(1)
+        .mount(&server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/pages/2"))
+        .respond_with(
+            ResponseTemplate::new(200).set_body_string(r#"<div class="price">9,99 kr.</div>"#),
+        )
+        .mount(&server)
+        .await;
+
+    let providers: Vec<serde_json::Value> = (1..=2)
+        .map(|id| {
+            json!({
+                "id": id,
+                "name": format!("Provider {}", id),
+                "url": format!("{}/pages/{}", server.uri(), id),
+                "html_element": ".price",
+            })
+        })
+        .collect();
+    let providers_file = std::env::temp_dir().join(format!(
+        "oliepriser-verify-providers-test-{}.json",
+        Uuid::new_v4()
+    ));
+    std::fs::write(&providers_file, json!(providers).to_string()).unwrap();
+
+    let config = ScraperConfig {
+        providers_file: Some(providers_file.clone()),
+        verify: true,
+        ..ScraperConfig::default()
+    };
+    let credentials = Credentials::new("".to_string(), "".to_string());
+    let mut scraper = Scraper::new(vec![], credentials, config).unwrap();
+
+    let report = scraper.run().await.unwrap();
+
+    assert_eq!(report.scraped, 1);
+    assert_eq!(report.failures.get(&FailureKind::Unstable), Some(&1));
+    server.verify().await;
+    std::fs::remove_file(&providers_file).unwrap();
+}
+
+#[tokio::test]
+async fn traced_runs_behave_like_untraced_runs() {
+    let server = mock_api().await;
+    let credentials = Credentials::new("client_id".to_string(), "client_secret".to_string());
+    let config = ScraperConfig {
+        trace_requests: true,
+        ..ScraperConfig::default()
+    };
+    let mut scraper = Scraper::new(vec![server.uri()], credentials, config).unwrap();
+
+    let report = scraper.run().await.unwrap();
+
+    assert_eq!(report.scraped, 1);
+    server.verify().await;
+}
+
+#[tokio::test]
+async fn api_paths_can_be_overridden() {
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(path("/auth/login"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "access_token": "access_token",
+            "token_type": "Bearer",
+        })))
+        .mount(&server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/v2/providers"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!([{ "id": 1 }])))
+        .expect(1)
+        .mount(&server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/v2/providers/1/details"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(test_provider(&server)))
+        .expect(1)
+        .mount(&server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/pages/1"))
+        .respond_with(ResponseTemplate::new(200).set_body_string(PROVIDER_PAGE))
+        .mount(&server)
+        .await;
+    Mock::given(method("POST"))
+        .and(path("/v2/prices/1"))
+        .and(body_json(json!({ "price": 12.49 })))
+        .respond_with(ResponseTemplate::new(201))
+        .expect(1)
+        .mount(&server)
+        .await;
+    Mock::given(method("POST"))
+        .and(path("/v2/runs"))
+        .respond_with(ResponseTemplate::new(201))
+        .expect(1)
+        .mount(&server)
+        .await;
+    let credentials = Credentials::new("client_id".to_string(), "client_secret".to_string());
+    let config = ScraperConfig {
+        paths: ApiPaths {
+            providers: "/v2/providers".to_string(),
+            provider_detail: "/v2/providers/{id}/details".to_string(),
+            prices: "/v2/prices/{id}".to_string(),
+            bulk_prices: "/v2/prices".to_string(),
+            runs: "/v2/runs".to_string(),
+        },
+        ..ScraperConfig::default()
+    };
+    let mut scraper = Scraper::new(vec![server.uri()], credentials, config).unwrap();
+
+    let report = scraper.run().await.unwrap();
+
+    assert_eq!(report.scraped, 1);
+    server.verify().await;
+}
+
+#[tokio::test]
+async fn unreachable_providers_are_reported_without_aborting_the_run() {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path("/pages/1"))
+        .respond_with(ResponseTemplate::new(200).set_body_string(PROVIDER_PAGE))
+        .mount(&server)
+        .await;
+
+    let providers_file = std::env::temp_dir().join(format!(
+        "oliepriser-unreachable-providers-test-{}.json",
+        Uuid::new_v4()
+    ));
+    std::fs::write(
+        &providers_file,
+        json!([
+            {
+                "id": 1,
+                "name": "Reachable provider",
+                "url": format!("{}/pages/1", server.uri()),
+                "html_element": ".price",
+            },
+            {
+                // Nothing listens on port 1, so the connection is refused
+                "id": 2,
+                "name": "Unreachable provider",
+                "url": "http://127.0.0.1:1/pages/2",
+                "html_element": ".price",
+            },
+            {
+                "id": 3,
+                "name": "Expired domain",
+                "url": "http://oliepriser-expired.invalid/pages/3",
+                "html_element": ".price",
+            },
+        ])
+        .to_string(),
+    )
+    .unwrap();
+
+    let config = ScraperConfig {
+        providers_file: Some(providers_file.clone()),
+        ..ScraperConfig::default()
+    };
+    let credentials = Credentials::new("".to_string(), "".to_string());
+    let mut scraper = Scraper::new(vec![], credentials, config).unwrap();
+
+    let report = scraper.run().await.unwrap();
+
+    assert_eq!(report.scraped, 1);
+    assert_eq!(report.failures.get(&FailureKind::Connection), Some(&2));
+    std::fs::remove_file(&providers_file).unwrap();
+}
+
+#[tokio::test]
+async fn significant_price_changes_post_an_alert() {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path("/pages/1"))
+        .respond_with(
+            ResponseTemplate::new(200).set_body_string(r#"<div class="price">10,00 kr.</div>"#),
+        )
+        .up_to_n_times(1)
+        .with_priority(1)
+        .mount(&server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/pages/1"))
+        .respond_with(
+            ResponseTemplate::new(200).set_body_string(r#"<div class="price">12,00 kr.</div>"#),
+        )
+        .mount(&server)
+        .await;
+    Mock::given(method("POST"))
+        .and(path("/alerts"))
+        .and(body_json(json!({
+            "provider_id": 1,
+            "provider_name": "Volatile provider",
+            "old_price": 10.0,
+            "new_price": 12.0,
+            "change_pct": 20.0,
+        })))
+        .respond_with(ResponseTemplate::new(500))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let providers_file = std::env::temp_dir().join(format!(
+        "oliepriser-alert-providers-test-{}.json",
+        Uuid::new_v4()
+    ));
+    std::fs::write(
+        &providers_file,
+        json!([{
+            "id": 1,
+            "name": "Volatile provider",
+            "url": format!("{}/pages/1", server.uri()),
+            "html_element": ".price",
+        }])
+        .to_string(),
+    )
+    .unwrap();
+
+    let config = ScraperConfig {
+        providers_file: Some(providers_file.clone()),
+        alert_webhook: Some(format!("{}/alerts", server.uri())),
+        ..ScraperConfig::default()
+    };
+    let credentials = Credentials::new("".to_string(), "".to_string());
+    let mut scraper = Scraper::new(vec![], credentials, config).unwrap();
+
+    // The failing webhook doesn't fail the run, and an unchanged price doesn't alert again
+    for _ in 0..3 {
+        assert_eq!(scraper.run().await.unwrap().scraped, 1);
+    }
+    server.verify().await;
+    std::fs::remove_file(&providers_file).unwrap();
+}
+
+#[test]
+fn capped_providers_rotate_through_every_provider() {
+    let mut scraper = offline_scraper();
+    scraper.config.max_providers = Some(2);
+    let providers: Vec<Provider> = (1..=5)
+        .map(|id| {
+            serde_json::from_value(json!({
+                "id": id,
+                "name": format!("Provider {}", id),
+                "url": "http://localhost",
+            }))
+            .unwrap()
+        })
+        .collect();
+    let capped_ids = |scraper: &mut Scraper| {
+        scraper.providers = providers.clone();
+        scraper.cap_providers();
+        scraper
+            .providers
+            .iter()
+            .map(|provider| provider.id)
+            .collect::<Vec<_>>()
+    };
+
+    assert_eq!(capped_ids(&mut scraper), vec![1, 2]);
+    assert_eq!(capped_ids(&mut scraper), vec![1, 2]);
+
+    scraper.config.rotate_providers = true;
+    assert_eq!(capped_ids(&mut scraper), vec![1, 2]);
+    assert_eq!(capped_ids(&mut scraper), vec![3, 4]);
+    assert_eq!(capped_ids(&mut scraper), vec![5, 1]);
+}
+
+#[test]
+fn groups_select_providers_and_set_their_poll_interval() {
+    let mut scraper = offline_scraper();
+    let providers: Vec<Provider> = [(1, Some("north")), (2, Some("south")), (3, None)]
+        .into_iter()
+        .map(|(id, group)| {
+            serde_json::from_value(json!({
+                "id": id,
+                "name": format!("Provider {}", id),
+                "url": "http://localhost",
+                "group": group,
+                "poll_interval_secs": 60,
+            }))
+            .unwrap()
+        })
+        .collect();
+
+    scraper.providers = providers.clone();
+    scraper.select_groups();
+    assert_eq!(scraper.providers.len(), 3);
+
+    scraper.config.groups = vec!["north".to_string()];
+    scraper.select_groups();
+    assert_eq!(scraper.providers.len(), 1);
+    assert_eq!(scraper.providers[0].id, 1);
+
+    let one_hour = std::time::Duration::from_secs(3600);
+    scraper.config.group_intervals = HashMap::from([("south".to_string(), one_hour)]);
+    assert_eq!(scraper.poll_interval(&providers[0]), None);
+    assert_eq!(scraper.poll_interval(&providers[1]), Some(one_hour));
+    scraper.config.per_provider_intervals = true;
+    assert_eq!(
+        scraper.poll_interval(&providers[1]),
+        Some(std::time::Duration::from_secs(60))
+    );
+
+    scraper.config.per_provider_intervals = false;
+    scraper
+        .last_scraped
+        .lock()
+        .unwrap()
+        .insert(2, chrono::Utc::now() - TimeDelta::minutes(30));
+    assert!(!scraper.is_due(&providers[1]));
+    assert!(scraper.is_due(&providers[2]));
+}
+
+#[test]
+fn shuffled_providers_follow_the_seed() {
+    let providers: Vec<Provider> = (1..=20)
+        .map(|id| {
+            serde_json::from_value(json!({
+                "id": id,
+                "name": format!("Provider {}", id),
+                "url": "http://localhost",
+            }))
+            .unwrap()
+        })
+        .collect();
+    let shuffled_ids = |scraper: &mut Scraper| {
+        scraper.providers = providers.clone();
+        scraper.shuffle_providers();
+        scraper
+            .providers
+            .iter()
+            .map(|provider| provider.id)
+            .collect::<Vec<_>>()
+    };
+    let seeded_scraper = || {
+        let config = ScraperConfig {
+            shuffle_providers: true,
+            shuffle_seed: Some(42),
+            ..ScraperConfig::default()
+        };
+        Scraper::new(
+            vec![],
+            Credentials::new("".to_string(), "".to_string()),
+            config,
+        )
+        .unwrap()
+    };
+
+    let mut scraper = seeded_scraper();
+    let first = shuffled_ids(&mut scraper);
+    let second = shuffled_ids(&mut scraper);
+    assert_ne!(first, (1..=20).collect::<Vec<_>>());
+    assert_ne!(first, second);
+    let mut sorted = first.clone();
+    sorted.sort();
+    assert_eq!(sorted, (1..=20).collect::<Vec<_>>());
+
+    let mut replay = seeded_scraper();
+    assert_eq!(shuffled_ids(&mut replay), first);
+    assert_eq!(shuffled_ids(&mut replay), second);
+
+    let mut unshuffled = offline_scraper();
+    assert_eq!(shuffled_ids(&mut unshuffled), (1..=20).collect::<Vec<_>>());
+}
+
+#[tokio::test]
+async fn reconcile_reports_prices_differing_from_the_stored_price() {
+    let server = MockServer::start().await;
+    mount_backend(&server, 1).await;
+    Mock::given(method("GET"))
+        .and(path("/pages/1"))
+        .respond_with(ResponseTemplate::new(200).set_body_string(PROVIDER_PAGE))
+        .mount(&server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/providers/1/prices"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!([
+            { "price": 11.99 },
+            { "price": 12.49 },
+        ])))
+        .expect(1)
+        .mount(&server)
+        .await;
+    let credentials = Credentials::new("client_id".to_string(), "client_secret".to_string());
+    let config = ScraperConfig {
+        reconcile: true,
+        ..ScraperConfig::default()
+    };
+    let mut scraper = Scraper::new(vec![server.uri()], credentials, config).unwrap();
+
+    let report = scraper.run().await.unwrap();
+
+    assert_eq!(report.discrepancies, 1);
+    assert_eq!(report.records[0].stored_price, Some(11.99));
+    let requests = server.received_requests().await.unwrap();
+    let position = |method: &str| {
+        requests
+            .iter()
+            .position(|request| {
+                request.method.as_str() == method && request.url.path() == "/providers/1/prices"
+            })
+            .unwrap()
+    };
+    assert_eq!(requests[position("GET")].url.query(), Some("latest"));
+    // The stored price is looked up before the scraped price is posted over it
+    assert!(position("GET") < position("POST"));
+    server.verify().await;
+}
+
+#[tokio::test]
+async fn warmup_runs_scrape_without_posting() {
+    let server = MockServer::start().await;
+    mount_provider_api(&server, 2, test_provider(&server)).await;
+    Mock::given(method("GET"))
+        .and(path("/pages/1"))
+        .respond_with(ResponseTemplate::new(200).set_body_string(PROVIDER_PAGE))
+        .expect(2)
+        .mount(&server)
+        .await;
+    // Only the live run posts its price and run
+    Mock::given(method("POST"))
+        .and(path("/providers/1/prices"))
+        .respond_with(ResponseTemplate::new(201))
+        .expect(1)
+        .mount(&server)
+        .await;
+    Mock::given(method("POST"))
+        .and(path("/scraping_runs"))
+        .respond_with(ResponseTemplate::new(201))
+        .expect(1)
+        .mount(&server)
+        .await;
+    let credentials = Credentials::new("client_id".to_string(), "client_secret".to_string());
+    let config = ScraperConfig {
+        warmup_runs: 1,
+        ..ScraperConfig::default()
+    };
+    let mut scraper = Scraper::new(vec![server.uri()], credentials, config).unwrap();
+
+    assert_eq!(scraper.run().await.unwrap().scraped, 1);
+    assert_eq!(scraper.run().await.unwrap().scraped, 1);
+
+    server.verify().await;
+}
+
+#[tokio::test]
+async fn posts_are_signed_when_a_signing_secret_is_set() {
+    let server = mock_api().await;
+    let credentials = Credentials::new("client_id".to_string(), "client_secret".to_string());
+    let config = ScraperConfig {
+        signing_secret: Some("secret".to_string()),
+        ..ScraperConfig::default()
+    };
+    let mut scraper = Scraper::new(vec![server.uri()], credentials, config).unwrap();
+
+    scraper.run().await.unwrap();
+
+    let requests = server.received_requests().await.unwrap();
+    let posts: Vec<_> = requests
+        .iter()
+        .filter(|request| ["/providers/1/prices", "/scraping_runs"].contains(&request.url.path()))
+        .collect();
+    assert_eq!(posts.len(), 2);
+    for post in posts {
+        let timestamp: i64 = post.headers["x-timestamp"]
+            .to_str()
+            .unwrap()
+            .parse()
+            .unwrap();
+        assert_eq!(
+            post.headers["x-signature"].to_str().unwrap(),
+            crate::signing::sign("secret", timestamp, &post.body)
+        );
+    }
+    server.verify().await;
+}
+
+#[tokio::test]
+async fn validate_schema_names_the_field_that_differs() {
+    let validate = |provider: serde_json::Value, run: serde_json::Value| async move {
+        let server = MockServer::start().await;
+        mount_provider_api(&server, 1, provider).await;
+        Mock::given(method("GET"))
+            .and(path("/scraping_runs"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(run))
+            .mount(&server)
+            .await;
+        test_scraper(&server)
+            .validate_schema()
+            .await
+            .map_err(|e| e.to_string())
+    };
+    let run = json!([{
+        "start_time": "2024-05-14T10:00:00Z",
+        "end_time": "2024-05-14T10:01:00Z",
+    }]);
+    let provider = json!({
+        "id": 1,
+        "name": "Test provider",
+        "url": "http://localhost/pages/1",
+        "html_element": ".price",
+    });
+    assert!(validate(provider.clone(), run.clone()).await.is_ok());
+
+    let mut missing = provider.clone();
+    missing.as_object_mut().unwrap().remove("url");
+    let error = validate(missing, run.clone()).await.unwrap_err();
+    assert!(
+        error.contains("unexpected provider shape: missing field `url`"),
+        "{}",
+        error
+    );
+
+    let mut retyped = provider.clone();
+    retyped["enabled"] = json!("yes");
+    let error = validate(retyped, run).await.unwrap_err();
+    assert!(
+        error.contains("unexpected provider shape: field `enabled`: invalid type"),
+        "{}",
+        error
+    );
+
+    let error = validate(provider, json!({ "end_time": 5 }))
+        .await
+        .unwrap_err();
+    assert!(
+        error.contains("unexpected run shape: field `end_time`: invalid type"),
+        "{}",
+        error
+    );
+}
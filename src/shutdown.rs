@@ -0,0 +1,108 @@
+use std::sync::Arc;
+use tokio::sync::watch;
+
+///
+/// A request to stop the scraper, set by SIGINT or SIGTERM once the handlers are installed
+/// A run that is interrupted skips the providers it hasn't started on, delivers the prices it already scraped and
+/// posts its summary with the interrupted status before the scrape loop exits
+///
+/// # Fields
+///
+/// - requested: Arc<watch::Sender<bool>> - Whether shutdown was requested, shared by every clone and waited on by `wait`
+#[derive(Clone, Debug)]
+pub struct Shutdown {
+    requested: Arc<watch::Sender<bool>>,
+}
+
+impl Default for Shutdown {
+    fn default() -> Self {
+        Self {
+            requested: Arc::new(watch::Sender::new(false)),
+        }
+    }
+}
+
+impl Shutdown {
+    ///
+    /// A shutdown that is only requested by calling `request`, e.g. in tests or when embedding the scraper
+    ///
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    ///
+    /// A shutdown requested by SIGINT or SIGTERM, or by Ctrl-C where there are no Unix signals, e.g. on Windows
+    /// A second signal exits right away, for when flushing the run hangs
+    ///
+    /// # Returns
+    ///
+    /// Result<Shutdown, std::io::Error> - The shutdown the signals request
+    ///
+    /// # Errors
+    ///
+    /// If the signal handlers can't be installed, an error is returned
+    ///
+    pub fn on_signals() -> Result<Self, std::io::Error> {
+        let shutdown = Self::new();
+        #[cfg(unix)]
+        {
+            use tokio::signal::unix::{signal, SignalKind};
+
+            let mut interrupt = signal(SignalKind::interrupt())?;
+            let mut terminate = signal(SignalKind::terminate())?;
+            let requested = shutdown.clone();
+            tokio::spawn(async move {
+                loop {
+                    tokio::select! {
+                        _ = interrupt.recv() => {}
+                        _ = terminate.recv() => {}
+                    }
+                    requested.signalled();
+                }
+            });
+        }
+        #[cfg(not(unix))]
+        {
+            let requested = shutdown.clone();
+            tokio::spawn(async move {
+                while tokio::signal::ctrl_c().await.is_ok() {
+                    requested.signalled();
+                }
+            });
+        }
+        Ok(shutdown)
+    }
+
+    ///
+    /// Request shutdown on the first signal and exit on the second
+    ///
+    fn signalled(&self) {
+        if self.requested() {
+            std::process::exit(130);
+        }
+        self.request();
+    }
+
+    ///
+    /// Request shutdown, like a signal would
+    ///
+    pub fn request(&self) {
+        self.requested.send_replace(true);
+    }
+
+    ///
+    /// Whether shutdown was requested
+    ///
+    pub fn requested(&self) -> bool {
+        *self.requested.borrow()
+    }
+
+    ///
+    /// Wait until shutdown is requested
+    ///
+    pub async fn wait(&self) {
+        let mut requested = self.requested.subscribe();
+        // The sender lives as long as `self`, so waiting only ends once shutdown is requested
+        let _ = requested.wait_for(|requested| *requested).await;
+    }
+}
@@ -0,0 +1,155 @@
+use chrono::{DateTime, Utc};
+use std::fs;
+use std::io::Error;
+use std::path::{Path, PathBuf};
+
+/// Timestamp format used in snapshot file names, sortable and safe on every filesystem
+const TIMESTAMP_FORMAT: &str = "%Y%m%dT%H%M%S%.3fZ";
+
+///
+/// Saves provider pages that failed to yield a price, for debugging selector drift
+///
+/// # Fields
+///
+/// - dir: PathBuf - The directory to save snapshots in
+/// - max_bytes: usize - Maximum size of a single snapshot, larger pages are truncated
+/// - max_files: usize - Maximum number of snapshots kept, the oldest are removed first
+#[derive(Clone, Debug)]
+pub struct SnapshotDir {
+    pub dir: PathBuf,
+    pub max_bytes: usize,
+    pub max_files: usize,
+}
+
+impl SnapshotDir {
+    ///
+    /// Save a snapshot of a provider page and rotate out the oldest snapshots
+    ///
+    /// # Arguments
+    ///
+    /// - provider_id: i32 - The ID of the provider
+    /// - scraped_at: DateTime<Utc> - When the page was fetched
+    /// - html: &str - The page HTML
+    ///
+    /// # Returns
+    ///
+    /// Result<PathBuf, Error> - The path of the saved snapshot
+    ///
+    /// # Errors
+    ///
+    /// If the directory or file cannot be written, an error is returned
+    ///
+    pub(crate) fn save(
+        &self,
+        provider_id: i32,
+        scraped_at: DateTime<Utc>,
+        html: &str,
+    ) -> Result<PathBuf, Error> {
+        fs::create_dir_all(&self.dir)?;
+        let path = self.dir.join(format!(
+            "provider-{}-{}.html",
+            provider_id,
+            scraped_at.format(TIMESTAMP_FORMAT)
+        ));
+
+        let mut end = html.len().min(self.max_bytes);
+        while !html.is_char_boundary(end) {
+            end -= 1;
+        }
+        fs::write(&path, &html[..end])?;
+
+        self.rotate()?;
+        Ok(path)
+    }
+
+    ///
+    /// Remove the oldest snapshots until at most `max_files` remain
+    ///
+    fn rotate(&self) -> Result<(), Error> {
+        let mut snapshots = self.list(None)?;
+        if snapshots.len() <= self.max_files {
+            return Ok(());
+        }
+
+        snapshots.sort_by_key(|(_, _, scraped_at)| *scraped_at);
+        let excess = snapshots.len() - self.max_files;
+        for (path, _, _) in snapshots.into_iter().take(excess) {
+            fs::remove_file(path)?;
+        }
+        Ok(())
+    }
+
+    ///
+    /// List the snapshots in the directory, optionally only those of one provider
+    ///
+    /// # Arguments
+    ///
+    /// - provider_id: Option<i32> - Only list snapshots of this provider
+    ///
+    /// # Returns
+    ///
+    /// Result<Vec<(PathBuf, i32, DateTime<Utc>)>, Error> - The path, provider ID and timestamp of each snapshot
+    ///
+    pub(crate) fn list(
+        &self,
+        provider_id: Option<i32>,
+    ) -> Result<Vec<(PathBuf, i32, DateTime<Utc>)>, Error> {
+        let mut snapshots = vec![];
+        for entry in fs::read_dir(&self.dir)? {
+            let path = entry?.path();
+            if let Some((id, scraped_at)) = parse_snapshot_name(&path) {
+                if provider_id.is_none_or(|provider_id| provider_id == id) {
+                    snapshots.push((path, id, scraped_at));
+                }
+            }
+        }
+        Ok(snapshots)
+    }
+}
+
+///
+/// Parse the provider ID and timestamp out of a snapshot file name
+///
+/// # Arguments
+///
+/// - path: &Path - The snapshot path
+///
+/// # Returns
+///
+/// Option<(i32, DateTime<Utc>)> - The provider ID and timestamp, or None if the file isn't a snapshot
+///
+fn parse_snapshot_name(path: &Path) -> Option<(i32, DateTime<Utc>)> {
+    let name = path.file_name()?.to_str()?;
+    let rest = name.strip_prefix("provider-")?.strip_suffix(".html")?;
+    let (id, timestamp) = rest.split_once('-')?;
+    let scraped_at = chrono::NaiveDateTime::parse_from_str(timestamp, TIMESTAMP_FORMAT).ok()?;
+    Some((id.parse().ok()?, scraped_at.and_utc()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use uuid::Uuid;
+
+    #[test]
+    fn save_truncates_and_rotates_snapshots() {
+        let snapshots = SnapshotDir {
+            dir: std::env::temp_dir().join(format!("oliepriser-snapshot-test-{}", Uuid::new_v4())),
+            max_bytes: 4,
+            max_files: 2,
+        };
+        let start = Utc::now();
+
+        for offset in 0..3 {
+            let scraped_at = start + chrono::TimeDelta::seconds(offset);
+            snapshots.save(7, scraped_at, "<html></html>").unwrap();
+        }
+
+        let mut saved = snapshots.list(Some(7)).unwrap();
+        saved.sort_by_key(|(_, _, scraped_at)| *scraped_at);
+        assert_eq!(saved.len(), 2);
+        assert!(saved[0].2 > start);
+        assert_eq!(fs::read_to_string(&saved[0].0).unwrap(), "<htm");
+        fs::remove_dir_all(&snapshots.dir).unwrap();
+    }
+}
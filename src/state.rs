@@ -0,0 +1,140 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::io::ErrorKind;
+use std::path::PathBuf;
+
+use crate::breaker::CircuitBreaker;
+use crate::cache::CachedPage;
+use crate::error::ScraperError;
+
+///
+/// Per-provider state the scraper keeps between runs, all keyed by provider ID
+///
+/// # Fields
+///
+/// - pages: HashMap<i32, CachedPage> - Cache validators and last price of each provider page
+/// - last_scraped: HashMap<i32, DateTime<Utc>> - When each provider was last scraped
+/// - breakers: HashMap<i32, CircuitBreaker> - Circuit breaker state of each provider
+/// - last_prices: HashMap<i32, f64> - The last price scraped for each provider
+/// - last_posted: HashMap<i32, DateTime<Utc>> - When a price of each provider was last posted
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct ScraperState {
+    #[serde(default)]
+    pub(crate) pages: HashMap<i32, CachedPage>,
+    #[serde(default)]
+    pub(crate) last_scraped: HashMap<i32, DateTime<Utc>>,
+    #[serde(default)]
+    pub(crate) breakers: HashMap<i32, CircuitBreaker>,
+    #[serde(default)]
+    pub(crate) last_prices: HashMap<i32, f64>,
+    #[serde(default)]
+    pub(crate) last_posted: HashMap<i32, DateTime<Utc>>,
+}
+
+///
+/// Storage the scraper state is loaded from at startup and saved to after every run
+///
+pub trait StateStore: Send + Sync {
+    ///
+    /// Load the saved state
+    ///
+    /// # Returns
+    ///
+    /// Result<Option<ScraperState>, ScraperError> - The saved state, or None if nothing was saved yet
+    ///
+    /// # Errors
+    ///
+    /// If the state can't be read or is invalid, an error is returned
+    ///
+    fn load(&self) -> Result<Option<ScraperState>, ScraperError>;
+
+    ///
+    /// Save the state, replacing the previously saved state
+    ///
+    /// # Arguments
+    ///
+    /// - state: &ScraperState - The state to save
+    ///
+    /// # Errors
+    ///
+    /// If the state can't be written, an error is returned
+    ///
+    fn save(&self, state: &ScraperState) -> Result<(), ScraperError>;
+}
+
+///
+/// State store keeping the state in a JSON file
+/// The file is written next to its final path first and renamed into place, so a crash never leaves half a file
+///
+/// # Fields
+///
+/// - path: PathBuf - The path of the JSON file
+#[derive(Clone, Debug)]
+pub struct StateFile {
+    path: PathBuf,
+}
+
+impl StateFile {
+    pub fn new(path: PathBuf) -> Self {
+        Self { path }
+    }
+}
+
+impl StateStore for StateFile {
+    fn load(&self) -> Result<Option<ScraperState>, ScraperError> {
+        let json = match fs::read_to_string(&self.path) {
+            Ok(json) => json,
+            Err(e) if e.kind() == ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e.into()),
+        };
+        serde_json::from_str(&json).map(Some).map_err(|e| {
+            ScraperError::Config(format!("Invalid state file {}: {}", self.path.display(), e))
+        })
+    }
+
+    fn save(&self, state: &ScraperState) -> Result<(), ScraperError> {
+        let json = serde_json::to_string(state).map_err(std::io::Error::other)?;
+        let mut temp_path = self.path.clone().into_os_string();
+        temp_path.push(".tmp");
+        fs::write(&temp_path, json)?;
+        fs::rename(&temp_path, &self.path)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use uuid::Uuid;
+
+    #[test]
+    fn state_file_round_trips_and_starts_empty() {
+        let path =
+            std::env::temp_dir().join(format!("oliepriser-state-test-{}.json", Uuid::new_v4()));
+        let store = StateFile::new(path.clone());
+
+        assert!(store.load().unwrap().is_none());
+
+        let mut state = ScraperState::default();
+        state.last_prices.insert(1, 12.49);
+        state.pages.insert(
+            1,
+            CachedPage {
+                etag: Some("\"v1\"".to_string()),
+                last_modified: None,
+                price: Some(12.49),
+            },
+        );
+        store.save(&state).unwrap();
+
+        let loaded = store.load().unwrap().unwrap();
+        assert_eq!(loaded.last_prices, state.last_prices);
+        assert_eq!(loaded.pages, state.pages);
+
+        fs::write(&path, "{").unwrap();
+        assert!(matches!(store.load(), Err(ScraperError::Config(_))));
+        fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,122 @@
+use cadence::prelude::*;
+use cadence::{MetricError, StatsdClient, UdpMetricSink};
+use std::net::{SocketAddr, ToSocketAddrs, UdpSocket};
+use std::time::Duration;
+
+use crate::error::ScraperError;
+use crate::report::RunReport;
+
+/// Prefix of every metric name
+const METRIC_PREFIX: &str = "oliepriser";
+
+///
+/// Send the metrics of a run to a StatsD or DogStatsD endpoint
+/// Metrics are sent over UDP without waiting for a reply, so an unreachable endpoint never blocks the scraping loop
+/// Failure kinds are part of the metric name rather than tags, so plain StatsD servers understand them too
+///
+/// # Arguments
+///
+/// - addr: &str - The StatsD endpoint, e.g. `localhost:8125`
+/// - result: &Result<RunReport, ScraperError> - The result of the run
+/// - duration: Option<Duration> - How long the run took, not sent when None
+///
+/// # Returns
+///
+/// Result<(), MetricError> - The result of sending the metrics
+///
+/// # Errors
+///
+/// If the address can't be resolved or the socket can't be opened, an error is returned
+///
+pub(crate) fn send_run_metrics(
+    addr: &str,
+    result: &Result<RunReport, ScraperError>,
+    duration: Option<Duration>,
+) -> Result<(), MetricError> {
+    let client = connect(addr)?;
+
+    let status = if result.is_ok() { "ok" } else { "failed" };
+    client.count(&format!("runs.{}", status), 1)?;
+    if let Some(duration) = duration {
+        client.time("run.duration", duration)?;
+    }
+
+    let Ok(report) = result else {
+        return Ok(());
+    };
+    client.count("prices.scraped", report.scraped as i64)?;
+    client.count("providers.unchanged", report.unchanged as i64)?;
+    client.count("providers.skipped", report.skipped as i64)?;
+    client.count("providers.failed", report.failed as i64)?;
+    for (kind, count) in &report.failures {
+        // The serialized name, e.g. `http_5xx`, is a valid metric name unlike the display name
+        let kind = serde_json::to_value(kind)
+            .ok()
+            .and_then(|kind| kind.as_str().map(str::to_string))
+            .unwrap_or_else(|| "unknown".to_string());
+        client.count(&format!("providers.failed.{}", kind), *count as i64)?;
+    }
+    Ok(())
+}
+
+fn connect(addr: &str) -> Result<StatsdClient, MetricError> {
+    let target: SocketAddr = addr.to_socket_addrs()?.next().ok_or_else(|| {
+        MetricError::from((
+            cadence::ErrorKind::InvalidInput,
+            "address resolved to nothing",
+        ))
+    })?;
+    let local: SocketAddr = if target.is_ipv6() {
+        "[::]:0".parse().unwrap()
+    } else {
+        "0.0.0.0:0".parse().unwrap()
+    };
+    let socket = UdpSocket::bind(local)?;
+    socket.set_nonblocking(true)?;
+    let sink = UdpMetricSink::from(target, socket)?;
+    Ok(StatsdClient::from_sink(METRIC_PREFIX, sink))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::report::FailureKind;
+
+    #[test]
+    fn send_run_metrics_emits_counters_and_timers() {
+        let server = UdpSocket::bind("127.0.0.1:0").unwrap();
+        server
+            .set_read_timeout(Some(Duration::from_secs(5)))
+            .unwrap();
+        let addr = server.local_addr().unwrap().to_string();
+
+        let mut report = RunReport {
+            scraped: 2,
+            failed: 1,
+            ..RunReport::default()
+        };
+        report.failures.insert(FailureKind::Timeout, 1);
+        send_run_metrics(&addr, &Ok(report), Some(Duration::from_millis(1500))).unwrap();
+
+        let mut lines = vec![];
+        let mut buffer = [0; 512];
+        while let Ok(len) = server.recv(&mut buffer) {
+            lines.push(String::from_utf8_lossy(&buffer[..len]).to_string());
+            if lines.len() == 7 {
+                break;
+            }
+        }
+        assert_eq!(
+            lines,
+            vec![
+                "oliepriser.runs.ok:1|c",
+                "oliepriser.run.duration:1500|ms",
+                "oliepriser.prices.scraped:2|c",
+                "oliepriser.providers.unchanged:0|c",
+                "oliepriser.providers.skipped:0|c",
+                "oliepriser.providers.failed:1|c",
+                "oliepriser.providers.failed.timeout:1|c",
+            ]
+        );
+    }
+}
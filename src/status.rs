@@ -0,0 +1,249 @@
+use std::collections::VecDeque;
+use std::io::{Error, ErrorKind};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader, Take};
+use tokio::net::{TcpListener, TcpStream};
+
+use crate::report::RecentRun;
+
+/// How long a client may take to send its request, so idle connections don't pile up
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Largest request line and headers read, larger requests are answered with 400
+const MAX_REQUEST_BYTES: u64 = 8192;
+
+///
+/// The reports of the last runs, shared between the scraper and the status server
+/// Once full, every new run pushes out the oldest one
+///
+/// # Fields
+///
+/// - runs: Arc<Mutex<VecDeque<RecentRun>>> - The runs, oldest first
+/// - capacity: usize - How many runs are kept, none when 0
+#[derive(Clone, Debug)]
+pub(crate) struct RecentRuns {
+    runs: Arc<Mutex<VecDeque<RecentRun>>>,
+    capacity: usize,
+}
+
+impl RecentRuns {
+    pub(crate) fn new(capacity: usize) -> Self {
+        Self {
+            runs: Arc::new(Mutex::new(VecDeque::with_capacity(capacity))),
+            capacity,
+        }
+    }
+
+    ///
+    /// Keep the report of a finished run, dropping the oldest run once the buffer is full
+    ///
+    /// # Arguments
+    ///
+    /// - run: RecentRun - The finished run
+    ///
+    pub(crate) fn push(&self, run: RecentRun) {
+        if self.capacity == 0 {
+            return;
+        }
+        let mut runs = self.runs.lock().unwrap();
+        if runs.len() == self.capacity {
+            runs.pop_front();
+        }
+        runs.push_back(run);
+    }
+
+    ///
+    /// Serialize the kept runs as a JSON array, newest first
+    ///
+    /// # Returns
+    ///
+    /// String - The JSON array
+    ///
+    pub(crate) fn to_json(&self) -> String {
+        let runs = self.runs.lock().unwrap();
+        serde_json::to_string(&runs.iter().rev().collect::<Vec<_>>()).unwrap()
+    }
+}
+
+///
+/// Serve the recent runs over HTTP at `/runs` until the process exits
+/// Speaks just enough HTTP/1.1 for `curl` and monitoring probes: every request gets one response and the connection
+/// is closed
+///
+/// # Arguments
+///
+/// - listener: TcpListener - The bound listener
+/// - runs: RecentRuns - The runs to serve
+///
+pub(crate) async fn serve(listener: TcpListener, runs: RecentRuns) {
+    loop {
+        let (stream, peer) = match listener.accept().await {
+            Ok(connection) => connection,
+            Err(e) => {
+                eprintln!("Failed to accept status connection: {}", e);
+                continue;
+            }
+        };
+        let runs = runs.clone();
+        tokio::spawn(async move {
+            if let Err(e) = respond(stream, &runs).await {
+                eprintln!("Failed to answer status request from {}: {}", peer, e);
+            }
+        });
+    }
+}
+
+///
+/// Answer a single request on a status connection
+///
+/// # Arguments
+///
+/// - stream: TcpStream - The connection
+/// - runs: &RecentRuns - The runs to serve
+///
+/// # Errors
+///
+/// If the request can't be read or the response can't be written, an error is returned
+///
+async fn respond(stream: TcpStream, runs: &RecentRuns) -> Result<(), Error> {
+    let mut stream = BufReader::new(stream).take(MAX_REQUEST_BYTES);
+    let request_line = tokio::time::timeout(REQUEST_TIMEOUT, read_request_line(&mut stream))
+        .await
+        .map_err(|_| Error::new(ErrorKind::TimedOut, "request timed out"))??;
+
+    let mut parts = request_line
+        .as_deref()
+        .unwrap_or_default()
+        .split_whitespace();
+    let (status, body) = match (parts.next(), parts.next()) {
+        _ if request_line.is_none() => (
+            "400 Bad Request",
+            r#"{"error":"request too large"}"#.to_string(),
+        ),
+        (Some("GET"), Some("/runs")) => ("200 OK", runs.to_json()),
+        (Some("GET"), _) => ("404 Not Found", r#"{"error":"not found"}"#.to_string()),
+        _ => (
+            "405 Method Not Allowed",
+            r#"{"error":"method not allowed"}"#.to_string(),
+        ),
+    };
+    let response = format!(
+        "HTTP/1.1 {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        body.len(),
+        body
+    );
+    let stream = stream.get_mut().get_mut();
+    stream.write_all(response.as_bytes()).await?;
+    stream.shutdown().await
+}
+
+///
+/// Read the request line of a status request
+///
+/// # Arguments
+///
+/// - stream: &mut Take<BufReader<TcpStream>> - The connection, limited to the bytes a request may take
+///
+/// # Returns
+///
+/// Result<Option<String>, Error> - The request line, or None if the request doesn't fit in the limit
+///
+/// # Errors
+///
+/// If the request can't be read, an error is returned
+///
+async fn read_request_line(
+    stream: &mut Take<BufReader<TcpStream>>,
+) -> Result<Option<String>, Error> {
+    let mut request_line = String::new();
+    stream.read_line(&mut request_line).await?;
+    // The headers aren't needed, but are read so the client isn't reset while still sending them
+    let mut header = request_line.clone();
+    while !header.trim().is_empty() {
+        if stream.limit() == 0 {
+            return Ok(None);
+        }
+        header.clear();
+        if stream.read_line(&mut header).await? == 0 {
+            break;
+        }
+    }
+    Ok(Some(request_line))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::report::RunMetrics;
+    use std::net::SocketAddr;
+    use uuid::Uuid;
+
+    fn run(status: &'static str) -> RecentRun {
+        RecentRun {
+            metrics: RunMetrics {
+                timestamp: chrono::Utc::now(),
+                run_id: Uuid::new_v4(),
+                status,
+                duration_secs: Some(1.5),
+                providers: Some(1),
+                succeeded: Some(1),
+                failed: Some(0),
+                prices_posted: Some(1),
+            },
+            error: None,
+            failures: Default::default(),
+            records: vec![],
+        }
+    }
+
+    async fn get(addr: SocketAddr, path: &str) -> String {
+        let mut stream = TcpStream::connect(addr).await.unwrap();
+        let request = format!("GET {} HTTP/1.1\r\nHost: localhost\r\n\r\n", path);
+        stream.write_all(request.as_bytes()).await.unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).await.unwrap();
+        response
+    }
+
+    #[test]
+    fn only_the_last_runs_are_kept_newest_first() {
+        let runs = RecentRuns::new(2);
+        runs.push(run("first"));
+        runs.push(run("second"));
+        runs.push(run("third"));
+
+        let json: serde_json::Value = serde_json::from_str(&runs.to_json()).unwrap();
+        let statuses: Vec<&str> = json
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|run| run["status"].as_str().unwrap())
+            .collect();
+        assert_eq!(statuses, vec!["third", "second"]);
+        assert_eq!(json[0]["duration_secs"], 1.5);
+
+        let disabled = RecentRuns::new(0);
+        disabled.push(run("first"));
+        assert_eq!(disabled.to_json(), "[]");
+    }
+
+    #[tokio::test]
+    async fn runs_are_served_over_http() {
+        let runs = RecentRuns::new(5);
+        runs.push(run("ok"));
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(serve(listener, runs));
+
+        let response = get(addr, "/runs").await;
+        assert!(response.starts_with("HTTP/1.1 200 OK"));
+        assert!(response.contains(r#""status":"ok""#));
+
+        assert!(get(addr, "/").await.starts_with("HTTP/1.1 404"));
+
+        let path = format!("/{}", "a".repeat(MAX_REQUEST_BYTES as usize));
+        assert!(get(addr, &path).await.starts_with("HTTP/1.1 400"));
+    }
+}
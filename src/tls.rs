@@ -0,0 +1,108 @@
+use reqwest::{Certificate, ClientBuilder, Identity};
+use std::path::Path;
+
+use crate::error::ScraperError;
+
+///
+/// Client certificate and CA for mutual TLS to the backend API, loaded from PEM files
+///
+/// # Fields
+///
+/// - identity: Identity - The client certificate and its PKCS#8 private key
+/// - ca_cert: Option<Certificate> - Extra root certificate the API's certificate is verified against
+#[derive(Clone, Debug)]
+pub struct ClientTls {
+    identity: Identity,
+    ca_cert: Option<Certificate>,
+}
+
+impl ClientTls {
+    ///
+    /// Load the client certificate, key and optional CA certificate from PEM files
+    ///
+    /// # Arguments
+    ///
+    /// - cert: &Path - The PEM client certificate
+    /// - key: &Path - The PEM PKCS#8 private key of the certificate
+    /// - ca_cert: Option<&Path> - The PEM CA certificate, the system roots only when None
+    ///
+    /// # Returns
+    ///
+    /// Result<ClientTls, ScraperError> - The loaded certificates
+    ///
+    /// # Errors
+    ///
+    /// If a file cannot be read or doesn't hold a valid PEM certificate or key, an error is returned
+    ///
+    pub fn load(cert: &Path, key: &Path, ca_cert: Option<&Path>) -> Result<Self, ScraperError> {
+        let cert_pem = read_pem(cert)?;
+        let key_pem = read_pem(key)?;
+        let identity = Identity::from_pkcs8_pem(&cert_pem, &key_pem).map_err(|e| {
+            ScraperError::Config(format!(
+                "Invalid client certificate {} or key {}: {}",
+                cert.display(),
+                key.display(),
+                e
+            ))
+        })?;
+
+        let ca_cert = match ca_cert {
+            Some(path) => Some(Certificate::from_pem(&read_pem(path)?).map_err(|e| {
+                ScraperError::Config(format!("Invalid CA certificate {}: {}", path.display(), e))
+            })?),
+            None => None,
+        };
+        Ok(Self { identity, ca_cert })
+    }
+
+    ///
+    /// Present the client certificate and trust the CA certificate in a client builder
+    ///
+    /// # Arguments
+    ///
+    /// - builder: ClientBuilder - The client builder
+    ///
+    /// # Returns
+    ///
+    /// ClientBuilder - The client builder with the certificates applied
+    ///
+    pub(crate) fn apply(&self, builder: ClientBuilder) -> ClientBuilder {
+        let builder = builder.identity(self.identity.clone());
+        match &self.ca_cert {
+            Some(ca_cert) => builder.add_root_certificate(ca_cert.clone()),
+            None => builder,
+        }
+    }
+}
+
+fn read_pem(path: &Path) -> Result<Vec<u8>, ScraperError> {
+    std::fs::read(path)
+        .map_err(|e| ScraperError::Config(format!("Failed to read {}: {}", path.display(), e)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use uuid::Uuid;
+
+    #[test]
+    fn load_errors_on_missing_and_malformed_files() {
+        let dir = std::env::temp_dir();
+        let missing = dir.join(format!(
+            "oliepriser-tls-missing-test-{}.pem",
+            Uuid::new_v4()
+        ));
+        let malformed = dir.join(format!(
+            "oliepriser-tls-malformed-test-{}.pem",
+            Uuid::new_v4()
+        ));
+        std::fs::write(&malformed, "not a certificate").unwrap();
+
+        let error = ClientTls::load(&missing, &malformed, None).unwrap_err();
+        assert!(error.to_string().contains("Failed to read"));
+
+        let error = ClientTls::load(&malformed, &malformed, None).unwrap_err();
+        assert!(error.to_string().contains("Invalid client certificate"));
+        std::fs::remove_file(&malformed).unwrap();
+    }
+}
@@ -0,0 +1,230 @@
+use reqwest::header::HeaderMap;
+use reqwest::{RequestBuilder, Response, ResponseBuilderExt, Url};
+
+/// How much of a response body is logged, the rest is cut off
+const TRACE_BODY_BYTES: usize = 2048;
+
+/// Header names containing any of these are secrets, this covers `Authorization`, cookies and API key headers
+const SECRET_HEADERS: [&str; 5] = ["auth", "cookie", "key", "token", "secret"];
+
+/// JSON keys containing any of these hold secrets, e.g. the access token returned by `/auth/login`
+const SECRET_FIELDS: [&str; 4] = ["token", "secret", "password", "key"];
+
+/// Query parameters containing any of these are secrets, e.g. the `api_key` of a provider's price API
+const SECRET_PARAMS: [&str; 5] = ["auth", "key", "token", "secret", "password"];
+
+///
+/// Send a request, logging the request, the response and the start of the response body to stderr when tracing
+/// Request bodies are never logged, since the login request carries the client secret
+/// The traced response body is read up to the size limit and handed back in a new response, a body over the limit is
+/// cut off one byte past it so the caller still rejects it
+///
+/// # Arguments
+///
+/// - request: RequestBuilder - The request to send
+/// - trace: bool - Whether to log the request and response
+/// - max_body_bytes: Option<usize> - The size limit of the response body, if any
+///
+/// # Returns
+///
+/// Result<Response, reqwest::Error> - The response
+///
+/// # Errors
+///
+/// If the request fails, an error is returned
+///
+pub(crate) async fn send(
+    request: RequestBuilder,
+    trace: bool,
+    max_body_bytes: Option<usize>,
+) -> Result<Response, reqwest::Error> {
+    if !trace {
+        return request.send().await;
+    }
+
+    let (client, request) = request.build_split();
+    let request = request?;
+    eprintln!(
+        "[trace] > {} {}",
+        request.method(),
+        redact_url(request.url())
+    );
+    log_headers(">", request.headers());
+
+    let mut response = match client.execute(request).await {
+        Ok(response) => response,
+        Err(e) => {
+            let message = match e.url() {
+                Some(url) => e.to_string().replace(url.as_str(), &redact_url(url)),
+                None => e.to_string(),
+            };
+            eprintln!("[trace] < {}", message);
+            return Err(e);
+        }
+    };
+    eprintln!(
+        "[trace] < {:?} {} from {}",
+        response.version(),
+        response.status(),
+        redact_url(response.url())
+    );
+    log_headers("<", response.headers());
+
+    let mut builder = http::Response::builder()
+        .status(response.status())
+        .version(response.version())
+        .url(response.url().clone());
+    if let Some(headers) = builder.headers_mut() {
+        *headers = response.headers().clone();
+    }
+    let mut body = Vec::new();
+    while let Some(chunk) = response.chunk().await? {
+        body.extend_from_slice(&chunk);
+        if let Some(max) = max_body_bytes.filter(|max| body.len() > *max) {
+            eprintln!("[trace] < body over the {} byte limit, cut off", max);
+            body.truncate(max + 1);
+            break;
+        }
+    }
+    eprintln!("[trace] < body: {}", redact_body(&body));
+
+    Ok(Response::from(
+        builder
+            .body(body)
+            .expect("status and headers come from a valid response"),
+    ))
+}
+
+fn log_headers(direction: &str, headers: &HeaderMap) {
+    for (name, value) in headers {
+        let value = if value.is_sensitive() || is_secret(name.as_str(), &SECRET_HEADERS) {
+            "<redacted>"
+        } else {
+            value.to_str().unwrap_or("<binary>")
+        };
+        eprintln!("[trace] {} {}: {}", direction, name, value);
+    }
+}
+
+///
+/// Render a URL for the trace log, redacting the values of secret query parameters
+///
+/// # Arguments
+///
+/// - url: &Url - The URL
+///
+/// # Returns
+///
+/// String - The URL as logged
+///
+fn redact_url(url: &Url) -> String {
+    if !url
+        .query_pairs()
+        .any(|(name, _)| is_secret(&name, &SECRET_PARAMS))
+    {
+        return url.to_string();
+    }
+    let pairs: Vec<(String, String)> = url
+        .query_pairs()
+        .map(|(name, value)| {
+            let value = if is_secret(&name, &SECRET_PARAMS) {
+                "<redacted>".to_string()
+            } else {
+                value.into_owned()
+            };
+            (name.into_owned(), value)
+        })
+        .collect();
+    let mut url = url.clone();
+    url.query_pairs_mut().clear().extend_pairs(pairs);
+    url.to_string()
+}
+
+///
+/// Render a response body for the trace log, redacting secret JSON fields and cutting it off after `TRACE_BODY_BYTES`
+///
+/// # Arguments
+///
+/// - body: &[u8] - The response body
+///
+/// # Returns
+///
+/// String - The body as logged
+///
+fn redact_body(body: &[u8]) -> String {
+    let text = match serde_json::from_slice::<serde_json::Value>(body) {
+        Ok(mut json) => {
+            redact_json(&mut json);
+            json.to_string()
+        }
+        Err(_) => String::from_utf8_lossy(body).into_owned(),
+    };
+    if text.len() <= TRACE_BODY_BYTES {
+        return text;
+    }
+
+    let mut end = TRACE_BODY_BYTES;
+    while !text.is_char_boundary(end) {
+        end -= 1;
+    }
+    format!("{}... ({} bytes)", &text[..end], body.len())
+}
+
+fn redact_json(json: &mut serde_json::Value) {
+    match json {
+        serde_json::Value::Object(fields) => {
+            for (name, value) in fields {
+                if is_secret(name, &SECRET_FIELDS) {
+                    *value = serde_json::Value::String("<redacted>".to_string());
+                } else {
+                    redact_json(value);
+                }
+            }
+        }
+        serde_json::Value::Array(values) => values.iter_mut().for_each(redact_json),
+        _ => {}
+    }
+}
+
+fn is_secret(name: &str, needles: &[&str]) -> bool {
+    let name = name.to_lowercase();
+    needles.iter().any(|needle| name.contains(needle))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn redact_body_hides_secret_fields_and_truncates() {
+        let login = br#"{"access_token":"abc123","token_type":"Bearer","data":[{"api_key":"k"}]}"#;
+        let redacted = redact_body(login);
+        assert!(!redacted.contains("abc123"));
+        assert!(!redacted.contains(r#""k""#));
+        assert!(redacted.contains(r#""access_token":"<redacted>""#));
+
+        let page = "ø".repeat(TRACE_BODY_BYTES);
+        let truncated = redact_body(page.as_bytes());
+        assert!(truncated.ends_with(&format!("... ({} bytes)", page.len())));
+        assert!(truncated.len() < page.len());
+    }
+
+    #[test]
+    fn secret_query_parameters_are_redacted_from_urls() {
+        let url = Url::parse("https://partner.example/prices?station=12&api_key=abc123").unwrap();
+        let redacted = redact_url(&url);
+        assert!(!redacted.contains("abc123"));
+        assert!(redacted.contains("station=12"));
+
+        let url = Url::parse("https://example.com/prices?station=12").unwrap();
+        assert_eq!(redact_url(&url), url.to_string());
+    }
+
+    #[test]
+    fn secret_headers_are_recognized() {
+        assert!(is_secret("authorization", &SECRET_HEADERS));
+        assert!(is_secret("X-API-Key", &SECRET_HEADERS));
+        assert!(is_secret("set-cookie", &SECRET_HEADERS));
+        assert!(!is_secret("content-type", &SECRET_HEADERS));
+    }
+}
@@ -0,0 +1,284 @@
+use chrono::{
+    DateTime, Datelike, FixedOffset, Local, NaiveDate, NaiveDateTime, NaiveTime, TimeDelta,
+    TimeZone, Utc, Weekday,
+};
+use std::fmt;
+use std::str::FromStr;
+
+/// Date formats recognised in update texts, day first as on Danish pages
+/// Two-digit years come first, as `%Y` would read `14.05.24` as the year 24
+const DATE_FORMATS: [&str; 7] = [
+    "%d.%m.%y", "%d-%m-%y", "%d/%m/%y", "%d.%m.%Y", "%d-%m-%Y", "%d/%m/%Y", "%Y-%m-%d",
+];
+
+/// Time formats recognised in update texts
+const TIME_FORMATS: [&str; 2] = ["%H:%M:%S", "%H:%M"];
+
+/// Earliest year an update text can mean, anything before is a misread date
+const MIN_YEAR: i32 = 2000;
+
+///
+/// Timezone the dates and times of update texts are read in
+///
+/// # Variants
+///
+/// - Copenhagen: `Europe/Copenhagen`, CET with CEST in summer as on Danish pages (default)
+/// - Local: The local time of the scraper host
+/// - Fixed: A fixed UTC offset, e.g. `+01:00` or `UTC`
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Timezone {
+    #[default]
+    Copenhagen,
+    Local,
+    Fixed(FixedOffset),
+}
+
+impl Timezone {
+    ///
+    /// Get the local time of a UTC time in the timezone
+    ///
+    /// # Arguments
+    ///
+    /// - time: DateTime<Utc> - The UTC time
+    ///
+    /// # Returns
+    ///
+    /// NaiveDateTime - The wall clock time in the timezone
+    ///
+    fn local(&self, time: DateTime<Utc>) -> NaiveDateTime {
+        match self {
+            Timezone::Copenhagen => time.naive_utc() + copenhagen_offset(time),
+            Timezone::Local => time.with_timezone(&Local).naive_local(),
+            Timezone::Fixed(offset) => time.with_timezone(offset).naive_local(),
+        }
+    }
+
+    ///
+    /// Get the UTC time of a wall clock time in the timezone, the earlier one when clocks are turned back
+    ///
+    /// # Arguments
+    ///
+    /// - local: NaiveDateTime - The wall clock time
+    ///
+    /// # Returns
+    ///
+    /// Option<DateTime<Utc>> - The UTC time, or None if the time is skipped when clocks are turned forward
+    ///
+    fn utc(&self, local: NaiveDateTime) -> Option<DateTime<Utc>> {
+        match self {
+            // Summer time first, so the repeated hour in October is the earlier one
+            Timezone::Copenhagen => [TimeDelta::hours(2), TimeDelta::hours(1)]
+                .into_iter()
+                .map(|offset| (local - offset).and_utc())
+                .find(|time| copenhagen_offset(*time) == local - time.naive_utc()),
+            Timezone::Local => Local
+                .from_local_datetime(&local)
+                .earliest()
+                .map(|time| time.with_timezone(&Utc)),
+            Timezone::Fixed(offset) => offset
+                .from_local_datetime(&local)
+                .earliest()
+                .map(|time| time.with_timezone(&Utc)),
+        }
+    }
+}
+
+impl FromStr for Timezone {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "Europe/Copenhagen" => Ok(Timezone::Copenhagen),
+            "local" => Ok(Timezone::Local),
+            "UTC" | "Z" => Ok(Timezone::Fixed(FixedOffset::east_opt(0).unwrap())),
+            offset => DateTime::parse_from_str(
+                &format!("2000-01-01 00:00 {}", offset),
+                "%Y-%m-%d %H:%M %:z",
+            )
+            .map(|time| Timezone::Fixed(*time.offset()))
+            .map_err(|_| {
+                format!(
+                    "expected Europe/Copenhagen, local, UTC or an offset like +01:00, got {:?}",
+                    value
+                )
+            }),
+        }
+    }
+}
+
+impl fmt::Display for Timezone {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Timezone::Copenhagen => write!(f, "Europe/Copenhagen"),
+            Timezone::Local => write!(f, "local"),
+            Timezone::Fixed(offset) => write!(f, "{}", offset),
+        }
+    }
+}
+
+///
+/// Get the UTC offset of Copenhagen at a time, by the EU rule of summer time from 01:00 UTC on the last Sunday of
+/// March to 01:00 UTC on the last Sunday of October
+///
+/// # Arguments
+///
+/// - time: DateTime<Utc> - The UTC time
+///
+/// # Returns
+///
+/// TimeDelta - Two hours in summer, one hour otherwise
+///
+fn copenhagen_offset(time: DateTime<Utc>) -> TimeDelta {
+    let switch = |month: u32| {
+        let last_day =
+            NaiveDate::from_ymd_opt(time.year(), month + 1, 1).unwrap() - TimeDelta::days(1);
+        let back = last_day.weekday().days_since(Weekday::Sun);
+        (last_day - TimeDelta::days(back.into()))
+            .and_hms_opt(1, 0, 0)
+            .unwrap()
+            .and_utc()
+    };
+    if (switch(3)..switch(10)).contains(&time) {
+        TimeDelta::hours(2)
+    } else {
+        TimeDelta::hours(1)
+    }
+}
+
+///
+/// Parse the time a provider page says its price was last updated, e.g. `Opdateret 14.05.2024 kl. 12:30`
+/// RFC 3339 timestamps are used as-is; otherwise a date and a time are picked out of the words of the text, in the
+/// given timezone. A time without a date is today's, or yesterday's if that time hasn't come yet today, and a date
+/// without a time is the start of that day
+///
+/// # Arguments
+///
+/// - text: &str - The update text of the page
+/// - now: DateTime<Utc> - The current time, which dates and times without a date are resolved against
+/// - timezone: Timezone - The timezone the text's date and time are in
+///
+/// # Returns
+///
+/// Option<DateTime<Utc>> - The update time, or None if the text has no recognisable date or time
+///
+pub(crate) fn parse_updated_at(
+    text: &str,
+    now: DateTime<Utc>,
+    timezone: Timezone,
+) -> Option<DateTime<Utc>> {
+    if let Ok(timestamp) = DateTime::parse_from_rfc3339(text.trim()) {
+        return Some(timestamp.with_timezone(&Utc));
+    }
+
+    let words: Vec<&str> = text
+        .split_whitespace()
+        .map(|word| word.trim_matches(|c: char| matches!(c, ',' | '(' | ')')))
+        .collect();
+    let date = words.iter().find_map(|word| {
+        DATE_FORMATS
+            .iter()
+            .find_map(|format| NaiveDate::parse_from_str(word, format).ok())
+            .filter(|date| date.year() >= MIN_YEAR)
+    });
+    let time = words.iter().find_map(|word| {
+        TIME_FORMATS
+            .iter()
+            .find_map(|format| NaiveTime::parse_from_str(word, format).ok())
+    });
+
+    let updated_at = match (date, time) {
+        (Some(date), time) => NaiveDateTime::new(date, time.unwrap_or(NaiveTime::MIN)),
+        (None, Some(time)) => {
+            let now = timezone.local(now);
+            let today = NaiveDateTime::new(now.date(), time);
+            if today > now {
+                today - TimeDelta::days(1)
+            } else {
+                today
+            }
+        }
+        (None, None) => return None,
+    };
+    timezone.utc(updated_at)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn utc(date: &str) -> Option<DateTime<Utc>> {
+        Some(DateTime::parse_from_rfc3339(date).unwrap().to_utc())
+    }
+
+    #[test]
+    fn dates_and_times_are_picked_out_of_update_texts() {
+        let now = utc("2024-05-14T13:00:00Z").unwrap();
+        let parse = |text: &str| parse_updated_at(text, now, Timezone::Copenhagen);
+
+        assert_eq!(
+            parse("Opdateret 14.05.2024 kl. 12:30"),
+            utc("2024-05-14T10:30:00Z")
+        );
+        assert_eq!(
+            parse("Sidst opdateret: 13/05/2024, 08:05"),
+            utc("2024-05-13T06:05:00Z")
+        );
+        assert_eq!(parse("Pris pr. 2024-05-12"), utc("2024-05-11T22:00:00Z"));
+        assert_eq!(
+            parse("2024-05-14T10:30:00+02:00"),
+            utc("2024-05-14T08:30:00Z")
+        );
+        assert_eq!(parse("Opdateret i dag"), None);
+    }
+
+    #[test]
+    fn two_digit_years_are_this_century() {
+        let now = utc("2024-05-14T13:00:00Z").unwrap();
+        let parse = |text: &str| parse_updated_at(text, now, Timezone::Copenhagen);
+
+        assert_eq!(
+            parse("Opdateret 14.05.24 12:30"),
+            utc("2024-05-14T10:30:00Z")
+        );
+        assert_eq!(parse("Opdateret 14-05-24"), utc("2024-05-13T22:00:00Z"));
+        assert_eq!(parse("Opdateret 14.05.0024"), None);
+    }
+
+    #[test]
+    fn copenhagen_times_follow_summer_time() {
+        let now = utc("2024-12-01T12:00:00Z").unwrap();
+        let parse = |text: &str| parse_updated_at(text, now, Timezone::Copenhagen);
+
+        assert_eq!(parse("14.01.2024 12:30"), utc("2024-01-14T11:30:00Z"));
+        assert_eq!(parse("14.07.2024 12:30"), utc("2024-07-14T10:30:00Z"));
+        // Clocks go forward at 02:00 on the last Sunday of March and back at 03:00 on the last Sunday of October
+        assert_eq!(parse("31.03.2024 02:30"), None);
+        assert_eq!(parse("31.03.2024 03:00"), utc("2024-03-31T01:00:00Z"));
+        assert_eq!(parse("27.10.2024 02:30"), utc("2024-10-27T00:30:00Z"));
+        assert_eq!(parse("27.10.2024 03:00"), utc("2024-10-27T02:00:00Z"));
+    }
+
+    #[test]
+    fn times_without_a_date_are_the_latest_such_time() {
+        let now = utc("2024-05-14T13:00:00Z").unwrap();
+        let parse = |text: &str| parse_updated_at(text, now, Timezone::Copenhagen);
+
+        assert_eq!(parse("Opdateret 12:30"), utc("2024-05-14T10:30:00Z"));
+        assert_eq!(parse("Opdateret 23:45"), utc("2024-05-13T21:45:00Z"));
+        assert_eq!(
+            parse_updated_at("Opdateret 12:30", now, "UTC".parse().unwrap()),
+            utc("2024-05-14T12:30:00Z")
+        );
+    }
+
+    #[test]
+    fn timezones_are_named_or_offsets() {
+        assert_eq!("Europe/Copenhagen".parse(), Ok(Timezone::Copenhagen));
+        assert_eq!("local".parse(), Ok(Timezone::Local));
+        assert_eq!(
+            "+01:00".parse(),
+            Ok(Timezone::Fixed(FixedOffset::east_opt(3600).unwrap()))
+        );
+        assert!("Europe/Oslo".parse::<Timezone>().is_err());
+    }
+}